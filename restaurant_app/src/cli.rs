@@ -0,0 +1,177 @@
+use std::fs;
+
+use crate::common::{
+    database::{DBTrait, DB},
+    errors::ErrorResponse,
+    indexes_db::check_indexes,
+    menu_records::MenuFormat,
+};
+use crate::handlers::menu_transfer_handler::menu_transfer::{export_menu, import_menu, ImportMode};
+
+const USAGE: &str = "usage:
+    restaurant_app import-menu <file> [--format csv|json|yaml] [--mode merge|upsert|replace] [--dry-run]
+    restaurant_app export-menu [--format csv|json|yaml] [--output <file>]
+    restaurant_app check-indexes";
+
+/// The admin commands the binary runs instead of the server when it is given arguments.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    ImportMenu {
+        file: String,
+        format: MenuFormat,
+        mode: ImportMode,
+        dry_run: bool,
+    },
+    ExportMenu {
+        format: MenuFormat,
+        output: Option<String>,
+    },
+    /// reports the declared indexes that are missing and the ones that aren't declared
+    CheckIndexes,
+}
+
+impl Command {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let (command, args) = args.split_first().ok_or("no command given")?;
+        let mut file = None;
+        let mut format = None;
+        let mut mode = ImportMode::default();
+        let mut dry_run = false;
+        let mut output = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--format" => format = Some(parse_value(&mut args, "--format")?),
+                "--mode" => mode = parse_value(&mut args, "--mode")?,
+                "--dry-run" => dry_run = true,
+                "--output" => output = Some(args.next().ok_or("--output needs a file")?.clone()),
+                flag if flag.starts_with("--") => return Err(format!("unknown flag {flag}")),
+                path if file.is_none() => file = Some(path.to_string()),
+                extra => return Err(format!("unexpected argument {extra}")),
+            }
+        }
+
+        match command.as_str() {
+            "import-menu" => {
+                let file = file.ok_or("import-menu needs a file")?;
+                let format = match format.or_else(|| MenuFormat::from_path(&file)) {
+                    Some(format) => format,
+                    None => return Err(format!("can't tell the format of {file}, pass --format")),
+                };
+                Ok(Command::ImportMenu {
+                    file,
+                    format,
+                    mode,
+                    dry_run,
+                })
+            }
+            "export-menu" => Ok(Command::ExportMenu {
+                format: format
+                    .or_else(|| output.as_deref().and_then(MenuFormat::from_path))
+                    .unwrap_or(MenuFormat::Json),
+                output,
+            }),
+            "check-indexes" => match file {
+                Some(extra) => Err(format!("unexpected argument {extra}")),
+                None => Ok(Command::CheckIndexes),
+            },
+            other => Err(format!("unknown command {other}")),
+        }
+    }
+}
+
+/// Parses the arguments and runs the command against the database, returning the exit code.
+pub async fn run(args: &[String]) -> i32 {
+    let command = match Command::parse(args) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{e}\n{USAGE}");
+            return 2;
+        }
+    };
+    let db = match DB::init().await {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Was unable to initialize the db! {e}");
+            return 1;
+        }
+    };
+
+    match command {
+        Command::ImportMenu {
+            file,
+            format,
+            mode,
+            dry_run,
+        } => {
+            let text = match fs::read_to_string(&file) {
+                Ok(text) => text,
+                Err(e) => {
+                    eprintln!("Was unable to read {file}! Error: {e}");
+                    return 1;
+                }
+            };
+            // a running server sees the import once its cached menu expires
+            match import_menu(&db, &text, format, mode, dry_run).await {
+                Ok(summary) => print_json(&summary),
+                Err(e) => report(e),
+            }
+        }
+        Command::ExportMenu { format, output } => match export_menu(&db, format).await {
+            Ok(text) => match output {
+                Some(output) => match fs::write(&output, text) {
+                    Ok(_) => 0,
+                    Err(e) => {
+                        eprintln!("Was unable to write {output}! Error: {e}");
+                        1
+                    }
+                },
+                None => {
+                    print!("{text}");
+                    0
+                }
+            },
+            Err(e) => report(e),
+        },
+        // a missing index fails the check, an extra one only shows up in the report
+        Command::CheckIndexes => match check_indexes(&db).await {
+            Ok(indexes) => match print_json(&indexes) {
+                0 if !indexes.missing.is_empty() => 1,
+                exit_code => exit_code,
+            },
+            Err(e) => report(e),
+        },
+    }
+}
+
+fn parse_value<'a, T: serde::de::DeserializeOwned>(
+    args: &mut impl Iterator<Item = &'a String>,
+    flag: &str,
+) -> Result<T, String> {
+    let value = args.next().ok_or(format!("{flag} needs a value"))?;
+    serde_json::from_value(serde_json::Value::String(value.clone()))
+        .map_err(|_| format!("{value} is not a valid {flag}"))
+}
+
+fn print_json<T: serde::Serialize>(value: &T) -> i32 {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => {
+            println!("{json}");
+            0
+        }
+        Err(e) => {
+            eprintln!("Was unable to print the result! Error: {e}");
+            1
+        }
+    }
+}
+
+// the same problem document the end points answer with
+fn report(e: ErrorResponse) -> i32 {
+    eprintln!(
+        "{}",
+        serde_json::to_string_pretty(&e.error).unwrap_or_else(|_| e.error.detail.clone())
+    );
+    1
+}
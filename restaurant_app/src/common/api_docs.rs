@@ -0,0 +1,71 @@
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+use crate::handlers::{
+    daypart_handler::daypart, inventory_handler::inventory, item_handler::item,
+    kitchen_handler::kitchen, menu_transfer_handler::menu_transfer,
+    menu_version_handler::menu_version, order_handler::order, table_handler::table,
+};
+
+pub const OPENAPI_JSON_PATH: &str = "/openapi.json";
+pub const DOCS_PATH: &str = "/docs";
+
+/// The OpenAPI document is generated from the handlers' `#[utoipa::path]` annotations
+/// and the request/response types, so adding a route means listing its handler here.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Restaurant API", description = "Tables, orders and the menu of the restaurant"),
+    servers(
+        (url = "/v2", description = "Current version"),
+        (url = "/v1", description = "Reports held and queued orders as InProgress"),
+    ),
+    paths(
+        table::create_table,
+        table::list_table,
+        table::get_table,
+        table::delete_table,
+        order::create_order,
+        order::get_order,
+        order::list_all_orders,
+        order::delete_order,
+        order::move_orders,
+        order::fire_course,
+        item::list_items,
+        item::update_availability,
+        item::update_recipe,
+        item::update_dayparts,
+        item::get_menu,
+        kitchen::list_stations,
+        inventory::list_ingredients,
+        inventory::list_low_stock,
+        inventory::set_ingredient,
+        inventory::list_unmakeable_items,
+        daypart::list_dayparts,
+        daypart::set_daypart,
+        menu_version::list_menu_versions,
+        menu_version::create_menu_version,
+        menu_version::get_menu_version,
+        menu_version::set_menu_version_item,
+        menu_version::remove_menu_version_item,
+        menu_version::publish_menu_version,
+        menu_version::diff_menu_versions,
+        menu_transfer::import_menu_file,
+        menu_transfer::export_menu_file,
+    ),
+    tags(
+        (name = "table", description = "Seating and clearing tables"),
+        (name = "order", description = "Ordering food for a table"),
+        (name = "item", description = "The menu"),
+        (name = "kitchen", description = "What the stations of the kitchen are cooking"),
+        (name = "inventory", description = "The ingredients the recipes of the items take"),
+        (name = "daypart", description = "When the items of each part of the day are served"),
+        (name = "menu_version", description = "Drafting, publishing and comparing revisions of the menu"),
+        (name = "menu_transfer", description = "Importing and exporting the whole menu as csv, json or yaml"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Serves the document at `/openapi.json` and a Swagger UI for it at `/docs`.
+pub fn docs_router() -> SwaggerUi {
+    SwaggerUi::new(DOCS_PATH).url(OPENAPI_JSON_PATH, ApiDoc::openapi())
+}
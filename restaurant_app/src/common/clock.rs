@@ -0,0 +1,68 @@
+use chrono::{DateTime, Utc};
+use tracing::warn;
+
+/// Where the handlers get the current time from, so the cook statuses can be checked without waiting for them.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The wall clock, what the app runs with.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when it is told to, e.g. to see an order finish without sleeping through its cook time.
+/// Started with a speed it also runs on its own that many times faster than the wall clock, to replay a service quickly.
+pub struct FakeClock {
+    now: std::sync::Mutex<DateTime<Utc>>,
+    speed: f64,
+    started: std::time::Instant,
+}
+
+impl FakeClock {
+    #[cfg(test)]
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self::running(now, 0.0)
+    }
+
+    pub fn running(now: DateTime<Utc>, speed: f64) -> Self {
+        Self {
+            now: std::sync::Mutex::new(now),
+            speed: speed.max(0.0),
+            started: std::time::Instant::now(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn advance(&self, by: chrono::Duration) {
+        let mut now = self.now.lock().unwrap_or_else(|e| e.into_inner());
+        *now += by;
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Utc> {
+        let now = *self.now.lock().unwrap_or_else(|e| e.into_inner());
+        //a stopped clock is left exactly where it was put
+        match self.speed > 0.0 {
+            true => {
+                let elapsed = self.started.elapsed().as_secs_f64() * self.speed;
+                let elapsed = chrono::Duration::microseconds((elapsed * 1_000_000.0) as i64);
+                match now.checked_add_signed(elapsed) {
+                    Some(now) => now,
+                    None => {
+                        warn!(
+                            "The fake clock ran past the last date it can hold, falling back to where it started, {now}"
+                        );
+                        now
+                    }
+                }
+            }
+            false => now,
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use std::{collections::HashMap, str::FromStr, sync::OnceLock, time::Duration};
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use tracing::warn;
+
+use super::{models::restaurant_schema::Station, seeding::SeedMode};
+
+static VALIDATION_LIMITS: OnceLock<ValidationLimits> = OnceLock::new();
+static IDEMPOTENCY_KEY_TTL: OnceLock<Duration> = OnceLock::new();
+static IDEMPOTENCY_LOCK_LEASE: OnceLock<Duration> = OnceLock::new();
+static TRANSACTION_MAX_ATTEMPTS: OnceLock<u32> = OnceLock::new();
+static MENU_CACHE_TTL: OnceLock<Duration> = OnceLock::new();
+static STATION_SLOTS: OnceLock<HashMap<Station, usize>> = OnceLock::new();
+static RESTAURANT_TIMEZONE: OnceLock<Tz> = OnceLock::new();
+static MENU_VERSION_POLL_INTERVAL: OnceLock<Duration> = OnceLock::new();
+static KITCHEN_SCHEDULE_LOOKBACK: OnceLock<Duration> = OnceLock::new();
+static KITCHEN_SCHEDULE_CACHE_TTL: OnceLock<Duration> = OnceLock::new();
+
+const DEFAULT_IDEMPOTENCY_KEY_TTL_SECS: u64 = 24 * 60 * 60;
+const DEFAULT_IDEMPOTENCY_LOCK_LEASE_SECS: u64 = 30;
+const DEFAULT_TRANSACTION_MAX_ATTEMPTS: u32 = 5;
+const DEFAULT_MENU_CACHE_TTL_SECS: u64 = 5 * 60;
+const DEFAULT_STATION_SLOTS: usize = 2;
+const DEFAULT_KITCHEN_SCHEDULE_LOOKBACK_SECS: u64 = 3 * 60 * 60;
+const DEFAULT_KITCHEN_SCHEDULE_CACHE_MS: u64 = 1000;
+const DEFAULT_MENU_VERSION_POLL_SECS: u64 = 60;
+const DEFAULT_SEED_MAX_ATTEMPTS: u32 = 10;
+const DEFAULT_SEED_RETRY_DELAY_MS: u64 = 2000;
+const DEFAULT_FAKE_CLOCK_SPEED: f64 = 1.0;
+// a day a second, a faster clock would run past the dates chrono can hold within the lifetime of the app
+const MAX_FAKE_CLOCK_SPEED: f64 = 86_400.0;
+
+/// Bounds enforced on every request by the validating extractors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationLimits {
+    /// largest `limit` a list end point accepts, `MAX_PAGE_SIZE`
+    pub max_page_size: i64,
+    /// most items a single create request may order, `MAX_ORDERS_PER_REQUEST`
+    pub max_orders_per_request: usize,
+    /// largest quantity of a single order, `MAX_QUANTITY_PER_ORDER`
+    pub max_quantity_per_order: i64,
+    /// highest course an order can be part of, `MAX_COURSES`
+    pub max_courses: i64,
+    /// longest item name accepted in bodies and filters, `MAX_ITEM_NAME_LENGTH`
+    pub max_item_name_length: usize,
+    /// most of an ingredient one portion of an item can take, `MAX_RECIPE_QUANTITY`
+    pub max_recipe_quantity: i64,
+}
+
+impl Default for ValidationLimits {
+    fn default() -> Self {
+        Self {
+            max_page_size: 100,
+            max_orders_per_request: 50,
+            max_quantity_per_order: 20,
+            max_courses: 5,
+            max_item_name_length: 64,
+            max_recipe_quantity: 100_000,
+        }
+    }
+}
+
+impl ValidationLimits {
+    /// Reads the limits from the environment, falling back to the defaults for anything unset or unparsable.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            max_page_size: env_or("MAX_PAGE_SIZE", defaults.max_page_size),
+            max_orders_per_request: env_or(
+                "MAX_ORDERS_PER_REQUEST",
+                defaults.max_orders_per_request,
+            ),
+            max_quantity_per_order: env_or(
+                "MAX_QUANTITY_PER_ORDER",
+                defaults.max_quantity_per_order,
+            ),
+            max_courses: env_or("MAX_COURSES", defaults.max_courses),
+            max_item_name_length: env_or("MAX_ITEM_NAME_LENGTH", defaults.max_item_name_length),
+            max_recipe_quantity: env_or("MAX_RECIPE_QUANTITY", defaults.max_recipe_quantity),
+        }
+    }
+}
+
+/// The limits of this process, read from the environment the first time they are needed.
+pub fn validation_limits() -> &'static ValidationLimits {
+    VALIDATION_LIMITS.get_or_init(ValidationLimits::from_env)
+}
+
+/// How long a response stays stored under its `Idempotency-Key`, `IDEMPOTENCY_KEY_TTL_SECS`.
+pub fn idempotency_key_ttl() -> Duration {
+    *IDEMPOTENCY_KEY_TTL.get_or_init(|| {
+        Duration::from_secs(env_or(
+            "IDEMPOTENCY_KEY_TTL_SECS",
+            DEFAULT_IDEMPOTENCY_KEY_TTL_SECS,
+        ))
+    })
+}
+
+/// How long a running request holds its `Idempotency-Key` before a retry may take it over, `IDEMPOTENCY_LOCK_LEASE_SECS`.
+pub fn idempotency_lock_lease() -> Duration {
+    *IDEMPOTENCY_LOCK_LEASE.get_or_init(|| {
+        Duration::from_secs(
+            env_or(
+                "IDEMPOTENCY_LOCK_LEASE_SECS",
+                DEFAULT_IDEMPOTENCY_LOCK_LEASE_SECS,
+            )
+            .max(1),
+        )
+    })
+}
+
+/// How often a transaction is tried before a transient error is given up on, `MONGO_TRANSACTION_MAX_ATTEMPTS`.
+pub fn transaction_max_attempts() -> u32 {
+    *TRANSACTION_MAX_ATTEMPTS.get_or_init(|| {
+        env_or(
+            "MONGO_TRANSACTION_MAX_ATTEMPTS",
+            DEFAULT_TRANSACTION_MAX_ATTEMPTS,
+        )
+        .max(1)
+    })
+}
+
+/// How long the menu is served from memory before it is read again, `MENU_CACHE_TTL_SECS`, 0 turns the cache off.
+pub fn menu_cache_ttl() -> Duration {
+    *MENU_CACHE_TTL.get_or_init(|| {
+        Duration::from_secs(env_or("MENU_CACHE_TTL_SECS", DEFAULT_MENU_CACHE_TTL_SECS))
+    })
+}
+
+/// How often to look for a published menu version whose effective time has come, `MENU_VERSION_POLL_SECS`.
+pub fn menu_version_poll_interval() -> Duration {
+    *MENU_VERSION_POLL_INTERVAL.get_or_init(|| {
+        Duration::from_secs(env_or("MENU_VERSION_POLL_SECS", DEFAULT_MENU_VERSION_POLL_SECS).max(1))
+    })
+}
+
+/// How the seed file is put into the database on start up, `SEED_MODE`, `if_empty` by default.
+pub fn seed_mode() -> SeedMode {
+    env_or("SEED_MODE", SeedMode::default())
+}
+
+/// How often seeding is tried while the database isn't reachable, `SEED_MAX_ATTEMPTS`.
+pub fn seed_max_attempts() -> u32 {
+    env_or("SEED_MAX_ATTEMPTS", DEFAULT_SEED_MAX_ATTEMPTS).max(1)
+}
+
+/// How long to wait before trying to seed again, `SEED_RETRY_DELAY_MS`.
+pub fn seed_retry_delay() -> Duration {
+    Duration::from_millis(env_or("SEED_RETRY_DELAY_MS", DEFAULT_SEED_RETRY_DELAY_MS))
+}
+
+/// How many dishes a station cooks at once, `KITCHEN_GRILL_SLOTS`, `KITCHEN_FRYER_SLOTS` and `KITCHEN_WOK_SLOTS`.
+pub fn station_slots(station: Station) -> usize {
+    STATION_SLOTS.get_or_init(|| {
+        Station::ALL
+            .into_iter()
+            .map(|station| {
+                let key = format!("KITCHEN_{}_SLOTS", station.to_string().to_uppercase());
+                (station, env_or(&key, DEFAULT_STATION_SLOTS).max(1))
+            })
+            .collect()
+    })[&station]
+}
+
+/// How far back the kitchen schedule looks for fired orders, `KITCHEN_SCHEDULE_LOOKBACK_SECS`,
+/// orders fired before that are taken to be off their station.
+pub fn kitchen_schedule_lookback() -> Duration {
+    *KITCHEN_SCHEDULE_LOOKBACK.get_or_init(|| {
+        Duration::from_secs(env_or(
+            "KITCHEN_SCHEDULE_LOOKBACK_SECS",
+            DEFAULT_KITCHEN_SCHEDULE_LOOKBACK_SECS,
+        ))
+    })
+}
+
+/// How long reads share a kitchen schedule before it is worked out again, `KITCHEN_SCHEDULE_CACHE_MS`, 0 turns the cache off.
+pub fn kitchen_schedule_cache_ttl() -> Duration {
+    *KITCHEN_SCHEDULE_CACHE_TTL.get_or_init(|| {
+        Duration::from_millis(env_or(
+            "KITCHEN_SCHEDULE_CACHE_MS",
+            DEFAULT_KITCHEN_SCHEDULE_CACHE_MS,
+        ))
+    })
+}
+
+/// The time zone the dayparts are in, an IANA name such as `Europe/Berlin` in `RESTAURANT_TIMEZONE`, UTC when unset.
+/// A zone rather than an offset, so the dayparts keep to the wall clock when it changes for daylight saving time.
+pub fn restaurant_timezone() -> Tz {
+    *RESTAURANT_TIMEZONE.get_or_init(|| env_or("RESTAURANT_TIMEZONE", Tz::UTC))
+}
+
+/// Where a fake clock starts and how many times faster than the wall clock it runs, `FAKE_CLOCK_START` and `FAKE_CLOCK_SPEED`.
+/// Without a valid RFC 3339 `FAKE_CLOCK_START` the app runs on the wall clock,
+/// the speed has to be above 0 and at most `MAX_FAKE_CLOCK_SPEED`.
+pub fn fake_clock() -> Option<(DateTime<Utc>, f64)> {
+    let start = std::env::var("FAKE_CLOCK_START").ok()?;
+    match DateTime::parse_from_rfc3339(&start) {
+        Ok(start) => Some((start.with_timezone(&Utc), fake_clock_speed())),
+        Err(_) => {
+            warn!("FAKE_CLOCK_START={start} is not an RFC 3339 time, using the wall clock instead");
+            None
+        }
+    }
+}
+
+fn fake_clock_speed() -> f64 {
+    let speed = env_or("FAKE_CLOCK_SPEED", DEFAULT_FAKE_CLOCK_SPEED);
+    match speed.is_finite() && speed > 0.0 && speed <= MAX_FAKE_CLOCK_SPEED {
+        true => speed,
+        false => {
+            warn!("FAKE_CLOCK_SPEED={speed} has to be above 0 and at most {MAX_FAKE_CLOCK_SPEED}, using {DEFAULT_FAKE_CLOCK_SPEED} instead");
+            DEFAULT_FAKE_CLOCK_SPEED
+        }
+    }
+}
+
+fn env_or<T: FromStr + Copy + std::fmt::Display>(key: &str, default: T) -> T {
+    match std::env::var(key) {
+        Ok(value) => match value.parse::<T>() {
+            Ok(parsed) => parsed,
+            Err(_) => {
+                warn!("{key}={value} is not a valid value, using {default} instead");
+                default
+            }
+        },
+        Err(_) => default,
+    }
+}
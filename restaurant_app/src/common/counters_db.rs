@@ -0,0 +1,219 @@
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use futures::stream::TryStreamExt;
+use mongodb::{
+    bson::{doc, Document},
+    options::{FindOneAndUpdateOptions, FindOneOptions, ReturnDocument},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, Instrument};
+
+use super::database;
+use super::errors::{AxumErrors, ErrorResponse};
+use super::telemetry::mongo_span;
+
+/// The ids handed out by the `id_management.counters` collection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Counter {
+    Table,
+    Order,
+    MenuVersion,
+}
+
+impl Counter {
+    fn name(&self) -> &'static str {
+        match self {
+            Counter::Table => "table_id",
+            Counter::Order => "order_id",
+            Counter::MenuVersion => "menu_version",
+        }
+    }
+}
+
+/// Last id handed out for a counter, `_id` is the name of the counter.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CounterRecord {
+    #[serde(rename = "_id")]
+    pub name: String,
+    pub seq: i64,
+}
+
+#[async_trait]
+pub trait DBCounterTrait {
+    /// Moves the counters past the ids already in use, so a fresh counters collection doesn't hand them out again.
+    async fn set_up_counters(&self) -> Result<(), ErrorResponse>;
+    /// Reserves `count` consecutive ids and returns the first one.
+    /// A single atomic `$inc`, so concurrent requests never get the same id and never wait on each other.
+    async fn next_ids(&self, counter: Counter, count: i64) -> Result<i64, ErrorResponse>;
+}
+
+#[faux::methods]
+#[async_trait]
+impl DBCounterTrait for database::DB {
+    async fn set_up_counters(&self) -> Result<(), ErrorResponse> {
+        let table_collection = self
+            .db
+            .database("table_management")
+            .collection::<Document>("tables");
+        let counter_collection = self
+            .db
+            .database("id_management")
+            .collection::<CounterRecord>("counters");
+
+        let pipeline = vec![
+            doc! {
+                "$project": {
+                    "table_id": 1,
+                    "order_id": { "$max": "$orders.order_id" }
+                }
+            },
+            doc! {
+                "$group": {
+                    "_id": null,
+                    "table_id": { "$max": "$table_id" },
+                    "order_id": { "$max": "$order_id" }
+                }
+            },
+        ];
+
+        let highest = match table_collection
+            .aggregate(pipeline, None)
+            .instrument(mongo_span("aggregate", "tables"))
+            .await
+        {
+            Ok(cursor) => {
+                match cursor.try_collect::<Vec<Document>>().await {
+                    Ok(documents) => documents.into_iter().next().unwrap_or_default(),
+                    Err(e) => {
+                        error!("Unexpected error occured while reading the highest ids in use. Error: {e}");
+                        return Err(ErrorResponse {
+                            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                            error: AxumErrors::DBError.into(),
+                        });
+                    }
+                }
+            }
+            Err(e) => {
+                error!(
+                    "Unexpected error occured while looking up the highest ids in use. Error: {e}"
+                );
+                return Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                });
+            }
+        };
+
+        //the menu versions are numbered in a collection of their own
+        let menu_version_collection = self
+            .db
+            .database("item_management")
+            .collection::<Document>("menu_versions");
+        let newest_version = match menu_version_collection
+            .find_one(
+                doc! {},
+                FindOneOptions::builder()
+                    .sort(doc! { "version": -1 })
+                    .projection(doc! { "version": 1 })
+                    .build(),
+            )
+            .instrument(mongo_span("find_one", "menu_versions"))
+            .await
+        {
+            Ok(newest) => newest.unwrap_or_default(),
+            Err(e) => {
+                error!(
+                    "Unexpected error occured while looking up the newest menu version. Error: {e}"
+                );
+                return Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                });
+            }
+        };
+
+        for (counter, in_use) in [
+            (Counter::Table, id_in_use(&highest, "table_id")),
+            (Counter::Order, id_in_use(&highest, "order_id")),
+            (Counter::MenuVersion, id_in_use(&newest_version, "version")),
+        ] {
+            // $max never moves a counter back, e.g. when tables were deleted since
+            match counter_collection
+                .update_one(
+                    doc! { "_id": counter.name() },
+                    doc! { "$max": { "seq": in_use } },
+                    mongodb::options::UpdateOptions::builder()
+                        .upsert(true)
+                        .build(),
+                )
+                .instrument(mongo_span("update_one", "counters"))
+                .await
+            {
+                Ok(_) => (),
+                Err(e) => {
+                    error!(
+                        "Unexpected error occured while setting up the {} counter. Error: {e}",
+                        counter.name()
+                    );
+                    return Err(ErrorResponse {
+                        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                        error: AxumErrors::DBError.into(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn next_ids(&self, counter: Counter, count: i64) -> Result<i64, ErrorResponse> {
+        let counter_collection = self
+            .db
+            .database("id_management")
+            .collection::<CounterRecord>("counters");
+
+        let options = FindOneAndUpdateOptions::builder()
+            .upsert(true)
+            .return_document(ReturnDocument::After)
+            .build();
+
+        match counter_collection
+            .find_one_and_update(
+                doc! { "_id": counter.name() },
+                doc! { "$inc": { "seq": count } },
+                options,
+            )
+            .instrument(mongo_span("find_one_and_update", "counters"))
+            .await
+        {
+            Ok(Some(record)) => Ok(record.seq - count + 1),
+            Ok(None) => {
+                error!(
+                    "The {} counter was not returned by the upsert!",
+                    counter.name()
+                );
+                Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                })
+            }
+            Err(e) => {
+                error!(
+                    "Unexpected error occured while reserving ids from the {} counter. Error: {e}",
+                    counter.name()
+                );
+                Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                })
+            }
+        }
+    }
+}
+
+// ids are stored as Int64, but a hand made record may hold an Int32
+fn id_in_use(document: &Document, key: &str) -> i64 {
+    document
+        .get_i64(key)
+        .or_else(|_| document.get_i32(key).map(i64::from))
+        .unwrap_or(0)
+}
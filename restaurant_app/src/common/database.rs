@@ -1,15 +1,17 @@
-use super::models::restaurant_schema::Item;
+use super::config::{seed_max_attempts, seed_retry_delay};
+use super::telemetry::mongo_span;
 use async_trait::async_trait;
-use futures::stream::TryStreamExt;
 use mongodb::{bson::doc, options::ClientOptions, Client};
 use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
-use serde_json::Value;
-use std::{error::Error, fs, time::Duration};
+use std::{error::Error, time::Duration};
+use tracing::{info, warn, Instrument};
 
 #[faux::create]
 #[derive(Clone)]
 pub struct DB {
     pub db: Client,
+    /// whether the deployment is a replica set or sharded, standalone servers can't run transactions
+    pub transactions: bool,
 }
 
 #[async_trait]
@@ -17,7 +19,6 @@ pub trait DBTrait {
     async fn init() -> Result<Self, Box<dyn Error>>
     where
         Self: Sized;
-    async fn set_up_item_records(&self) -> Result<(), Box<dyn Error>>;
 }
 
 #[faux::methods]
@@ -39,7 +40,7 @@ impl DBTrait for DB {
                     .to_string()
                     .as_str(),
             );
-            
+
         match ClientOptions::parse(connection_string).await {
             Ok(mut client_options) => {
                 client_options.app_name = Some("MongoDB Client".to_string());
@@ -48,61 +49,52 @@ impl DBTrait for DB {
                 client_options.connect_timeout = Some(Duration::from_secs(5));
                 client_options.max_idle_time = Some(Duration::from_secs(30));
 
-                Ok(Self {
-                    db: Client::with_options(client_options)?,
-                })
+                let db = Client::with_options(client_options)?;
+                let transactions = supports_transactions(&db).await;
+
+                Ok(Self { db, transactions })
             }
             Err(e) => Err(format!("Could not connect to the DB: {e}").into()),
         }
     }
+}
 
-    async fn set_up_item_records(&self) -> Result<(), Box<dyn Error>> {
-        let item_collection = self
-            .db
-            .database("item_management")
-            .collection::<Item>("items");
-
-        match item_collection.find(doc! {}, None).await {
-            Ok(cursor) => {
-                let items: Vec<Item> = match cursor.try_collect().await {
-                    Ok(items) => items,
-                    Err(e) => panic!("Couldn't get the records! Error: {e}"),
-                };
-
-                if items.is_empty() {
-                    ()
-                } else {
-                    return Ok(());
-                }
+/// Asks the server what it is, only replica set members and mongos routers support transactions.
+/// The server may still be starting, so asking is retried like seeding is, `SEED_MAX_ATTEMPTS` times `SEED_RETRY_DELAY_MS` apart.
+async fn supports_transactions(client: &Client) -> bool {
+    let max_attempts = seed_max_attempts();
+    let mut attempts = 0;
+    let transactions = loop {
+        attempts += 1;
+        match client
+            .database("admin")
+            .run_command(doc! { "hello": 1 }, None)
+            .instrument(mongo_span("hello", "admin"))
+            .await
+        {
+            // a replica set member that hasn't been initiated yet doesn't know its set name
+            Ok(hello) if hello.get_bool("isreplicaset") == Ok(true) && attempts < max_attempts => {
+                warn!("The replica set isn't initiated yet, attempt {attempts} of {max_attempts}");
+                tokio::time::sleep(seed_retry_delay()).await;
+            }
+            Ok(hello) => {
+                break hello.contains_key("setName") || hello.get_str("msg") == Ok("isdbgrid")
+            }
+            Err(e) if attempts < max_attempts => {
+                warn!("The DB isn't ready to say whether it supports transactions yet, attempt {attempts} of {max_attempts}: {e}");
+                tokio::time::sleep(seed_retry_delay()).await;
             }
             Err(e) => {
-                panic!("unexpected error finding the records  when populating the DB: {e}")
-                //drop all the records
+                warn!("Was unable to tell whether the DB supports transactions after {attempts} attempts. Error: {e}");
+                break false;
             }
-        };
-
-        let file_path: String =
-            std::env::var("FILE_PATH").expect("FILE_PATH env var must be set!");
-
-        let file = fs::File::open(file_path)
-            .expect("File for pre-loading the DB not found!");
-
-        let json: Value = serde_json::from_reader(file)
-            .expect("Was unable to read the file for pre-loading the DB!");
-
-        let records_arr: Vec<Item> = match json.get("records") {
-            Some(records) => serde_json::from_value(records.clone())
-                .expect("unable to parse the item records in the file for pre-loading the DB!"),
-            None => panic!(
-                "Was unable to find the record's key. Check the file for pre-loading the DB!"
-            ),
-        };
-
-        match item_collection.insert_many(records_arr, None).await {
-            Ok(_) => Ok(()),
-            Err(e) => panic!(
-                "Unexpected error while inserting many records into the Item Database! Error: {e}"
-            ),
         }
+    };
+
+    if transactions {
+        info!("Connected to a replica set, multi step writes run in transactions");
+    } else {
+        warn!("Running without transactions, the steps a multi step write made before failing midway stay in the database and the menu cache only refreshes by its ttl");
     }
+    transactions
 }
@@ -1,11 +1,14 @@
+use axum::http::StatusCode;
 use futures::stream::TryStreamExt;
-use mongodb::bson::Document;
+use mongodb::bson::{doc, Bson, Document};
+use mongodb::error::{Error, ErrorKind, WriteFailure};
 use mongodb::Cursor;
 use serde::de::DeserializeOwned;
 use std::fmt::Debug;
 use tracing::error;
 
-use super::errors::AxumErrors;
+use super::errors::{AxumErrors, ErrorResponse};
+use super::models::restaurant_schema::Item;
 
 pub struct CollectCusrorResult<BsonStruct> {
     successfully_deserialized: Vec<BsonStruct>,
@@ -17,6 +20,7 @@ impl<BsonStruct> CollectCusrorResult<BsonStruct>
 where
     BsonStruct: Clone,
 {
+    #[allow(clippy::needless_return)]
     pub fn get_results(&self) -> (Vec<BsonStruct>, Vec<String>, u64) {
         return (
             self.successfully_deserialized.clone(),
@@ -26,6 +30,7 @@ where
     }
 }
 
+#[allow(clippy::single_match)]
 pub async fn collect_cursor<JsonStruct, BsonStruct>(
     cursor: Cursor<Document>,
 ) -> Result<CollectCusrorResult<BsonStruct>, AxumErrors>
@@ -73,3 +78,91 @@ where
         dropped,
     })
 }
+
+const DUPLICATE_KEY_CODE: i32 = 11000;
+
+/// Whether the write was rejected by a unique index.
+pub fn is_duplicate_key_error(error: &Error) -> bool {
+    match error.kind.as_ref() {
+        ErrorKind::Command(command_error) => command_error.code == DUPLICATE_KEY_CODE,
+        ErrorKind::Write(WriteFailure::WriteError(write_error)) => {
+            write_error.code == DUPLICATE_KEY_CODE
+        }
+        _ => false,
+    }
+}
+
+/// Narrows `filter` down to tables at one of `expected_versions`, when there are any.
+/// Tables from before versioning have no version field and count as version 0.
+pub fn with_expected_versions(
+    mut filter: Document,
+    expected_versions: &Option<Vec<i64>>,
+) -> Document {
+    if let Some(versions) = expected_versions {
+        match versions.contains(&0) {
+            true => filter.insert(
+                "$or",
+                vec![
+                    doc! { "version": { "$in": versions.clone() } },
+                    doc! { "version": { "$exists": false } },
+                ],
+            ),
+            false => filter.insert("version", doc! { "$in": versions.clone() }),
+        };
+    }
+    filter
+}
+
+// the kitchen's side of an item, a write of the menu only sets them on the items it adds
+const KITCHEN_ITEM_FIELDS: [&str; 2] = ["available", "stock"];
+// left out of an item document when they are empty, so a write of the menu has to unset them
+const OPTIONAL_ITEM_FIELDS: [&str; 6] = [
+    "description",
+    "station",
+    "recipe",
+    "allergens",
+    "diets",
+    "dayparts",
+];
+
+/// The upsert that puts `item` on the menu without touching the availability and stock of an item that is already there.
+/// The menu version is set to `menu_version` when there is one, and left as it is otherwise.
+pub fn menu_item_update(item: &Item, menu_version: Option<i64>) -> Result<Document, ErrorResponse> {
+    let mut menu_fields = match mongodb::bson::to_document(item) {
+        Ok(document) => document,
+        Err(e) => {
+            error!(
+                "Unexpected error occured while serializing the Item {}. Error: {e}",
+                item.item_name
+            );
+            return Err(ErrorResponse {
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                error: AxumErrors::BsonSerializeError.into(),
+            });
+        }
+    };
+    menu_fields.remove("menu_version");
+    let mut kitchen_fields = Document::new();
+    for field in KITCHEN_ITEM_FIELDS {
+        if let Some(value) = menu_fields.remove(field) {
+            kitchen_fields.insert(field, value);
+        }
+    }
+    let unset = OPTIONAL_ITEM_FIELDS
+        .into_iter()
+        .filter(|field| !menu_fields.contains_key(field))
+        .map(|field| (field.to_string(), Bson::String(String::new())))
+        .collect::<Document>();
+    if let Some(menu_version) = menu_version {
+        menu_fields.insert("menu_version", menu_version);
+    }
+
+    let mut update = doc! { "$set": menu_fields };
+    if !kitchen_fields.is_empty() {
+        update.insert("$setOnInsert", kitchen_fields);
+    }
+    if !unset.is_empty() {
+        update.insert("$unset", unset);
+    }
+    Ok(update)
+}
@@ -1,24 +1,113 @@
-use axum::{http::StatusCode, response::IntoResponse, Json};
+use std::{fmt, ops::Deref};
+
+use axum::{
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::request_tracing::current_request_id;
+
+pub const PROBLEM_JSON_CONTENT_TYPE: &str = "application/problem+json";
 
+// the body is boxed to keep the `Err` side of the db results small
+#[derive(Debug)]
 pub struct ErrorResponse {
     pub status_code: StatusCode,
-    pub error: AxumErrorResponse,
+    pub error: Box<AxumErrorResponse>,
 }
 
 impl ErrorResponse {
-    pub fn to_axum_error(self) -> (StatusCode, Json<AxumErrorResponse>) {
-        (self.status_code, Json(self.error))
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_axum_error(mut self) -> (StatusCode, ProblemJson<AxumErrorResponse>) {
+        self.error.status = self.status_code.as_u16();
+        (self.status_code, ProblemJson(*self.error))
+    }
+}
+
+impl From<AxumErrors> for ErrorResponse {
+    fn from(axum_errors: AxumErrors) -> Self {
+        Self {
+            status_code: axum_errors.status_code(),
+            error: axum_errors.into(),
+        }
+    }
+}
+
+impl From<AxumErrorResponse> for ErrorResponse {
+    fn from(error: AxumErrorResponse) -> Self {
+        Self {
+            status_code: StatusCode::from_u16(error.status)
+                .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            error: Box::new(error),
+        }
+    }
+}
+
+/// Lets `ErrorResponse` be used as the rejection of our extractors.
+impl IntoResponse for ErrorResponse {
+    fn into_response(self) -> Response {
+        self.to_axum_error().into_response()
     }
 }
 
 pub async fn handler_404() -> impl IntoResponse {
-    (StatusCode::NOT_FOUND, "Path not found!")
+    ErrorResponse::from(
+        AxumErrorResponse::from(AxumErrors::NotFound).with_detail("Path not found!"),
+    )
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// `Json` but answering with the `application/problem+json` content type of RFC 7807.
+pub struct ProblemJson<T>(pub T);
+
+impl<T> Deref for ProblemJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T: Serialize> IntoResponse for ProblemJson<T> {
+    fn into_response(self) -> Response {
+        let mut response = Json(self.0).into_response();
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(PROBLEM_JSON_CONTENT_TYPE),
+        );
+        response
+    }
+}
+
+/// Every error the api can answer with.
+/// The names double as the `error_type` of the response and are part of the api, so never rename one,
+/// add a new variant instead.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub enum AxumErrors {
     NotFound,
+    TableNotFound,
+    OrderNotFound,
+    UnknownItems,
+    UnknownModifiers,
+    ItemNotFound,
+    ItemUnavailable,
+    UnknownIngredients,
+    UnknownDayparts,
+    MenuVersionNotFound,
+    MenuVersionNotDraft,
+    InvalidMenuImport,
+    InvalidJsonBody,
+    UnsupportedMediaType,
+    InvalidQuery,
+    InvalidPath,
+    ValidationFailed,
+    InvalidIdempotencyKey,
+    IdempotencyKeyReused,
+    IdempotencyKeyInProgress,
+    PreconditionFailed,
     SerializationError,
     DeserializationError,
     DBError,
@@ -26,54 +115,268 @@ pub enum AxumErrors {
     BsonDeserializeError,
 }
 
-impl ToString for AxumErrors {
-    fn to_string(&self) -> String {
+impl AxumErrors {
+    /// Stable machine readable code, also used to build the problem `type` uri.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AxumErrors::NotFound => "not_found",
+            AxumErrors::TableNotFound => "table_not_found",
+            AxumErrors::OrderNotFound => "order_not_found",
+            AxumErrors::UnknownItems => "unknown_items",
+            AxumErrors::UnknownModifiers => "unknown_modifiers",
+            AxumErrors::ItemNotFound => "item_not_found",
+            AxumErrors::ItemUnavailable => "item_unavailable",
+            AxumErrors::UnknownIngredients => "unknown_ingredients",
+            AxumErrors::UnknownDayparts => "unknown_dayparts",
+            AxumErrors::MenuVersionNotFound => "menu_version_not_found",
+            AxumErrors::MenuVersionNotDraft => "menu_version_not_draft",
+            AxumErrors::InvalidMenuImport => "invalid_menu_import",
+            AxumErrors::InvalidJsonBody => "invalid_json_body",
+            AxumErrors::UnsupportedMediaType => "unsupported_media_type",
+            AxumErrors::InvalidQuery => "invalid_query",
+            AxumErrors::InvalidPath => "invalid_path",
+            AxumErrors::ValidationFailed => "validation_failed",
+            AxumErrors::InvalidIdempotencyKey => "invalid_idempotency_key",
+            AxumErrors::IdempotencyKeyReused => "idempotency_key_reused",
+            AxumErrors::IdempotencyKeyInProgress => "idempotency_key_in_progress",
+            AxumErrors::PreconditionFailed => "precondition_failed",
+            AxumErrors::SerializationError => "serialization_error",
+            AxumErrors::DeserializationError => "deserialization_error",
+            AxumErrors::DBError => "db_error",
+            AxumErrors::BsonSerializeError => "bson_serialize_error",
+            AxumErrors::BsonDeserializeError => "bson_deserialize_error",
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            AxumErrors::NotFound
+            | AxumErrors::TableNotFound
+            | AxumErrors::OrderNotFound
+            | AxumErrors::ItemNotFound
+            | AxumErrors::MenuVersionNotFound => StatusCode::NOT_FOUND,
+            AxumErrors::UnknownItems
+            | AxumErrors::UnknownModifiers
+            | AxumErrors::UnknownIngredients
+            | AxumErrors::UnknownDayparts
+            | AxumErrors::InvalidMenuImport
+            | AxumErrors::ValidationFailed => StatusCode::UNPROCESSABLE_ENTITY,
+            AxumErrors::InvalidJsonBody
+            | AxumErrors::InvalidQuery
+            | AxumErrors::InvalidPath
+            | AxumErrors::InvalidIdempotencyKey => StatusCode::BAD_REQUEST,
+            AxumErrors::IdempotencyKeyReused
+            | AxumErrors::IdempotencyKeyInProgress
+            | AxumErrors::ItemUnavailable
+            | AxumErrors::MenuVersionNotDraft => StatusCode::CONFLICT,
+            AxumErrors::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AxumErrors::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            AxumErrors::SerializationError
+            | AxumErrors::DeserializationError
+            | AxumErrors::DBError
+            | AxumErrors::BsonSerializeError
+            | AxumErrors::BsonDeserializeError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn title(&self) -> &'static str {
         match self {
-            AxumErrors::NotFound => "NotFound".into(),
-            AxumErrors::SerializationError => "SerializationError".into(),
-            AxumErrors::DeserializationError => "DeserializationError".into(),
-            AxumErrors::DBError => "DBError".into(),
-            AxumErrors::BsonSerializeError => "BsonSerializeError".into(),
-            AxumErrors::BsonDeserializeError => "BsonDeserializeError".into(),
+            AxumErrors::NotFound => "Not Found",
+            AxumErrors::TableNotFound => "Table Not Found",
+            AxumErrors::OrderNotFound => "Order Not Found",
+            AxumErrors::UnknownItems => "Unknown Items",
+            AxumErrors::UnknownModifiers => "Unknown Modifiers",
+            AxumErrors::ItemNotFound => "Item Not Found",
+            AxumErrors::ItemUnavailable => "Item Unavailable",
+            AxumErrors::UnknownIngredients => "Unknown Ingredients",
+            AxumErrors::UnknownDayparts => "Unknown Dayparts",
+            AxumErrors::MenuVersionNotFound => "Menu Version Not Found",
+            AxumErrors::MenuVersionNotDraft => "Menu Version Not Draft",
+            AxumErrors::InvalidMenuImport => "Invalid Menu Import",
+            AxumErrors::InvalidJsonBody => "Invalid Json Body",
+            AxumErrors::UnsupportedMediaType => "Unsupported Media Type",
+            AxumErrors::InvalidQuery => "Invalid Query",
+            AxumErrors::InvalidPath => "Invalid Path",
+            AxumErrors::ValidationFailed => "Validation Failed",
+            AxumErrors::InvalidIdempotencyKey => "Invalid Idempotency Key",
+            AxumErrors::IdempotencyKeyReused => "Idempotency Key Reused",
+            AxumErrors::IdempotencyKeyInProgress => "Idempotency Key In Progress",
+            AxumErrors::PreconditionFailed => "Precondition Failed",
+            AxumErrors::SerializationError => "Serialization Error",
+            AxumErrors::DeserializationError => "Deserialization Error",
+            AxumErrors::DBError => "Database Error",
+            AxumErrors::BsonSerializeError => "Bson Serialize Error",
+            AxumErrors::BsonDeserializeError => "Bson Deserialize Error",
         }
     }
+
+    fn detail(&self) -> &'static str {
+        match self {
+            AxumErrors::NotFound => "Document Was Not Found!",
+            AxumErrors::TableNotFound => "Table Was Not Found!",
+            AxumErrors::OrderNotFound => "Order Was Not Found!",
+            AxumErrors::UnknownItems => "Some of the requested items are not on the menu!",
+            AxumErrors::UnknownModifiers => {
+                "Some of the requested modifiers are not offered for their item!"
+            }
+            AxumErrors::ItemNotFound => "Item Was Not Found!",
+            AxumErrors::ItemUnavailable => {
+                "Some of the requested items are not available, not served at this time of day, or not enough portions or ingredients are left!"
+            }
+            AxumErrors::UnknownIngredients => "Some of the ingredients are not in the inventory!",
+            AxumErrors::UnknownDayparts => "Some of the dayparts don't exist!",
+            AxumErrors::MenuVersionNotFound => "Menu Version Was Not Found!",
+            AxumErrors::MenuVersionNotDraft => {
+                "The menu version was already published, only drafts can be changed!"
+            }
+            AxumErrors::InvalidMenuImport => {
+                "Some of the rows of the menu file can't be imported, nothing was changed!"
+            }
+            AxumErrors::InvalidJsonBody => "The request body is not valid json for this end point!",
+            AxumErrors::UnsupportedMediaType => {
+                "The request body must be sent with `Content-Type: application/json`!"
+            }
+            AxumErrors::InvalidQuery => "The query parameters could not be parsed!",
+            AxumErrors::InvalidPath => "The path parameters could not be parsed!",
+            AxumErrors::ValidationFailed => "Some of the fields of the request are invalid!",
+            AxumErrors::InvalidIdempotencyKey => {
+                "The Idempotency-Key header must be between 1 and 255 visible ascii characters!"
+            }
+            AxumErrors::IdempotencyKeyReused => {
+                "The Idempotency-Key was already used for a different request!"
+            }
+            AxumErrors::IdempotencyKeyInProgress => {
+                "A request with this Idempotency-Key is still being processed, retry later!"
+            }
+            AxumErrors::PreconditionFailed => {
+                "The table was changed since it was read, fetch it again and retry!"
+            }
+            AxumErrors::SerializationError => "Serde Serialization Error Occurred!",
+            AxumErrors::DeserializationError => "Serde Deserialization Error Occurred!",
+            AxumErrors::DBError => "Unexpected Error response from MongoDB!",
+            AxumErrors::BsonSerializeError => {
+                "Unexpected error from serializing struct into Bson Document!"
+            }
+            AxumErrors::BsonDeserializeError => {
+                "Unexpected error from deserializing struct into Bson Document!"
+            }
+        }
+    }
+}
+
+impl fmt::Display for AxumErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
 }
 
-#[derive(Serialize, Deserialize)]
+/// RFC 7807 problem details, plus a few extension members of our own.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
 pub struct AxumErrorResponse {
+    /// uri identifying the kind of problem, `/problems/<code>`
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    /// stable machine readable code of the error
+    pub code: String,
+    /// name of the error, kept for clients from before problem details
     pub error_type: String,
+    /// the same as `detail`, kept for clients from before problem details
     pub error_message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// what is wrong with each offending field, for validation errors
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct FieldError {
+    /// path to the field, e.g. `orders[2]`
+    pub field: String,
+    pub code: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, code: &str, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            code: code.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl AxumErrorResponse {
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = detail.into();
+        self.error_message = self.detail.clone();
+        self
+    }
+
+    pub fn with_errors(mut self, errors: Vec<FieldError>) -> Self {
+        self.errors = errors;
+        self
+    }
 }
 
 impl From<AxumErrors> for AxumErrorResponse {
     fn from(axum_errors: AxumErrors) -> Self {
-        match axum_errors {
-            AxumErrors::NotFound => Self {
-                error_type: AxumErrors::NotFound.to_string(),
-                error_message: "Document Was Not Found!".to_string(),
-            },
-            AxumErrors::SerializationError => Self {
-                error_type: AxumErrors::SerializationError.to_string(),
-                error_message: "Serde Serialization Error Occurred!".to_string(),
-            },
-            AxumErrors::DeserializationError => Self {
-                error_type: AxumErrors::DeserializationError.to_string(),
-                error_message: "Serde Deserialization Error Occurred!".to_string(),
-            },
-            AxumErrors::DBError => Self {
-                error_type: AxumErrors::DBError.to_string(),
-                error_message: "Unexpected Error response from MongoDB!".to_string(),
-            },
-            AxumErrors::BsonSerializeError => Self {
-                error_type: AxumErrors::BsonSerializeError.to_string(),
-                error_message: "Unexpected error from serializing struct into Bson Document!"
-                    .to_string(),
-            },
-            AxumErrors::BsonDeserializeError => Self {
-                error_type: AxumErrors::BsonDeserializeError.to_string(),
-                error_message: "Unexpected error from deserializing struct into Bson Document!"
-                    .to_string(),
-            },
+        Self {
+            problem_type: format!("/problems/{}", axum_errors.code()),
+            title: axum_errors.title().to_string(),
+            status: axum_errors.status_code().as_u16(),
+            detail: axum_errors.detail().to_string(),
+            code: axum_errors.code().to_string(),
+            error_type: axum_errors.to_string(),
+            error_message: axum_errors.detail().to_string(),
+            request_id: current_request_id(),
+            errors: vec![],
         }
     }
 }
+
+impl From<AxumErrors> for Box<AxumErrorResponse> {
+    fn from(axum_errors: AxumErrors) -> Self {
+        Box::new(axum_errors.into())
+    }
+}
+
+impl From<JsonRejection> for ErrorResponse {
+    fn from(rejection: JsonRejection) -> Self {
+        let axum_error = match rejection {
+            JsonRejection::MissingJsonContentType(_) => AxumErrors::UnsupportedMediaType,
+            _ => AxumErrors::InvalidJsonBody,
+        };
+        AxumErrorResponse::from(axum_error)
+            .with_detail(rejection.body_text())
+            .into()
+    }
+}
+
+impl From<QueryRejection> for ErrorResponse {
+    fn from(rejection: QueryRejection) -> Self {
+        AxumErrorResponse::from(AxumErrors::InvalidQuery)
+            .with_detail(rejection.body_text())
+            .into()
+    }
+}
+
+impl From<axum_extra::extract::QueryRejection> for ErrorResponse {
+    fn from(rejection: axum_extra::extract::QueryRejection) -> Self {
+        AxumErrorResponse::from(AxumErrors::InvalidQuery)
+            .with_detail(rejection.to_string())
+            .into()
+    }
+}
+
+impl From<PathRejection> for ErrorResponse {
+    fn from(rejection: PathRejection) -> Self {
+        AxumErrorResponse::from(AxumErrors::InvalidPath)
+            .with_detail(rejection.body_text())
+            .into()
+    }
+}
@@ -0,0 +1,190 @@
+use std::convert::Infallible;
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap, HeaderName, HeaderValue},
+    response::{IntoResponseParts, ResponseParts},
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+// Tables are versioned, the version is bumped on every change and doubles as the entity tag, e.g. `"3"`.
+// Reads add a digest of what they answered, e.g. `"3-5e0f1a2b3c4d5e6f"`, as the cook statuses and times
+// in there move on while the version stays put. Either way the version in front is what `If-Match` checks.
+
+/// Sets the `ETag` header to the given table version.
+pub struct ETag(pub i64);
+
+impl ETag {
+    pub fn header_value(&self) -> HeaderValue {
+        // a quoted number is always a valid header value
+        HeaderValue::from_str(&format!("\"{}\"", self.0))
+            .unwrap_or_else(|_| HeaderValue::from_static("\"0\""))
+    }
+}
+
+impl IntoResponseParts for ETag {
+    type Error = Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        res.headers_mut().insert(header::ETAG, self.header_value());
+        Ok(res)
+    }
+}
+
+/// Sets the `ETag` header of a read to the table version and a digest of the body that goes with it.
+pub struct ReadETag {
+    pub version: i64,
+    pub digest: String,
+}
+
+impl ReadETag {
+    pub fn of<T: Serialize>(version: i64, body: &T) -> Self {
+        // serializing a response body that is about to be sent can't fail in a way that matters here
+        let body = serde_json::to_vec(body).unwrap_or_default();
+        Self {
+            version,
+            digest: hex::encode(&Sha256::digest(&body)[..8]),
+        }
+    }
+
+    /// The tag without its quotes.
+    pub fn tag(&self) -> String {
+        format!("{}-{}", self.version, self.digest)
+    }
+
+    pub fn header_value(&self) -> HeaderValue {
+        // a number and a hex digest are always a valid header value
+        HeaderValue::from_str(&format!("\"{}\"", self.tag()))
+            .unwrap_or_else(|_| ETag(self.version).header_value())
+    }
+}
+
+impl IntoResponseParts for ReadETag {
+    type Error = Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        res.headers_mut().insert(header::ETAG, self.header_value());
+        Ok(res)
+    }
+}
+
+/// One tag of a conditional header, without its quotes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityTag {
+    pub weak: bool,
+    pub tag: String,
+}
+
+impl EntityTag {
+    /// The table version the tag was made from, `None` for tags that aren't ours.
+    pub fn version(&self) -> Option<i64> {
+        self.tag.split('-').next()?.parse::<i64>().ok()
+    }
+}
+
+/// The entity tags listed in a conditional header.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EntityTags {
+    /// `*`, any current version
+    Any,
+    /// `"3", W/"4-5e0f1a2b3c4d5e6f"`, tags that aren't quoted are kept empty so they never match
+    Tags(Vec<EntityTag>),
+}
+
+impl EntityTags {
+    fn from_headers(headers: &HeaderMap, name: &HeaderName) -> Option<Self> {
+        let values = headers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|tag| tag.trim())
+            .filter(|tag| !tag.is_empty())
+            .collect::<Vec<&str>>();
+
+        if values.is_empty() {
+            return None;
+        }
+        if values.contains(&"*") {
+            return Some(EntityTags::Any);
+        }
+        Some(EntityTags::Tags(
+            values
+                .into_iter()
+                .map(|tag| {
+                    let (weak, tag) = match tag.strip_prefix("W/") {
+                        Some(tag) => (true, tag),
+                        None => (false, tag),
+                    };
+                    let tag = tag
+                        .strip_prefix('"')
+                        .and_then(|tag| tag.strip_suffix('"'))
+                        .unwrap_or_default();
+                    EntityTag {
+                        weak,
+                        tag: tag.to_string(),
+                    }
+                })
+                .collect(),
+        ))
+    }
+}
+
+/// `If-Match` of a mutating request, the change only goes through if the table is still at one of these versions.
+pub struct IfMatch(pub Option<EntityTags>);
+
+impl IfMatch {
+    /// The versions the table has to be at, `None` when any version will do.
+    /// Weak tags never match here, as RFC 9110 asks for a strong comparison.
+    pub fn expected_versions(&self) -> Option<Vec<i64>> {
+        match &self.0 {
+            None | Some(EntityTags::Any) => None,
+            Some(EntityTags::Tags(tags)) => Some(
+                tags.iter()
+                    .filter(|tag| !tag.weak)
+                    .filter_map(|tag| tag.version())
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for IfMatch {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self(EntityTags::from_headers(
+            &parts.headers,
+            &header::IF_MATCH,
+        )))
+    }
+}
+
+/// `If-None-Match` of a read, the caller already has one of these copies.
+pub struct IfNoneMatch(pub Option<EntityTags>);
+
+impl IfNoneMatch {
+    /// Whether the caller's copy is still current, using the weak comparison of RFC 9110.
+    pub fn matches(&self, tag: &str) -> bool {
+        match &self.0 {
+            None => false,
+            Some(EntityTags::Any) => true,
+            Some(EntityTags::Tags(tags)) => tags.iter().any(|other| other.tag == tag),
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for IfNoneMatch {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self(EntityTags::from_headers(
+            &parts.headers,
+            &header::IF_NONE_MATCH,
+        )))
+    }
+}
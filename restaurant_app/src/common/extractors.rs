@@ -0,0 +1,214 @@
+use std::{convert::Infallible, ops::Deref};
+
+use axum::{
+    async_trait,
+    extract::{FromRequest, FromRequestParts, Request},
+    http::request::Parts,
+    response::{IntoResponse, Response},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use validator::ValidateArgs;
+
+use super::{
+    config::{validation_limits, ValidationLimits},
+    errors::{AxumErrorResponse, AxumErrors, ErrorResponse},
+    validation::field_errors,
+};
+
+// Drop-in replacements for the axum extractors whose rejections are our problem+json errors
+// instead of axum's plain text ones.
+
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(ErrorResponse))]
+pub struct Json<T>(pub T);
+
+impl<T> Deref for Json<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(ErrorResponse))]
+pub struct Path<T>(pub T);
+
+impl<T> Deref for Path<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(ErrorResponse))]
+pub struct Query<T>(pub T);
+
+impl<T> Deref for Query<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Query supporting repeated keys for `Vec` fields, e.g. `?item_names=Udon&item_names=Ramen`.
+#[derive(FromRequestParts)]
+#[from_request(via(axum_extra::extract::Query), rejection(ErrorResponse))]
+pub struct ExtraQuery<T>(pub T);
+
+impl<T> Deref for ExtraQuery<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Request types checked by the validating extractors against the configured limits.
+pub trait Validated: for<'v> ValidateArgs<'v, Args = &'v ValidationLimits> {}
+
+impl<T> Validated for T where T: for<'v> ValidateArgs<'v, Args = &'v ValidationLimits> {}
+
+fn validate<T: Validated>(value: &T) -> Result<(), ErrorResponse> {
+    value
+        .validate_with_args(validation_limits())
+        .map_err(|errors| {
+            AxumErrorResponse::from(AxumErrors::ValidationFailed)
+                .with_errors(field_errors(&errors))
+                .into()
+        })
+}
+
+// Same as the extractors above, but once parsed the value is validated as well,
+// answering with a 422 listing every offending field.
+
+pub struct ValidJson<T>(pub T);
+
+impl<T> Deref for ValidJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidJson<T>
+where
+    T: DeserializeOwned + Validated,
+    S: Send + Sync,
+{
+    type Rejection = ErrorResponse;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(request, state).await?;
+        validate(&value)?;
+        Ok(Self(value))
+    }
+}
+
+pub struct ValidPath<T>(pub T);
+
+impl<T> Deref for ValidPath<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ValidPath<T>
+where
+    T: DeserializeOwned + Validated + Send,
+    S: Send + Sync,
+{
+    type Rejection = ErrorResponse;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(value) = Path::<T>::from_request_parts(parts, state).await?;
+        validate(&value)?;
+        Ok(Self(value))
+    }
+}
+
+pub struct ValidQuery<T>(pub T);
+
+impl<T> Deref for ValidQuery<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ValidQuery<T>
+where
+    T: DeserializeOwned + Validated,
+    S: Send + Sync,
+{
+    type Rejection = ErrorResponse;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::<T>::from_request_parts(parts, state).await?;
+        validate(&value)?;
+        Ok(Self(value))
+    }
+}
+
+pub struct ValidExtraQuery<T>(pub T);
+
+impl<T> Deref for ValidExtraQuery<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ValidExtraQuery<T>
+where
+    T: DeserializeOwned + Validated,
+    S: Send + Sync,
+{
+    type Rejection = ErrorResponse;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let ExtraQuery(value) = ExtraQuery::<T>::from_request_parts(parts, state).await?;
+        validate(&value)?;
+        Ok(Self(value))
+    }
+}
+
+/// The api version the request came in on, set by the router serving it, the newest one when none is.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ApiVersion {
+    /// only knows the `InProgress` and `Done` cook statuses
+    V1,
+    #[default]
+    V2,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ApiVersion {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<ApiVersion>()
+            .copied()
+            .unwrap_or_default())
+    }
+}
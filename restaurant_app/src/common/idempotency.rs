@@ -0,0 +1,226 @@
+use std::sync::Arc;
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use tracing::{error, info, warn};
+
+use super::{
+    config::{idempotency_key_ttl, idempotency_lock_lease},
+    errors::{AxumErrorResponse, AxumErrors, ErrorResponse},
+    idempotency_db::{DBIdempotencyTrait, IdempotencyRecord, StoredResponse},
+};
+use crate::AppState;
+
+pub static IDEMPOTENCY_KEY_HEADER: HeaderName = HeaderName::from_static("idempotency-key");
+/// Set on responses that were replayed from a previous request with the same key.
+pub static IDEMPOTENT_REPLAYED_HEADER: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+// same as the default body limit of axum's Json
+const MAX_BODY_SIZE: usize = 2 * 1024 * 1024;
+
+/// Makes retries of the create and move end points safe.
+/// The first request with a given `Idempotency-Key` runs normally and its response is stored for
+/// `IDEMPOTENCY_KEY_TTL_SECS`. Retrying with the same key, path and body replays the stored response
+/// instead of running the handler again, while reusing the key for anything else is answered with a 409.
+/// While the first request runs the key is only held for `IDEMPOTENCY_LOCK_LEASE_SECS`, so a request that
+/// never answers doesn't lock its retries out. Server errors aren't stored so the request can simply be retried.
+pub async fn idempotency_middleware(
+    State(app_state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let key = match request.headers().get(&IDEMPOTENCY_KEY_HEADER) {
+        Some(header) => match parse_key(header) {
+            Some(key) => key,
+            None => return ErrorResponse::from(AxumErrors::InvalidIdempotencyKey).into_response(),
+        },
+        None => return next.run(request).await,
+    };
+
+    let (parts, body) = request.into_parts();
+    let body = match to_bytes(body, MAX_BODY_SIZE).await {
+        Ok(body) => body,
+        Err(e) => {
+            return ErrorResponse::from(
+                AxumErrorResponse::from(AxumErrors::InvalidJsonBody).with_detail(e.to_string()),
+            )
+            .into_response()
+        }
+    };
+
+    //mongo expires the keys by the wall clock, so they are never timed by the app's clock, which may be a fake one
+    let now = Utc::now();
+    let record = IdempotencyRecord {
+        key,
+        fingerprint: fingerprint(parts.method.as_str(), parts.uri.path(), &body),
+        expires_at: now + idempotency_lock_lease(),
+        response: None,
+    };
+
+    let db = &app_state.db;
+    match db.reserve_idempotency_key(&record, now).await {
+        Ok(None) => (),
+        Ok(Some(existing)) if existing.fingerprint != record.fingerprint => {
+            return ErrorResponse::from(AxumErrors::IdempotencyKeyReused).into_response()
+        }
+        Ok(Some(IdempotencyRecord {
+            response: Some(stored_response),
+            ..
+        })) => {
+            info!(idempotency_key = %record.key, "replaying the stored response");
+            return replay(stored_response);
+        }
+        Ok(Some(_)) => {
+            return ErrorResponse::from(AxumErrors::IdempotencyKeyInProgress).into_response()
+        }
+        Err(e) => return e.into_response(),
+    }
+
+    let reservation = Reservation {
+        app_state: app_state.clone(),
+        record,
+        held: true,
+    };
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+
+    if response.status().is_server_error() {
+        reservation.release().await;
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            error!("Was unable to read the response to store it! Error: {e}");
+            reservation.release().await;
+            return ErrorResponse::from(AxumErrors::SerializationError).into_response();
+        }
+    };
+
+    let stored_response = StoredResponse {
+        status: parts.status.as_u16(),
+        content_type: parts
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .map(|content_type| content_type.to_string()),
+        etag: parts
+            .headers
+            .get(header::ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(|etag| etag.to_string()),
+        body: String::from_utf8_lossy(&body).to_string(),
+    };
+    // the request already went through, so failing to store the response is only worth a log line
+    let record = reservation.keep();
+    let expires_at = Utc::now() + idempotency_key_ttl();
+    if let Err(e) = db
+        .complete_idempotency_key(&record, &stored_response, expires_at)
+        .await
+    {
+        error!(
+            "Was unable to store the response of idempotency key {}! Error: {}",
+            record.key, e.error.detail
+        );
+    }
+
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// The key held by a running request.
+/// Dropping it without [`Reservation::keep`] gives the key back, so a request whose handler never finished,
+/// because the client went away or it panicked, doesn't hold the key until its lease runs out.
+struct Reservation {
+    app_state: Arc<AppState>,
+    record: IdempotencyRecord,
+    held: bool,
+}
+
+impl Reservation {
+    /// Gives the key back right away.
+    async fn release(mut self) {
+        self.held = false;
+        release_key(&self.app_state, &self.record).await;
+    }
+
+    /// Keeps the key, its response is about to be stored.
+    fn keep(mut self) -> IdempotencyRecord {
+        self.held = false;
+        self.record.clone()
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if !self.held {
+            return;
+        }
+        let app_state = self.app_state.clone();
+        let record = self.record.clone();
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(async move { release_key(&app_state, &record).await });
+            }
+            Err(_) => warn!(
+                "Was unable to release the idempotency key {}, it is held until its lease runs out",
+                record.key
+            ),
+        }
+    }
+}
+
+async fn release_key(app_state: &AppState, record: &IdempotencyRecord) {
+    if let Err(e) = app_state.db.release_idempotency_key(record).await {
+        error!(
+            "Was unable to release the idempotency key {}! Error: {}",
+            record.key, e.error.detail
+        );
+    }
+}
+
+fn parse_key(header: &HeaderValue) -> Option<String> {
+    let key = header.to_str().ok()?;
+    let is_valid = !key.is_empty()
+        && key.len() <= MAX_IDEMPOTENCY_KEY_LEN
+        && key.chars().all(|c| c.is_ascii_graphic());
+    is_valid.then(|| key.to_string())
+}
+
+fn fingerprint(method: &str, path: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(path.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(body);
+    hex::encode(hasher.finalize())
+}
+
+fn replay(stored_response: StoredResponse) -> Response {
+    let mut response = Response::builder().status(stored_response.status);
+    if let Some(content_type) = stored_response.content_type {
+        response = response.header(header::CONTENT_TYPE, content_type);
+    }
+    if let Some(etag) = stored_response.etag {
+        response = response.header(header::ETAG, etag);
+    }
+    match response
+        .header(IDEMPOTENT_REPLAYED_HEADER.clone(), "true")
+        .body(Body::from(stored_response.body))
+    {
+        Ok(response) => response,
+        Err(e) => {
+            error!("Was unable to rebuild the stored response! Error: {e}");
+            ErrorResponse::from(AxumErrors::DeserializationError).into_response()
+        }
+    }
+}
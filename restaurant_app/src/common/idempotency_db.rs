@@ -0,0 +1,287 @@
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use mongodb::{
+    bson::{doc, serde_helpers::chrono_datetime_as_bson_datetime, Bson},
+    options::{FindOneAndUpdateOptions, IndexOptions, ReturnDocument},
+    IndexModel,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::{error, Instrument};
+
+use super::database;
+use super::database_helpers::is_duplicate_key_error;
+use super::errors::{AxumErrorResponse, AxumErrors, ErrorResponse};
+use super::telemetry::mongo_span;
+
+/// A request made with an `Idempotency-Key`, along with its response once there is one.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct IdempotencyRecord {
+    pub key: String,
+    /// hash of the method, path and body the key was first used with
+    pub fingerprint: String,
+    /// mongo drops the record once this is in the past
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+    /// `None` while the first request is still being processed
+    pub response: Option<StoredResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub content_type: Option<String>,
+    #[serde(default)]
+    pub etag: Option<String>,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait DBIdempotencyTrait {
+    async fn set_up_idempotency_indexes(&self) -> Result<(), ErrorResponse>;
+    /// Claims the key for `record`, returning whatever was stored under it instead if the key is already taken.
+    /// A record that expired before `now`, a finished one or an abandoned reservation, is taken over.
+    async fn reserve_idempotency_key(
+        &self,
+        record: &IdempotencyRecord,
+        now: DateTime<Utc>,
+    ) -> Result<Option<IdempotencyRecord>, ErrorResponse>;
+    /// Stores the response of the reservation `record`, keeping it until `expires_at`.
+    async fn complete_idempotency_key(
+        &self,
+        record: &IdempotencyRecord,
+        response: &StoredResponse,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), ErrorResponse>;
+    /// Frees the reservation `record` whose request failed, so it can be retried.
+    async fn release_idempotency_key(
+        &self,
+        record: &IdempotencyRecord,
+    ) -> Result<(), ErrorResponse>;
+}
+
+#[faux::methods]
+#[async_trait]
+impl DBIdempotencyTrait for database::DB {
+    async fn set_up_idempotency_indexes(&self) -> Result<(), ErrorResponse> {
+        let idempotency_collection = self
+            .db
+            .database("request_management")
+            .collection::<IdempotencyRecord>("idempotency_keys");
+
+        let indexes = vec![
+            IndexModel::builder()
+                .keys(doc! { "key": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "expires_at": 1 })
+                .options(
+                    IndexOptions::builder()
+                        .expire_after(Duration::from_secs(0))
+                        .build(),
+                )
+                .build(),
+        ];
+
+        match idempotency_collection
+            .create_indexes(indexes, None)
+            .instrument(mongo_span("create_indexes", "idempotency_keys"))
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!("Unexpected error occured while creating the idempotency key indexes. Error: {e}");
+                Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                })
+            }
+        }
+    }
+
+    async fn reserve_idempotency_key(
+        &self,
+        record: &IdempotencyRecord,
+        now: DateTime<Utc>,
+    ) -> Result<Option<IdempotencyRecord>, ErrorResponse> {
+        let idempotency_collection = self
+            .db
+            .database("request_management")
+            .collection::<IdempotencyRecord>("idempotency_keys");
+
+        let record_as_bson = match mongodb::bson::to_document(record) {
+            Ok(document) => document,
+            Err(e) => {
+                error!(
+                    "Unexpected error occured while serializing the idempotency record! Error: {e}"
+                );
+                return Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::BsonSerializeError.into(),
+                });
+            }
+        };
+
+        // records past their expiry may linger until mongo's ttl monitor comes around, those are up for grabs
+        let filter = doc! {
+            "key": &record.key,
+            "expires_at": { "$gt": Bson::DateTime(now.into()) },
+        };
+        let update = doc! { "$setOnInsert": record_as_bson.clone() };
+        let options = FindOneAndUpdateOptions::builder()
+            .upsert(true)
+            .return_document(ReturnDocument::Before)
+            .build();
+
+        match idempotency_collection
+            .find_one_and_update(filter, update, options)
+            .instrument(mongo_span("find_one_and_update", "idempotency_keys"))
+            .await
+        {
+            Ok(opt_record) => Ok(opt_record),
+            Err(e) if is_duplicate_key_error(&e) => {
+                // either another request claimed the key in the meantime, or an expired record is in the way
+                let filter = doc! { "key": &record.key };
+                match idempotency_collection
+                    .find_one(filter.clone(), None)
+                    .instrument(mongo_span("find_one", "idempotency_keys"))
+                    .await
+                {
+                    Ok(Some(existing)) if existing.expires_at > now => Ok(Some(existing)),
+                    Ok(_) => {
+                        let filter = doc! {
+                            "key": &record.key,
+                            "expires_at": { "$lte": Bson::DateTime(now.into()) },
+                        };
+                        match idempotency_collection
+                            .replace_one(filter, record, None)
+                            .instrument(mongo_span("replace_one", "idempotency_keys"))
+                            .await
+                        {
+                            Ok(result) if result.modified_count == 1 => Ok(None),
+                            // lost the race for the expired record
+                            Ok(_) => Err(ErrorResponse::from(AxumErrors::IdempotencyKeyInProgress)),
+                            Err(e) => {
+                                error!("Unexpected error occured while replacing the expired idempotency record. Error: {e}");
+                                Err(ErrorResponse {
+                                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                                    error: AxumErrors::DBError.into(),
+                                })
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        error!("Unexpected error occured while finding the idempotency record. Error: {e}");
+                        Err(ErrorResponse {
+                            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                            error: AxumErrors::DBError.into(),
+                        })
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Unexpected error occured while reserving the idempotency key. Error: {e}");
+                Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                })
+            }
+        }
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        record: &IdempotencyRecord,
+        response: &StoredResponse,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), ErrorResponse> {
+        let idempotency_collection = self
+            .db
+            .database("request_management")
+            .collection::<IdempotencyRecord>("idempotency_keys");
+
+        let response_as_bson = match mongodb::bson::to_bson(response) {
+            Ok(bson) => bson,
+            Err(e) => {
+                error!(
+                    "Unexpected error occured while serializing the stored response! Error: {e}"
+                );
+                return Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::BsonSerializeError.into(),
+                });
+            }
+        };
+
+        // a request that outran its lease may have lost the key to a retry, which then owns it
+        let filter = doc! {
+            "key": &record.key,
+            "fingerprint": &record.fingerprint,
+            "expires_at": Bson::DateTime(record.expires_at.into()),
+            "response": Bson::Null,
+        };
+        let update = doc! {
+            "$set": {
+                "response": response_as_bson,
+                "expires_at": Bson::DateTime(expires_at.into()),
+            }
+        };
+
+        match idempotency_collection
+            .update_one(filter, update, None)
+            .instrument(mongo_span("update_one", "idempotency_keys"))
+            .await
+        {
+            Ok(result) if result.matched_count == 1 => Ok(()),
+            // the lease ran out and a retry took the key over, its response is the one kept
+            Ok(_) => Err(ErrorResponse::from(
+                AxumErrorResponse::from(AxumErrors::IdempotencyKeyInProgress).with_detail(
+                    "The key was taken over by a retry before the response was stored",
+                ),
+            )),
+            Err(e) => {
+                error!(
+                    "Unexpected error occured while storing the idempotent response. Error: {e}"
+                );
+                Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                })
+            }
+        }
+    }
+
+    async fn release_idempotency_key(
+        &self,
+        record: &IdempotencyRecord,
+    ) -> Result<(), ErrorResponse> {
+        let idempotency_collection = self
+            .db
+            .database("request_management")
+            .collection::<IdempotencyRecord>("idempotency_keys");
+
+        let filter = doc! {
+            "key": &record.key,
+            "fingerprint": &record.fingerprint,
+            "expires_at": Bson::DateTime(record.expires_at.into()),
+            "response": Bson::Null,
+        };
+
+        match idempotency_collection
+            .delete_one(filter, None)
+            .instrument(mongo_span("delete_one", "idempotency_keys"))
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!("Unexpected error occured while releasing the idempotency key. Error: {e}");
+                Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                })
+            }
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use futures::stream::TryStreamExt;
+use mongodb::{
+    bson::{doc, Document},
+    error::ErrorKind,
+    options::IndexOptions,
+    IndexModel,
+};
+use serde::Serialize;
+use tracing::{error, info, warn, Instrument};
+
+use super::database::{self, DB};
+use super::errors::{AxumErrors, ErrorResponse};
+use super::telemetry::mongo_span;
+
+/// An index on a collection, the declared ones are created on start up.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct IndexSpec {
+    pub database: &'static str,
+    pub collection: &'static str,
+    pub name: String,
+    pub keys: Document,
+    pub unique: bool,
+}
+
+impl IndexSpec {
+    fn new(
+        database: &'static str,
+        collection: &'static str,
+        name: &str,
+        keys: Document,
+        unique: bool,
+    ) -> Self {
+        Self {
+            database,
+            collection,
+            name: name.to_string(),
+            keys,
+            unique,
+        }
+    }
+
+    // the name doesn't matter, an index made by hand with the same keys does the same job
+    fn same_index(&self, other: &IndexSpec) -> bool {
+        self.database == other.database
+            && self.collection == other.collection
+            && self.keys == other.keys
+            && self.unique == other.unique
+    }
+}
+
+/// The indexes the queries rely on, the orders are embedded in their table so theirs are multikey.
+pub fn declared_indexes() -> Vec<IndexSpec> {
+    vec![
+        IndexSpec::new(
+            "table_management",
+            "tables",
+            "table_id_unique",
+            doc! { "table_id": 1 },
+            true,
+        ),
+        // get_order's $elemMatch
+        IndexSpec::new(
+            "table_management",
+            "tables",
+            "orders_order_id",
+            doc! { "orders.order_id": 1 },
+            false,
+        ),
+        // list_tables' item name filter
+        IndexSpec::new(
+            "table_management",
+            "tables",
+            "orders_item_item_name",
+            doc! { "orders.item.item_name": 1 },
+            false,
+        ),
+        // get_fired_orders' window
+        IndexSpec::new(
+            "table_management",
+            "tables",
+            "orders_fired_time",
+            doc! { "orders.fired_time": 1 },
+            false,
+        ),
+        IndexSpec::new(
+            "table_management",
+            "tables",
+            "orders_ordered_time",
+            doc! { "orders.ordered_time": 1 },
+            false,
+        ),
+        IndexSpec::new(
+            "item_management",
+            "items",
+            "item_name_unique",
+            doc! { "item_name": 1 },
+            true,
+        ),
+    ]
+}
+
+/// A declared index the database refused to build, e.g. a unique one over values that are already duplicated.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FailedIndex {
+    pub index: IndexSpec,
+    pub reason: String,
+}
+
+/// How the indexes in the database differ from the declared ones.
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct IndexReport {
+    /// declared but not in the database
+    pub missing: Vec<IndexSpec>,
+    /// in the database on a collection with declared indexes, but not declared, `_id` aside
+    pub extra: Vec<IndexSpec>,
+    /// declared, but the database couldn't build them, these aren't listed as missing too
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failed: Vec<FailedIndex>,
+}
+
+impl IndexReport {
+    /// Moves the indexes that failed to build out of the missing ones.
+    pub fn with_failed(mut self, failed: Vec<FailedIndex>) -> Self {
+        self.missing
+            .retain(|index| !failed.iter().any(|failed| failed.index.same_index(index)));
+        self.failed = failed;
+        self
+    }
+
+    pub fn log(&self) {
+        if self.missing.is_empty() && self.extra.is_empty() && self.failed.is_empty() {
+            info!("Every declared index is in the DB");
+        }
+        for failed in &self.failed {
+            error!(
+                "The index {} on {}.{} couldn't be built, the queries on it scan the collection until it is fixed. Error: {}",
+                failed.index.name, failed.index.database, failed.index.collection, failed.reason
+            );
+        }
+        for index in &self.missing {
+            warn!(
+                "The index {} on {}.{} is missing",
+                index.name, index.database, index.collection
+            );
+        }
+        for index in &self.extra {
+            warn!(
+                "The index {} on {}.{} isn't declared",
+                index.name, index.database, index.collection
+            );
+        }
+    }
+}
+
+// DuplicateKey, IndexOptionsConflict and IndexKeySpecsConflict, trying again won't build the index
+const REFUSED_INDEX_CODES: [i32; 3] = [11000, 85, 86];
+
+#[async_trait]
+pub trait DBIndexTrait {
+    /// Creates the declared indexes, the ones already in the database are left as they are.
+    /// The ones the database refuses, a unique one over duplicates or one clashing with an index made by hand,
+    /// are returned instead of failing the rest.
+    async fn set_up_indexes(&self) -> Result<Vec<FailedIndex>, ErrorResponse>;
+    /// The indexes of the collections with declared ones, except `_id`.
+    async fn list_indexes(&self) -> Result<Vec<IndexSpec>, ErrorResponse>;
+}
+
+#[faux::methods]
+#[async_trait]
+impl DBIndexTrait for database::DB {
+    async fn set_up_indexes(&self) -> Result<Vec<FailedIndex>, ErrorResponse> {
+        let existing = self.list_indexes().await?;
+        let mut failed = vec![];
+        //one at a time, so an index that can't be built doesn't keep the others from being built
+        for index in declared_indexes() {
+            //an index made by hand with the same keys does the same job, whatever it is called
+            if existing.iter().any(|other| index.same_index(other)) {
+                continue;
+            }
+
+            let model = IndexModel::builder()
+                .keys(index.keys.clone())
+                .options(
+                    IndexOptions::builder()
+                        .name(index.name.clone())
+                        .unique(index.unique)
+                        .build(),
+                )
+                .build();
+
+            match self
+                .db
+                .database(index.database)
+                .collection::<Document>(index.collection)
+                .create_index(model, None)
+                .instrument(mongo_span("create_index", index.collection))
+                .await
+            {
+                Ok(_) => (),
+                // duplicate key, the documents already break the unique index,
+                // or an index on the same keys with other options or under the same name with other keys is in the way
+                Err(e) if matches!(*e.kind, ErrorKind::Command(ref command) if REFUSED_INDEX_CODES.contains(&command.code)) =>
+                {
+                    failed.push(FailedIndex {
+                        index,
+                        reason: e.to_string(),
+                    });
+                }
+                Err(e) => {
+                    error!(
+                        "Unexpected error occured while creating the index {} of {}.{}. Error: {e}",
+                        index.name, index.database, index.collection
+                    );
+                    return Err(ErrorResponse {
+                        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                        error: AxumErrors::DBError.into(),
+                    });
+                }
+            }
+        }
+        Ok(failed)
+    }
+
+    async fn list_indexes(&self) -> Result<Vec<IndexSpec>, ErrorResponse> {
+        let mut indexes = vec![];
+        for (database, collection) in declared_collections() {
+            let listed = self
+                .db
+                .database(database)
+                .collection::<Document>(collection)
+                .list_indexes(None)
+                .instrument(mongo_span("list_indexes", collection))
+                .await;
+            let models = match listed {
+                Ok(cursor) => cursor.try_collect::<Vec<IndexModel>>().await,
+                Err(e) => Err(e),
+            };
+            let models = match models {
+                Ok(models) => models,
+                // a collection that doesn't exist yet has no indexes
+                Err(e) if matches!(*e.kind, ErrorKind::Command(ref command) if command.code == 26) =>
+                {
+                    vec![]
+                }
+                Err(e) => {
+                    error!("Unexpected error occured while listing the indexes of {database}.{collection}. Error: {e}");
+                    return Err(ErrorResponse {
+                        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                        error: AxumErrors::DBError.into(),
+                    });
+                }
+            };
+
+            for model in models {
+                let options = model.options.unwrap_or_default();
+                let name = options.name.unwrap_or_default();
+                if name == "_id_" {
+                    continue;
+                }
+                indexes.push(IndexSpec::new(
+                    database,
+                    collection,
+                    &name,
+                    model.keys,
+                    options.unique.unwrap_or(false),
+                ));
+            }
+        }
+        Ok(indexes)
+    }
+}
+
+/// Compares the indexes in the database with the declared ones.
+pub async fn check_indexes(db: &DB) -> Result<IndexReport, ErrorResponse> {
+    Ok(compare_indexes(
+        &declared_indexes(),
+        &db.list_indexes().await?,
+    ))
+}
+
+pub fn compare_indexes(declared: &[IndexSpec], existing: &[IndexSpec]) -> IndexReport {
+    IndexReport {
+        missing: declared
+            .iter()
+            .filter(|index| !existing.iter().any(|other| index.same_index(other)))
+            .cloned()
+            .collect(),
+        extra: existing
+            .iter()
+            .filter(|index| !declared.iter().any(|other| index.same_index(other)))
+            .cloned()
+            .collect(),
+        failed: vec![],
+    }
+}
+
+fn declared_collections() -> Vec<(&'static str, &'static str)> {
+    let mut collections = vec![];
+    for index in declared_indexes() {
+        let collection = (index.database, index.collection);
+        if !collections.contains(&collection) {
+            collections.push(collection);
+        }
+    }
+    collections
+}
@@ -0,0 +1,213 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use opentelemetry::metrics::Counter;
+use tokio::{sync::RwLock, time::Instant};
+use tracing::{info, warn};
+
+use super::{
+    database::DB, errors::ErrorResponse, models::restaurant_schema::Item, telemetry::meter,
+};
+use crate::{handlers::item_handler::item_db::DBTableTrait, AppState};
+
+/// The whole menu, kept in memory so placing orders and showing the menu don't have to query the items every time.
+///
+/// A lookup is a hit while the copy is younger than the ttl, and a miss when the menu has to be read again.
+/// Against a replica set the copy is also dropped as soon as an item changes, see [`watch_menu_changes`].
+/// Orders only move the stock counts, those are updated in place instead.
+/// A ttl of zero turns the cache off and every lookup queries the requested items.
+pub struct MenuCache {
+    ttl: Duration,
+    menu: RwLock<Option<CachedMenu>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    hit_counter: Counter<u64>,
+    miss_counter: Counter<u64>,
+}
+
+struct CachedMenu {
+    items: HashMap<String, Item>,
+    loaded_at: Instant,
+}
+
+impl CachedMenu {
+    fn is_fresh(&self, ttl: Duration) -> bool {
+        self.loaded_at.elapsed() < ttl
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MenuCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl MenuCache {
+    pub fn new(ttl: Duration) -> Self {
+        let meter = meter();
+        Self {
+            ttl,
+            menu: RwLock::new(None),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            hit_counter: meter
+                .u64_counter("menu_cache.hits")
+                .with_description("Item lookups answered from the cached menu")
+                .build(),
+            miss_counter: meter
+                .u64_counter("menu_cache.misses")
+                .with_description("Item lookups that had to read the menu from the database")
+                .build(),
+        }
+    }
+
+    /// Reads the menu up front so the first orders don't pay for it.
+    pub async fn load(&self, db: &DB) -> Result<(), ErrorResponse> {
+        if self.ttl.is_zero() {
+            return Ok(());
+        }
+        let mut menu = self.menu.write().await;
+        *menu = Some(read_menu(db).await?);
+        Ok(())
+    }
+
+    /// Drops the cached menu, the next lookup reads it again.
+    pub async fn invalidate(&self) {
+        *self.menu.write().await = None;
+    }
+
+    /// Counts the portions `reserve_stock` took off the cached items, so the copy doesn't have to be read again.
+    pub async fn take_portions(&self, portions: &HashMap<String, i64>) {
+        self.adjust_stock(portions, -1).await;
+    }
+
+    /// Counts the portions `release_stock` gave back on the cached items.
+    pub async fn give_back_portions(&self, portions: &HashMap<String, i64>) {
+        self.adjust_stock(portions, 1).await;
+    }
+
+    async fn adjust_stock(&self, portions: &HashMap<String, i64>, sign: i64) {
+        if let Some(menu) = self.menu.write().await.as_mut() {
+            for (item_name, quantity) in portions {
+                if let Some(stock) = menu
+                    .items
+                    .get_mut(item_name)
+                    .and_then(|item| item.stock.as_mut())
+                {
+                    *stock += sign * quantity;
+                }
+            }
+        }
+    }
+
+    /// The requested items that are on the menu, by name, names that aren't on it are left out.
+    pub async fn get_items(
+        &self,
+        db: &DB,
+        item_names: &[String],
+    ) -> Result<HashMap<String, Item>, ErrorResponse> {
+        if self.ttl.is_zero() {
+            self.record_miss();
+            return db.get_items_by_name(item_names).await;
+        }
+        self.with_menu(db, |items| pick(items, item_names)).await
+    }
+
+    /// Every item on the menu, in no particular order.
+    pub async fn get_menu(&self, db: &DB) -> Result<Vec<Item>, ErrorResponse> {
+        if self.ttl.is_zero() {
+            self.record_miss();
+            return db.get_menu().await;
+        }
+        self.with_menu(db, |items| items.values().cloned().collect())
+            .await
+    }
+
+    async fn with_menu<T>(
+        &self,
+        db: &DB,
+        read: impl Fn(&HashMap<String, Item>) -> T,
+    ) -> Result<T, ErrorResponse> {
+        if let Some(menu) = self.menu.read().await.as_ref() {
+            if menu.is_fresh(self.ttl) {
+                self.record_hit();
+                return Ok(read(&menu.items));
+            }
+        }
+
+        let mut menu = self.menu.write().await;
+        // another request may have read the menu while this one was waiting for the lock
+        if let Some(menu) = menu.as_ref() {
+            if menu.is_fresh(self.ttl) {
+                self.record_hit();
+                return Ok(read(&menu.items));
+            }
+        }
+        self.record_miss();
+        let fresh_menu = read_menu(db).await?;
+        let items = read(&fresh_menu.items);
+        *menu = Some(fresh_menu);
+        let stats = self.stats();
+        info!(
+            menu_cache.hits = stats.hits,
+            menu_cache.misses = stats.misses,
+            "read the menu into the cache"
+        );
+        Ok(items)
+    }
+
+    pub fn stats(&self) -> MenuCacheStats {
+        MenuCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    fn record_hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        self.hit_counter.add(1, &[]);
+    }
+
+    fn record_miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        self.miss_counter.add(1, &[]);
+    }
+}
+
+async fn read_menu(db: &DB) -> Result<CachedMenu, ErrorResponse> {
+    let items = db.get_menu().await?;
+    Ok(CachedMenu {
+        items: items
+            .into_iter()
+            .map(|item| (item.item_name.clone(), item))
+            .collect(),
+        loaded_at: Instant::now(),
+    })
+}
+
+fn pick(menu: &HashMap<String, Item>, item_names: &[String]) -> HashMap<String, Item> {
+    item_names
+        .iter()
+        .filter_map(|item_name| {
+            menu.get(item_name)
+                .map(|item| (item_name.clone(), item.clone()))
+        })
+        .collect()
+}
+
+/// Drops the cached menu whenever an item changes, for as long as the change stream stays open.
+/// Change streams need a replica set, on a standalone server the cache relies on its ttl alone.
+pub async fn watch_menu_changes(app_state: Arc<AppState>) {
+    if let Err(e) = app_state.db.watch_menu_changes(&app_state.menu_cache).await {
+        warn!(
+            "Stopped watching the items for changes, the menu cache is only refreshed by its ttl from now on. Error: {}",
+            e.error.detail
+        );
+    }
+}
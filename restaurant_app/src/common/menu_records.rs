@@ -0,0 +1,385 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use super::{
+    errors::{AxumErrorResponse, AxumErrors, ErrorResponse, FieldError},
+    models::restaurant_schema::{Category, Item, Modifier, RecipeIngredient, Station},
+};
+
+/// The file formats the menu is imported from and exported to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MenuFormat {
+    /// one row per item, the lists are `;` separated
+    Csv,
+    /// `{"records": [...]}` like the seed file, or just the list of items
+    Json,
+    /// the same shapes as json
+    Yaml,
+}
+
+impl MenuFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            MenuFormat::Csv => "text/csv",
+            MenuFormat::Json => "application/json",
+            MenuFormat::Yaml => "application/yaml",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            MenuFormat::Csv => "csv",
+            MenuFormat::Json => "json",
+            MenuFormat::Yaml => "yaml",
+        }
+    }
+
+    /// The format of a `Content-Type`, parameters like the charset are ignored.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        match content_type.split(';').next().unwrap_or("").trim() {
+            "text/csv" => Some(MenuFormat::Csv),
+            "application/json" => Some(MenuFormat::Json),
+            "application/yaml" | "application/x-yaml" | "text/yaml" => Some(MenuFormat::Yaml),
+            _ => None,
+        }
+    }
+
+    /// The format of a file by its extension.
+    pub fn from_path(path: &str) -> Option<Self> {
+        match path.rsplit_once('.')?.1.to_ascii_lowercase().as_str() {
+            "csv" => Some(MenuFormat::Csv),
+            "json" => Some(MenuFormat::Json),
+            "yaml" | "yml" => Some(MenuFormat::Yaml),
+            _ => None,
+        }
+    }
+}
+
+/// A row of the csv, the columns of the lists hold their elements separated by `;`,
+/// `Name:quantity` for the recipe and `Name:Kind` or `Name:Kind:price_delta` for the modifiers.
+#[derive(Serialize, Deserialize, Debug)]
+struct CsvRecord {
+    item_name: String,
+    cook_time: i64,
+    #[serde(default)]
+    category: Option<Category>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    display_order: Option<i64>,
+    #[serde(default)]
+    station: Option<Station>,
+    #[serde(default)]
+    available: Option<bool>,
+    #[serde(default)]
+    stock: Option<i64>,
+    #[serde(default)]
+    modifiers: Option<String>,
+    #[serde(default)]
+    recipe: Option<String>,
+    #[serde(default)]
+    allergens: Option<String>,
+    #[serde(default)]
+    diets: Option<String>,
+    #[serde(default)]
+    dayparts: Option<String>,
+}
+
+const CSV_COLUMNS: [&str; 13] = [
+    "item_name",
+    "cook_time",
+    "category",
+    "description",
+    "display_order",
+    "station",
+    "available",
+    "stock",
+    "modifiers",
+    "recipe",
+    "allergens",
+    "diets",
+    "dayparts",
+];
+
+#[derive(Serialize)]
+struct MenuFile<'a> {
+    records: &'a [Item],
+}
+
+/// Reads the items of a menu file, row by row so one bad row doesn't hide what is wrong with the others.
+/// Fails as a whole only when the file can't be read at all, e.g. it isn't valid json.
+pub fn parse_menu_records(
+    format: MenuFormat,
+    text: &str,
+) -> Result<Vec<Result<Item, Vec<FieldError>>>, ErrorResponse> {
+    let rows = match format {
+        MenuFormat::Csv => return parse_csv(text),
+        MenuFormat::Json => serde_json::from_str::<Value>(text).map_err(|e| e.to_string()),
+        MenuFormat::Yaml => serde_yaml::from_str::<Value>(text).map_err(|e| e.to_string()),
+    };
+    let rows = match rows {
+        Ok(Value::Array(rows)) => rows,
+        Ok(Value::Object(mut file)) => match file.remove("records") {
+            Some(Value::Array(rows)) => rows,
+            _ => return Err(unreadable_file("the file has no `records` list")),
+        },
+        Ok(_) => return Err(unreadable_file("the file has to hold a list of items")),
+        Err(e) => return Err(unreadable_file(&e)),
+    };
+
+    Ok(rows
+        .into_iter()
+        .enumerate()
+        .map(|(index, row)| {
+            serde_json::from_value::<Item>(row)
+                .map(|item| Item {
+                    menu_version: None,
+                    ..item
+                })
+                .map_err(|e| {
+                    vec![FieldError::new(
+                        format!("rows[{index}]"),
+                        "invalid_row",
+                        e.to_string(),
+                    )]
+                })
+        })
+        .collect())
+}
+
+/// Writes the items in the format, so that importing the file gives them back.
+pub fn write_menu_records(format: MenuFormat, items: &[Item]) -> Result<String, ErrorResponse> {
+    let items = items
+        .iter()
+        .map(|item| Item {
+            menu_version: None,
+            ..item.clone()
+        })
+        .collect::<Vec<Item>>();
+    let file = MenuFile { records: &items };
+    let written = match format {
+        MenuFormat::Csv => write_csv(&items),
+        MenuFormat::Json => serde_json::to_string_pretty(&file).map_err(|e| e.to_string()),
+        MenuFormat::Yaml => serde_yaml::to_string(&file).map_err(|e| e.to_string()),
+    };
+    written.map_err(|e| {
+        ErrorResponse::from(
+            AxumErrorResponse::from(AxumErrors::SerializationError).with_detail(format!(
+                "Was unable to write the menu as {}! Error: {e}",
+                format.extension()
+            )),
+        )
+    })
+}
+
+fn parse_csv(text: &str) -> Result<Vec<Result<Item, Vec<FieldError>>>, ErrorResponse> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(text.as_bytes());
+    if let Err(e) = reader.headers() {
+        return Err(unreadable_file(&e.to_string()));
+    }
+
+    Ok(reader
+        .deserialize::<CsvRecord>()
+        .enumerate()
+        .map(|(index, row)| match row {
+            Ok(row) => from_csv_record(row, index),
+            Err(e) => Err(vec![FieldError::new(
+                format!("rows[{index}]"),
+                "invalid_row",
+                e.to_string(),
+            )]),
+        })
+        .collect())
+}
+
+fn from_csv_record(row: CsvRecord, index: usize) -> Result<Item, Vec<FieldError>> {
+    let mut errors = vec![];
+    let modifiers = take_column(
+        &mut errors,
+        index,
+        "modifiers",
+        split_list(&row.modifiers, parse_modifier),
+    );
+    let recipe = take_column(
+        &mut errors,
+        index,
+        "recipe",
+        split_list(&row.recipe, parse_recipe_ingredient),
+    );
+    let allergens = take_column(
+        &mut errors,
+        index,
+        "allergens",
+        split_list(&row.allergens, parse_name),
+    );
+    let diets = take_column(
+        &mut errors,
+        index,
+        "diets",
+        split_list(&row.diets, parse_name),
+    );
+    let dayparts = take_column(
+        &mut errors,
+        index,
+        "dayparts",
+        split_list(&row.dayparts, |s| Ok(s.to_string())),
+    );
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Ok(Item {
+        item_name: row.item_name,
+        cook_time: row.cook_time,
+        modifiers,
+        category: row.category.unwrap_or_default(),
+        description: row
+            .description
+            .filter(|description| !description.is_empty()),
+        display_order: row.display_order.unwrap_or_default(),
+        station: row.station,
+        available: row.available.unwrap_or(true),
+        stock: row.stock,
+        recipe,
+        allergens,
+        diets,
+        dayparts,
+        menu_version: None,
+    })
+}
+
+/// The elements of a list column, noting what is wrong with it when they can't be read.
+fn take_column<T>(
+    errors: &mut Vec<FieldError>,
+    index: usize,
+    column: &str,
+    parsed: Result<Vec<T>, String>,
+) -> Vec<T> {
+    parsed.unwrap_or_else(|message| {
+        errors.push(FieldError::new(
+            format!("rows[{index}].{column}"),
+            "invalid_value",
+            message,
+        ));
+        vec![]
+    })
+}
+
+fn write_csv(items: &[Item]) -> Result<String, String> {
+    // the header is written up front, so an empty menu still gets one
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(vec![]);
+    writer
+        .write_record(CSV_COLUMNS)
+        .map_err(|e| e.to_string())?;
+    for item in items {
+        let row = CsvRecord {
+            item_name: item.item_name.clone(),
+            cook_time: item.cook_time,
+            category: Some(item.category),
+            description: item.description.clone(),
+            display_order: Some(item.display_order),
+            station: item.station,
+            available: Some(item.available),
+            stock: item.stock,
+            modifiers: join_list(&item.modifiers, |modifier| {
+                let kind = name_of(&modifier.kind);
+                match modifier.price_delta {
+                    Some(price_delta) => format!("{}:{kind}:{price_delta}", modifier.name),
+                    None => format!("{}:{kind}", modifier.name),
+                }
+            }),
+            recipe: join_list(&item.recipe, |ingredient| {
+                format!("{}:{}", ingredient.ingredient_name, ingredient.quantity)
+            }),
+            allergens: join_list(&item.allergens, name_of),
+            diets: join_list(&item.diets, name_of),
+            dayparts: join_list(&item.dayparts, |daypart| daypart.clone()),
+        };
+        writer.serialize(row).map_err(|e| e.to_string())?;
+    }
+    String::from_utf8(writer.into_inner().map_err(|e| e.to_string())?).map_err(|e| e.to_string())
+}
+
+fn split_list<T>(
+    column: &Option<String>,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<Vec<T>, String> {
+    column
+        .as_deref()
+        .unwrap_or_default()
+        .split(';')
+        .map(str::trim)
+        .filter(|element| !element.is_empty())
+        .map(parse)
+        .collect()
+}
+
+fn join_list<T>(list: &[T], write: impl Fn(&T) -> String) -> Option<String> {
+    match list.is_empty() {
+        true => None,
+        false => Some(list.iter().map(write).collect::<Vec<String>>().join(";")),
+    }
+}
+
+/// An enum by the name it is stored under, e.g. `tree_nut`.
+fn parse_name<T: DeserializeOwned>(name: &str) -> Result<T, String> {
+    serde_json::from_value(Value::String(name.to_string())).map_err(|e| e.to_string())
+}
+
+fn name_of<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+fn parse_recipe_ingredient(element: &str) -> Result<RecipeIngredient, String> {
+    let Some((ingredient_name, quantity)) = element.rsplit_once(':') else {
+        return Err(format!("{element} is not `Name:quantity`"));
+    };
+    Ok(RecipeIngredient {
+        ingredient_name: ingredient_name.trim().to_string(),
+        quantity: quantity
+            .trim()
+            .parse()
+            .map_err(|_| format!("the quantity of {ingredient_name} is not a whole number"))?,
+    })
+}
+
+fn parse_modifier(element: &str) -> Result<Modifier, String> {
+    let parts = element.split(':').map(str::trim).collect::<Vec<&str>>();
+    let (name, kind, price_delta) = match parts[..] {
+        [name, kind] => (name, kind, None),
+        [name, kind, price_delta] => (name, kind, Some(price_delta)),
+        _ => {
+            return Err(format!(
+                "{element} is not `Name:Kind` or `Name:Kind:price_delta`"
+            ))
+        }
+    };
+    Ok(Modifier {
+        name: name.to_string(),
+        kind: parse_name(kind)?,
+        price_delta: match price_delta {
+            Some(price_delta) => Some(
+                price_delta
+                    .parse()
+                    .map_err(|_| format!("the price_delta of {name} is not a whole number"))?,
+            ),
+            None => None,
+        },
+    })
+}
+
+fn unreadable_file(reason: &str) -> ErrorResponse {
+    ErrorResponse::from(
+        AxumErrorResponse::from(AxumErrors::InvalidMenuImport)
+            .with_detail(format!("The menu file can't be read, {reason}!")),
+    )
+}
@@ -1,4 +1,21 @@
+pub mod api_docs;
+pub mod clock;
+pub mod config;
+pub mod counters_db;
 pub mod database;
 pub mod database_helpers;
 pub mod errors;
+pub mod etag;
+pub mod extractors;
+pub mod idempotency;
+pub mod idempotency_db;
+pub mod indexes_db;
+pub mod menu_cache;
+pub mod menu_records;
 pub mod models;
+pub mod request_tracing;
+pub mod seeding;
+pub mod telemetry;
+pub mod transactions;
+pub mod unit_tests;
+pub mod validation;
@@ -1,2 +1,3 @@
 pub mod pagination_schema;
+pub mod path_schema;
 pub mod restaurant_schema;
\ No newline at end of file
@@ -1,10 +1,19 @@
 use serde::Deserialize;
+use utoipa::IntoParams;
+use validator::Validate;
 
-#[derive(Deserialize)]
+use crate::common::{config::ValidationLimits, validation::validate_page_size};
+
+#[derive(Deserialize, IntoParams, Validate)]
+#[into_params(parameter_in = Query)]
+#[validate(context = ValidationLimits)]
 pub struct Pagination {
+    /// how many records to skip
     #[serde(default = "default_offset")]
     pub offset: u64,
+    /// how many records to return at most, capped by `MAX_PAGE_SIZE`
     #[serde(default = "default_limit")]
+    #[validate(custom(function = "validate_page_size", use_context))]
     pub limit: i64,
 }
 
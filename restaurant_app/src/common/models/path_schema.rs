@@ -0,0 +1,68 @@
+use serde::Deserialize;
+use validator::Validate;
+
+use crate::common::{config::ValidationLimits, validation::validate_item_name};
+
+#[derive(Deserialize, Validate, Debug, Clone, Copy)]
+#[validate(context = ValidationLimits)]
+pub struct TablePath {
+    #[validate(range(min = 1, code = "not_positive", message = "must be at least 1"))]
+    pub table_id: i64,
+}
+
+#[derive(Deserialize, Validate, Debug, Clone, Copy)]
+#[validate(context = ValidationLimits)]
+pub struct OrderPath {
+    #[validate(range(min = 1, code = "not_positive", message = "must be at least 1"))]
+    pub table_id: i64,
+    #[validate(range(min = 1, code = "not_positive", message = "must be at least 1"))]
+    pub order_id: i64,
+}
+
+#[derive(Deserialize, Validate, Debug, Clone)]
+#[validate(context = ValidationLimits)]
+pub struct ItemPath {
+    #[validate(custom(function = "validate_item_name", use_context))]
+    pub item_name: String,
+}
+
+/// Daypart names follow the same rules as item names.
+#[derive(Deserialize, Validate, Debug, Clone)]
+#[validate(context = ValidationLimits)]
+pub struct DaypartPath {
+    #[validate(custom(function = "validate_item_name", use_context))]
+    pub daypart_name: String,
+}
+
+/// Ingredient names follow the same rules as item names.
+#[derive(Deserialize, Validate, Debug, Clone)]
+#[validate(context = ValidationLimits)]
+pub struct IngredientPath {
+    #[validate(custom(function = "validate_item_name", use_context))]
+    pub ingredient_name: String,
+}
+
+#[derive(Deserialize, Validate, Debug, Clone, Copy)]
+#[validate(context = ValidationLimits)]
+pub struct MenuVersionPath {
+    #[validate(range(min = 1, code = "not_positive", message = "must be at least 1"))]
+    pub version: i64,
+}
+
+#[derive(Deserialize, Validate, Debug, Clone)]
+#[validate(context = ValidationLimits)]
+pub struct MenuVersionItemPath {
+    #[validate(range(min = 1, code = "not_positive", message = "must be at least 1"))]
+    pub version: i64,
+    #[validate(custom(function = "validate_item_name", use_context))]
+    pub item_name: String,
+}
+
+#[derive(Deserialize, Validate, Debug, Clone, Copy)]
+#[validate(context = ValidationLimits)]
+pub struct MenuVersionDiffPath {
+    #[validate(range(min = 1, code = "not_positive", message = "must be at least 1"))]
+    pub version: i64,
+    #[validate(range(min = 1, code = "not_positive", message = "must be at least 1"))]
+    pub other_version: i64,
+}
@@ -1,8 +1,14 @@
-use std::str::FromStr;
+use std::{fmt, str::FromStr};
 
 use chrono::{prelude::*, DateTime, Duration};
-use mongodb::bson::serde_helpers::{chrono_datetime_as_bson_datetime, hex_string_as_object_id};
+use mongodb::bson::serde_helpers::{
+    chrono_datetime_as_bson_datetime, chrono_datetime_as_bson_datetime_optional,
+    hex_string_as_object_id,
+};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::common::extractors::ApiVersion;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Table {
@@ -10,6 +16,15 @@ pub struct Table {
     pub id: String, //kept private to prevent user interaction
     pub table_id: i64,
     pub orders: Vec<Order>,
+    /// bumped on every change to the table, tables from before versioning start at 0
+    #[serde(default)]
+    pub version: i64,
+    /// highest course fired so far, orders for it or an earlier course go straight to the kitchen
+    #[serde(default = "default_course")]
+    pub fired_course: i64,
+    /// what the guests can't eat, set when the table is seated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allergy_note: Option<AllergyNote>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -20,76 +35,555 @@ pub struct Order {
     pub ordered_time: DateTime<Utc>,
     pub cook_status: CookStatus,
     pub item: Item,
+    /// orders from before quantities were one of the item
+    #[serde(default = "default_quantity")]
+    pub quantity: i64,
+    /// copied from the item like the item itself, so later menu changes don't rewrite placed orders
+    #[serde(default)]
+    pub modifiers: Vec<Modifier>,
+    /// orders from before courses were all part of the first one
+    #[serde(default = "default_course")]
+    pub course: i64,
+    /// waiting for its course to be fired, the kitchen doesn't start on it yet
+    #[serde(default)]
+    pub held: bool,
+    /// when the kitchen was told to start, orders from before courses started when they were ordered
+    #[serde(default, with = "chrono_datetime_as_bson_datetime_optional")]
+    pub fired_time: Option<DateTime<Utc>>,
+    /// allergens of the item the table's allergy note lists, found when the order was placed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allergy_conflicts: Vec<Allergen>,
+    /// what placing the order took from the stock, orders from before it was kept give nothing back
+    #[serde(default, skip_serializing_if = "TakenStock::is_empty")]
+    pub taken_stock: TakenStock,
+}
+
+/// The portions and ingredients an order took when it was placed, voiding it gives back exactly these.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TakenStock {
+    /// portions of the item, none when its portions weren't counted at the time
+    #[serde(default)]
+    pub portions: i64,
+    /// the amount of each ingredient for the whole quantity, not per portion
+    #[serde(default)]
+    pub ingredients: Vec<RecipeIngredient>,
+}
+
+impl TakenStock {
+    pub fn is_empty(&self) -> bool {
+        self.portions == 0 && self.ingredients.is_empty()
+    }
+}
+
+impl Order {
+    /// The time the cook time counts from, nothing is cooking while the order is held.
+    pub fn cooking_since(&self) -> Option<DateTime<Utc>> {
+        match self.held {
+            true => None,
+            false => Some(self.fired_time.unwrap_or(self.ordered_time)),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Item {
     pub item_name: String,
     pub cook_time: i64,
+    /// the modifiers that can be ordered with the item, names are unique per item
+    #[serde(default)]
+    pub modifiers: Vec<Modifier>,
+    /// items from before categories are mains
+    #[serde(default)]
+    pub category: Category,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// position of the item within its category on the menu, lowest first
+    #[serde(default)]
+    pub display_order: i64,
+    /// where the item is cooked, items without one don't wait for a slot, e.g. drinks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub station: Option<Station>,
+    /// off the menu for now, e.g. the kitchen ran out, items from before availability can be ordered
+    #[serde(default = "default_available")]
+    pub available: bool,
+    /// portions left, every order takes its quantity from it, missing when the item isn't counted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stock: Option<i64>,
+    /// the ingredients one portion takes from the inventory, items without a recipe don't take any
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipe: Vec<RecipeIngredient>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allergens: Vec<Allergen>,
+    /// the diets the item fits, e.g. vegan
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub diets: Vec<Diet>,
+    /// names of the dayparts the item is served in, all day when there are none
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dayparts: Vec<String>,
+    /// the published menu version the item comes from, missing for items from before versioning
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub menu_version: Option<i64>,
+}
+
+/// What an item document with only a name and a cook time reads as, a main that can be ordered all day.
+impl Default for Item {
+    fn default() -> Self {
+        Self {
+            item_name: String::new(),
+            cook_time: 0,
+            modifiers: vec![],
+            category: Category::default(),
+            description: None,
+            display_order: 0,
+            station: None,
+            available: default_available(),
+            stock: None,
+            recipe: vec![],
+            allergens: vec![],
+            diets: vec![],
+            dayparts: vec![],
+            menu_version: None,
+        }
+    }
+}
+
+impl Item {
+    /// Whether the item is served while the `active` dayparts are.
+    pub fn is_served(&self, active: &[String]) -> bool {
+        self.dayparts.is_empty() || self.dayparts.iter().any(|daypart| active.contains(daypart))
+    }
+
+    /// The allergens of the item that are on the allergy note, in the order the item lists them.
+    pub fn allergy_conflicts(&self, allergy_note: Option<&AllergyNote>) -> Vec<Allergen> {
+        match allergy_note {
+            Some(allergy_note) => self
+                .allergens
+                .iter()
+                .filter(|allergen| allergy_note.allergens.contains(allergen))
+                .copied()
+                .collect(),
+            None => vec![],
+        }
+    }
+}
+
+/// The major food allergens, as the guests and the seed file name them, e.g. `peanut`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Allergen {
+    Gluten,
+    Dairy,
+    Egg,
+    Peanut,
+    TreeNut,
+    Soy,
+    Fish,
+    Shellfish,
+    Sesame,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Diet {
+    Vegetarian,
+    Vegan,
+    GlutenFree,
+    Halal,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, ToSchema)]
+pub struct AllergyNote {
+    /// orders of items with any of these are flagged
+    #[serde(default)]
+    pub allergens: Vec<Allergen>,
+    /// anything else the kitchen should know, e.g. how severe it is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// How much of an ingredient one portion of an item takes, in the unit of the ingredient.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct RecipeIngredient {
+    pub ingredient_name: String,
+    pub quantity: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct Ingredient {
+    pub ingredient_name: String,
+    pub unit: Unit,
+    /// how much is left, in `unit`
+    pub stock: i64,
+    /// the ingredient is low on stock once `stock` drops to it, never without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub low_stock_threshold: Option<i64>,
+}
+
+impl Ingredient {
+    pub fn is_low(&self) -> bool {
+        self.low_stock_threshold
+            .is_some_and(|threshold| self.stock <= threshold)
+    }
+}
+
+/// A part of the day with its own menu, e.g. breakfast, in the restaurant's local time.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct Daypart {
+    pub daypart_name: String,
+    /// the days it starts on, every day when there are none
+    #[serde(default)]
+    pub days: Vec<DayOfWeek>,
+    #[schema(value_type = String)]
+    pub start: NaiveTime,
+    /// an end before the start runs past midnight, into the next day, the same as the start for the whole day
+    #[schema(value_type = String)]
+    pub end: NaiveTime,
+}
+
+impl Daypart {
+    /// Whether the daypart is on at the local time `at`, the start is part of it and the end isn't.
+    pub fn is_active(&self, at: NaiveDateTime) -> bool {
+        let time = at.time();
+        let runs_on = |day: Weekday| self.days.is_empty() || self.days.contains(&day.into());
+        if self.start == self.end {
+            return runs_on(at.weekday());
+        }
+        match self.start < self.end {
+            true => self.start <= time && time < self.end && runs_on(at.weekday()),
+            //past midnight it still belongs to the day it started on
+            false => {
+                (self.start <= time && runs_on(at.weekday()))
+                    || (time < self.end && runs_on(at.weekday().pred()))
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+pub enum DayOfWeek {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl From<Weekday> for DayOfWeek {
+    fn from(weekday: Weekday) -> Self {
+        match weekday {
+            Weekday::Mon => DayOfWeek::Monday,
+            Weekday::Tue => DayOfWeek::Tuesday,
+            Weekday::Wed => DayOfWeek::Wednesday,
+            Weekday::Thu => DayOfWeek::Thursday,
+            Weekday::Fri => DayOfWeek::Friday,
+            Weekday::Sat => DayOfWeek::Saturday,
+            Weekday::Sun => DayOfWeek::Sunday,
+        }
+    }
+}
+
+/// A revision of the whole menu.
+/// Drafts are edited until they are published, then the version takes over the items at `effective_at`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MenuVersion {
+    pub version: i64,
+    pub status: MenuVersionStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// the menu as of this version, the availability and stock of the items stay the live ones
+    #[serde(default)]
+    pub items: Vec<Item>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+    /// when it takes over the menu, set when it is published
+    #[serde(default, with = "chrono_datetime_as_bson_datetime_optional")]
+    pub effective_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MenuVersionStatus {
+    /// still being edited
+    Draft,
+    /// published, waiting for its effective time
+    Scheduled,
+    /// the items on the menu right now
+    Live,
+    /// was live, or was superseded before it got to be
+    Retired,
+}
+
+/// What the stock of an ingredient is counted in, whole numbers of it so taking and giving back never drifts.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+pub enum Unit {
+    Gram,
+    Milliliter,
+    Piece,
+}
+
+/// The parts of the kitchen that can only cook so many dishes at once, see `KITCHEN_<STATION>_SLOTS`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, ToSchema)]
+pub enum Station {
+    Grill,
+    Fryer,
+    Wok,
+}
+
+impl Station {
+    pub const ALL: [Station; 3] = [Station::Grill, Station::Fryer, Station::Wok];
+}
+
+/// The sections of the menu, in the order they are listed.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+pub enum Category {
+    Appetizer,
+    #[default]
+    Main,
+    Dessert,
+    Drink,
+}
+
+impl Category {
+    pub const ALL: [Category; 4] = [
+        Category::Appetizer,
+        Category::Main,
+        Category::Dessert,
+        Category::Drink,
+    ];
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct Modifier {
+    /// what is added, left out or swapped in, e.g. `No Scallions`
+    pub name: String,
+    pub kind: ModifierKind,
+    /// change to the price of the item, in cents
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price_delta: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+pub enum ModifierKind {
+    Add,
+    Remove,
+    Substitute,
+}
+
+pub fn default_quantity() -> i64 {
+    1
+}
+
+pub fn default_course() -> i64 {
+    1
+}
+
+pub fn default_available() -> bool {
+    true
+}
+
+/// An item to order, either just its name or the name with a quantity and modifiers.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(untagged)]
+pub enum OrderRequest {
+    /// one of the item, as it comes
+    ItemName(String),
+    Detailed(DetailedOrderRequest),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct DetailedOrderRequest {
+    pub item_name: String,
+    /// how many of the item, 1 if left out
+    #[serde(default = "default_quantity")]
+    pub quantity: i64,
+    /// names of modifiers the item offers
+    #[serde(default)]
+    pub modifiers: Vec<String>,
+    /// courses after the first are held until the server fires them, 1 if left out
+    #[serde(default = "default_course")]
+    pub course: i64,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
 pub enum CookStatus {
+    /// its course wasn't fired yet
+    Held,
+    /// fired, but waiting for a free slot at its station
+    Queued,
     InProgress,
     Done,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct TableResponse {
     pub table_id: i64,
     pub orders: Vec<OrderResponse>,
+    /// same as the `ETag` header, send it back in `If-Match` to make sure nobody changed the table in between
+    pub version: i64,
+    pub fired_course: i64,
+    pub allergy_note: Option<AllergyNote>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct OrderResponse {
     pub order_id: i64,
     pub table_id: i64,
     pub ordered_time: DateTime<Utc>,
     pub cook_status: CookStatus,
     pub item: ItemResponse,
+    pub quantity: i64,
+    pub modifiers: Vec<Modifier>,
+    pub course: i64,
+    /// when the kitchen was told to start, missing while the order is held
+    pub fired_time: Option<DateTime<Utc>>,
+    pub station: Option<Station>,
+    /// when a slot at the station starts on the order, missing while the order is held
+    pub started_time: Option<DateTime<Utc>>,
+    /// when the whole quantity should be done, missing while the order is held
+    pub estimated_ready_time: Option<DateTime<Utc>>,
+    /// allergens of the item the table's allergy note lists, the kitchen has to check with the guest
+    pub allergy_conflicts: Vec<Allergen>,
+    /// the menu version the order was placed against, missing for orders from before versioning
+    pub menu_version: Option<i64>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct ItemResponse {
     pub item_name: String,
     pub cook_time: i64,
+    pub modifiers: Vec<Modifier>,
+    pub category: Category,
+    pub description: Option<String>,
+    pub display_order: i64,
+    pub station: Option<Station>,
+    pub available: bool,
+    pub allergens: Vec<Allergen>,
+    pub diets: Vec<Diet>,
+    /// served all day when there are none
+    pub dayparts: Vec<String>,
+    pub menu_version: Option<i64>,
+}
+
+/// The item as the kitchen and the managers see it, with the stock and the recipe the guests don't get to see.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct KitchenItemResponse {
+    #[serde(flatten)]
+    pub item: ItemResponse,
+    /// portions left, missing when the item isn't counted
+    pub stock: Option<i64>,
+    pub recipe: Vec<RecipeIngredient>,
 }
 
 impl From<Table> for TableResponse {
     fn from(table: Table) -> Self {
         let Table {
-            table_id, orders, ..
+            table_id,
+            orders,
+            version,
+            fired_course,
+            allergy_note,
+            ..
         } = table;
         Self {
-            table_id: table_id,
+            table_id,
             orders: orders.into_iter().map(|order| order.into()).collect(),
+            version,
+            fired_course,
+            allergy_note,
         }
     }
 }
 
+// Without the kitchen schedule the order is assumed to start as soon as it is fired,
+// the handlers replace these times with the ones of the scheduler, see `KitchenSchedule`.
+// The cook status is the stored one until `at` works it out for the handler's clock.
 impl From<Order> for OrderResponse {
     fn from(order: Order) -> Self {
+        let started_time = order.cooking_since();
+        let estimated_ready_time =
+            started_time.map(|started_time| started_time + Duration::seconds(order.item.cook_time));
+        let station = order.item.station;
+        let menu_version = order.item.menu_version;
         let Order {
             order_id,
             table_id,
             ordered_time,
+            cook_status,
             item,
+            quantity,
+            modifiers,
+            course,
+            fired_time,
+            allergy_conflicts,
             ..
         } = order;
         Self {
             order_id,
             table_id,
-            ordered_time: ordered_time.clone(),
-            cook_status: {
-                let current_time = Utc::now();
-                let time_cooked = ordered_time + Duration::seconds(item.cook_time);
-                if current_time >= time_cooked {
-                    CookStatus::Done
-                } else {
-                    CookStatus::InProgress
-                }
-            },
+            ordered_time,
+            cook_status,
             item: item.into(),
+            quantity,
+            modifiers,
+            course,
+            fired_time,
+            station,
+            started_time,
+            estimated_ready_time,
+            allergy_conflicts,
+            menu_version,
+        }
+    }
+}
+
+impl OrderResponse {
+    /// The order with the cook status it has at `now`.
+    pub fn at(mut self, now: DateTime<Utc>) -> Self {
+        self.cook_status = CookStatus::at(self.started_time, self.estimated_ready_time, now);
+        self
+    }
+
+    /// The order as `api_version` shows it.
+    pub fn in_version(mut self, api_version: ApiVersion) -> Self {
+        self.cook_status = self.cook_status.in_version(api_version);
+        self
+    }
+}
+
+impl TableResponse {
+    /// The table with its orders as `api_version` shows them.
+    pub fn in_version(mut self, api_version: ApiVersion) -> Self {
+        self.orders = self
+            .orders
+            .into_iter()
+            .map(|order| order.in_version(api_version))
+            .collect();
+        self
+    }
+}
+
+impl CookStatus {
+    /// v1 only knows `InProgress` and `Done`, to it a held or queued order is still in progress.
+    pub fn in_version(self, api_version: ApiVersion) -> CookStatus {
+        match (api_version, self) {
+            (ApiVersion::V1, CookStatus::Held | CookStatus::Queued) => CookStatus::InProgress,
+            (_, cook_status) => cook_status,
+        }
+    }
+
+    /// Where an order with these times is at `now`, no start time means it is held.
+    pub fn at(
+        started_time: Option<DateTime<Utc>>,
+        estimated_ready_time: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> CookStatus {
+        match (started_time, estimated_ready_time) {
+            (Some(started_time), _) if now < started_time => CookStatus::Queued,
+            (Some(_), Some(ready_time)) if now < ready_time => CookStatus::InProgress,
+            (Some(_), _) => CookStatus::Done,
+            (None, _) => CookStatus::Held,
         }
     }
 }
@@ -99,30 +593,186 @@ impl From<Item> for ItemResponse {
         let Item {
             item_name,
             cook_time,
+            modifiers,
+            category,
+            description,
+            display_order,
+            station,
+            available,
+            allergens,
+            diets,
+            dayparts,
+            menu_version,
+            ..
         } = item;
         Self {
             item_name,
             cook_time,
+            modifiers,
+            category,
+            description,
+            display_order,
+            station,
+            available,
+            allergens,
+            diets,
+            dayparts,
+            menu_version,
         }
     }
 }
 
-impl ToString for CookStatus {
-    fn to_string(&self) -> String {
-        match self {
-            CookStatus::InProgress => "InProgress".into(),
-            CookStatus::Done => "Done".into(),
+impl From<Item> for KitchenItemResponse {
+    fn from(item: Item) -> Self {
+        let stock = item.stock;
+        let recipe = item.recipe.clone();
+        Self {
+            item: item.into(),
+            stock,
+            recipe,
         }
     }
 }
 
+impl fmt::Display for CookStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CookStatus::Held => "Held",
+            CookStatus::Queued => "Queued",
+            CookStatus::InProgress => "InProgress",
+            CookStatus::Done => "Done",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl fmt::Display for Station {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Station::Grill => "Grill",
+            Station::Fryer => "Fryer",
+            Station::Wok => "Wok",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Unit::Gram => "g",
+            Unit::Milliliter => "ml",
+            Unit::Piece => "pcs",
+        };
+        write!(f, "{name}")
+    }
+}
+
+// the names it is stored under
+impl fmt::Display for MenuVersionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MenuVersionStatus::Draft => "draft",
+            MenuVersionStatus::Scheduled => "scheduled",
+            MenuVersionStatus::Live => "live",
+            MenuVersionStatus::Retired => "retired",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl fmt::Display for Allergen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Allergen::Gluten => "gluten",
+            Allergen::Dairy => "dairy",
+            Allergen::Egg => "egg",
+            Allergen::Peanut => "peanut",
+            Allergen::TreeNut => "tree_nut",
+            Allergen::Soy => "soy",
+            Allergen::Fish => "fish",
+            Allergen::Shellfish => "shellfish",
+            Allergen::Sesame => "sesame",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl fmt::Display for Diet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Diet::Vegetarian => "vegetarian",
+            Diet::Vegan => "vegan",
+            Diet::GlutenFree => "gluten_free",
+            Diet::Halal => "halal",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Category::Appetizer => "Appetizer",
+            Category::Main => "Main",
+            Category::Dessert => "Dessert",
+            Category::Drink => "Drink",
+        };
+        write!(f, "{name}")
+    }
+}
+
 impl FromStr for CookStatus {
     type Err = ();
     fn from_str(input: &str) -> Result<CookStatus, Self::Err> {
         match input {
+            "Held" => Ok(CookStatus::Held),
+            "Queued" => Ok(CookStatus::Queued),
             "InProgress" => Ok(CookStatus::InProgress),
             "Done" => Ok(CookStatus::Done),
             _ => Err(()),
         }
     }
 }
+
+impl OrderRequest {
+    pub fn item_name(&self) -> &str {
+        match self {
+            OrderRequest::ItemName(item_name) => item_name,
+            OrderRequest::Detailed(order) => &order.item_name,
+        }
+    }
+
+    pub fn quantity(&self) -> i64 {
+        match self {
+            OrderRequest::ItemName(_) => default_quantity(),
+            OrderRequest::Detailed(order) => order.quantity,
+        }
+    }
+
+    pub fn modifiers(&self) -> &[String] {
+        match self {
+            OrderRequest::ItemName(_) => &[],
+            OrderRequest::Detailed(order) => &order.modifiers,
+        }
+    }
+
+    pub fn course(&self) -> i64 {
+        match self {
+            OrderRequest::ItemName(_) => default_course(),
+            OrderRequest::Detailed(order) => order.course,
+        }
+    }
+}
+
+impl From<&str> for OrderRequest {
+    fn from(item_name: &str) -> Self {
+        OrderRequest::ItemName(item_name.to_string())
+    }
+}
+
+impl From<String> for OrderRequest {
+    fn from(item_name: String) -> Self {
+        OrderRequest::ItemName(item_name)
+    }
+}
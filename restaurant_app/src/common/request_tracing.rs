@@ -0,0 +1,121 @@
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request},
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use tracing::{field::Empty, info, info_span, Instrument};
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+use uuid::Uuid;
+
+use super::telemetry::{
+    init_meter_provider, init_tracer_provider, inject_trace_context, set_remote_parent, tracer,
+    Telemetry,
+};
+
+pub static REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+// anything longer than this is most likely garbage, so we generate our own id instead
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Returns the id of the request currently being handled, if any.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|request_id| request_id.clone()).ok()
+}
+
+/// Sets up the global subscriber.
+/// `LOG_FORMAT=json` switches to json lines, anything else keeps the plain text output.
+/// The level can be tuned with `RUST_LOG`, defaulting to `info`.
+/// The trace and metric exporters are set up here too, and returned so they can be flushed on shutdown.
+pub fn init_tracing() -> Telemetry {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+
+    let fmt_layer = match std::env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_target(false)
+            .boxed(),
+        _ => fmt::layer().with_target(false).boxed(),
+    };
+
+    let tracer_provider = init_tracer_provider();
+    let otel_layer = tracer_provider
+        .as_ref()
+        .map(|provider| tracing_opentelemetry::layer().with_tracer(tracer(provider)));
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt_layer)
+        .with(otel_layer)
+        .init();
+
+    Telemetry {
+        tracer_provider,
+        meter_provider: init_meter_provider(),
+    }
+}
+
+/// Wraps every request in a span carrying its request id.
+/// The id is taken from the `X-Request-Id` header when the caller sent one, otherwise a new one is generated,
+/// and it is echoed back in the response headers.
+/// An incoming W3C `traceparent` makes the span part of the caller's trace, and the response carries ours back.
+pub async fn request_tracing_middleware(request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|header| header.to_str().ok())
+        .filter(|header| !header.is_empty() && header.len() <= MAX_REQUEST_ID_LEN)
+        .map(|header| header.to_string())
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|matched_path| matched_path.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+
+    // table_id and order_id are filled in by the handlers once the path is extracted
+    let span = info_span!(
+        "request",
+        otel.name = format!("{} {}", request.method(), route),
+        otel.kind = "server",
+        request_id = %request_id,
+        method = %request.method(),
+        path = %request.uri().path(),
+        table_id = Empty,
+        order_id = Empty,
+        status = Empty,
+    );
+    set_remote_parent(&span, request.headers());
+
+    let start = Instant::now();
+    let mut response = REQUEST_ID
+        .scope(
+            request_id.clone(),
+            next.run(request).instrument(span.clone()),
+        )
+        .await;
+
+    span.record("status", response.status().as_u16());
+    span.in_scope(|| {
+        info!(
+            latency_ms = start.elapsed().as_millis() as u64,
+            "finished processing request"
+        )
+    });
+
+    if let Ok(header) = HeaderValue::from_str(&request_id) {
+        response
+            .headers_mut()
+            .insert(REQUEST_ID_HEADER.clone(), header);
+    }
+    inject_trace_context(&span, response.headers_mut());
+    response
+}
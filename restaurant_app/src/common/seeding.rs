@@ -0,0 +1,257 @@
+use std::{collections::HashMap, fmt, fs, future::Future, str::FromStr, time::Duration};
+
+use serde::Serialize;
+use serde_json::Value;
+use tracing::{info, warn};
+
+use super::{
+    config::{seed_max_attempts, seed_retry_delay},
+    database::DB,
+    errors::ErrorResponse,
+    menu_records::MenuFormat,
+    models::restaurant_schema::{Daypart, Item},
+};
+use crate::handlers::{
+    daypart_handler::daypart_db::DBDaypartTrait,
+    item_handler::item_db::DBTableTrait,
+    menu_transfer_handler::menu_transfer::{apply_menu_items, read_menu_file, ImportMode},
+};
+
+/// How the seed file at `FILE_PATH` is put into the database on start up, `SEED_MODE`.
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SeedMode {
+    /// the file is only loaded into an empty items collection
+    #[default]
+    IfEmpty,
+    /// the items and dayparts of the file that are missing or differ are upserted,
+    /// the availability and stock of the items already on the menu are kept
+    Reconcile,
+    /// the file isn't read at all
+    Off,
+}
+
+impl FromStr for SeedMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "if_empty" => Ok(SeedMode::IfEmpty),
+            "reconcile" => Ok(SeedMode::Reconcile),
+            "off" => Ok(SeedMode::Off),
+            other => Err(format!("{other} is not a seed mode")),
+        }
+    }
+}
+
+impl fmt::Display for SeedMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeedMode::IfEmpty => write!(f, "if_empty"),
+            SeedMode::Reconcile => write!(f, "reconcile"),
+            SeedMode::Off => write!(f, "off"),
+        }
+    }
+}
+
+/// Why the database couldn't be seeded.
+#[derive(Debug)]
+pub enum SeedError {
+    /// `FILE_PATH` isn't set
+    MissingFilePath,
+    /// the seed file can't be opened
+    UnreadableFile { path: String, reason: String },
+    /// the seed file was read but its contents are wrong, every problem is listed
+    InvalidFile { reasons: Vec<String> },
+    /// the database failed, after every attempt
+    Database { attempts: u32, reason: String },
+}
+
+impl fmt::Display for SeedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeedError::MissingFilePath => {
+                write!(
+                    f,
+                    "FILE_PATH env var must be set to the file for pre-loading the DB"
+                )
+            }
+            SeedError::UnreadableFile { path, reason } => {
+                write!(
+                    f,
+                    "Was unable to read the file {path} for pre-loading the DB: {reason}"
+                )
+            }
+            SeedError::InvalidFile { reasons } => write!(
+                f,
+                "The file for pre-loading the DB is invalid: {}",
+                reasons.join(", ")
+            ),
+            SeedError::Database { attempts, reason } => {
+                write!(f, "The DB still failed after {attempts} attempts: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SeedError {}
+
+impl From<ErrorResponse> for SeedError {
+    fn from(e: ErrorResponse) -> Self {
+        match e.status_code.is_server_error() {
+            true => SeedError::Database {
+                attempts: 1,
+                reason: e.error.detail,
+            },
+            false => SeedError::InvalidFile {
+                reasons: match e.error.errors.is_empty() {
+                    true => vec![e.error.detail],
+                    false => e
+                        .error
+                        .errors
+                        .into_iter()
+                        .map(|error| format!("{}: {}", error.field, error.message))
+                        .collect(),
+                },
+            },
+        }
+    }
+}
+
+/// What seeding changed, logged on start up.
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct SeedSummary {
+    pub mode: SeedMode,
+    /// the dayparts that were added or replaced
+    pub dayparts: Vec<String>,
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub unchanged: Vec<String>,
+}
+
+/// Seeds the database from the file at `FILE_PATH` in `mode`, retrying while the database isn't reachable yet.
+pub async fn set_up_item_records(db: &DB, mode: SeedMode) -> Result<SeedSummary, SeedError> {
+    if mode == SeedMode::Off {
+        return Ok(SeedSummary {
+            mode,
+            ..Default::default()
+        });
+    }
+    let path = std::env::var("FILE_PATH").map_err(|_| SeedError::MissingFilePath)?;
+    let text = fs::read_to_string(&path).map_err(|e| SeedError::UnreadableFile {
+        path: path.clone(),
+        reason: e.to_string(),
+    })?;
+
+    let summary = with_retries(seed_max_attempts(), seed_retry_delay(), || {
+        seed_menu(db, mode, &text)
+    })
+    .await?;
+    info!(
+        mode = %summary.mode,
+        dayparts = ?summary.dayparts,
+        created = ?summary.created,
+        updated = ?summary.updated,
+        unchanged = summary.unchanged.len(),
+        "seeded the DB from {path}"
+    );
+    Ok(summary)
+}
+
+/// Puts the dayparts and the items of the seed file into the database.
+pub async fn seed_menu(db: &DB, mode: SeedMode, text: &str) -> Result<SeedSummary, SeedError> {
+    let mut summary = SeedSummary {
+        mode,
+        ..Default::default()
+    };
+    let menu = match mode {
+        SeedMode::Off => return Ok(summary),
+        SeedMode::IfEmpty => {
+            if !db.get_menu().await?.is_empty() {
+                return Ok(summary);
+            }
+            HashMap::new()
+        }
+        SeedMode::Reconcile => db
+            .get_menu()
+            .await?
+            .into_iter()
+            .map(|item| (item.item_name.clone(), item))
+            .collect::<HashMap<String, Item>>(),
+    };
+
+    // the items name the dayparts, so those go in first
+    let current_dayparts = match mode {
+        SeedMode::Reconcile => db.list_dayparts().await?,
+        _ => vec![],
+    };
+    for daypart in seed_dayparts(text)? {
+        if current_dayparts.contains(&daypart) {
+            continue;
+        }
+        summary.dayparts.push(daypart.daypart_name.clone());
+        db.set_daypart(daypart).await?;
+    }
+
+    // the kitchen owns the availability and the stock, the seed file doesn't take them back
+    let items = read_menu_file(db, text, MenuFormat::Json)
+        .await?
+        .into_iter()
+        .map(|item| match menu.get(&item.item_name) {
+            Some(current) => Item {
+                available: current.available,
+                stock: current.stock,
+                ..item
+            },
+            None => item,
+        })
+        .collect();
+    let applied = apply_menu_items(db, items, ImportMode::Upsert, false).await?;
+    summary.created = applied.created;
+    summary.updated = applied.updated;
+    summary.unchanged = applied.unchanged;
+    Ok(summary)
+}
+
+/// Runs `attempt` until it succeeds, the file is found invalid or the attempts run out,
+/// waiting `delay` between the attempts that failed on the database.
+/// The other start up steps that need the database go through it too.
+pub async fn with_retries<T, F, Fut>(
+    max_attempts: u32,
+    delay: Duration,
+    mut attempt: F,
+) -> Result<T, SeedError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, SeedError>>,
+{
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match attempt().await {
+            Err(SeedError::Database { reason, .. }) if attempts < max_attempts => {
+                warn!("The DB isn't ready yet, attempt {attempts} of {max_attempts}: {reason}");
+                tokio::time::sleep(delay).await;
+            }
+            Err(SeedError::Database { reason, .. }) => {
+                return Err(SeedError::Database { attempts, reason })
+            }
+            result => return result,
+        }
+    }
+}
+
+// the file may leave the dayparts out, every item is served all day then
+fn seed_dayparts(text: &str) -> Result<Vec<Daypart>, SeedError> {
+    let json: Value = serde_json::from_str(text).map_err(|e| SeedError::InvalidFile {
+        reasons: vec![e.to_string()],
+    })?;
+    match json.get("dayparts") {
+        Some(dayparts) => {
+            serde_json::from_value(dayparts.clone()).map_err(|e| SeedError::InvalidFile {
+                reasons: vec![format!("dayparts: {e}")],
+            })
+        }
+        None => Ok(vec![]),
+    }
+}
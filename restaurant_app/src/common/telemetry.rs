@@ -0,0 +1,285 @@
+use std::{
+    fmt::{Debug, Display},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use opentelemetry::{
+    global,
+    metrics::Meter,
+    propagation::{Extractor, Injector},
+    trace::TracerProvider,
+    Context, KeyValue,
+};
+use opentelemetry_sdk::{
+    error::OTelSdkResult,
+    metrics::{
+        data::{AggregatedMetrics, MetricData, ResourceMetrics},
+        exporter::PushMetricExporter,
+        SdkMeterProvider, Temporality,
+    },
+    propagation::TraceContextPropagator,
+    trace::{SdkTracer, SdkTracerProvider, SpanData, SpanExporter},
+    Resource,
+};
+use tracing::{info_span, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+const SERVICE_NAME: &str = "restaurant_app";
+
+/// The providers exporting the traces and the metrics, flushed by `shutdown` before the app exits.
+pub struct Telemetry {
+    pub tracer_provider: Option<SdkTracerProvider>,
+    pub meter_provider: Option<SdkMeterProvider>,
+}
+
+impl Telemetry {
+    /// Flushes whatever spans and metrics are still buffered.
+    pub fn shutdown(&self) {
+        if let Some(tracer_provider) = &self.tracer_provider {
+            if let Err(e) = tracer_provider.shutdown() {
+                eprintln!("Was unable to flush the remaining traces! Error: {e}");
+            }
+        }
+        if let Some(meter_provider) = &self.meter_provider {
+            if let Err(e) = meter_provider.shutdown() {
+                eprintln!("Was unable to flush the remaining metrics! Error: {e}");
+            }
+        }
+    }
+}
+
+/// Builds the tracer provider picked by `OTEL_TRACES_EXPORTER`.
+/// - `otlp` exports over gRPC to `OTEL_EXPORTER_OTLP_ENDPOINT` (defaults to `http://localhost:4317`)
+/// - `stdout` prints every finished span, handy for local debugging
+/// - anything else (or unset) disables trace export entirely
+pub fn init_tracer_provider() -> Option<SdkTracerProvider> {
+    let exporter = std::env::var("OTEL_TRACES_EXPORTER").unwrap_or_default();
+
+    let builder = SdkTracerProvider::builder()
+        .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build());
+
+    let provider = match exporter.as_str() {
+        "otlp" => {
+            let otlp_exporter = match opentelemetry_otlp::SpanExporter::builder()
+                .with_tonic()
+                .build()
+            {
+                Ok(otlp_exporter) => otlp_exporter,
+                Err(e) => {
+                    eprintln!("Was unable to build the OTLP exporter, traces won't be exported! Error: {e}");
+                    return None;
+                }
+            };
+            builder.with_batch_exporter(otlp_exporter).build()
+        }
+        "stdout" => builder.with_simple_exporter(StdoutSpanExporter).build(),
+        _ => return None,
+    };
+
+    // W3C traceparent/tracestate headers
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    global::set_tracer_provider(provider.clone());
+    Some(provider)
+}
+
+/// Builds the meter provider picked by `OTEL_METRICS_EXPORTER`, with the same choices as the traces.
+/// The metrics are exported every `OTEL_METRIC_EXPORT_INTERVAL` milliseconds, once a minute by default.
+/// It has to be installed before the instruments are created, the ones created earlier stay no-ops.
+pub fn init_meter_provider() -> Option<SdkMeterProvider> {
+    let exporter = std::env::var("OTEL_METRICS_EXPORTER").unwrap_or_default();
+
+    let builder = SdkMeterProvider::builder()
+        .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build());
+
+    let provider = match exporter.as_str() {
+        "otlp" => {
+            let otlp_exporter = match opentelemetry_otlp::MetricExporter::builder()
+                .with_tonic()
+                .build()
+            {
+                Ok(otlp_exporter) => otlp_exporter,
+                Err(e) => {
+                    eprintln!("Was unable to build the OTLP metric exporter, metrics won't be exported! Error: {e}");
+                    return None;
+                }
+            };
+            builder.with_periodic_exporter(otlp_exporter).build()
+        }
+        "stdout" => builder.with_periodic_exporter(StdoutMetricExporter).build(),
+        _ => return None,
+    };
+
+    global::set_meter_provider(provider.clone());
+    Some(provider)
+}
+
+/// Meter for the app's own metrics, they go nowhere unless `init_meter_provider` installed an exporter.
+pub fn meter() -> Meter {
+    global::meter(SERVICE_NAME)
+}
+
+pub fn tracer(provider: &SdkTracerProvider) -> SdkTracer {
+    provider.tracer(SERVICE_NAME)
+}
+
+/// Makes `span` a child of the trace described by the incoming `traceparent` header, if there is one.
+pub fn set_remote_parent(span: &Span, headers: &HeaderMap) {
+    let parent_context =
+        global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)));
+    let _ = span.set_parent(parent_context);
+}
+
+/// Writes the `traceparent` of `span` into `headers` so callers can stitch the trace together.
+pub fn inject_trace_context(span: &Span, headers: &mut HeaderMap) {
+    let context: Context = span.context();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut HeaderInjector(headers))
+    });
+}
+
+/// Child span wrapping a single MongoDB operation.
+pub fn mongo_span(operation: &'static str, collection: &'static str) -> Span {
+    info_span!(
+        "mongodb",
+        otel.name = format!("mongodb.{operation} {collection}"),
+        otel.kind = "client",
+        db.system = "mongodb",
+        db.operation = operation,
+        db.collection = collection,
+    )
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(key.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+/// Prints finished spans as single lines on stdout.
+#[derive(Debug)]
+pub struct StdoutSpanExporter;
+
+impl SpanExporter for StdoutSpanExporter {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        for span in batch {
+            let duration = span
+                .end_time
+                .duration_since(span.start_time)
+                .unwrap_or_default();
+            let start = span
+                .start_time
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            let attributes = span
+                .attributes
+                .iter()
+                .map(|attribute| format!("{}={}", attribute.key, attribute.value))
+                .collect::<Vec<String>>()
+                .join(" ");
+            println!(
+                "span name=\"{}\" trace_id={} span_id={} parent_span_id={} kind={:?} start_unix_ns={} duration_us={} status={:?} {}",
+                span.name,
+                span.span_context.trace_id(),
+                span.span_context.span_id(),
+                span.parent_span_id,
+                span.span_kind,
+                start.as_nanos(),
+                duration.as_micros(),
+                span.status,
+                attributes
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Prints the metrics as single lines on stdout, one per data point of the counters and gauges.
+#[derive(Debug)]
+pub struct StdoutMetricExporter;
+
+impl PushMetricExporter for StdoutMetricExporter {
+    async fn export(&self, metrics: &ResourceMetrics) -> OTelSdkResult {
+        for scope_metrics in metrics.scope_metrics() {
+            for metric in scope_metrics.metrics() {
+                match metric.data() {
+                    AggregatedMetrics::F64(data) => print_metric_data(metric.name(), data),
+                    AggregatedMetrics::U64(data) => print_metric_data(metric.name(), data),
+                    AggregatedMetrics::I64(data) => print_metric_data(metric.name(), data),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+
+    fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+        Ok(())
+    }
+
+    fn temporality(&self) -> Temporality {
+        Temporality::Cumulative
+    }
+}
+
+// the app only has counters, histograms are printed without their buckets
+fn print_metric_data<T: Display + Copy>(name: &str, data: &MetricData<T>) {
+    let points: Vec<(String, Vec<&KeyValue>)> = match data {
+        MetricData::Sum(sum) => sum
+            .data_points()
+            .map(|point| (point.value().to_string(), point.attributes().collect()))
+            .collect(),
+        MetricData::Gauge(gauge) => gauge
+            .data_points()
+            .map(|point| (point.value().to_string(), point.attributes().collect()))
+            .collect(),
+        MetricData::Histogram(histogram) => histogram
+            .data_points()
+            .map(|point| {
+                (
+                    format!("count:{}", point.count()),
+                    point.attributes().collect(),
+                )
+            })
+            .collect(),
+        MetricData::ExponentialHistogram(histogram) => histogram
+            .data_points()
+            .map(|point| {
+                (
+                    format!("count:{}", point.count()),
+                    point.attributes().collect(),
+                )
+            })
+            .collect(),
+    };
+    for (value, attributes) in points {
+        let attributes = attributes
+            .iter()
+            .map(|attribute| format!("{}={}", attribute.key, attribute.value))
+            .collect::<Vec<String>>()
+            .join(" ");
+        println!("metric name=\"{name}\" value={value} {attributes}");
+    }
+}
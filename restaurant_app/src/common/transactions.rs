@@ -0,0 +1,129 @@
+use axum::http::StatusCode;
+use futures::future::BoxFuture;
+use mongodb::{
+    error::{Error, TRANSIENT_TRANSACTION_ERROR, UNKNOWN_TRANSACTION_COMMIT_RESULT},
+    Client, ClientSession,
+};
+use tracing::{error, warn};
+
+use super::config::transaction_max_attempts;
+use super::errors::{AxumErrors, ErrorResponse};
+
+/// Why a step of a transaction failed.
+pub enum TransactionError {
+    /// the driver failed, transient failures are retried
+    Mongo(Error),
+    /// the operation decided not to go through, e.g. the table doesn't exist, this is returned as is
+    Aborted(ErrorResponse),
+}
+
+impl From<Error> for TransactionError {
+    fn from(e: Error) -> Self {
+        TransactionError::Mongo(e)
+    }
+}
+
+impl From<ErrorResponse> for TransactionError {
+    fn from(e: ErrorResponse) -> Self {
+        TransactionError::Aborted(e)
+    }
+}
+
+/// Runs `operation` in a transaction of its own session and commits it.
+///
+/// Transient errors, like a write conflict with a concurrent request, run the whole operation again,
+/// while an unknown commit result only retries the commit, up to `MONGO_TRANSACTION_MAX_ATTEMPTS` times in total.
+/// Anything else aborts the transaction.
+/// Transactions need a replica set, against a standalone server `transactions_enabled` is off
+/// and the operation runs once in a plain session, without the atomicity.
+///
+/// The operation may run more than once, so it has to clone whatever it moves into its future.
+pub async fn run_in_transaction<T, F>(
+    client: &Client,
+    transactions_enabled: bool,
+    description: &str,
+    mut operation: F,
+) -> Result<T, ErrorResponse>
+where
+    F: for<'s> FnMut(&'s mut ClientSession) -> BoxFuture<'s, Result<T, TransactionError>>,
+{
+    let mut session = match client.start_session(None).await {
+        Ok(session) => session,
+        Err(e) => {
+            error!(
+                "Unexpected error occured while starting a session to {description}. Error: {e}"
+            );
+            return Err(db_error());
+        }
+    };
+
+    if !transactions_enabled {
+        return operation(&mut session)
+            .await
+            .map_err(|e| into_error_response(e, description));
+    }
+
+    let max_attempts = transaction_max_attempts();
+    let mut attempt = 0;
+    'transaction: loop {
+        attempt += 1;
+        if let Err(e) = session.start_transaction(None).await {
+            error!("Unexpected error occured while starting a transaction to {description}. Error: {e}");
+            return Err(db_error());
+        }
+
+        let result = match operation(&mut session).await {
+            Ok(result) => result,
+            Err(e) => {
+                // errors of the abort don't matter, the server drops the transaction on its own eventually
+                let _ = session.abort_transaction().await;
+                if let TransactionError::Mongo(mongo_error) = &e {
+                    if mongo_error.contains_label(TRANSIENT_TRANSACTION_ERROR)
+                        && attempt < max_attempts
+                    {
+                        warn!("Retrying the transaction to {description} after a transient error. Error: {mongo_error}");
+                        continue 'transaction;
+                    }
+                }
+                return Err(into_error_response(e, description));
+            }
+        };
+
+        loop {
+            match session.commit_transaction().await {
+                Ok(()) => return Ok(result),
+                Err(e)
+                    if e.contains_label(UNKNOWN_TRANSACTION_COMMIT_RESULT)
+                        && attempt < max_attempts =>
+                {
+                    attempt += 1;
+                    warn!("Retrying the commit of the transaction to {description}. Error: {e}");
+                }
+                Err(e)
+                    if e.contains_label(TRANSIENT_TRANSACTION_ERROR) && attempt < max_attempts =>
+                {
+                    warn!("Retrying the transaction to {description} after a transient error. Error: {e}");
+                    continue 'transaction;
+                }
+                Err(e) => return Err(into_error_response(TransactionError::Mongo(e), description)),
+            }
+        }
+    }
+}
+
+fn into_error_response(e: TransactionError, description: &str) -> ErrorResponse {
+    match e {
+        TransactionError::Aborted(e) => e,
+        TransactionError::Mongo(e) => {
+            error!("Unexpected error occured in the Database while trying to {description}. Error: {e}");
+            db_error()
+        }
+    }
+}
+
+fn db_error() -> ErrorResponse {
+    ErrorResponse {
+        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        error: AxumErrors::DBError.into(),
+    }
+}
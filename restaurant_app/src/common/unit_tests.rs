@@ -0,0 +1,1328 @@
+#[cfg(test)]
+pub mod common_unit_tests {
+
+    use std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use axum::{
+        body::{to_bytes, Body},
+        http::{Request, StatusCode},
+        middleware,
+        routing::get,
+        routing::post,
+        routing::put,
+        Json, Router,
+    };
+    use chrono::{DateTime, NaiveTime, Utc};
+    use mongodb::bson::doc;
+    use serde::Deserialize;
+    use tower::ServiceExt;
+    use utoipa::OpenApi;
+
+    use crate::{
+        cli::Command,
+        common::{
+            api_docs::{docs_router, ApiDoc},
+            clock::{Clock, FakeClock},
+            config::{idempotency_key_ttl, idempotency_lock_lease, validation_limits},
+            database::DB,
+            database_helpers::menu_item_update,
+            errors::{
+                handler_404, AxumErrorResponse, AxumErrors, ErrorResponse,
+                PROBLEM_JSON_CONTENT_TYPE,
+            },
+            etag::{IfMatch, IfNoneMatch},
+            extractors::{
+                Json as JsonBody, Path, ValidExtraQuery, ValidJson, ValidPath, ValidQuery,
+            },
+            idempotency::{
+                idempotency_middleware, IDEMPOTENCY_KEY_HEADER, IDEMPOTENT_REPLAYED_HEADER,
+            },
+            idempotency_db::IdempotencyRecord,
+            indexes_db::{check_indexes, declared_indexes, FailedIndex, IndexReport, IndexSpec},
+            menu_cache::{MenuCache, MenuCacheStats},
+            models::{
+                pagination_schema::Pagination,
+                path_schema::TablePath,
+                restaurant_schema::{Daypart, Item},
+            },
+            request_tracing::{request_tracing_middleware, REQUEST_ID_HEADER},
+            seeding::{seed_menu, with_retries, SeedError, SeedMode, SeedSummary},
+        },
+        handlers::{
+            item_handler::item::{DaypartsRequest, RecipeRequest},
+            kitchen_handler::scheduler::ScheduleCache,
+            order_handler::order::CreateOrdersRequest,
+            table_handler::table::{CreateTableOrdersRequest, ListTableFiltersRequest},
+        },
+        tests::fixtures::item,
+        AppState,
+    };
+
+    async fn failing_handler() -> (StatusCode, Json<AxumErrorResponse>) {
+        (StatusCode::NOT_FOUND, Json(AxumErrors::NotFound.into()))
+    }
+
+    fn test_app() -> Router {
+        Router::new()
+            .route("/fail", get(failing_handler))
+            .layer(middleware::from_fn(request_tracing_middleware))
+    }
+
+    //////////////////////
+    //  REQUEST_TRACING //
+    //////////////////////
+
+    #[tokio::test]
+    pub async fn successful_request_id_propagated() {
+        let request = Request::builder()
+            .uri("/fail")
+            .header(&REQUEST_ID_HEADER, "abc-123")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = test_app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[&REQUEST_ID_HEADER], "abc-123");
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let error: AxumErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.request_id, Some("abc-123".to_string()));
+    }
+
+    #[tokio::test]
+    pub async fn successful_request_id_generated() {
+        let request = Request::builder().uri("/fail").body(Body::empty()).unwrap();
+
+        let response = test_app().oneshot(request).await.unwrap();
+        let request_id = response.headers()[&REQUEST_ID_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(!request_id.is_empty());
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let error: AxumErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.request_id, Some(request_id));
+    }
+
+    #[tokio::test]
+    pub async fn successful_no_request_id_outside_request() {
+        let error: AxumErrorResponse = AxumErrors::DBError.into();
+        assert_eq!(error.request_id, None);
+    }
+
+    //////////////////////
+    //  API_DOCS        //
+    //////////////////////
+
+    #[tokio::test]
+    pub async fn successful_openapi_covers_all_routes() {
+        let openapi = ApiDoc::openapi();
+        let paths = openapi.paths.paths;
+
+        for path in [
+            "/table",
+            "/table/{table_id}",
+            "/table/{table_id}/order",
+            "/table/{table_id}/order/{order_id}",
+            "/table/order",
+            "/item",
+            "/inventory",
+            "/inventory/{ingredient_name}",
+            "/inventory/unmakeable_items",
+            "/dayparts",
+            "/dayparts/{daypart_name}",
+            "/item/{item_name}/dayparts",
+            "/menu/versions",
+            "/menu/versions/{version}/publish",
+            "/menu/versions/{version}/diff/{other_version}",
+            "/admin/menu/import",
+            "/admin/menu/export",
+        ] {
+            assert!(paths.contains_key(path), "{path} is missing from the spec");
+        }
+
+        let schemas = openapi.components.unwrap().schemas;
+        for schema in [
+            "PostTableResponse",
+            "ListTableResponse",
+            "ListOrdersResponse",
+            "AxumErrorResponse",
+            "TableResponse",
+            "CookStatus",
+        ] {
+            assert!(
+                schemas.contains_key(schema),
+                "{schema} is missing from the spec"
+            );
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_openapi_json_served() {
+        let app: Router = Router::new().merge(docs_router());
+        let request = Request::builder()
+            .uri("/openapi.json")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let spec: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(spec["openapi"].as_str().unwrap().starts_with("3."));
+    }
+
+    //////////////////////
+    //  ERRORS          //
+    //////////////////////
+
+    #[derive(Deserialize)]
+    struct EchoRequest {
+        name: String,
+    }
+
+    async fn echo_handler(Path(id): Path<i64>, JsonBody(body): JsonBody<EchoRequest>) -> String {
+        format!("{id} {}", body.name)
+    }
+
+    fn extractor_app() -> Router {
+        Router::new()
+            .route("/echo/:id", post(echo_handler))
+            .fallback(handler_404)
+    }
+
+    async fn into_problem(response: axum::response::Response) -> AxumErrorResponse {
+        assert_eq!(
+            response.headers()["content-type"],
+            PROBLEM_JSON_CONTENT_TYPE
+        );
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    pub async fn successful_extractors() {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/echo/1")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"name":"Udon"}"#))
+            .unwrap();
+
+        let response = extractor_app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    pub async fn failed_extractor_malformed_json() {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/echo/1")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"name":"#))
+            .unwrap();
+
+        let response = extractor_app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let problem = into_problem(response).await;
+        assert_eq!(problem.error_type, AxumErrors::InvalidJsonBody.to_string());
+        assert_eq!(problem.status, 400);
+        assert_eq!(problem.problem_type, "/problems/invalid_json_body");
+    }
+
+    #[tokio::test]
+    pub async fn failed_extractor_missing_content_type() {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/echo/1")
+            .body(Body::from(r#"{"name":"Udon"}"#))
+            .unwrap();
+
+        let response = extractor_app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let problem = into_problem(response).await;
+        assert_eq!(
+            problem.error_type,
+            AxumErrors::UnsupportedMediaType.to_string()
+        );
+    }
+
+    #[tokio::test]
+    pub async fn failed_extractor_invalid_path() {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/echo/one")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"name":"Udon"}"#))
+            .unwrap();
+
+        let response = extractor_app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let problem = into_problem(response).await;
+        assert_eq!(problem.error_type, AxumErrors::InvalidPath.to_string());
+    }
+
+    #[tokio::test]
+    pub async fn failed_unknown_route() {
+        let request = Request::builder()
+            .uri("/nowhere")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = extractor_app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let problem = into_problem(response).await;
+        assert_eq!(problem.error_type, AxumErrors::NotFound.to_string());
+        // older clients read the message from `error_message`
+        assert_eq!(problem.error_message, "Path not found!");
+        assert_eq!(problem.error_message, problem.detail);
+    }
+
+    //////////////////////
+    //  VALIDATION      //
+    //////////////////////
+
+    async fn validated_order_handler(
+        ValidPath(path): ValidPath<TablePath>,
+        ValidJson(body): ValidJson<CreateOrdersRequest>,
+    ) -> String {
+        format!("{} {}", path.table_id, body.orders.len())
+    }
+
+    async fn validated_list_handler(
+        ValidQuery(pagination): ValidQuery<Pagination>,
+        ValidExtraQuery(filters): ValidExtraQuery<ListTableFiltersRequest>,
+    ) -> String {
+        format!("{} {}", pagination.limit, filters.item_names.len())
+    }
+
+    async fn validated_recipe_handler(ValidJson(body): ValidJson<RecipeRequest>) -> String {
+        format!("{}", body.recipe.len())
+    }
+
+    async fn validated_table_handler(
+        ValidJson(body): ValidJson<CreateTableOrdersRequest>,
+    ) -> String {
+        format!("{}", body.orders.len())
+    }
+
+    async fn validated_dayparts_handler(ValidJson(body): ValidJson<DaypartsRequest>) -> String {
+        format!("{}", body.dayparts.len())
+    }
+
+    fn validation_app() -> Router {
+        Router::new()
+            .route("/table", post(validated_table_handler))
+            .route("/table/:table_id/order", post(validated_order_handler))
+            .route("/table", get(validated_list_handler))
+            .route("/item/:item_name/recipe", put(validated_recipe_handler))
+            .route("/item/:item_name/dayparts", put(validated_dayparts_handler))
+    }
+
+    fn order_request(uri: &str, body: &str) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    fn list_request(uri: &str) -> Request<Body> {
+        Request::builder().uri(uri).body(Body::empty()).unwrap()
+    }
+
+    async fn into_validation_problem(response: axum::response::Response) -> AxumErrorResponse {
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let problem = into_problem(response).await;
+        assert_eq!(problem.error_type, AxumErrors::ValidationFailed.to_string());
+        assert_eq!(problem.code, "validation_failed");
+        problem
+    }
+
+    #[tokio::test]
+    pub async fn successful_validation() {
+        let response = validation_app()
+            .oneshot(order_request("/table/1/order", r#"{"orders":["Udon"]}"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = validation_app()
+            .oneshot(list_request("/table?limit=5&table_id=1&item_names=Udon"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    pub async fn failed_validation_negative_limit() {
+        let response = validation_app()
+            .oneshot(list_request("/table?limit=-1"))
+            .await
+            .unwrap();
+
+        let problem = into_validation_problem(response).await;
+        assert_eq!(problem.errors.len(), 1);
+        assert_eq!(problem.errors[0].field, "limit");
+        assert_eq!(problem.errors[0].code, "out_of_range");
+    }
+
+    #[tokio::test]
+    pub async fn failed_validation_limit_above_max_page_size() {
+        let uri = format!("/table?limit={}", validation_limits().max_page_size + 1);
+        let response = validation_app().oneshot(list_request(&uri)).await.unwrap();
+
+        let problem = into_validation_problem(response).await;
+        assert_eq!(problem.errors[0].field, "limit");
+    }
+
+    #[tokio::test]
+    pub async fn failed_validation_empty_orders() {
+        let response = validation_app()
+            .oneshot(order_request("/table/1/order", r#"{"orders":[]}"#))
+            .await
+            .unwrap();
+
+        let problem = into_validation_problem(response).await;
+        assert_eq!(problem.errors.len(), 1);
+        assert_eq!(problem.errors[0].field, "orders");
+        assert_eq!(problem.errors[0].code, "empty");
+    }
+
+    #[tokio::test]
+    pub async fn failed_validation_long_item_name() {
+        let long_name = "a".repeat(validation_limits().max_item_name_length + 1);
+        let body = format!(r#"{{"orders":["Udon","{long_name}"]}}"#);
+        let response = validation_app()
+            .oneshot(order_request("/table/1/order", &body))
+            .await
+            .unwrap();
+
+        let problem = into_validation_problem(response).await;
+        assert_eq!(problem.errors.len(), 1);
+        assert_eq!(problem.errors[0].field, "orders[1]");
+        assert_eq!(problem.errors[0].code, "too_long");
+    }
+
+    #[tokio::test]
+    pub async fn successful_validation_detailed_orders() {
+        let body = r#"{"orders":["Udon",{"item_name":"Ramen","quantity":3,"modifiers":["No Scallions"]},{"item_name":"Gyoza"}]}"#;
+        let response = validation_app()
+            .oneshot(order_request("/table/1/order", body))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, "1 3");
+    }
+
+    #[tokio::test]
+    pub async fn failed_validation_quantity_out_of_range() {
+        let body = format!(
+            r#"{{"orders":["Udon",{{"item_name":"Ramen","quantity":{}}}]}}"#,
+            validation_limits().max_quantity_per_order + 1
+        );
+        let response = validation_app()
+            .oneshot(order_request("/table/1/order", &body))
+            .await
+            .unwrap();
+
+        let problem = into_validation_problem(response).await;
+        assert_eq!(problem.errors.len(), 1);
+        assert_eq!(problem.errors[0].field, "orders[1].quantity");
+        assert_eq!(problem.errors[0].code, "out_of_range");
+    }
+
+    #[tokio::test]
+    pub async fn failed_validation_duplicate_modifier() {
+        let body =
+            r#"{"orders":[{"item_name":"Ramen","modifiers":["No Scallions","No Scallions"]}]}"#;
+        let response = validation_app()
+            .oneshot(order_request("/table/1/order", body))
+            .await
+            .unwrap();
+
+        let problem = into_validation_problem(response).await;
+        assert_eq!(problem.errors.len(), 1);
+        assert_eq!(problem.errors[0].field, "orders[0].modifiers[1]");
+        assert_eq!(problem.errors[0].code, "duplicate_modifier");
+    }
+
+    #[tokio::test]
+    pub async fn failed_validation_recipe() {
+        let body = r#"{"recipe":[{"ingredient_name":"Eel","quantity":120},{"ingredient_name":"Rice","quantity":0},{"ingredient_name":"Eel","quantity":10}]}"#;
+        let request = Request::builder()
+            .method("PUT")
+            .uri("/item/UnagiDon/recipe")
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        let response = validation_app().oneshot(request).await.unwrap();
+
+        // the first offending ingredient is reported
+        let problem = into_validation_problem(response).await;
+        assert_eq!(problem.errors.len(), 1);
+        assert_eq!(problem.errors[0].field, "recipe[1].quantity");
+        assert_eq!(problem.errors[0].code, "not_positive");
+    }
+
+    #[tokio::test]
+    pub async fn failed_validation_recipe_quantity_too_large() {
+        let body = r#"{"recipe":[{"ingredient_name":"Eel","quantity":100001}]}"#;
+        let request = Request::builder()
+            .method("PUT")
+            .uri("/item/UnagiDon/recipe")
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        let response = validation_app().oneshot(request).await.unwrap();
+
+        let problem = into_validation_problem(response).await;
+        assert_eq!(problem.errors.len(), 1);
+        assert_eq!(problem.errors[0].field, "recipe[0].quantity");
+        assert_eq!(problem.errors[0].code, "out_of_range");
+    }
+
+    #[tokio::test]
+    pub async fn failed_validation_dayparts() {
+        let request = Request::builder()
+            .method("PUT")
+            .uri("/item/MatchaLatte/dayparts")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                r#"{"dayparts":["Breakfast","Lunch","Breakfast"]}"#,
+            ))
+            .unwrap();
+        let response = validation_app().oneshot(request).await.unwrap();
+
+        let problem = into_validation_problem(response).await;
+        assert_eq!(problem.errors.len(), 1);
+        assert_eq!(problem.errors[0].field, "dayparts[2]");
+        assert_eq!(problem.errors[0].code, "duplicate_daypart");
+    }
+
+    #[tokio::test]
+    pub async fn failed_validation_allergy_note() {
+        let body = r#"{"orders":["Udon"],"allergy_note":{"allergens":["peanut","soy","peanut"]}}"#;
+        let response = validation_app()
+            .oneshot(order_request("/table", body))
+            .await
+            .unwrap();
+
+        let problem = into_validation_problem(response).await;
+        assert_eq!(problem.errors.len(), 1);
+        assert_eq!(problem.errors[0].field, "allergy_note.allergens[2]");
+        assert_eq!(problem.errors[0].code, "duplicate_allergen");
+
+        let body = format!(
+            r#"{{"orders":["Udon"],"allergy_note":{{"allergens":["peanut"],"note":"{}"}}}}"#,
+            "a".repeat(501)
+        );
+        let response = validation_app()
+            .oneshot(order_request("/table", &body))
+            .await
+            .unwrap();
+
+        let problem = into_validation_problem(response).await;
+        assert_eq!(problem.errors[0].field, "allergy_note.note");
+        assert_eq!(problem.errors[0].code, "too_long");
+    }
+
+    #[tokio::test]
+    pub async fn failed_validation_non_positive_table_id() {
+        let response = validation_app()
+            .oneshot(order_request("/table/0/order", r#"{"orders":["Udon"]}"#))
+            .await
+            .unwrap();
+
+        let problem = into_validation_problem(response).await;
+        assert_eq!(problem.errors[0].field, "table_id");
+        assert_eq!(problem.errors[0].code, "not_positive");
+    }
+
+    #[tokio::test]
+    pub async fn failed_validation_reports_every_field() {
+        let response = validation_app()
+            .oneshot(list_request(
+                "/table?table_id=-3&order_id=0&item_names=Udon&item_names=%20",
+            ))
+            .await
+            .unwrap();
+
+        let problem = into_validation_problem(response).await;
+        let fields = problem
+            .errors
+            .iter()
+            .map(|error| error.field.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(fields, vec!["item_names[1]", "order_id", "table_id"]);
+    }
+
+    //////////////////////
+    //  IDEMPOTENCY     //
+    //////////////////////
+
+    // a db keeping a single idempotency record, like mongo would with its unique index on the key
+    fn idempotency_db(store: Arc<std::sync::Mutex<Option<IdempotencyRecord>>>) -> DB {
+        let mut mock_db = DB::faux();
+
+        let reserve_store = store.clone();
+        faux::when!(mock_db.reserve_idempotency_key).then(move |(record, now)| {
+            let mut stored = reserve_store.lock().unwrap();
+            match stored.as_ref() {
+                Some(existing) if existing.expires_at > now => Ok(Some(existing.clone())),
+                _ => {
+                    *stored = Some(record.clone());
+                    Ok(None)
+                }
+            }
+        });
+
+        let complete_store = store.clone();
+        faux::when!(mock_db.complete_idempotency_key).then(
+            move |(record, response, expires_at)| match complete_store.lock().unwrap().as_mut() {
+                Some(stored)
+                    if stored.expires_at == record.expires_at && stored.response.is_none() =>
+                {
+                    stored.response = Some(response.clone());
+                    stored.expires_at = expires_at;
+                    Ok(())
+                }
+                _ => Err(ErrorResponse::from(AxumErrors::IdempotencyKeyInProgress)),
+            },
+        );
+
+        faux::when!(mock_db.release_idempotency_key).then(move |record| {
+            let mut stored = store.lock().unwrap();
+            if stored.as_ref() == Some(record) {
+                *stored = None;
+            }
+            Ok(())
+        });
+
+        mock_db
+    }
+
+    // the handler never answers a request for "Hang", like one whose client went away
+    fn idempotency_app(db: DB, calls: Arc<AtomicUsize>, status: StatusCode) -> Router {
+        // on a fake clock that is far off, the keys still expire by the wall clock
+        let app_state = Arc::new(AppState {
+            db,
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(FakeClock::new(Utc::now() - chrono::Duration::days(365))),
+        });
+
+        let create_handler = move |body: String| async move {
+            let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+            if body == "Hang" {
+                std::future::pending::<()>().await;
+            }
+            (status, format!("{call} {body}"))
+        };
+
+        Router::new()
+            .route(
+                "/table",
+                post(create_handler).layer(middleware::from_fn_with_state(
+                    app_state.clone(),
+                    idempotency_middleware,
+                )),
+            )
+            .with_state(app_state)
+    }
+
+    fn create_request(key: Option<&str>, body: &str) -> Request<Body> {
+        let mut request = Request::builder().method("POST").uri("/table");
+        if let Some(key) = key {
+            request = request.header(&IDEMPOTENCY_KEY_HEADER, key);
+        }
+        request.body(Body::from(body.to_string())).unwrap()
+    }
+
+    async fn into_text(response: axum::response::Response) -> String {
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    pub async fn successful_idempotency_retry_replays_response() {
+        let store = Arc::new(std::sync::Mutex::new(None));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = idempotency_app(idempotency_db(store), calls.clone(), StatusCode::CREATED);
+
+        let first = app
+            .clone()
+            .oneshot(create_request(Some("key-1"), "Udon"))
+            .await
+            .unwrap();
+        assert_eq!(first.status(), StatusCode::CREATED);
+        assert!(first.headers().get(&IDEMPOTENT_REPLAYED_HEADER).is_none());
+        assert_eq!(into_text(first).await, "1 Udon");
+
+        let retry = app
+            .oneshot(create_request(Some("key-1"), "Udon"))
+            .await
+            .unwrap();
+        assert_eq!(retry.status(), StatusCode::CREATED);
+        assert_eq!(retry.headers()[&IDEMPOTENT_REPLAYED_HEADER], "true");
+        assert_eq!(into_text(retry).await, "1 Udon");
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    pub async fn successful_idempotency_without_key() {
+        // no db expectations, any call to it would panic
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = idempotency_app(DB::faux(), calls.clone(), StatusCode::CREATED);
+
+        for _ in 0..2 {
+            let response = app
+                .clone()
+                .oneshot(create_request(None, "Udon"))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::CREATED);
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    pub async fn failed_idempotency_key_reused_with_different_body() {
+        let store = Arc::new(std::sync::Mutex::new(None));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = idempotency_app(idempotency_db(store), calls.clone(), StatusCode::CREATED);
+
+        app.clone()
+            .oneshot(create_request(Some("key-1"), "Udon"))
+            .await
+            .unwrap();
+        let response = app
+            .oneshot(create_request(Some("key-1"), "Ramen"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let problem = into_problem(response).await;
+        assert_eq!(problem.code, "idempotency_key_reused");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    pub async fn failed_idempotency_key_in_progress() {
+        let store = Arc::new(std::sync::Mutex::new(None));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let db = idempotency_db(store.clone());
+        let app = idempotency_app(db, calls.clone(), StatusCode::CREATED);
+
+        app.clone()
+            .oneshot(create_request(Some("key-1"), "Udon"))
+            .await
+            .unwrap();
+        // as if the first request had not answered yet
+        store.lock().unwrap().as_mut().unwrap().response = None;
+
+        let response = app
+            .oneshot(create_request(Some("key-1"), "Udon"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let problem = into_problem(response).await;
+        assert_eq!(problem.code, "idempotency_key_in_progress");
+    }
+
+    #[tokio::test]
+    pub async fn successful_idempotency_server_error_not_stored() {
+        let store = Arc::new(std::sync::Mutex::new(None));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = idempotency_app(
+            idempotency_db(store.clone()),
+            calls.clone(),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        );
+
+        for _ in 0..2 {
+            let response = app
+                .clone()
+                .oneshot(create_request(Some("key-1"), "Udon"))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        assert!(store.lock().unwrap().is_none());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    pub async fn successful_idempotency_response_kept_for_the_ttl() {
+        let store = Arc::new(std::sync::Mutex::new(None));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = idempotency_app(
+            idempotency_db(store.clone()),
+            calls.clone(),
+            StatusCode::CREATED,
+        );
+
+        let before = Utc::now();
+        app.clone()
+            .oneshot(create_request(Some("key-1"), "Udon"))
+            .await
+            .unwrap();
+        let expires_at = store.lock().unwrap().as_ref().unwrap().expires_at;
+        assert!(expires_at >= before + idempotency_key_ttl());
+        assert!(expires_at <= Utc::now() + idempotency_key_ttl());
+
+        // kept for longer than the lease, the stored response is still replayed
+        assert!(expires_at > Utc::now() + idempotency_lock_lease() * 2);
+        let retry = app
+            .oneshot(create_request(Some("key-1"), "Udon"))
+            .await
+            .unwrap();
+        assert_eq!(retry.headers()[&IDEMPOTENT_REPLAYED_HEADER], "true");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    pub async fn successful_idempotency_expired_lease_taken_over() {
+        let store = Arc::new(std::sync::Mutex::new(None));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = idempotency_app(
+            idempotency_db(store.clone()),
+            calls.clone(),
+            StatusCode::CREATED,
+        );
+
+        app.clone()
+            .oneshot(create_request(Some("key-1"), "Udon"))
+            .await
+            .unwrap();
+        // as if the first request had stalled without answering
+        {
+            let mut stored = store.lock().unwrap();
+            let stored = stored.as_mut().unwrap();
+            stored.response = None;
+            // its lease ran out a moment ago
+            stored.expires_at = Utc::now() - chrono::Duration::seconds(1);
+        }
+
+        let response = app
+            .oneshot(create_request(Some("key-1"), "Udon"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(response
+            .headers()
+            .get(&IDEMPOTENT_REPLAYED_HEADER)
+            .is_none());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    pub async fn successful_idempotency_dropped_request_releases_key() {
+        let store = Arc::new(std::sync::Mutex::new(None));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = idempotency_app(
+            idempotency_db(store.clone()),
+            calls.clone(),
+            StatusCode::CREATED,
+        );
+
+        // the client gives up on the request
+        let request = app.clone().oneshot(create_request(Some("key-1"), "Hang"));
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(50), request)
+                .await
+                .is_err()
+        );
+        // the key is given back on a spawned task
+        tokio::task::yield_now().await;
+        assert!(store.lock().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    pub async fn failed_idempotency_invalid_key() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = idempotency_app(DB::faux(), calls.clone(), StatusCode::CREATED);
+
+        let long_key = "k".repeat(256);
+        let response = app
+            .oneshot(create_request(Some(&long_key), "Udon"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let problem = into_problem(response).await;
+        assert_eq!(problem.code, "invalid_idempotency_key");
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    //////////////////////
+    //  ETAG            //
+    //////////////////////
+
+    async fn conditional_handler(if_match: IfMatch, if_none_match: IfNoneMatch) -> String {
+        format!(
+            "{:?} {}",
+            if_match.expected_versions(),
+            if_none_match.matches("3-5e0f1a2b3c4d5e6f")
+        )
+    }
+
+    async fn conditional_response(headers: &[(&str, &str)]) -> String {
+        let mut request = Request::builder().uri("/conditional");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let response = Router::new()
+            .route("/conditional", get(conditional_handler))
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        into_text(response).await
+    }
+
+    #[tokio::test]
+    pub async fn successful_conditional_headers_parsed() {
+        assert_eq!(conditional_response(&[]).await, "None false");
+        assert_eq!(
+            conditional_response(&[("if-match", "*"), ("if-none-match", "*")]).await,
+            "None true"
+        );
+        assert_eq!(
+            conditional_response(&[
+                ("if-match", "\"1\", W/\"2\""),
+                ("if-match", "\"4-5e0f1a2b3c4d5e6f\""),
+                ("if-none-match", "W/\"3-5e0f1a2b3c4d5e6f\""),
+            ])
+            .await,
+            "Some([1, 4]) true"
+        );
+    }
+
+    #[tokio::test]
+    pub async fn failed_conditional_headers_garbage_never_matches() {
+        assert_eq!(
+            conditional_response(&[("if-match", "abc"), ("if-none-match", "\"3\"")]).await,
+            "Some([]) false"
+        );
+    }
+
+    //////////////////////
+    //  MENU_CACHE      //
+    //////////////////////
+
+    fn names(item_names: &[&str]) -> Vec<String> {
+        item_names
+            .iter()
+            .map(|item_name| item_name.to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    pub async fn successful_menu_cache_hits_after_load() {
+        let mut db = DB::faux();
+        faux::when!(db.get_menu)
+            .once()
+            .then(|_| Ok(vec![item("Burger", 5), item("Ramen", 5)]));
+
+        let menu_cache = MenuCache::new(Duration::from_secs(60));
+        menu_cache.load(&db).await.unwrap();
+
+        let items = menu_cache
+            .get_items(&db, &names(&["Burger", "Pizza", "Burger"]))
+            .await
+            .unwrap();
+        assert_eq!(items.len(), 1);
+        assert!(items.contains_key("Burger"));
+        menu_cache.get_items(&db, &names(&["Ramen"])).await.unwrap();
+
+        assert_eq!(menu_cache.stats(), MenuCacheStats { hits: 2, misses: 0 });
+    }
+
+    #[tokio::test]
+    pub async fn successful_menu_cache_reads_again_when_invalidated_or_expired() {
+        let mut db = DB::faux();
+        faux::when!(db.get_menu)
+            .times(3)
+            .then(|_| Ok(vec![item("Burger", 5)]));
+
+        let menu_cache = MenuCache::new(Duration::from_millis(50));
+        // nothing loaded yet
+        menu_cache
+            .get_items(&db, &names(&["Burger"]))
+            .await
+            .unwrap();
+        menu_cache
+            .get_items(&db, &names(&["Burger"]))
+            .await
+            .unwrap();
+
+        menu_cache.invalidate().await;
+        menu_cache
+            .get_items(&db, &names(&["Burger"]))
+            .await
+            .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        menu_cache
+            .get_items(&db, &names(&["Burger"]))
+            .await
+            .unwrap();
+
+        assert_eq!(menu_cache.stats(), MenuCacheStats { hits: 1, misses: 3 });
+    }
+
+    #[tokio::test]
+    pub async fn successful_menu_cache_counts_portions_in_place() {
+        let mut db = DB::faux();
+        faux::when!(db.get_menu).once().then(|_| {
+            Ok(vec![
+                Item {
+                    stock: Some(5),
+                    ..item("Burger", 5)
+                },
+                item("Ramen", 5),
+            ])
+        });
+
+        let menu_cache = MenuCache::new(Duration::from_secs(60));
+        menu_cache.load(&db).await.unwrap();
+        menu_cache
+            .take_portions(&HashMap::from([
+                ("Burger".to_string(), 3),
+                ("Ramen".to_string(), 1),
+            ]))
+            .await;
+        menu_cache
+            .give_back_portions(&HashMap::from([("Burger".to_string(), 1)]))
+            .await;
+
+        let items = menu_cache
+            .get_items(&db, &names(&["Burger", "Ramen"]))
+            .await
+            .unwrap();
+        assert_eq!(items["Burger"].stock, Some(3));
+        // uncounted items stay uncounted
+        assert_eq!(items["Ramen"].stock, None);
+        assert_eq!(menu_cache.stats(), MenuCacheStats { hits: 1, misses: 0 });
+    }
+
+    #[tokio::test]
+    pub async fn successful_menu_cache_disabled_queries_the_items() {
+        // get_menu isn't mocked, a ttl of zero never reads the whole menu
+        let mut db = DB::faux();
+        faux::when!(db.get_items_by_name).then(|item_names| {
+            Ok(item_names
+                .iter()
+                .map(|item_name| (item_name.clone(), item(item_name, 5)))
+                .collect())
+        });
+
+        let menu_cache = MenuCache::new(Duration::ZERO);
+        menu_cache.load(&db).await.unwrap();
+        let items = menu_cache
+            .get_items(&db, &names(&["Burger"]))
+            .await
+            .unwrap();
+
+        assert!(items.contains_key("Burger"));
+        assert_eq!(menu_cache.stats(), MenuCacheStats { hits: 0, misses: 1 });
+    }
+
+    #[tokio::test]
+    pub async fn failed_menu_cache_db_error_is_not_cached() {
+        let mut db = DB::faux();
+        faux::when!(db.get_menu).then(|_| Err(ErrorResponse::from(AxumErrors::DBError)));
+
+        let menu_cache = MenuCache::new(Duration::from_secs(60));
+        for _ in 0..2 {
+            match menu_cache.get_items(&db, &names(&["Burger"])).await {
+                Ok(_) => panic!("supposed to fail"),
+                Err(e) => assert_eq!(e.error.error_type, AxumErrors::DBError.to_string()),
+            }
+        }
+        assert_eq!(menu_cache.stats().misses, 2);
+    }
+
+    //////////////////////
+    //  SEEDING         //
+    //////////////////////
+
+    const SEED_FILE: &str = r#"{
+        "records": [
+            {"item_name": "Burger", "cook_time": 5},
+            {"item_name": "Pancakes", "cook_time": 8, "dayparts": ["Breakfast"]}
+        ],
+        "dayparts": [
+            {"daypart_name": "Breakfast", "start": "07:00:00", "end": "11:00:00"}
+        ]
+    }"#;
+
+    #[tokio::test]
+    pub async fn successful_seed_if_empty_leaves_a_seeded_db_alone() {
+        let mut db = DB::faux();
+        faux::when!(db.get_menu).then(|_| Ok(vec![item("Burger", 5)]));
+        faux::when!(db.set_daypart).then(|_| panic!("shouldn't write the dayparts!"));
+        faux::when!(db.import_menu_items).then(|_| panic!("shouldn't write the items!"));
+
+        let summary = seed_menu(&db, SeedMode::IfEmpty, SEED_FILE).await.unwrap();
+        assert_eq!(
+            summary,
+            SeedSummary {
+                mode: SeedMode::IfEmpty,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    pub async fn successful_seed_reconcile_upserts_missing_and_changed_items() {
+        let mut db = DB::faux();
+        faux::when!(db.get_menu).then(|_| {
+            Ok(vec![Item {
+                cook_time: 4,
+                available: false,
+                ..item("Burger", 5)
+            }])
+        });
+        // breakfast used to end later
+        faux::when!(db.list_dayparts).then(|_| {
+            Ok(vec![Daypart {
+                daypart_name: "Breakfast".to_string(),
+                days: vec![],
+                start: NaiveTime::from_hms_opt(7, 0, 0).unwrap(),
+                end: NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+            }])
+        });
+        faux::when!(db.set_daypart).then(|daypart| {
+            assert_eq!(daypart.end, NaiveTime::from_hms_opt(11, 0, 0).unwrap());
+            Ok(daypart)
+        });
+        faux::when!(db.import_menu_items).then(|(items, deleted_item_names)| {
+            assert_eq!(items.len(), 2);
+            // the kitchen took the burger off, the seed file doesn't put it back on
+            assert!(!items[0].available);
+            assert_eq!(items[0].cook_time, 5);
+            assert!(deleted_item_names.is_empty());
+            Ok(())
+        });
+
+        let summary = seed_menu(&db, SeedMode::Reconcile, SEED_FILE)
+            .await
+            .unwrap();
+        assert_eq!(summary.dayparts, names(&["Breakfast"]));
+        assert_eq!(summary.created, names(&["Pancakes"]));
+        assert_eq!(summary.updated, names(&["Burger"]));
+        assert!(summary.unchanged.is_empty());
+    }
+
+    #[tokio::test]
+    pub async fn failed_seed_invalid_file() {
+        let mut db = DB::faux();
+        faux::when!(db.get_menu).then(|_| Ok(vec![]));
+        faux::when!(db.list_dayparts).then(|_| Ok(vec![]));
+
+        let file =
+            r#"{"records": [{"item_name": "Burger", "cook_time": -5}, {"item_name": "Soup"}]}"#;
+        match seed_menu(&db, SeedMode::IfEmpty, file).await {
+            Err(SeedError::InvalidFile { reasons }) => assert_eq!(reasons.len(), 2),
+            other => panic!("supposed to be an invalid file, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_seed_retries_until_the_db_is_up() {
+        let calls = AtomicUsize::new(0);
+        let summary = with_retries(5, Duration::ZERO, || async {
+            match calls.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err(SeedError::from(ErrorResponse::from(AxumErrors::DBError))),
+                _ => Ok(SeedSummary::default()),
+            }
+        })
+        .await;
+
+        assert!(summary.is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    pub async fn failed_seed_gives_up_after_the_attempts() {
+        let calls = AtomicUsize::new(0);
+        let summary = with_retries(3, Duration::ZERO, || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err::<SeedSummary, SeedError>(SeedError::from(ErrorResponse::from(AxumErrors::DBError)))
+        })
+        .await;
+
+        match summary {
+            Err(SeedError::Database { attempts, .. }) => assert_eq!(attempts, 3),
+            other => panic!("supposed to give up, got {other:?}"),
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    //////////////////////
+    //  INDEXES         //
+    //////////////////////
+
+    #[tokio::test]
+    pub async fn successful_check_indexes_all_declared() {
+        let mut db = DB::faux();
+        faux::when!(db.list_indexes).then(|_| {
+            // named by hand, the keys are what counts
+            Ok(declared_indexes()
+                .into_iter()
+                .map(|index| IndexSpec {
+                    name: format!("{}_by_hand", index.name),
+                    ..index
+                })
+                .collect())
+        });
+
+        let report = check_indexes(&db).await.unwrap();
+        assert_eq!(report, IndexReport::default());
+    }
+
+    #[tokio::test]
+    pub async fn failed_check_indexes_missing_and_extra() {
+        let mut db = DB::faux();
+        faux::when!(db.list_indexes).then(|_| {
+            let mut indexes = declared_indexes();
+            // the item names aren't unique
+            indexes
+                .iter_mut()
+                .find(|index| index.name == "item_name_unique")
+                .unwrap()
+                .unique = false;
+            indexes.remove(1);
+            Ok(indexes)
+        });
+
+        let report = check_indexes(&db).await.unwrap();
+        assert_eq!(
+            report
+                .missing
+                .iter()
+                .map(|index| index.name.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["orders_order_id", "item_name_unique"]
+        );
+        assert_eq!(report.extra.len(), 1);
+        assert_eq!(report.extra[0].keys, doc! { "item_name": 1 });
+        assert!(!report.extra[0].unique);
+    }
+
+    #[tokio::test]
+    pub async fn failed_check_indexes_duplicates_reported_as_failed() {
+        let mut db = DB::faux();
+        faux::when!(db.list_indexes).then(|_| {
+            Ok(declared_indexes()
+                .into_iter()
+                .filter(|index| index.name != "table_id_unique")
+                .collect())
+        });
+        let duplicated = declared_indexes()
+            .into_iter()
+            .find(|index| index.name == "table_id_unique")
+            .unwrap();
+
+        let report = check_indexes(&db)
+            .await
+            .unwrap()
+            .with_failed(vec![FailedIndex {
+                index: duplicated.clone(),
+                reason: "E11000 duplicate key error".to_string(),
+            }]);
+        assert!(report.missing.is_empty());
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].index, duplicated);
+    }
+
+    #[test]
+    pub fn successful_parse_check_indexes_command() {
+        assert_eq!(
+            Command::parse(&["check-indexes".to_string()]),
+            Ok(Command::CheckIndexes)
+        );
+        assert!(Command::parse(&["check-indexes".to_string(), "tables".to_string()]).is_err());
+    }
+
+    //////////////////////
+    //  MENU_WRITES     //
+    //////////////////////
+
+    #[test]
+    pub fn menu_item_update_leaves_the_kitchen_fields_alone() {
+        let update = menu_item_update(
+            &Item {
+                stock: Some(3),
+                description: Some("Grilled eel".to_string()),
+                ..item("UnagiDon", 10)
+            },
+            Some(2),
+        )
+        .unwrap();
+
+        let set = update.get_document("$set").unwrap();
+        assert_eq!(set.get_str("item_name").unwrap(), "UnagiDon");
+        assert_eq!(set.get_i64("menu_version").unwrap(), 2);
+        assert!(!set.contains_key("available"));
+        assert!(!set.contains_key("stock"));
+        // only an item that wasn't on the menu takes them from the write
+        let set_on_insert = update.get_document("$setOnInsert").unwrap();
+        assert!(set_on_insert.get_bool("available").unwrap());
+        assert_eq!(set_on_insert.get_i64("stock").unwrap(), 3);
+        // the fields the item doesn't have anymore go away
+        let unset = update.get_document("$unset").unwrap();
+        assert!(unset.contains_key("station"));
+        assert!(!unset.contains_key("description"));
+    }
+
+    #[test]
+    pub fn menu_item_update_keeps_the_menu_version_without_one() {
+        let update = menu_item_update(&item("UnagiDon", 10), None).unwrap();
+
+        assert!(!update
+            .get_document("$set")
+            .unwrap()
+            .contains_key("menu_version"));
+        assert!(!update
+            .get_document("$unset")
+            .unwrap()
+            .contains_key("menu_version"));
+    }
+
+    //////////////////////
+    //  CLOCK           //
+    //////////////////////
+
+    #[test]
+    pub fn fake_clock_runs_faster_than_the_wall_clock() {
+        let start = Utc::now();
+        let clock = FakeClock::running(start, 1000.0);
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(clock.now() - start >= chrono::Duration::seconds(20));
+
+        let stopped = FakeClock::new(start);
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(stopped.now(), start);
+    }
+
+    #[test]
+    pub fn fake_clock_past_the_last_date_stays_put() {
+        let start = DateTime::<Utc>::MAX_UTC - chrono::Duration::seconds(1);
+        let clock = FakeClock::running(start, 86_400.0);
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(clock.now(), start);
+    }
+}
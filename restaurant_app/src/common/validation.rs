@@ -0,0 +1,315 @@
+use std::borrow::Cow;
+
+use serde_json::Value;
+use validator::{ValidationError, ValidationErrors, ValidationErrorsKind};
+
+use super::{
+    config::ValidationLimits,
+    errors::FieldError,
+    models::restaurant_schema::{AllergyNote, Item, OrderRequest, RecipeIngredient},
+};
+
+// Checks that need the configured limits, or that have to look at every element of a list,
+// which the built in validators of the derive can't do.
+// Errors on a list element carry its `index` so the field can be reported as e.g. `orders[2]`,
+// and a `field` when they are about a field of that element, e.g. `orders[2].quantity`.
+
+pub fn validate_page_size(limit: i64, limits: &ValidationLimits) -> Result<(), ValidationError> {
+    if limit < 1 || limit > limits.max_page_size {
+        return Err(
+            ValidationError::new("out_of_range").with_message(Cow::Owned(format!(
+                "must be between 1 and {}",
+                limits.max_page_size
+            ))),
+        );
+    }
+    Ok(())
+}
+
+pub fn validate_order_count(
+    orders: &[OrderRequest],
+    limits: &ValidationLimits,
+) -> Result<(), ValidationError> {
+    if orders.len() > limits.max_orders_per_request {
+        return Err(
+            ValidationError::new("too_many_items").with_message(Cow::Owned(format!(
+                "at most {} items can be ordered at once",
+                limits.max_orders_per_request
+            ))),
+        );
+    }
+    for (index, order) in orders.iter().enumerate() {
+        validate_order(order, limits).map_err(|error| at_index(error, index))?;
+    }
+    Ok(())
+}
+
+fn validate_order(order: &OrderRequest, limits: &ValidationLimits) -> Result<(), ValidationError> {
+    validate_item_name(order.item_name(), limits)?;
+    if order.quantity() < 1 || order.quantity() > limits.max_quantity_per_order {
+        return Err(at_field(
+            ValidationError::new("out_of_range").with_message(Cow::Owned(format!(
+                "must be between 1 and {}",
+                limits.max_quantity_per_order
+            ))),
+            "quantity".to_string(),
+        ));
+    }
+    validate_course(order.course(), limits)
+        .map_err(|error| at_field(error, "course".to_string()))?;
+    for (index, modifier) in order.modifiers().iter().enumerate() {
+        let field = format!("modifiers[{index}]");
+        validate_item_name(modifier, limits).map_err(|error| at_field(error, field.clone()))?;
+        if order.modifiers()[..index].contains(modifier) {
+            return Err(at_field(
+                ValidationError::new("duplicate_modifier")
+                    .with_message(Cow::Borrowed("can only be asked for once")),
+                field,
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub fn validate_course(course: i64, limits: &ValidationLimits) -> Result<(), ValidationError> {
+    if course < 1 || course > limits.max_courses {
+        return Err(
+            ValidationError::new("out_of_range").with_message(Cow::Owned(format!(
+                "must be between 1 and {}",
+                limits.max_courses
+            ))),
+        );
+    }
+    Ok(())
+}
+
+pub fn validate_item_name(name: &str, limits: &ValidationLimits) -> Result<(), ValidationError> {
+    if name.trim().is_empty() {
+        return Err(ValidationError::new("blank").with_message(Cow::Borrowed("must not be blank")));
+    }
+    if name.chars().count() > limits.max_item_name_length {
+        return Err(
+            ValidationError::new("too_long").with_message(Cow::Owned(format!(
+                "must be at most {} characters long",
+                limits.max_item_name_length
+            ))),
+        );
+    }
+    Ok(())
+}
+
+pub fn validate_recipe(
+    recipe: &[RecipeIngredient],
+    limits: &ValidationLimits,
+) -> Result<(), ValidationError> {
+    for (index, ingredient) in recipe.iter().enumerate() {
+        validate_item_name(&ingredient.ingredient_name, limits)
+            .map_err(|error| at_index(at_field(error, "ingredient_name".to_string()), index))?;
+        if ingredient.quantity < 1 {
+            return Err(at_index(
+                at_field(
+                    ValidationError::new("not_positive")
+                        .with_message(Cow::Borrowed("must be at least 1")),
+                    "quantity".to_string(),
+                ),
+                index,
+            ));
+        }
+        if ingredient.quantity > limits.max_recipe_quantity {
+            return Err(at_index(
+                at_field(
+                    ValidationError::new("out_of_range").with_message(Cow::Owned(format!(
+                        "must be at most {}",
+                        limits.max_recipe_quantity
+                    ))),
+                    "quantity".to_string(),
+                ),
+                index,
+            ));
+        }
+        if recipe[..index]
+            .iter()
+            .any(|earlier| earlier.ingredient_name == ingredient.ingredient_name)
+        {
+            return Err(at_index(
+                ValidationError::new("duplicate_ingredient")
+                    .with_message(Cow::Borrowed("can only be listed once")),
+                index,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Longer notes won't fit on a kitchen ticket.
+const MAX_ALLERGY_NOTE_LENGTH: usize = 500;
+
+pub fn validate_allergy_note(
+    allergy_note: &AllergyNote,
+    _limits: &ValidationLimits,
+) -> Result<(), ValidationError> {
+    for (index, allergen) in allergy_note.allergens.iter().enumerate() {
+        if allergy_note.allergens[..index].contains(allergen) {
+            return Err(at_field(
+                ValidationError::new("duplicate_allergen")
+                    .with_message(Cow::Borrowed("can only be listed once")),
+                format!("allergens[{index}]"),
+            ));
+        }
+    }
+    if let Some(note) = &allergy_note.note {
+        if note.chars().count() > MAX_ALLERGY_NOTE_LENGTH {
+            return Err(at_field(
+                ValidationError::new("too_long").with_message(Cow::Owned(format!(
+                    "must be at most {MAX_ALLERGY_NOTE_LENGTH} characters long"
+                ))),
+                "note".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub fn validate_daypart_names(
+    names: &[String],
+    limits: &ValidationLimits,
+) -> Result<(), ValidationError> {
+    for (index, name) in names.iter().enumerate() {
+        validate_item_name(name, limits).map_err(|error| at_index(error, index))?;
+        if names[..index].contains(name) {
+            return Err(at_index(
+                ValidationError::new("duplicate_daypart")
+                    .with_message(Cow::Borrowed("can only be listed once")),
+                index,
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub fn validate_item_names(
+    names: &[String],
+    limits: &ValidationLimits,
+) -> Result<(), ValidationError> {
+    for (index, name) in names.iter().enumerate() {
+        validate_item_name(name, limits).map_err(|error| at_index(error, index))?;
+    }
+    Ok(())
+}
+
+/// Every check an item of an imported menu file has to pass, for all of its fields at once.
+pub fn validate_menu_record(
+    item: &Item,
+    limits: &ValidationLimits,
+) -> Result<(), ValidationErrors> {
+    let mut errors = ValidationErrors::new();
+    if let Err(error) = validate_item_name(&item.item_name, limits) {
+        errors.add("item_name", error);
+    }
+    if item.cook_time < 0 {
+        errors.add(
+            "cook_time",
+            ValidationError::new("negative").with_message(Cow::Borrowed("must not be negative")),
+        );
+    }
+    if item.stock.is_some_and(|stock| stock < 0) {
+        errors.add(
+            "stock",
+            ValidationError::new("negative").with_message(Cow::Borrowed("must not be negative")),
+        );
+    }
+    for (index, modifier) in item.modifiers.iter().enumerate() {
+        let checked = validate_item_name(&modifier.name, limits).and_then(|_| {
+            match item.modifiers[..index]
+                .iter()
+                .any(|earlier| earlier.name == modifier.name)
+            {
+                true => Err(ValidationError::new("duplicate_modifier")
+                    .with_message(Cow::Borrowed("can only be listed once"))),
+                false => Ok(()),
+            }
+        });
+        if let Err(error) = checked {
+            errors.add(
+                "modifiers",
+                at_index(at_field(error, "name".to_string()), index),
+            );
+            break;
+        }
+    }
+    if let Err(error) = validate_recipe(&item.recipe, limits) {
+        errors.add("recipe", error);
+    }
+    if let Err(error) = validate_daypart_names(&item.dayparts, limits) {
+        errors.add("dayparts", error);
+    }
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors),
+    }
+}
+
+pub fn validate_ids(ids: &[i64]) -> Result<(), ValidationError> {
+    match ids.iter().position(|id| *id < 1) {
+        Some(index) => Err(at_index(
+            ValidationError::new("not_positive").with_message(Cow::Borrowed("must be at least 1")),
+            index,
+        )),
+        None => Ok(()),
+    }
+}
+
+fn at_index(mut error: ValidationError, index: usize) -> ValidationError {
+    error.add_param(Cow::Borrowed("index"), &index);
+    error
+}
+
+fn at_field(mut error: ValidationError, field: String) -> ValidationError {
+    error.add_param(Cow::Borrowed("field"), &field);
+    error
+}
+
+/// Flattens the errors of the derive into one entry per offending field, sorted by field so responses are stable.
+pub fn field_errors(errors: &ValidationErrors) -> Vec<FieldError> {
+    let mut field_errors = vec![];
+    collect_field_errors(errors, "", &mut field_errors);
+    field_errors.sort_by(|a, b| a.field.cmp(&b.field));
+    field_errors
+}
+
+fn collect_field_errors(
+    errors: &ValidationErrors,
+    prefix: &str,
+    field_errors: &mut Vec<FieldError>,
+) {
+    for (field, kind) in errors.errors() {
+        let path = format!("{prefix}{field}");
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                for error in errors {
+                    let mut field = match error.params.get("index") {
+                        Some(index) => format!("{path}[{index}]"),
+                        None => path.clone(),
+                    };
+                    if let Some(Value::String(element_field)) = error.params.get("field") {
+                        field = format!("{field}.{element_field}");
+                    }
+                    let message = error
+                        .message
+                        .as_ref()
+                        .map(|message| message.to_string())
+                        .unwrap_or_else(|| format!("failed the {} check", error.code));
+                    field_errors.push(FieldError::new(field, &error.code, message));
+                }
+            }
+            ValidationErrorsKind::Struct(errors) => {
+                collect_field_errors(errors, &format!("{path}."), field_errors)
+            }
+            ValidationErrorsKind::List(errors) => {
+                for (index, errors) in errors {
+                    collect_field_errors(errors, &format!("{path}[{index}]."), field_errors)
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode};
+use chrono::{DateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::{
+    common::{
+        config::{restaurant_timezone, ValidationLimits},
+        errors::{AxumErrorResponse, ProblemJson},
+        extractors::{Json, ValidJson, ValidPath},
+        models::{
+            path_schema::DaypartPath,
+            restaurant_schema::{DayOfWeek, Daypart},
+        },
+    },
+    AppState,
+};
+
+use super::daypart_db::DBDaypartTrait;
+
+#[derive(Deserialize, Serialize, ToSchema, Validate)]
+#[validate(context = ValidationLimits)]
+pub struct DaypartRequest {
+    /// the days it starts on, leave it out for every day
+    #[serde(default)]
+    pub days: Vec<DayOfWeek>,
+    /// local time, e.g. `07:00`
+    #[schema(value_type = String, example = "07:00")]
+    pub start: NaiveTime,
+    /// local time, before `start` to run past midnight, equal to it for the whole day
+    #[schema(value_type = String, example = "11:00")]
+    pub end: NaiveTime,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct DaypartResponse {
+    pub daypart_name: String,
+    pub days: Vec<DayOfWeek>,
+    #[schema(value_type = String)]
+    pub start: NaiveTime,
+    #[schema(value_type = String)]
+    pub end: NaiveTime,
+    /// its items are served right now
+    pub active: bool,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct DaypartsResponse {
+    /// sorted by start
+    pub dayparts: Vec<DaypartResponse>,
+}
+
+impl DaypartResponse {
+    fn new(daypart: Daypart, at: DateTime<Utc>) -> Self {
+        let active = is_active(&daypart, at);
+        let Daypart {
+            daypart_name,
+            days,
+            start,
+            end,
+        } = daypart;
+        Self {
+            daypart_name,
+            days,
+            start,
+            end,
+            active,
+        }
+    }
+}
+
+/// Whether the daypart is on at `at`, in the restaurant's local time.
+pub fn is_active(daypart: &Daypart, at: DateTime<Utc>) -> bool {
+    is_active_in(daypart, at, restaurant_timezone())
+}
+
+/// Whether the daypart is on at `at` on the wall clock of `timezone`.
+pub fn is_active_in(daypart: &Daypart, at: DateTime<Utc>, timezone: Tz) -> bool {
+    daypart.is_active(at.with_timezone(&timezone).naive_local())
+}
+
+/// The names of the dayparts that are on at `at`.
+pub fn active_dayparts(dayparts: &[Daypart], at: DateTime<Utc>) -> Vec<String> {
+    dayparts
+        .iter()
+        .filter(|daypart| is_active(daypart, at))
+        .map(|daypart| daypart.daypart_name.clone())
+        .collect()
+}
+
+#[utoipa::path(
+    get,
+    path = "/dayparts",
+    tag = "daypart",
+    responses(
+        (status = 200, description = "Every daypart, and whether it is on right now", body = DaypartsResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
+pub async fn list_dayparts(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<DaypartsResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)> {
+    let db = &app_state.db;
+
+    let now = app_state.clock.now();
+    match db.list_dayparts().await {
+        Ok(dayparts) => Ok((
+            StatusCode::OK,
+            Json(DaypartsResponse {
+                dayparts: dayparts
+                    .into_iter()
+                    .map(|daypart| DaypartResponse::new(daypart, now))
+                    .collect(),
+            }),
+        )),
+        Err(e) => Err(e.to_axum_error()),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/dayparts/{daypart_name}",
+    tag = "daypart",
+    params(("daypart_name" = String, Path, description = "Name of the daypart")),
+    request_body = DaypartRequest,
+    responses(
+        (status = 200, description = "The daypart as it is now, added if there wasn't one with its name", body = DaypartResponse),
+        (status = 422, description = "The daypart name or the times are invalid", body = AxumErrorResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
+pub async fn set_daypart(
+    State(app_state): State<Arc<AppState>>,
+    ValidPath(DaypartPath { daypart_name }): ValidPath<DaypartPath>,
+    ValidJson(request): ValidJson<DaypartRequest>,
+) -> Result<(StatusCode, Json<DaypartResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)> {
+    let db = &app_state.db;
+
+    let daypart = Daypart {
+        daypart_name,
+        days: request.days,
+        start: request.start,
+        end: request.end,
+    };
+    match db.set_daypart(daypart).await {
+        Ok(daypart) => Ok((
+            StatusCode::OK,
+            Json(DaypartResponse::new(daypart, app_state.clock.now())),
+        )),
+        Err(e) => Err(e.to_axum_error()),
+    }
+}
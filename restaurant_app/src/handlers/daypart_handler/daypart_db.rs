@@ -0,0 +1,96 @@
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use futures::stream::TryStreamExt;
+use mongodb::bson::doc;
+use mongodb::options::ReturnDocument;
+use tracing::{error, Instrument};
+
+use crate::common::{
+    database,
+    errors::{AxumErrors, ErrorResponse},
+    models::restaurant_schema::Daypart,
+    telemetry::mongo_span,
+};
+
+#[async_trait]
+pub trait DBDaypartTrait {
+    /// Every daypart, by start.
+    async fn list_dayparts(&self) -> Result<Vec<Daypart>, ErrorResponse>;
+    /// Adds the daypart, or replaces the one with its name.
+    async fn set_daypart(&self, daypart: Daypart) -> Result<Daypart, ErrorResponse>;
+}
+
+#[faux::methods]
+#[async_trait]
+impl DBDaypartTrait for database::DB {
+    async fn list_dayparts(&self) -> Result<Vec<Daypart>, ErrorResponse> {
+        let daypart_collection = self
+            .db
+            .database("item_management")
+            .collection::<Daypart>("dayparts");
+
+        let options = mongodb::options::FindOptions::builder()
+            .sort(doc! { "start": 1, "daypart_name": 1 })
+            .build();
+
+        match daypart_collection
+            .find(doc! {}, options)
+            .instrument(mongo_span("find", "dayparts"))
+            .await
+        {
+            Ok(cursor) => match cursor.try_collect().await {
+                Ok(dayparts) => Ok(dayparts),
+                Err(e) => {
+                    error!("Unexpected error occured while reading the Dayparts from the Database. Error: {e}");
+                    Err(ErrorResponse {
+                        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                        error: AxumErrors::DBError.into(),
+                    })
+                }
+            },
+            Err(e) => {
+                error!("Unexpected error occured while searching for the Dayparts in the Database. Error: {e}");
+                Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                })
+            }
+        }
+    }
+
+    async fn set_daypart(&self, daypart: Daypart) -> Result<Daypart, ErrorResponse> {
+        let daypart_collection = self
+            .db
+            .database("item_management")
+            .collection::<Daypart>("dayparts");
+
+        let options = mongodb::options::FindOneAndReplaceOptions::builder()
+            .upsert(true)
+            .return_document(ReturnDocument::After)
+            .build();
+
+        match daypart_collection
+            .find_one_and_replace(
+                doc! { "daypart_name": &daypart.daypart_name },
+                &daypart,
+                options,
+            )
+            .instrument(mongo_span("find_one_and_replace", "dayparts"))
+            .await
+        {
+            Ok(Some(daypart)) => Ok(daypart),
+            //only happens if it was deleted right after the upsert
+            Ok(None) => Ok(daypart),
+            Err(e) => {
+                error!(
+                    "Unexpected error occured while setting the Daypart {}. Error: {e}",
+                    daypart.daypart_name
+                );
+                Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                })
+            }
+        }
+    }
+}
@@ -0,0 +1,3 @@
+pub mod daypart;
+pub mod daypart_db;
+pub mod unit_tests;
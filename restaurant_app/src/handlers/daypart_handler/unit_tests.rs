@@ -0,0 +1,214 @@
+#[cfg(test)]
+pub mod daypart_unit_tests {
+
+    use axum::http::StatusCode;
+    use chrono::{DateTime, NaiveDateTime, NaiveTime, Utc};
+    use chrono_tz::Tz;
+
+    use crate::{
+        common::{
+            database::DB,
+            errors::{AxumErrors, ErrorResponse},
+            extractors::{ValidJson, ValidPath},
+            models::{
+                path_schema::DaypartPath,
+                restaurant_schema::{DayOfWeek, Daypart},
+            },
+        },
+        handlers::daypart_handler::daypart::{
+            active_dayparts, is_active_in, list_dayparts, set_daypart, DaypartRequest,
+        },
+        tests::fixtures::app_state_at,
+    };
+
+    fn time(time: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(time, "%H:%M").unwrap()
+    }
+
+    fn daypart(daypart_name: &str, days: Vec<DayOfWeek>, start: &str, end: &str) -> Daypart {
+        Daypart {
+            daypart_name: daypart_name.to_string(),
+            days,
+            start: time(start),
+            end: time(end),
+        }
+    }
+
+    /// 2024-03-01 was a Friday.
+    fn local(at: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&format!("2024-03-{at}"), "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn utc(at: &str) -> DateTime<Utc> {
+        local(at).and_utc()
+    }
+
+    ////////////////////////
+    //                   //
+    //  SCHEDULE        //
+    //                 //
+    ////////////////////
+
+    #[test]
+    pub fn daypart_is_active_within_its_window() {
+        let breakfast = daypart("Breakfast", vec![], "07:00", "11:00");
+
+        assert!(!breakfast.is_active(local("01 06:59")));
+        // the start is part of it, the end isn't
+        assert!(breakfast.is_active(local("01 07:00")));
+        assert!(breakfast.is_active(local("02 10:59")));
+        assert!(!breakfast.is_active(local("01 11:00")));
+    }
+
+    #[test]
+    pub fn daypart_is_active_past_midnight_on_the_day_it_started() {
+        let late_night = daypart(
+            "LateNight",
+            vec![DayOfWeek::Friday, DayOfWeek::Saturday],
+            "22:00",
+            "02:00",
+        );
+
+        // the early hours of friday are still thursday night, which isn't late night
+        assert!(!late_night.is_active(local("01 01:00")));
+        assert!(late_night.is_active(local("01 22:00")));
+        assert!(late_night.is_active(local("02 01:59")));
+        assert!(late_night.is_active(local("02 23:00")));
+        assert!(late_night.is_active(local("03 01:00")));
+        assert!(!late_night.is_active(local("03 02:00")));
+        // sunday night isn't either
+        assert!(!late_night.is_active(local("03 22:00")));
+    }
+
+    #[test]
+    pub fn daypart_with_the_same_start_and_end_lasts_the_whole_day() {
+        let weekend = daypart(
+            "Weekend",
+            vec![DayOfWeek::Saturday, DayOfWeek::Sunday],
+            "00:00",
+            "00:00",
+        );
+
+        assert!(!weekend.is_active(local("01 23:59")));
+        assert!(weekend.is_active(local("02 00:00")));
+        assert!(weekend.is_active(local("03 23:59")));
+    }
+
+    #[test]
+    pub fn active_dayparts_lists_the_ones_on() {
+        let dayparts = vec![
+            daypart("Breakfast", vec![], "07:00", "11:00"),
+            daypart("Brunch", vec![DayOfWeek::Sunday], "10:00", "14:00"),
+            daypart("Lunch", vec![], "11:00", "16:00"),
+        ];
+
+        assert_eq!(
+            active_dayparts(&dayparts, utc("03 10:30")),
+            vec!["Breakfast".to_string(), "Brunch".to_string()]
+        );
+        assert_eq!(
+            active_dayparts(&dayparts, utc("01 10:30")),
+            vec!["Breakfast".to_string()]
+        );
+        assert!(active_dayparts(&dayparts, utc("01 23:00")).is_empty());
+    }
+
+    #[test]
+    pub fn daypart_keeps_to_the_wall_clock_across_daylight_saving() {
+        let breakfast = daypart("Breakfast", vec![], "07:00", "11:00");
+
+        // Berlin moves from UTC+1 to UTC+2 on 2024-03-31, breakfast starts and ends an hour earlier in UTC
+        assert!(!is_active_in(
+            &breakfast,
+            utc("30 05:30"),
+            Tz::Europe__Berlin
+        ));
+        assert!(is_active_in(
+            &breakfast,
+            utc("30 09:30"),
+            Tz::Europe__Berlin
+        ));
+        assert!(is_active_in(
+            &breakfast,
+            utc("31 05:30"),
+            Tz::Europe__Berlin
+        ));
+        assert!(!is_active_in(
+            &breakfast,
+            utc("31 09:30"),
+            Tz::Europe__Berlin
+        ));
+    }
+
+    ////////////////////////
+    //                   //
+    //  DAYPARTS        //
+    //                 //
+    ////////////////////
+
+    #[tokio::test]
+    pub async fn successful_list_dayparts() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.list_dayparts).then(|_| {
+            Ok(vec![
+                daypart("Breakfast", vec![], "07:00", "11:00"),
+                daypart("Lunch", vec![], "11:00", "16:00"),
+            ])
+        });
+
+        match list_dayparts(app_state_at(mock_db, utc("01 12:00"))).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::OK);
+                assert_eq!(
+                    resp.1
+                        .dayparts
+                        .iter()
+                        .map(|daypart| (daypart.daypart_name.as_str(), daypart.active))
+                        .collect::<Vec<(&str, bool)>>(),
+                    vec![("Breakfast", false), ("Lunch", true)]
+                );
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_list_dayparts_db_error() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.list_dayparts).then(|_| Err(ErrorResponse::from(AxumErrors::DBError)));
+
+        match list_dayparts(app_state_at(mock_db, utc("01 12:00"))).await {
+            Ok(_) => panic!("shouldn't succeed!"),
+            Err(e) => {
+                assert_eq!(e.1.error_type, AxumErrors::DBError.to_string());
+            }
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_set_daypart() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.set_daypart).then(|daypart| {
+            assert_eq!(daypart.daypart_name, "Brunch");
+            assert_eq!(daypart.days, vec![DayOfWeek::Sunday]);
+            Ok(daypart)
+        });
+
+        let path = ValidPath(DaypartPath {
+            daypart_name: "Brunch".to_string(),
+        });
+        let request = ValidJson(DaypartRequest {
+            days: vec![DayOfWeek::Sunday],
+            start: time("10:00"),
+            end: time("14:00"),
+        });
+        match set_daypart(app_state_at(mock_db, utc("03 12:00")), path, request).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::OK);
+                assert_eq!(resp.1.start, time("10:00"));
+                assert!(resp.1.active);
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{extract::State, http::StatusCode};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::{
+    common::{
+        config::ValidationLimits,
+        errors::{AxumErrorResponse, ProblemJson},
+        extractors::{Json, ValidJson, ValidPath},
+        models::{
+            path_schema::IngredientPath,
+            restaurant_schema::{Ingredient, Item, Unit},
+        },
+    },
+    AppState,
+};
+
+use super::inventory_db::DBInventoryTrait;
+
+#[derive(Deserialize, Serialize, ToSchema, Validate)]
+#[validate(context = ValidationLimits)]
+pub struct IngredientRequest {
+    pub unit: Unit,
+    /// how much is left, in `unit`
+    #[validate(range(min = 0, code = "negative", message = "must not be negative"))]
+    pub stock: i64,
+    /// the ingredient shows up in the alerts once `stock` drops to it, leave it out to never alert
+    #[validate(range(min = 0, code = "negative", message = "must not be negative"))]
+    pub low_stock_threshold: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct IngredientResponse {
+    pub ingredient_name: String,
+    pub unit: Unit,
+    pub stock: i64,
+    pub low_stock_threshold: Option<i64>,
+    /// `stock` dropped to the threshold
+    pub low_stock: bool,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct InventoryResponse {
+    /// sorted by name
+    pub ingredients: Vec<IngredientResponse>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UnmakeableItemsResponse {
+    /// sorted by name
+    pub items: Vec<UnmakeableItemResponse>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UnmakeableItemResponse {
+    pub item_name: String,
+    /// the ingredients of the recipe there isn't enough of for a single portion
+    pub missing: Vec<MissingIngredientResponse>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MissingIngredientResponse {
+    pub ingredient_name: String,
+    /// what one portion takes
+    pub needed: i64,
+    /// what is left, 0 if the ingredient isn't in the inventory
+    pub in_stock: i64,
+    /// missing if the ingredient isn't in the inventory
+    pub unit: Option<Unit>,
+}
+
+impl From<Ingredient> for IngredientResponse {
+    fn from(ingredient: Ingredient) -> Self {
+        let low_stock = ingredient.is_low();
+        let Ingredient {
+            ingredient_name,
+            unit,
+            stock,
+            low_stock_threshold,
+        } = ingredient;
+        Self {
+            ingredient_name,
+            unit,
+            stock,
+            low_stock_threshold,
+            low_stock,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/inventory",
+    tag = "inventory",
+    responses(
+        (status = 200, description = "Every ingredient in the inventory", body = InventoryResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
+pub async fn list_ingredients(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<InventoryResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)> {
+    let db = &app_state.db;
+
+    match db.list_ingredients().await {
+        Ok(ingredients) => Ok((
+            StatusCode::OK,
+            Json(InventoryResponse {
+                ingredients: ingredients
+                    .into_iter()
+                    .map(|ingredient| ingredient.into())
+                    .collect(),
+            }),
+        )),
+        Err(e) => Err(e.to_axum_error()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/inventory/alerts",
+    tag = "inventory",
+    responses(
+        (status = 200, description = "The ingredients that are low on stock", body = InventoryResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
+pub async fn list_low_stock(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<InventoryResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)> {
+    let db = &app_state.db;
+
+    match db.list_ingredients().await {
+        Ok(ingredients) => Ok((
+            StatusCode::OK,
+            Json(InventoryResponse {
+                ingredients: ingredients
+                    .into_iter()
+                    .filter(|ingredient| ingredient.is_low())
+                    .map(|ingredient| ingredient.into())
+                    .collect(),
+            }),
+        )),
+        Err(e) => Err(e.to_axum_error()),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/inventory/{ingredient_name}",
+    tag = "inventory",
+    params(("ingredient_name" = String, Path, description = "Name of the ingredient")),
+    request_body = IngredientRequest,
+    responses(
+        (status = 200, description = "The ingredient as it is now, added if it wasn't in the inventory", body = IngredientResponse),
+        (status = 422, description = "The ingredient name or the stock is invalid", body = AxumErrorResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
+pub async fn set_ingredient(
+    State(app_state): State<Arc<AppState>>,
+    ValidPath(IngredientPath { ingredient_name }): ValidPath<IngredientPath>,
+    ValidJson(request): ValidJson<IngredientRequest>,
+) -> Result<(StatusCode, Json<IngredientResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)> {
+    let db = &app_state.db;
+
+    let ingredient = Ingredient {
+        ingredient_name,
+        unit: request.unit,
+        stock: request.stock,
+        low_stock_threshold: request.low_stock_threshold,
+    };
+    match db.set_ingredient(ingredient).await {
+        Ok(ingredient) => Ok((StatusCode::OK, Json(ingredient.into()))),
+        Err(e) => Err(e.to_axum_error()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/inventory/unmakeable_items",
+    tag = "inventory",
+    responses(
+        (status = 200, description = "The items there aren't enough ingredients left for", body = UnmakeableItemsResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
+pub async fn list_unmakeable_items(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<UnmakeableItemsResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)>
+{
+    let db = &app_state.db;
+
+    let items = match app_state.menu_cache.get_menu(db).await {
+        Ok(items) => items,
+        Err(e) => return Err(e.to_axum_error()),
+    };
+    match db.list_ingredients().await {
+        Ok(ingredients) => Ok((
+            StatusCode::OK,
+            Json(build_unmakeable_items(items, ingredients)),
+        )),
+        Err(e) => Err(e.to_axum_error()),
+    }
+}
+
+/// Checks the recipe of every item against the inventory, items without a recipe can always be made.
+pub fn build_unmakeable_items(
+    mut items: Vec<Item>,
+    ingredients: Vec<Ingredient>,
+) -> UnmakeableItemsResponse {
+    let ingredients = ingredients
+        .into_iter()
+        .map(|ingredient| (ingredient.ingredient_name.clone(), ingredient))
+        .collect::<HashMap<String, Ingredient>>();
+    items.sort_by(|a, b| a.item_name.cmp(&b.item_name));
+
+    let items = items
+        .into_iter()
+        .filter_map(|item| {
+            let missing = item
+                .recipe
+                .into_iter()
+                .filter_map(|needed| {
+                    let ingredient = ingredients.get(&needed.ingredient_name);
+                    let in_stock = ingredient.map_or(0, |ingredient| ingredient.stock);
+                    match in_stock < needed.quantity {
+                        true => Some(MissingIngredientResponse {
+                            ingredient_name: needed.ingredient_name,
+                            needed: needed.quantity,
+                            in_stock,
+                            unit: ingredient.map(|ingredient| ingredient.unit),
+                        }),
+                        false => None,
+                    }
+                })
+                .collect::<Vec<MissingIngredientResponse>>();
+            match missing.is_empty() {
+                true => None,
+                false => Some(UnmakeableItemResponse {
+                    item_name: item.item_name,
+                    missing,
+                }),
+            }
+        })
+        .collect();
+
+    UnmakeableItemsResponse { items }
+}
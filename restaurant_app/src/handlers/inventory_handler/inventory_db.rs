@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use futures::stream::TryStreamExt;
+use mongodb::bson::doc;
+use mongodb::options::ReturnDocument;
+use tracing::{error, Instrument};
+
+use crate::common::{
+    database,
+    errors::{AxumErrorResponse, AxumErrors, ErrorResponse, FieldError},
+    models::restaurant_schema::Ingredient,
+    telemetry::mongo_span,
+};
+
+#[async_trait]
+pub trait DBInventoryTrait {
+    /// Every ingredient in the inventory, by name.
+    async fn list_ingredients(&self) -> Result<Vec<Ingredient>, ErrorResponse>;
+    /// Looks up every distinct name in one `$in` query, names that aren't in the inventory are simply left out of the map.
+    async fn get_ingredients_by_name(
+        &self,
+        ingredient_names: &[String],
+    ) -> Result<HashMap<String, Ingredient>, ErrorResponse>;
+    /// Adds the ingredient to the inventory, or replaces the one with its name.
+    async fn set_ingredient(&self, ingredient: Ingredient) -> Result<Ingredient, ErrorResponse>;
+    /// Takes the amounts from the stock of each ingredient, only while enough of it is left.
+    /// Like `reserve_stock` every ingredient is taken on its own, if one of them runs short the ones already taken are given back.
+    /// Returns the ingredients it took from as they are now.
+    async fn reserve_ingredients(
+        &self,
+        amounts: &HashMap<String, i64>,
+    ) -> Result<Vec<Ingredient>, ErrorResponse>;
+    /// Gives the amounts back, e.g. when an order is voided.
+    async fn release_ingredients(
+        &self,
+        amounts: &HashMap<String, i64>,
+    ) -> Result<(), ErrorResponse>;
+}
+
+#[faux::methods]
+#[async_trait]
+impl DBInventoryTrait for database::DB {
+    async fn list_ingredients(&self) -> Result<Vec<Ingredient>, ErrorResponse> {
+        let ingredient_collection = self
+            .db
+            .database("inventory_management")
+            .collection::<Ingredient>("ingredients");
+
+        let options = mongodb::options::FindOptions::builder()
+            .sort(doc! { "ingredient_name": 1 })
+            .build();
+
+        match ingredient_collection
+            .find(doc! {}, options)
+            .instrument(mongo_span("find", "ingredients"))
+            .await
+        {
+            Ok(cursor) => match cursor.try_collect().await {
+                Ok(ingredients) => Ok(ingredients),
+                Err(e) => {
+                    error!("Unexpected error occured while reading the Ingredients from the Database. Error: {e}");
+                    Err(ErrorResponse {
+                        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                        error: AxumErrors::DBError.into(),
+                    })
+                }
+            },
+            Err(e) => {
+                error!("Unexpected error occured while searching for the Ingredients in the Database. Error: {e}");
+                Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                })
+            }
+        }
+    }
+
+    async fn get_ingredients_by_name(
+        &self,
+        ingredient_names: &[String],
+    ) -> Result<HashMap<String, Ingredient>, ErrorResponse> {
+        let ingredient_collection = self
+            .db
+            .database("inventory_management")
+            .collection::<Ingredient>("ingredients");
+
+        let filter = doc! {
+            "ingredient_name": { "$in": ingredient_names }
+        };
+
+        match ingredient_collection
+            .find(filter, None)
+            .instrument(mongo_span("find", "ingredients"))
+            .await
+        {
+            Ok(cursor) => match cursor.try_collect::<Vec<Ingredient>>().await {
+                Ok(ingredients) => Ok(ingredients
+                    .into_iter()
+                    .map(|ingredient| (ingredient.ingredient_name.clone(), ingredient))
+                    .collect()),
+                Err(e) => {
+                    error!("Unexpected error occured while reading the Ingredients from the Database. Error: {e}");
+                    Err(ErrorResponse {
+                        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                        error: AxumErrors::DBError.into(),
+                    })
+                }
+            },
+            Err(e) => {
+                error!("Unexpected error occured while searching for the Ingredients in the Database. Error: {e}");
+                Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                })
+            }
+        }
+    }
+
+    async fn set_ingredient(&self, ingredient: Ingredient) -> Result<Ingredient, ErrorResponse> {
+        let ingredient_collection = self
+            .db
+            .database("inventory_management")
+            .collection::<Ingredient>("ingredients");
+
+        let options = mongodb::options::FindOneAndReplaceOptions::builder()
+            .upsert(true)
+            .return_document(ReturnDocument::After)
+            .build();
+
+        match ingredient_collection
+            .find_one_and_replace(
+                doc! { "ingredient_name": &ingredient.ingredient_name },
+                &ingredient,
+                options,
+            )
+            .instrument(mongo_span("find_one_and_replace", "ingredients"))
+            .await
+        {
+            Ok(Some(ingredient)) => Ok(ingredient),
+            //only happens if it was deleted right after the upsert
+            Ok(None) => Ok(ingredient),
+            Err(e) => {
+                error!(
+                    "Unexpected error occured while setting the Ingredient {}. Error: {e}",
+                    ingredient.ingredient_name
+                );
+                Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                })
+            }
+        }
+    }
+
+    async fn reserve_ingredients(
+        &self,
+        amounts: &HashMap<String, i64>,
+    ) -> Result<Vec<Ingredient>, ErrorResponse> {
+        let ingredient_collection = self
+            .db
+            .database("inventory_management")
+            .collection::<Ingredient>("ingredients");
+
+        let options = mongodb::options::FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        let mut reserved = HashMap::new();
+        let mut ingredients = vec![];
+        for (ingredient_name, amount) in amounts {
+            //the check and the decrement are one update, the stock never drops below zero
+            let filter = doc! {
+                "ingredient_name": ingredient_name,
+                "stock": { "$gte": amount }
+            };
+            let update = doc! {
+                "$inc": { "stock": -amount }
+            };
+            let error = match ingredient_collection
+                .find_one_and_update(filter, update, options.clone())
+                .instrument(mongo_span("find_one_and_update", "ingredients"))
+                .await
+            {
+                Ok(Some(ingredient)) => {
+                    reserved.insert(ingredient_name.clone(), *amount);
+                    ingredients.push(ingredient);
+                    continue;
+                }
+                Ok(None) => ErrorResponse::from(
+                    AxumErrorResponse::from(AxumErrors::ItemUnavailable).with_errors(vec![
+                        FieldError::new(
+                            "orders",
+                            "out_of_ingredients",
+                            format!(
+                                "the orders need {amount} of {ingredient_name}, fewer are left"
+                            ),
+                        ),
+                    ]),
+                ),
+                Err(e) => {
+                    error!("Unexpected error occured while taking {ingredient_name} from the inventory. Error: {e}");
+                    ErrorResponse {
+                        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                        error: AxumErrors::DBError.into(),
+                    }
+                }
+            };
+            if !reserved.is_empty() {
+                if let Err(e) = self.release_ingredients(&reserved).await {
+                    error!(
+                        "Was unable to give back the ingredients of a rejected order, the inventory is short by {reserved:?}. Error: {}",
+                        e.error.detail
+                    );
+                }
+            }
+            return Err(error);
+        }
+        Ok(ingredients)
+    }
+
+    async fn release_ingredients(
+        &self,
+        amounts: &HashMap<String, i64>,
+    ) -> Result<(), ErrorResponse> {
+        let ingredient_collection = self
+            .db
+            .database("inventory_management")
+            .collection::<Ingredient>("ingredients");
+
+        //every ingredient is given back even if one of them fails, so only the failed ones are short
+        let mut failed = vec![];
+        for (ingredient_name, amount) in amounts {
+            //an ingredient that was taken out of the inventory in the meantime stays out
+            if let Err(e) = ingredient_collection
+                .update_one(
+                    doc! { "ingredient_name": ingredient_name },
+                    doc! { "$inc": { "stock": amount } },
+                    None,
+                )
+                .instrument(mongo_span("update_one", "ingredients"))
+                .await
+            {
+                error!("Unexpected error occured while giving back {ingredient_name} to the inventory. Error: {e}");
+                failed.push(ingredient_name.as_str());
+            }
+        }
+        match failed.is_empty() {
+            true => Ok(()),
+            false => Err(ErrorResponse {
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                error: AxumErrorResponse::from(AxumErrors::DBError)
+                    .with_detail(format!(
+                        "Was unable to give back {} to the inventory",
+                        failed.join(", ")
+                    ))
+                    .into(),
+            }),
+        }
+    }
+}
@@ -0,0 +1,3 @@
+pub mod inventory;
+pub mod inventory_db;
+pub mod unit_tests;
@@ -0,0 +1,187 @@
+#[cfg(test)]
+pub mod inventory_unit_tests {
+
+    use axum::http::StatusCode;
+
+    use crate::{
+        common::{
+            database::DB,
+            errors::{AxumErrors, ErrorResponse},
+            extractors::{ValidJson, ValidPath},
+            models::{
+                path_schema::IngredientPath,
+                restaurant_schema::{Ingredient, Unit},
+            },
+        },
+        handlers::inventory_handler::inventory::{
+            build_unmakeable_items, list_ingredients, list_low_stock, list_unmakeable_items,
+            set_ingredient, IngredientRequest,
+        },
+        tests::fixtures::{app_state, recipe_item},
+    };
+
+    fn ingredient(
+        ingredient_name: &str,
+        stock: i64,
+        low_stock_threshold: Option<i64>,
+    ) -> Ingredient {
+        Ingredient {
+            ingredient_name: ingredient_name.to_string(),
+            unit: Unit::Gram,
+            stock,
+            low_stock_threshold,
+        }
+    }
+
+    ////////////////////////
+    //                   //
+    //  INGREDIENTS     //
+    //                 //
+    ////////////////////
+
+    #[tokio::test]
+    pub async fn successful_list_ingredients() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.list_ingredients).then(|_| {
+            Ok(vec![
+                ingredient("Eel", 100, Some(500)),
+                ingredient("Rice", 5000, Some(500)),
+                ingredient("Salt", 0, None),
+            ])
+        });
+
+        match list_ingredients(app_state(mock_db)).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::OK);
+                assert_eq!(
+                    resp.1
+                        .ingredients
+                        .iter()
+                        .map(|ingredient| (
+                            ingredient.ingredient_name.as_str(),
+                            ingredient.low_stock
+                        ))
+                        .collect::<Vec<(&str, bool)>>(),
+                    // without a threshold an ingredient never runs low, even when there is none left
+                    vec![("Eel", true), ("Rice", false), ("Salt", false)]
+                );
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_list_low_stock() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.list_ingredients).then(|_| {
+            Ok(vec![
+                ingredient("Eel", 500, Some(500)),
+                ingredient("Rice", 5000, Some(500)),
+            ])
+        });
+
+        match list_low_stock(app_state(mock_db)).await {
+            Ok(resp) => {
+                assert_eq!(resp.1.ingredients.len(), 1);
+                assert_eq!(resp.1.ingredients[0].ingredient_name, "Eel");
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_list_low_stock_db_error() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.list_ingredients)
+            .then(|_| Err(ErrorResponse::from(AxumErrors::DBError)));
+
+        match list_low_stock(app_state(mock_db)).await {
+            Ok(_) => panic!("shouldn't succeed!"),
+            Err(e) => {
+                assert_eq!(e.1.error_type, AxumErrors::DBError.to_string());
+            }
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_set_ingredient() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.set_ingredient).then(|ingredient| {
+            assert_eq!(ingredient.ingredient_name, "Eel");
+            assert_eq!(ingredient.unit, Unit::Gram);
+            Ok(ingredient)
+        });
+
+        let path = ValidPath(IngredientPath {
+            ingredient_name: "Eel".to_string(),
+        });
+        let request = ValidJson(IngredientRequest {
+            unit: Unit::Gram,
+            stock: 2000,
+            low_stock_threshold: Some(500),
+        });
+        match set_ingredient(app_state(mock_db), path, request).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::OK);
+                assert_eq!(resp.1.stock, 2000);
+                assert!(!resp.1.low_stock);
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    ////////////////////////
+    //                   //
+    //  UNMAKEABLE      //
+    //                 //
+    ////////////////////
+
+    #[test]
+    pub fn build_unmakeable_items_lists_missing_ingredients() {
+        let items = vec![
+            recipe_item("UnagiDon", &[("Eel", 120), ("Rice", 200)]),
+            recipe_item("Onigiri", &[("Rice", 100), ("Nori", 1)]),
+            recipe_item("Tea", &[]),
+        ];
+        let ingredients = vec![ingredient("Eel", 100, None), ingredient("Rice", 150, None)];
+
+        let items = build_unmakeable_items(items, ingredients).items;
+        assert_eq!(items.len(), 2);
+
+        // sorted by name, the tea has no recipe and can always be made
+        assert_eq!(items[0].item_name, "Onigiri");
+        assert_eq!(items[0].missing.len(), 1);
+        assert_eq!(items[0].missing[0].ingredient_name, "Nori");
+        assert_eq!(items[0].missing[0].in_stock, 0);
+        assert_eq!(items[0].missing[0].unit, None);
+
+        assert_eq!(items[1].item_name, "UnagiDon");
+        assert_eq!(
+            items[1]
+                .missing
+                .iter()
+                .map(|missing| (
+                    missing.ingredient_name.as_str(),
+                    missing.needed,
+                    missing.in_stock
+                ))
+                .collect::<Vec<(&str, i64, i64)>>(),
+            vec![("Eel", 120, 100), ("Rice", 200, 150)]
+        );
+    }
+
+    #[tokio::test]
+    pub async fn successful_list_unmakeable_items() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_menu).then(|_| Ok(vec![recipe_item("UnagiDon", &[("Eel", 120)])]));
+        faux::when!(mock_db.list_ingredients).then(|_| Ok(vec![ingredient("Eel", 240, None)]));
+
+        match list_unmakeable_items(app_state(mock_db)).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::OK);
+                assert!(resp.1.items.is_empty());
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+}
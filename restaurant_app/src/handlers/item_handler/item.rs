@@ -1,79 +1,400 @@
 use std::sync::Arc;
 
-use axum::{extract::State, http::StatusCode, Json};
-use axum_extra::extract::Query;
+use axum::{extract::State, http::StatusCode};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
 
 use crate::{
     common::{
-        errors::AxumErrorResponse,
-        models::{pagination_schema::Pagination, restaurant_schema::ItemResponse},
+        config::ValidationLimits,
+        database::DB,
+        errors::{AxumErrorResponse, AxumErrors, ErrorResponse, FieldError, ProblemJson},
+        extractors::{Json, ValidExtraQuery, ValidJson, ValidPath},
+        models::{
+            pagination_schema::Pagination,
+            path_schema::ItemPath,
+            restaurant_schema::{
+                Allergen, Category, Diet, Item, ItemResponse, KitchenItemResponse, RecipeIngredient,
+            },
+        },
+        validation::{validate_daypart_names, validate_item_names, validate_recipe},
     },
+    handlers::daypart_handler::{daypart::active_dayparts, daypart_db::DBDaypartTrait},
+    handlers::inventory_handler::inventory_db::DBInventoryTrait,
     AppState,
 };
 
 use super::item_db::DBTableTrait;
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, IntoParams, ToSchema, Validate)]
+#[into_params(parameter_in = Query)]
+#[validate(context = ValidationLimits)]
 pub struct ListItemsRequest {
+    /// only these items, repeat the param for each item
     #[serde(default = "empty_vec_of_strings")]
+    #[validate(custom(function = "validate_item_names", use_context))]
     pub item_names: Vec<String>,
+    /// only items of this section of the menu
+    pub category: Option<Category>,
+    /// only items that can be ordered right now, available and not sold out
+    #[serde(default)]
+    pub available_only: bool,
+    /// leave out items with any of these allergens, repeat the param for each allergen
+    #[serde(default)]
+    pub exclude_allergens: Vec<Allergen>,
+    /// only items that fit every one of these diets, repeat the param for each diet
+    #[serde(default)]
+    pub diet: Vec<Diet>,
+    /// only items served at this time, now when left out
+    pub at: Option<DateTime<Utc>>,
+}
+
+impl ListItemsRequest {
+    /// Whether the item passes every filter, `served_dayparts` are the dayparts on at `at`, `None` when no item has any.
+    fn matches(&self, item: &Item, served_dayparts: Option<&[String]>) -> bool {
+        (self.item_names.is_empty() || self.item_names.contains(&item.item_name))
+            && self
+                .category
+                .is_none_or(|category| item.category == category)
+            && !item
+                .allergens
+                .iter()
+                .any(|allergen| self.exclude_allergens.contains(allergen))
+            && self.diet.iter().all(|diet| item.diets.contains(diet))
+            && (!self.available_only
+                || (item.available && item.stock.is_none_or(|stock| stock > 0)))
+            && (item.dayparts.is_empty()
+                || served_dayparts.is_none_or(|served| {
+                    item.dayparts.iter().any(|daypart| served.contains(daypart))
+                }))
+    }
 }
 
 pub fn empty_vec_of_strings() -> Vec<String> {
     [].to_vec()
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ListItemsResponse {
     pub items: Vec<ItemResponse>,
     pub pagination: ListItemsPaginationResponse,
     pub filters: ListItemsRequest,
-    pub errors: ListItemsErrorResponse,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ListItemsPaginationResponse {
     pub total: u64,
     pub limit: i64,
     pub offset: u64,
 }
 
-#[derive(Serialize)]
-pub struct ListItemsErrorResponse {
-    pub failed_items_ids: Option<Vec<String>>,
-    pub failed_items_count: u64,
+#[derive(Deserialize, Serialize, ToSchema, Validate)]
+#[validate(context = ValidationLimits)]
+pub struct ItemAvailabilityRequest {
+    /// false takes the item off the menu, e.g. when the kitchen ran out ("86" it)
+    pub available: bool,
+    /// portions left, every order takes its quantity from it, leave it out to stop counting
+    #[validate(range(min = 0, code = "negative", message = "must not be negative"))]
+    pub stock: Option<i64>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Validate)]
+#[validate(context = ValidationLimits)]
+pub struct RecipeRequest {
+    /// what one portion takes from the inventory, every ingredient once, an empty recipe takes nothing
+    #[validate(custom(function = "validate_recipe", use_context))]
+    pub recipe: Vec<RecipeIngredient>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Validate)]
+#[validate(context = ValidationLimits)]
+pub struct DaypartsRequest {
+    /// names of the dayparts the item is served in, each once, none to serve it all day
+    #[validate(custom(function = "validate_daypart_names", use_context))]
+    pub dayparts: Vec<String>,
 }
 
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MenuResponse {
+    /// the sections that have items, appetizers first and drinks last
+    pub categories: Vec<MenuCategoryResponse>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MenuCategoryResponse {
+    pub category: Category,
+    /// sorted by `display_order`, then by name
+    pub items: Vec<ItemResponse>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/item",
+    tag = "item",
+    params(Pagination, ListItemsRequest),
+    responses(
+        (status = 200, description = "Items on the menu", body = ListItemsResponse),
+        (status = 422, description = "The pagination or the filters are invalid", body = AxumErrorResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
 pub async fn list_items(
     State(app_state): State<Arc<AppState>>,
-    pagination: Query<Pagination>,
-    Query(filters): Query<ListItemsRequest>,
-) -> Result<(StatusCode, Json<ListItemsResponse>), (StatusCode, Json<AxumErrorResponse>)> {
+    pagination: ValidExtraQuery<Pagination>,
+    ValidExtraQuery(mut filters): ValidExtraQuery<ListItemsRequest>,
+) -> Result<(StatusCode, Json<ListItemsResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)> {
     let db = &app_state.db;
 
+    //the response tells the caller which time the items were picked for
+    let at = *filters.at.get_or_insert_with(|| app_state.clock.now());
+
     let pagination = Pagination {
         limit: pagination.limit,
         offset: pagination.offset,
     };
 
-    match db.list_items(filters.item_names.clone(), &pagination).await {
-        Ok(list_results) => Ok((
-            StatusCode::OK,
-            Json(ListItemsResponse {
-                items: list_results.items,
-                filters: filters,
-                pagination: ListItemsPaginationResponse {
-                    total: list_results.count,
-                    limit: pagination.limit,
-                    offset: pagination.offset,
-                },
-                errors: ListItemsErrorResponse {
-                    failed_items_ids: list_results.failed_items,
-                    failed_items_count: list_results.dropped,
-                },
-            }),
-        )),
+    //the items are filtered on the cached menu, only the dayparts are read if some item is served in them
+    let menu = match app_state.menu_cache.get_menu(db).await {
+        Ok(menu) => menu,
+        Err(e) => return Err(e.to_axum_error()),
+    };
+    let served_dayparts = match menu.iter().any(|item| !item.dayparts.is_empty()) {
+        true => match db.list_dayparts().await {
+            Ok(dayparts) => Some(active_dayparts(&dayparts, at)),
+            Err(e) => return Err(e.to_axum_error()),
+        },
+        false => None,
+    };
+    let mut items = menu
+        .into_iter()
+        .filter(|item| filters.matches(item, served_dayparts.as_deref()))
+        .collect::<Vec<Item>>();
+    //the cached menu has no order of its own, by name keeps the pages stable
+    items.sort_by(|a, b| a.item_name.cmp(&b.item_name));
+    let total = items.len() as u64;
+    let items = items
+        .into_iter()
+        .skip(pagination.offset as usize)
+        .take(pagination.limit as usize)
+        .map(ItemResponse::from)
+        .collect();
+
+    Ok((
+        StatusCode::OK,
+        Json(ListItemsResponse {
+            items,
+            filters,
+            pagination: ListItemsPaginationResponse {
+                total,
+                limit: pagination.limit,
+                offset: pagination.offset,
+            },
+        }),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/menu",
+    tag = "item",
+    responses(
+        (status = 200, description = "The whole menu, by category", body = MenuResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
+pub async fn get_menu(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<MenuResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)> {
+    let db = &app_state.db;
+
+    match app_state.menu_cache.get_menu(db).await {
+        Ok(items) => Ok((StatusCode::OK, Json(build_menu(items)))),
+        Err(e) => Err(e.to_axum_error()),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/item/{item_name}/availability",
+    tag = "item",
+    params(("item_name" = String, Path, description = "Name of the item")),
+    request_body = ItemAvailabilityRequest,
+    responses(
+        (status = 200, description = "The item as it is now, with its stock and recipe", body = KitchenItemResponse),
+        (status = 404, description = "Item was not found", body = AxumErrorResponse),
+        (status = 422, description = "The item name or the stock is invalid", body = AxumErrorResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
+pub async fn update_availability(
+    State(app_state): State<Arc<AppState>>,
+    ValidPath(ItemPath { item_name }): ValidPath<ItemPath>,
+    ValidJson(request): ValidJson<ItemAvailabilityRequest>,
+) -> Result<(StatusCode, Json<KitchenItemResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)> {
+    let db = &app_state.db;
+
+    match db
+        .set_availability(&item_name, request.available, request.stock)
+        .await
+    {
+        Ok(item) => {
+            //new orders have to see the change right away, not once the cached menu expires
+            app_state.menu_cache.invalidate().await;
+            Ok((StatusCode::OK, Json(item.into())))
+        }
         Err(e) => Err(e.to_axum_error()),
     }
 }
+
+#[utoipa::path(
+    put,
+    path = "/item/{item_name}/recipe",
+    tag = "item",
+    params(("item_name" = String, Path, description = "Name of the item")),
+    request_body = RecipeRequest,
+    responses(
+        (status = 200, description = "The item as it is now, with its stock and recipe", body = KitchenItemResponse),
+        (status = 404, description = "Item was not found", body = AxumErrorResponse),
+        (status = 422, description = "The recipe is invalid, or some of its ingredients are not in the inventory", body = AxumErrorResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
+pub async fn update_recipe(
+    State(app_state): State<Arc<AppState>>,
+    ValidPath(ItemPath { item_name }): ValidPath<ItemPath>,
+    ValidJson(request): ValidJson<RecipeRequest>,
+) -> Result<(StatusCode, Json<KitchenItemResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)> {
+    let db = &app_state.db;
+
+    if let Err(e) = check_ingredients(db, &request.recipe).await {
+        return Err(e.to_axum_error());
+    }
+
+    match db.set_recipe(&item_name, request.recipe).await {
+        Ok(item) => {
+            //the cached menu has the recipes new orders take their ingredients by
+            app_state.menu_cache.invalidate().await;
+            Ok((StatusCode::OK, Json(item.into())))
+        }
+        Err(e) => Err(e.to_axum_error()),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/item/{item_name}/dayparts",
+    tag = "item",
+    params(("item_name" = String, Path, description = "Name of the item")),
+    request_body = DaypartsRequest,
+    responses(
+        (status = 200, description = "The item as it is now, with its stock and recipe", body = KitchenItemResponse),
+        (status = 404, description = "Item was not found", body = AxumErrorResponse),
+        (status = 422, description = "The daypart names are invalid, or some of the dayparts don't exist", body = AxumErrorResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
+pub async fn update_dayparts(
+    State(app_state): State<Arc<AppState>>,
+    ValidPath(ItemPath { item_name }): ValidPath<ItemPath>,
+    ValidJson(request): ValidJson<DaypartsRequest>,
+) -> Result<(StatusCode, Json<KitchenItemResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)> {
+    let db = &app_state.db;
+
+    if let Err(e) = check_dayparts(db, &request.dayparts).await {
+        return Err(e.to_axum_error());
+    }
+
+    match db.set_dayparts(&item_name, request.dayparts).await {
+        Ok(item) => {
+            //new orders are checked against the cached menu
+            app_state.menu_cache.invalidate().await;
+            Ok((StatusCode::OK, Json(item.into())))
+        }
+        Err(e) => Err(e.to_axum_error()),
+    }
+}
+
+/// Fails with the recipe's ingredients that aren't in the inventory, if there are any.
+pub async fn check_ingredients(db: &DB, recipe: &[RecipeIngredient]) -> Result<(), ErrorResponse> {
+    //every ingredient has to be in the inventory, otherwise the item could never be ordered
+    let ingredient_names = recipe
+        .iter()
+        .map(|ingredient| ingredient.ingredient_name.clone())
+        .collect::<Vec<String>>();
+    let inventory = db.get_ingredients_by_name(&ingredient_names).await?;
+    let unknown_ingredients = ingredient_names
+        .iter()
+        .enumerate()
+        .filter(|(_, ingredient_name)| !inventory.contains_key(*ingredient_name))
+        .map(|(index, ingredient_name)| {
+            FieldError::new(
+                format!("recipe[{index}].ingredient_name"),
+                "unknown_ingredient",
+                format!("{ingredient_name} is not in the inventory"),
+            )
+        })
+        .collect::<Vec<FieldError>>();
+    if !unknown_ingredients.is_empty() {
+        return Err(ErrorResponse::from(
+            AxumErrorResponse::from(AxumErrors::UnknownIngredients)
+                .with_errors(unknown_ingredients),
+        ));
+    }
+    Ok(())
+}
+
+/// Fails with the daypart names that aren't dayparts, if there are any.
+pub async fn check_dayparts(db: &DB, dayparts: &[String]) -> Result<(), ErrorResponse> {
+    //a daypart that doesn't exist is never on, the item could never be ordered
+    let known_dayparts = db.list_dayparts().await?;
+    let unknown_dayparts = dayparts
+        .iter()
+        .enumerate()
+        .filter(|(_, daypart_name)| {
+            !known_dayparts
+                .iter()
+                .any(|daypart| &daypart.daypart_name == *daypart_name)
+        })
+        .map(|(index, daypart_name)| {
+            FieldError::new(
+                format!("dayparts[{index}]"),
+                "unknown_daypart",
+                format!("{daypart_name} is not a daypart"),
+            )
+        })
+        .collect::<Vec<FieldError>>();
+    if !unknown_dayparts.is_empty() {
+        return Err(ErrorResponse::from(
+            AxumErrorResponse::from(AxumErrors::UnknownDayparts).with_errors(unknown_dayparts),
+        ));
+    }
+    Ok(())
+}
+
+/// Groups the items by category, leaving out the categories without any.
+pub fn build_menu(mut items: Vec<Item>) -> MenuResponse {
+    items.sort_by(|a, b| {
+        a.display_order
+            .cmp(&b.display_order)
+            .then_with(|| a.item_name.cmp(&b.item_name))
+    });
+
+    let categories = Category::ALL
+        .into_iter()
+        .filter_map(|category| {
+            let items = items
+                .iter()
+                .filter(|item| item.category == category)
+                .map(|item| item.clone().into())
+                .collect::<Vec<ItemResponse>>();
+            match items.is_empty() {
+                true => None,
+                false => Some(MenuCategoryResponse { category, items }),
+            }
+        })
+        .collect();
+
+    MenuResponse { categories }
+}
@@ -1,76 +1,191 @@
+use std::collections::{HashMap, HashSet};
+
 use async_trait::async_trait;
 use axum::http::StatusCode;
+use futures::stream::TryStreamExt;
 use mongodb::bson::{doc, Document};
-use serde::{Deserialize, Serialize};
-use tracing::error;
+use mongodb::options::ReturnDocument;
+use tracing::{error, info, Instrument};
 
 use crate::common::{
     database,
-    database_helpers::collect_cursor,
-    errors::{AxumErrors, ErrorResponse},
-    models::{
-        pagination_schema::Pagination,
-        restaurant_schema::{Item, ItemResponse},
-    },
+    errors::{AxumErrorResponse, AxumErrors, ErrorResponse, FieldError},
+    menu_cache::MenuCache,
+    models::restaurant_schema::{Item, RecipeIngredient},
+    telemetry::mongo_span,
 };
 
-#[derive(Serialize, Deserialize, Clone)]
-pub struct ListItemResults {
-    pub items: Vec<ItemResponse>,
-    pub failed_items: Option<Vec<String>>,
-    pub count: u64,
-    pub dropped: u64,
-}
-
 #[async_trait]
 pub trait DBTableTrait {
-    async fn list_items(
+    /// Looks up every distinct name in one `$in` query, names that aren't on the menu are simply left out of the map.
+    async fn get_items_by_name(
+        &self,
+        item_names: &[String],
+    ) -> Result<HashMap<String, Item>, ErrorResponse>;
+    /// Every item on the menu, for the menu cache.
+    async fn get_menu(&self) -> Result<Vec<Item>, ErrorResponse>;
+    /// Invalidates `menu_cache` on every change to the items, until the change stream fails.
+    /// Returns right away against a standalone server, which has no change streams.
+    async fn watch_menu_changes(&self, menu_cache: &MenuCache) -> Result<(), ErrorResponse>;
+    /// Takes the portions of each item from its stock, only while the item is available and enough are left.
+    /// Every item is taken on its own, so if one of them runs short the ones already taken are given back
+    /// and the whole request is rejected, the stock never drops below zero.
+    async fn reserve_stock(&self, portions: &HashMap<String, i64>) -> Result<(), ErrorResponse>;
+    /// Gives the portions back, e.g. when the orders they were taken for couldn't be placed.
+    async fn release_stock(&self, portions: &HashMap<String, i64>) -> Result<(), ErrorResponse>;
+    /// Takes the item off the menu or puts it back, `stock` of `None` stops counting its portions.
+    async fn set_availability(
         &self,
-        item_names: Vec<String>,
-        pagination: &Pagination,
-    ) -> Result<ListItemResults, ErrorResponse>;
+        item_name: &str,
+        available: bool,
+        stock: Option<i64>,
+    ) -> Result<Item, ErrorResponse>;
+    /// Replaces the recipe of the item, an empty one stops it from taking any ingredients.
+    async fn set_recipe(
+        &self,
+        item_name: &str,
+        recipe: Vec<RecipeIngredient>,
+    ) -> Result<Item, ErrorResponse>;
+    /// Replaces the dayparts the item is served in, none serves it all day.
+    async fn set_dayparts(
+        &self,
+        item_name: &str,
+        dayparts: Vec<String>,
+    ) -> Result<Item, ErrorResponse>;
 }
 
 #[faux::methods]
 #[async_trait]
 impl DBTableTrait for database::DB {
-    async fn list_items(
+    async fn get_items_by_name(
         &self,
-        item_names: Vec<String>,
-        pagination: &Pagination,
-    ) -> Result<ListItemResults, ErrorResponse> {
+        item_names: &[String],
+    ) -> Result<HashMap<String, Item>, ErrorResponse> {
+        let distinct_names = item_names
+            .iter()
+            .cloned()
+            .collect::<HashSet<String>>()
+            .into_iter()
+            .collect::<Vec<String>>();
+        if distinct_names.is_empty() {
+            return Ok(HashMap::new());
+        }
+
         let item_collection = self
             .db
             .database("item_management")
-            .collection::<Document>("items");
-        let filter = match item_names.is_empty() {
-            true => {
-                doc! {}
+            .collection::<Item>("items");
+
+        let filter = doc! {
+            "item_name":{
+                "$in":distinct_names
             }
-            false => {
-                doc! {
-                    "item_name":{
-                        "$in":item_names
-                    }
+        };
+
+        let items: Vec<Item> = match item_collection
+            .find(filter, None)
+            .instrument(mongo_span("find", "items"))
+            .await
+        {
+            Ok(cursor) => match cursor.try_collect().await {
+                Ok(items) => items,
+                Err(e) => {
+                    error!("Unexpected error occured while reading the Items from the Database. Error: {e}");
+                    return Err(ErrorResponse {
+                        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                        error: AxumErrors::DBError.into(),
+                    });
                 }
+            },
+            Err(e) => {
+                error!(
+                    "Unexpected error occured while searching for Items in the Database. Error: {e}"
+                );
+                return Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                });
             }
         };
 
-        let find_options = mongodb::options::FindOptions::builder()
-            .limit(pagination.limit)
-            .skip(pagination.offset)
-            .sort(None)
-            .build();
+        Ok(items
+            .into_iter()
+            .map(|item| (item.item_name.clone(), item))
+            .collect())
+    }
 
-        let count_options = mongodb::options::CountOptions::builder().build();
+    async fn get_menu(&self) -> Result<Vec<Item>, ErrorResponse> {
+        let item_collection = self
+            .db
+            .database("item_management")
+            .collection::<Item>("items");
 
-        let count = match item_collection
-            .count_documents(filter.clone(), Some(count_options))
+        match item_collection
+            .find(doc! {}, None)
+            .instrument(mongo_span("find", "items"))
             .await
         {
-            Ok(count) => count,
+            Ok(cursor) => match cursor.try_collect().await {
+                Ok(items) => Ok(items),
+                Err(e) => {
+                    error!("Unexpected error occured while reading the menu from the Database. Error: {e}");
+                    Err(ErrorResponse {
+                        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                        error: AxumErrors::DBError.into(),
+                    })
+                }
+            },
             Err(e) => {
-                error!("Unexpected error occured while coutning Items in the Database. Error: {e}");
+                error!(
+                    "Unexpected error occured while loading the menu from the Database. Error: {e}"
+                );
+                Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                })
+            }
+        }
+    }
+
+    async fn watch_menu_changes(&self, menu_cache: &MenuCache) -> Result<(), ErrorResponse> {
+        if !self.transactions {
+            info!("Change streams need a replica set, the menu cache is only refreshed by its ttl");
+            return Ok(());
+        }
+
+        let item_collection = self
+            .db
+            .database("item_management")
+            .collection::<Document>("items");
+
+        //orders only move the stock counts, the cache keeps those up to date itself
+        let pipeline = vec![doc! {
+            "$match": {
+                "$expr": {
+                    "$not": {
+                        "$and": [
+                            { "$eq": ["$operationType", "update"] },
+                            { "$eq": [
+                                { "$map": {
+                                    "input": { "$objectToArray": "$updateDescription.updatedFields" },
+                                    "in": "$$this.k"
+                                } },
+                                ["stock"]
+                            ] },
+                            { "$eq": [{ "$size": "$updateDescription.removedFields" }, 0] }
+                        ]
+                    }
+                }
+            }
+        }];
+        let mut change_stream = match item_collection
+            .watch(pipeline, None)
+            .instrument(mongo_span("watch", "items"))
+            .await
+        {
+            Ok(change_stream) => change_stream,
+            Err(e) => {
+                error!("Unexpected error occured while watching the Items for changes. Error: {e}");
                 return Err(ErrorResponse {
                     status_code: StatusCode::INTERNAL_SERVER_ERROR,
                     error: AxumErrors::DBError.into(),
@@ -78,31 +193,241 @@ impl DBTableTrait for database::DB {
             }
         };
 
-        match item_collection.find(filter, find_options).await {
-            Ok(cursor) => {
-                let (items, failed_items, dropped) =
-                    match collect_cursor::<Item, ItemResponse>(cursor).await {
-                        Ok(collect_cursor_result) => collect_cursor_result.get_results(),
-                        Err(e) => {
-                            return Err(ErrorResponse {
-                                status_code: StatusCode::INTERNAL_SERVER_ERROR,
-                                error: e.into(),
-                            });
-                        }
-                    };
-
-                Ok(ListItemResults {
-                    items,
-                    failed_items: match failed_items.len() {
-                        0 => None,
-                        _ => Some(failed_items),
-                    },
-                    count,
-                    dropped: dropped,
+        info!("Watching the items, the menu cache is dropped whenever one changes other than by its stock");
+        loop {
+            match change_stream.try_next().await {
+                Ok(Some(_)) => menu_cache.invalidate().await,
+                Ok(None) => return Ok(()),
+                Err(e) => {
+                    error!("Unexpected error occured while reading the changes to the Items. Error: {e}");
+                    return Err(ErrorResponse {
+                        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                        error: AxumErrors::DBError.into(),
+                    });
+                }
+            }
+        }
+    }
+
+    async fn reserve_stock(&self, portions: &HashMap<String, i64>) -> Result<(), ErrorResponse> {
+        let item_collection = self
+            .db
+            .database("item_management")
+            .collection::<Document>("items");
+
+        let mut reserved = HashMap::new();
+        for (item_name, quantity) in portions {
+            //the check and the decrement are one update, two requests can't both take the last portion
+            let filter = doc! {
+                "item_name": item_name,
+                "available": { "$ne": false },
+                "stock": { "$gte": quantity }
+            };
+            let update = doc! {
+                "$inc": { "stock": -quantity }
+            };
+            let error = match item_collection
+                .update_one(filter, update, None)
+                .instrument(mongo_span("update_one", "items"))
+                .await
+            {
+                Ok(result) if result.modified_count == 1 => {
+                    reserved.insert(item_name.clone(), *quantity);
+                    continue;
+                }
+                Ok(_) => ErrorResponse::from(
+                    AxumErrorResponse::from(AxumErrors::ItemUnavailable).with_errors(vec![
+                        FieldError::new(
+                            "orders",
+                            "out_of_stock",
+                            format!("{item_name} is not available, or fewer than {quantity} portions are left"),
+                        ),
+                    ]),
+                ),
+                Err(e) => {
+                    error!("Unexpected error occured while taking the portions of {item_name} from the stock. Error: {e}");
+                    ErrorResponse {
+                        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                        error: AxumErrors::DBError.into(),
+                    }
+                }
+            };
+            if !reserved.is_empty() {
+                if let Err(e) = self.release_stock(&reserved).await {
+                    error!(
+                        "Was unable to give back the portions of a rejected order, the stock is short by {reserved:?}. Error: {}",
+                        e.error.detail
+                    );
+                }
+            }
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    async fn release_stock(&self, portions: &HashMap<String, i64>) -> Result<(), ErrorResponse> {
+        let item_collection = self
+            .db
+            .database("item_management")
+            .collection::<Document>("items");
+
+        //every item is given back even if one of them fails, so only the failed ones are short
+        let mut failed = vec![];
+        for (item_name, quantity) in portions {
+            //an item that stopped being counted in the meantime stays uncounted
+            let filter = doc! {
+                "item_name": item_name,
+                "stock": { "$type": "number" }
+            };
+            let update = doc! {
+                "$inc": { "stock": quantity }
+            };
+            if let Err(e) = item_collection
+                .update_one(filter, update, None)
+                .instrument(mongo_span("update_one", "items"))
+                .await
+            {
+                error!("Unexpected error occured while giving back the portions of {item_name}. Error: {e}");
+                failed.push(item_name.as_str());
+            }
+        }
+        match failed.is_empty() {
+            true => Ok(()),
+            false => Err(ErrorResponse {
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                error: AxumErrorResponse::from(AxumErrors::DBError)
+                    .with_detail(format!(
+                        "Was unable to give back the portions of {}",
+                        failed.join(", ")
+                    ))
+                    .into(),
+            }),
+        }
+    }
+
+    async fn set_availability(
+        &self,
+        item_name: &str,
+        available: bool,
+        stock: Option<i64>,
+    ) -> Result<Item, ErrorResponse> {
+        let item_collection = self
+            .db
+            .database("item_management")
+            .collection::<Item>("items");
+
+        let update = match stock {
+            Some(stock) => doc! {
+                "$set": { "available": available, "stock": stock }
+            },
+            None => doc! {
+                "$set": { "available": available },
+                "$unset": { "stock": "" }
+            },
+        };
+        let options = mongodb::options::FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        match item_collection
+            .find_one_and_update(doc! { "item_name": item_name }, update, options)
+            .instrument(mongo_span("find_one_and_update", "items"))
+            .await
+        {
+            Ok(Some(item)) => Ok(item),
+            Ok(None) => Err(ErrorResponse::from(
+                AxumErrorResponse::from(AxumErrors::ItemNotFound)
+                    .with_detail(format!("{item_name} is not on the menu!")),
+            )),
+            Err(e) => {
+                error!("Unexpected error occured while updating the availability of {item_name}. Error: {e}");
+                Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
                 })
             }
+        }
+    }
+
+    async fn set_recipe(
+        &self,
+        item_name: &str,
+        recipe: Vec<RecipeIngredient>,
+    ) -> Result<Item, ErrorResponse> {
+        let item_collection = self
+            .db
+            .database("item_management")
+            .collection::<Item>("items");
+
+        let recipe = match mongodb::bson::to_bson(&recipe) {
+            Ok(recipe) => recipe,
+            Err(e) => {
+                error!("unexpected error occured while converting the recipe of {item_name} into bson! Error: {e}");
+                return Err(ErrorResponse::from(AxumErrors::BsonSerializeError));
+            }
+        };
+        let options = mongodb::options::FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        match item_collection
+            .find_one_and_update(
+                doc! { "item_name": item_name },
+                doc! { "$set": { "recipe": recipe } },
+                options,
+            )
+            .instrument(mongo_span("find_one_and_update", "items"))
+            .await
+        {
+            Ok(Some(item)) => Ok(item),
+            Ok(None) => Err(ErrorResponse::from(
+                AxumErrorResponse::from(AxumErrors::ItemNotFound)
+                    .with_detail(format!("{item_name} is not on the menu!")),
+            )),
+            Err(e) => {
+                error!(
+                    "Unexpected error occured while updating the recipe of {item_name}. Error: {e}"
+                );
+                Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                })
+            }
+        }
+    }
+
+    async fn set_dayparts(
+        &self,
+        item_name: &str,
+        dayparts: Vec<String>,
+    ) -> Result<Item, ErrorResponse> {
+        let item_collection = self
+            .db
+            .database("item_management")
+            .collection::<Item>("items");
+
+        let options = mongodb::options::FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        match item_collection
+            .find_one_and_update(
+                doc! { "item_name": item_name },
+                doc! { "$set": { "dayparts": dayparts } },
+                options,
+            )
+            .instrument(mongo_span("find_one_and_update", "items"))
+            .await
+        {
+            Ok(Some(item)) => Ok(item),
+            Ok(None) => Err(ErrorResponse::from(
+                AxumErrorResponse::from(AxumErrors::ItemNotFound)
+                    .with_detail(format!("{item_name} is not on the menu!")),
+            )),
             Err(e) => {
-                error!("Unexpected error occured while coutning Items in the Database. Error: {e}");
+                error!(
+                    "Unexpected error occured while updating the dayparts of {item_name}. Error: {e}"
+                );
                 Err(ErrorResponse {
                     status_code: StatusCode::INTERNAL_SERVER_ERROR,
                     error: AxumErrors::DBError.into(),
@@ -2,59 +2,106 @@
 pub mod order_unit_tests {
 
     use axum::{extract::State, http::StatusCode};
-    use axum_extra::extract::Query as ExtraQuery;
-    use std::sync::Arc;
-
-    use tokio::sync::Mutex;
+    use chrono::{NaiveTime, TimeZone, Utc};
+    use std::{collections::HashMap, sync::Arc, time::Duration};
 
     use crate::{
         common::{
+            clock::{FakeClock, SystemClock},
             database::DB,
             errors::{AxumErrors, ErrorResponse},
-            models::{pagination_schema::Pagination, restaurant_schema::ItemResponse},
+            extractors::{ValidExtraQuery, ValidJson, ValidPath},
+            menu_cache::MenuCache,
+            models::{
+                pagination_schema::Pagination,
+                path_schema::ItemPath,
+                restaurant_schema::{
+                    Allergen, Category, Daypart, Diet, Ingredient, Item, RecipeIngredient, Unit,
+                },
+            },
         },
-        handlers::item_handler::{
-            item::{list_items, ListItemsRequest},
-            item_db::ListItemResults,
+        handlers::item_handler::item::{
+            get_menu, list_items, update_availability, update_dayparts, update_recipe,
+            DaypartsRequest, ItemAvailabilityRequest, ListItemsRequest, RecipeRequest,
         },
+        handlers::kitchen_handler::scheduler::ScheduleCache,
+        tests::fixtures::item,
         AppState,
     };
 
     #[tokio::test]
     pub async fn successful_list_items() {
         let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_menu).then(|_| {
+            Ok(vec![
+                menu_item("Ramen", Category::Main, 0),
+                menu_item("Burger", Category::Main, 0),
+            ])
+        });
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
 
-        let items = [ItemResponse {
-            item_name: "Burger".to_string(),
-            cook_time: 5,
-        }]
-        .to_vec();
-
-        let list_item_results = ListItemResults {
-            items,
-            failed_items: None,
-            count: 1,
-            dropped: 0,
-        };
+        let state = State(app_state);
+        let pagination = ValidExtraQuery(Pagination {
+            offset: 0,
+            limit: 10,
+        });
+        let query = ValidExtraQuery(ListItemsRequest {
+            item_names: ["Burger".to_string()].to_vec(),
+            category: None,
+            available_only: false,
+            diet: vec![],
+            exclude_allergens: vec![],
+            at: None,
+        });
+        match list_items(state, pagination, query).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::OK);
+                assert_eq!(resp.1.pagination.total, 1);
+                assert_eq!(resp.1.items[0].item_name, "Burger");
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
 
-        faux::when!(mock_db.list_items).then(move |_| Ok(list_item_results.to_owned()));
+    #[tokio::test]
+    pub async fn successful_list_items_category_filter() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_menu).then(|_| {
+            Ok(vec![
+                menu_item("Ramen", Category::Main, 0),
+                menu_item("Mochi", Category::Dessert, 0),
+            ])
+        });
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(1),
-            orders: Mutex::new(1),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
 
         let state = State(app_state);
-        let pagination = ExtraQuery(Pagination {
+        let pagination = ValidExtraQuery(Pagination {
             offset: 0,
             limit: 10,
         });
-        let query = ExtraQuery(ListItemsRequest {
-            item_names: ["Burger".to_string()].to_vec(),
+        let query = ValidExtraQuery(ListItemsRequest {
+            item_names: [].to_vec(),
+            category: Some(Category::Dessert),
+            available_only: false,
+            diet: vec![],
+            exclude_allergens: vec![],
+            at: None,
         });
         match list_items(state, pagination, query).await {
             Ok(resp) => {
                 assert_eq!(resp.0, StatusCode::OK);
+                assert_eq!(resp.1.items.len(), 1);
+                assert_eq!(resp.1.items[0].item_name, "Mochi");
             }
             Err(e) => panic!("error! {:?}", e.1.error_type),
         }
@@ -63,25 +110,31 @@ pub mod order_unit_tests {
     #[tokio::test]
     pub async fn failed_list_items_db_error() {
         let mut mock_db = DB::faux();
-        faux::when!(mock_db.list_items).then(move |_| {
+        faux::when!(mock_db.get_menu).then(move |_| {
             Err(ErrorResponse {
                 status_code: StatusCode::INTERNAL_SERVER_ERROR,
                 error: AxumErrors::DBError.into(),
             })
         });
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(1),
-            orders: Mutex::new(1),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
 
         let state = State(app_state);
-        let pagination = ExtraQuery(Pagination {
+        let pagination = ValidExtraQuery(Pagination {
             offset: 0,
             limit: 10,
         });
-        let query = ExtraQuery(ListItemsRequest {
+        let query = ValidExtraQuery(ListItemsRequest {
             item_names: ["Burger".to_string()].to_vec(),
+            category: None,
+            available_only: false,
+            diet: vec![],
+            exclude_allergens: vec![],
+            at: None,
         });
         match list_items(state, pagination, query).await {
             Ok(_) => panic!("not supposed to succeed"),
@@ -94,25 +147,31 @@ pub mod order_unit_tests {
     #[tokio::test]
     pub async fn failed_list_items_b_deserialization_error() {
         let mut mock_db = DB::faux();
-        faux::when!(mock_db.list_items).then(move |_| {
+        faux::when!(mock_db.get_menu).then(move |_| {
             Err(ErrorResponse {
                 status_code: StatusCode::INTERNAL_SERVER_ERROR,
                 error: AxumErrors::BsonDeserializeError.into(),
             })
         });
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(1),
-            orders: Mutex::new(1),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
 
         let state = State(app_state);
-        let pagination = ExtraQuery(Pagination {
+        let pagination = ValidExtraQuery(Pagination {
             offset: 0,
             limit: 10,
         });
-        let query = ExtraQuery(ListItemsRequest {
+        let query = ValidExtraQuery(ListItemsRequest {
             item_names: ["Burger".to_string()].to_vec(),
+            category: None,
+            available_only: false,
+            diet: vec![],
+            exclude_allergens: vec![],
+            at: None,
         });
         match list_items(state, pagination, query).await {
             Ok(_) => panic!("not supposed to succeed"),
@@ -121,4 +180,523 @@ pub mod order_unit_tests {
             }
         }
     }
+
+    #[tokio::test]
+    pub async fn successful_list_items_pages_the_cached_menu() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_menu).once().then(|_| {
+            Ok(vec![
+                menu_item("Udon", Category::Main, 0),
+                menu_item("Gyoza", Category::Appetizer, 0),
+                menu_item("Ramen", Category::Main, 0),
+            ])
+        });
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::from_secs(60)),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+
+        // the menu is read once, every page is cut from the cached copy by name
+        for (offset, item_name) in [(0, "Gyoza"), (1, "Ramen"), (2, "Udon")] {
+            let pagination = ValidExtraQuery(Pagination { offset, limit: 1 });
+            let query = ValidExtraQuery(ListItemsRequest {
+                item_names: [].to_vec(),
+                category: None,
+                available_only: false,
+                diet: vec![],
+                exclude_allergens: vec![],
+                at: None,
+            });
+            match list_items(State(app_state.clone()), pagination, query).await {
+                Ok(resp) => {
+                    assert_eq!(resp.1.pagination.total, 3);
+                    assert_eq!(resp.1.items.len(), 1);
+                    assert_eq!(resp.1.items[0].item_name, item_name);
+                }
+                Err(e) => panic!("error! {:?}", e.1.error_type),
+            }
+        }
+        assert_eq!(app_state.menu_cache.stats().misses, 1);
+    }
+
+    fn menu_item(item_name: &str, category: Category, display_order: i64) -> Item {
+        Item {
+            category,
+            display_order,
+            ..item(item_name, 5)
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_get_menu() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_menu).then(|_| {
+            Ok(vec![
+                menu_item("Mochi", Category::Dessert, 0),
+                menu_item("Udon", Category::Main, 2),
+                menu_item("Gyoza", Category::Appetizer, 0),
+                menu_item("Ramen", Category::Main, 1),
+                menu_item("Hotdog", Category::Main, 2),
+            ])
+        });
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+
+        match get_menu(State(app_state)).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::OK);
+                let menu = resp
+                    .1
+                    .categories
+                    .iter()
+                    .map(|section| {
+                        (
+                            section.category,
+                            section
+                                .items
+                                .iter()
+                                .map(|item| item.item_name.as_str())
+                                .collect::<Vec<&str>>(),
+                        )
+                    })
+                    .collect::<Vec<(Category, Vec<&str>)>>();
+                // drinks are left out, there are none
+                assert_eq!(
+                    menu,
+                    vec![
+                        (Category::Appetizer, vec!["Gyoza"]),
+                        (Category::Main, vec!["Ramen", "Hotdog", "Udon"]),
+                        (Category::Dessert, vec!["Mochi"]),
+                    ]
+                );
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_get_menu_db_error() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_menu).then(|_| Err(ErrorResponse::from(AxumErrors::DBError)));
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+
+        match get_menu(State(app_state)).await {
+            Ok(_) => panic!("not supposed to succeed"),
+            Err(e) => {
+                assert_eq!(e.1.error_type, AxumErrors::DBError.to_string());
+            }
+        }
+    }
+
+    ////////////////////////
+    //                   //
+    //  AVAILABILITY    //
+    //                 //
+    ////////////////////
+
+    #[tokio::test]
+    pub async fn successful_list_items_available_only() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_menu).then(|_| {
+            Ok(vec![
+                menu_item("Ramen", Category::Main, 0),
+                Item {
+                    available: false,
+                    ..menu_item("Udon", Category::Main, 0)
+                },
+                Item {
+                    stock: Some(0),
+                    ..menu_item("Gyoza", Category::Appetizer, 0)
+                },
+                Item {
+                    stock: Some(2),
+                    ..menu_item("Mochi", Category::Dessert, 0)
+                },
+            ])
+        });
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+
+        let pagination = ValidExtraQuery(Pagination {
+            offset: 0,
+            limit: 10,
+        });
+        let query = ValidExtraQuery(ListItemsRequest {
+            item_names: [].to_vec(),
+            category: None,
+            available_only: true,
+            diet: vec![],
+            exclude_allergens: vec![],
+            at: None,
+        });
+        match list_items(State(app_state), pagination, query).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::OK);
+                // taken off the menu and sold out items are left out
+                assert_eq!(
+                    resp.1
+                        .items
+                        .iter()
+                        .map(|item| item.item_name.as_str())
+                        .collect::<Vec<&str>>(),
+                    vec!["Mochi", "Ramen"]
+                );
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_list_items_allergen_and_diet_filters() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_menu).then(|_| {
+            Ok(vec![
+                Item {
+                    diets: vec![Diet::Vegetarian, Diet::Vegan],
+                    ..menu_item("Salad", Category::Appetizer, 0)
+                },
+                Item {
+                    allergens: vec![Allergen::Peanut],
+                    diets: vec![Diet::Vegetarian],
+                    ..menu_item("Satay", Category::Appetizer, 0)
+                },
+                menu_item("Ramen", Category::Main, 0),
+            ])
+        });
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+
+        let pagination = ValidExtraQuery(Pagination {
+            offset: 0,
+            limit: 10,
+        });
+        let query = ValidExtraQuery(ListItemsRequest {
+            item_names: [].to_vec(),
+            category: None,
+            available_only: false,
+            exclude_allergens: vec![Allergen::Peanut],
+            diet: vec![Diet::Vegetarian],
+            at: None,
+        });
+        match list_items(State(app_state), pagination, query).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::OK);
+                assert_eq!(resp.1.items.len(), 1);
+                assert_eq!(resp.1.items[0].item_name, "Salad");
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_update_availability() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.set_availability).then(|(item_name, available, stock)| {
+            assert_eq!(item_name, "Ramen");
+            let mut item = menu_item("Ramen", Category::Main, 0);
+            item.available = available;
+            item.stock = stock;
+            Ok(item)
+        });
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+
+        let path = ValidPath(ItemPath {
+            item_name: "Ramen".to_string(),
+        });
+        let request = ValidJson(ItemAvailabilityRequest {
+            available: true,
+            stock: Some(12),
+        });
+        match update_availability(State(app_state), path, request).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::OK);
+                assert!(resp.1.item.available);
+                assert_eq!(resp.1.stock, Some(12));
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_update_availability_item_not_found() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.set_availability)
+            .then(|_| Err(ErrorResponse::from(AxumErrors::ItemNotFound)));
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+
+        let path = ValidPath(ItemPath {
+            item_name: "Pizza".to_string(),
+        });
+        let request = ValidJson(ItemAvailabilityRequest {
+            available: false,
+            stock: None,
+        });
+        match update_availability(State(app_state), path, request).await {
+            Ok(_) => panic!("not supposed to succeed"),
+            Err(e) => {
+                assert_eq!(e.0, StatusCode::NOT_FOUND);
+                assert_eq!(e.1.error_type, AxumErrors::ItemNotFound.to_string());
+            }
+        }
+    }
+
+    ////////////////////////
+    //                   //
+    //  RECIPE          //
+    //                 //
+    ////////////////////
+
+    fn recipe_request(ingredient_names: &[&str]) -> ValidJson<RecipeRequest> {
+        ValidJson(RecipeRequest {
+            recipe: ingredient_names
+                .iter()
+                .map(|ingredient_name| RecipeIngredient {
+                    ingredient_name: ingredient_name.to_string(),
+                    quantity: 100,
+                })
+                .collect(),
+        })
+    }
+
+    #[tokio::test]
+    pub async fn successful_update_recipe() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_ingredients_by_name).then(|ingredient_names| {
+            Ok(ingredient_names
+                .iter()
+                .map(|ingredient_name| {
+                    (
+                        ingredient_name.clone(),
+                        Ingredient {
+                            ingredient_name: ingredient_name.clone(),
+                            unit: Unit::Gram,
+                            stock: 1000,
+                            low_stock_threshold: None,
+                        },
+                    )
+                })
+                .collect())
+        });
+        faux::when!(mock_db.set_recipe).then(|(item_name, recipe)| {
+            assert_eq!(item_name, "UnagiDon");
+            let mut item = menu_item("UnagiDon", Category::Main, 0);
+            item.recipe = recipe;
+            Ok(item)
+        });
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+
+        let path = ValidPath(ItemPath {
+            item_name: "UnagiDon".to_string(),
+        });
+        match update_recipe(State(app_state), path, recipe_request(&["Eel", "Rice"])).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::OK);
+                assert_eq!(resp.1.recipe.len(), 2);
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_update_recipe_unknown_ingredients() {
+        // the recipe isn't stored when some of its ingredients aren't in the inventory
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_ingredients_by_name).then(|_| Ok(HashMap::new()));
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+
+        let path = ValidPath(ItemPath {
+            item_name: "UnagiDon".to_string(),
+        });
+        match update_recipe(State(app_state), path, recipe_request(&["Eel", "Rice"])).await {
+            Ok(_) => panic!("not supposed to succeed"),
+            Err(e) => {
+                assert_eq!(e.0, StatusCode::UNPROCESSABLE_ENTITY);
+                assert_eq!(e.1.error_type, AxumErrors::UnknownIngredients.to_string());
+                assert_eq!(e.1.errors.len(), 2);
+                assert_eq!(e.1.errors[1].field, "recipe[1].ingredient_name");
+                assert_eq!(e.1.errors[1].code, "unknown_ingredient");
+            }
+        }
+    }
+
+    ////////////////////////
+    //                   //
+    //  DAYPARTS        //
+    //                 //
+    ////////////////////
+
+    fn daypart(daypart_name: &str, start: &str, end: &str) -> Daypart {
+        Daypart {
+            daypart_name: daypart_name.to_string(),
+            days: vec![],
+            start: NaiveTime::parse_from_str(start, "%H:%M").unwrap(),
+            end: NaiveTime::parse_from_str(end, "%H:%M").unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_list_items_at_now_by_default() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 8, 0, 0).unwrap();
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_menu).then(|_| {
+            Ok(vec![
+                Item {
+                    dayparts: vec!["Breakfast".to_string()],
+                    ..menu_item("Pancakes", Category::Main, 0)
+                },
+                Item {
+                    dayparts: vec!["Dinner".to_string()],
+                    ..menu_item("Steak", Category::Main, 0)
+                },
+                menu_item("Ramen", Category::Main, 0),
+            ])
+        });
+        faux::when!(mock_db.list_dayparts).then(|_| {
+            Ok(vec![
+                daypart("Breakfast", "06:00", "11:00"),
+                daypart("Dinner", "17:00", "22:00"),
+            ])
+        });
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(FakeClock::new(now)),
+        });
+
+        let pagination = ValidExtraQuery(Pagination {
+            offset: 0,
+            limit: 10,
+        });
+        let query = ValidExtraQuery(ListItemsRequest {
+            item_names: [].to_vec(),
+            category: None,
+            available_only: false,
+            exclude_allergens: vec![],
+            diet: vec![],
+            at: None,
+        });
+        match list_items(State(app_state), pagination, query).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::OK);
+                assert_eq!(resp.1.filters.at, Some(now));
+                // only the items served right now are listed
+                assert_eq!(
+                    resp.1
+                        .items
+                        .iter()
+                        .map(|item| item.item_name.as_str())
+                        .collect::<Vec<&str>>(),
+                    vec!["Pancakes", "Ramen"]
+                );
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_update_dayparts() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.list_dayparts).then(|_| {
+            Ok(vec![
+                daypart("Breakfast", "07:00", "11:00"),
+                daypart("Lunch", "11:00", "16:00"),
+            ])
+        });
+        faux::when!(mock_db.set_dayparts).then(|(item_name, dayparts)| {
+            assert_eq!(item_name, "MatchaLatte");
+            let mut item = menu_item("MatchaLatte", Category::Drink, 0);
+            item.dayparts = dayparts;
+            Ok(item)
+        });
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+
+        let path = ValidPath(ItemPath {
+            item_name: "MatchaLatte".to_string(),
+        });
+        let request = ValidJson(DaypartsRequest {
+            dayparts: vec!["Breakfast".to_string(), "Lunch".to_string()],
+        });
+        match update_dayparts(State(app_state), path, request).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::OK);
+                assert_eq!(resp.1.item.dayparts, vec!["Breakfast", "Lunch"]);
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_update_dayparts_unknown_dayparts() {
+        // the dayparts aren't stored when some of them don't exist
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.list_dayparts).then(|_| Ok(vec![daypart("Lunch", "11:00", "16:00")]));
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+
+        let path = ValidPath(ItemPath {
+            item_name: "MatchaLatte".to_string(),
+        });
+        let request = ValidJson(DaypartsRequest {
+            dayparts: vec!["Lunch".to_string(), "Brunch".to_string()],
+        });
+        match update_dayparts(State(app_state), path, request).await {
+            Ok(_) => panic!("not supposed to succeed"),
+            Err(e) => {
+                assert_eq!(e.0, StatusCode::UNPROCESSABLE_ENTITY);
+                assert_eq!(e.1.error_type, AxumErrors::UnknownDayparts.to_string());
+                assert_eq!(e.1.errors.len(), 1);
+                assert_eq!(e.1.errors[0].field, "dayparts[1]");
+                assert_eq!(e.1.errors[0].code, "unknown_daypart");
+            }
+        }
+    }
 }
@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    common::{
+        config::station_slots,
+        errors::{AxumErrorResponse, ProblemJson},
+        models::restaurant_schema::{CookStatus, Order, OrderResponse, Station},
+    },
+    AppState,
+};
+
+use super::scheduler::{recent_fired_orders, KitchenSchedule};
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct KitchenStationsResponse {
+    /// every station, grill first and wok last
+    pub stations: Vec<StationResponse>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct StationResponse {
+    pub station: Station,
+    /// how many dishes the station cooks at once
+    pub slots: usize,
+    /// orders a slot is working on, sorted by their start
+    pub cooking: Vec<OrderResponse>,
+    /// orders waiting for a free slot, next one first
+    pub queued: Vec<OrderResponse>,
+    /// when the last order at the station should be done, missing while it has nothing to do
+    pub busy_until: Option<DateTime<Utc>>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/kitchen/stations",
+    tag = "kitchen",
+    responses(
+        (status = 200, description = "What every station is cooking and what is waiting for it", body = KitchenStationsResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
+pub async fn list_stations(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<KitchenStationsResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)>
+{
+    let db = &app_state.db;
+
+    let now = app_state.clock.now();
+    match recent_fired_orders(db, now).await {
+        Ok(orders) => Ok((
+            StatusCode::OK,
+            Json(build_stations(orders, station_slots, now)),
+        )),
+        Err(e) => Err(e.to_axum_error()),
+    }
+}
+
+/// Schedules the orders and splits the ones that aren't done yet by station.
+pub fn build_stations(
+    orders: Vec<Order>,
+    slots: impl Fn(Station) -> usize + Copy,
+    now: DateTime<Utc>,
+) -> KitchenStationsResponse {
+    let schedule = KitchenSchedule::new(&orders, slots);
+    let mut orders = orders
+        .into_iter()
+        .map(|order| schedule.order(order, now))
+        .filter(|order| order.station.is_some() && order.cook_status != CookStatus::Done)
+        .collect::<Vec<OrderResponse>>();
+    orders.sort_by_key(|order| (order.started_time, order.order_id));
+
+    let stations = Station::ALL
+        .into_iter()
+        .map(|station| {
+            let (queued, cooking): (Vec<OrderResponse>, Vec<OrderResponse>) = orders
+                .iter()
+                .filter(|order| order.station == Some(station))
+                .cloned()
+                .partition(|order| order.cook_status == CookStatus::Queued);
+            let busy_until = cooking
+                .iter()
+                .chain(queued.iter())
+                .filter_map(|order| order.estimated_ready_time)
+                .max();
+            StationResponse {
+                station,
+                slots: slots(station),
+                cooking,
+                queued,
+                busy_until,
+            }
+        })
+        .collect();
+
+    KitchenStationsResponse { stations }
+}
@@ -0,0 +1,85 @@
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{doc, Bson, Document};
+use tracing::{error, Instrument};
+
+use crate::common::{
+    database,
+    errors::{AxumErrors, ErrorResponse},
+    models::restaurant_schema::Order,
+    telemetry::mongo_span,
+};
+
+#[async_trait]
+pub trait DBKitchenTrait {
+    /// The orders on the tables that were fired from `since` on, the ones the kitchen may still be busy with.
+    async fn get_fired_orders(&self, since: DateTime<Utc>) -> Result<Vec<Order>, ErrorResponse>;
+}
+
+#[faux::methods]
+#[async_trait]
+impl DBKitchenTrait for database::DB {
+    async fn get_fired_orders(&self, since: DateTime<Utc>) -> Result<Vec<Order>, ErrorResponse> {
+        let table_collection = self
+            .db
+            .database("table_management")
+            .collection::<Document>("tables");
+
+        let since = Bson::DateTime(since.into());
+        let pipeline = vec![
+            // only the tables with a recent order, through the orders_fired_time and orders_ordered_time indexes
+            doc! {
+                "$match":{
+                    "$or":[
+                        {"orders.fired_time":{"$gte": &since}},
+                        {"orders.ordered_time":{"$gte": &since}},
+                    ]
+                }
+            },
+            doc! {
+                "$unwind":"$orders"
+            },
+            doc! {
+                "$replaceRoot":{
+                    "newRoot":"$orders"
+                }
+            },
+            // orders from before courses have no fired time, they were fired when they were ordered
+            doc! {
+                "$match":{
+                    "held":{"$ne": true},
+                    "$or":[
+                        {"fired_time":{"$gte": &since}},
+                        {"fired_time": null, "ordered_time":{"$gte": &since}},
+                    ]
+                }
+            },
+        ];
+
+        match table_collection
+            .aggregate(pipeline, None)
+            .instrument(mongo_span("aggregate", "tables"))
+            .await
+        {
+            Ok(cursor) => match cursor.with_type::<Order>().try_collect().await {
+                Ok(orders) => Ok(orders),
+                Err(e) => {
+                    error!("Unexpected error occured while reading the fired Orders from the Database. Error: {e}");
+                    Err(ErrorResponse {
+                        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                        error: AxumErrors::DBError.into(),
+                    })
+                }
+            },
+            Err(e) => {
+                error!("Unexpected error occured while searching for the fired Orders in the Database. Error: {e}");
+                Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                })
+            }
+        }
+    }
+}
@@ -0,0 +1,4 @@
+pub mod kitchen;
+pub mod kitchen_db;
+pub mod scheduler;
+pub mod unit_tests;
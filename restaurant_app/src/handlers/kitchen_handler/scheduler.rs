@@ -0,0 +1,181 @@
+use std::{collections::HashMap, sync::Arc};
+
+use chrono::{DateTime, Duration, Utc};
+use tokio::{sync::RwLock, time::Instant};
+
+use crate::{
+    common::{
+        config::{kitchen_schedule_lookback, station_slots},
+        database::DB,
+        errors::ErrorResponse,
+        models::restaurant_schema::{Order, OrderResponse, Station, TableResponse},
+    },
+    AppState,
+};
+
+use super::kitchen_db::DBKitchenTrait;
+
+/// When a slot starts on an order, and when the last dish of it should come off the station.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScheduledOrder {
+    pub started_time: DateTime<Utc>,
+    pub estimated_ready_time: DateTime<Utc>,
+}
+
+/// Start and ready times of every fired order, by order id.
+#[derive(Debug, Default)]
+pub struct KitchenSchedule {
+    orders: HashMap<i64, ScheduledOrder>,
+}
+
+impl KitchenSchedule {
+    /// Queues the fired orders of each station first come first served.
+    /// Every dish of an order takes the slot that frees up first for the cook time of the item,
+    /// orders of items without a station start as soon as they are fired.
+    pub fn new(orders: &[Order], slots: impl Fn(Station) -> usize) -> Self {
+        let mut scheduled = HashMap::new();
+        let mut queues: HashMap<Station, Vec<(DateTime<Utc>, &Order)>> = HashMap::new();
+        for order in orders {
+            let Some(cooking_since) = order.cooking_since() else {
+                continue;
+            };
+            match order.item.station {
+                Some(station) => queues
+                    .entry(station)
+                    .or_default()
+                    .push((cooking_since, order)),
+                None => {
+                    scheduled.insert(
+                        order.order_id,
+                        ScheduledOrder {
+                            started_time: cooking_since,
+                            estimated_ready_time: cooking_since
+                                + Duration::seconds(order.item.cook_time),
+                        },
+                    );
+                }
+            }
+        }
+
+        for (station, mut queue) in queues {
+            queue.sort_by_key(|(cooking_since, order)| (*cooking_since, order.order_id));
+            let mut free_at = vec![DateTime::<Utc>::MIN_UTC; slots(station).max(1)];
+            for (cooking_since, order) in queue {
+                let cook_time = Duration::seconds(order.item.cook_time);
+                let mut started_time = None;
+                let mut estimated_ready_time = cooking_since;
+                for _ in 0..order.quantity.max(1) {
+                    let Some(slot) = free_at.iter_mut().min() else {
+                        break;
+                    };
+                    let start = (*slot).max(cooking_since);
+                    *slot = start + cook_time;
+                    started_time.get_or_insert(start);
+                    estimated_ready_time = estimated_ready_time.max(*slot);
+                }
+                scheduled.insert(
+                    order.order_id,
+                    ScheduledOrder {
+                        started_time: started_time.unwrap_or(cooking_since),
+                        estimated_ready_time,
+                    },
+                );
+            }
+        }
+
+        Self { orders: scheduled }
+    }
+
+    pub fn get(&self, order_id: i64) -> Option<ScheduledOrder> {
+        self.orders.get(&order_id).copied()
+    }
+
+    /// The order with the times of the schedule and its cook status at `now`,
+    /// orders it doesn't know keep the times they came with.
+    pub fn order(&self, order: impl Into<OrderResponse>, now: DateTime<Utc>) -> OrderResponse {
+        let mut order = order.into();
+        if let Some(scheduled) = self.get(order.order_id) {
+            order.started_time = Some(scheduled.started_time);
+            order.estimated_ready_time = Some(scheduled.estimated_ready_time);
+        }
+        order.at(now)
+    }
+
+    pub fn table(&self, table: impl Into<TableResponse>, now: DateTime<Utc>) -> TableResponse {
+        let mut table = table.into();
+        table.orders = table
+            .orders
+            .into_iter()
+            .map(|order| self.order(order, now))
+            .collect();
+        table
+    }
+}
+
+/// The last kitchen schedule, so the reads of a busy moment don't each unwind the recent orders again.
+///
+/// The schedule is worked out again once it is older than the ttl, or right away after a write changed the orders.
+/// A ttl of zero turns the cache off.
+pub struct ScheduleCache {
+    ttl: std::time::Duration,
+    schedule: RwLock<Option<(Arc<KitchenSchedule>, Instant)>>,
+}
+
+impl ScheduleCache {
+    pub fn new(ttl: std::time::Duration) -> Self {
+        Self {
+            ttl,
+            schedule: RwLock::new(None),
+        }
+    }
+
+    /// Drops the cached schedule, the next read works it out again.
+    pub async fn invalidate(&self) {
+        *self.schedule.write().await = None;
+    }
+
+    async fn get(&self) -> Option<Arc<KitchenSchedule>> {
+        match self.schedule.read().await.as_ref() {
+            Some((schedule, made_at)) if made_at.elapsed() < self.ttl => Some(schedule.clone()),
+            _ => None,
+        }
+    }
+
+    async fn set(&self, schedule: Arc<KitchenSchedule>) {
+        if !self.ttl.is_zero() {
+            *self.schedule.write().await = Some((schedule, Instant::now()));
+        }
+    }
+}
+
+/// The orders the kitchen may still be busy with at `now`.
+pub async fn recent_fired_orders(db: &DB, now: DateTime<Utc>) -> Result<Vec<Order>, ErrorResponse> {
+    let lookback = Duration::from_std(kitchen_schedule_lookback()).unwrap_or(Duration::MAX);
+    db.get_fired_orders(
+        now.checked_sub_signed(lookback)
+            .unwrap_or(DateTime::<Utc>::MIN_UTC),
+    )
+    .await
+}
+
+/// The schedule of the orders on the tables right now.
+/// Reading the orders failing only costs the estimates their queueing, so it is logged and the schedule left empty.
+pub async fn kitchen_schedule(app_state: &AppState) -> Arc<KitchenSchedule> {
+    if let Some(schedule) = app_state.schedule_cache.get().await {
+        return schedule;
+    }
+    match recent_fired_orders(&app_state.db, app_state.clock.now()).await {
+        Ok(orders) => {
+            let schedule = Arc::new(KitchenSchedule::new(&orders, station_slots));
+            app_state.schedule_cache.set(schedule.clone()).await;
+            schedule
+        }
+        Err(e) => {
+            tracing::warn!(
+                "Was unable to schedule the kitchen, the orders are assumed to start when fired. Error: {}",
+                e.error.detail
+            );
+            Arc::new(KitchenSchedule::default())
+        }
+    }
+}
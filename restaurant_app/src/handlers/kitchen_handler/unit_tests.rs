@@ -0,0 +1,320 @@
+#[cfg(test)]
+pub mod kitchen_unit_tests {
+
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use axum::{extract::State, http::StatusCode};
+    use chrono::{DateTime, TimeZone, Utc};
+
+    use crate::{
+        common::{
+            clock::{FakeClock, SystemClock},
+            config::kitchen_schedule_lookback,
+            database::DB,
+            errors::{AxumErrors, ErrorResponse},
+            menu_cache::MenuCache,
+            models::restaurant_schema::{CookStatus, Item, Order, Station, TakenStock},
+        },
+        handlers::kitchen_handler::{
+            kitchen::{build_stations, list_stations},
+            scheduler::{kitchen_schedule, KitchenSchedule, ScheduleCache, ScheduledOrder},
+        },
+        tests::fixtures::item,
+        AppState,
+    };
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1_800_000_000 + seconds, 0).unwrap()
+    }
+
+    fn station_order(
+        order_id: i64,
+        station: Option<Station>,
+        cook_time: i64,
+        quantity: i64,
+        fired_time: DateTime<Utc>,
+    ) -> Order {
+        Order {
+            order_id,
+            table_id: 1,
+            ordered_time: fired_time,
+            cook_status: CookStatus::InProgress,
+            item: Item {
+                station,
+                ..item("Hamburger", cook_time)
+            },
+            quantity,
+            modifiers: vec![],
+            course: 1,
+            held: false,
+            fired_time: Some(fired_time),
+            allergy_conflicts: vec![],
+            taken_stock: TakenStock::default(),
+        }
+    }
+
+    fn scheduled(started: i64, ready: i64) -> Option<ScheduledOrder> {
+        Some(ScheduledOrder {
+            started_time: at(started),
+            estimated_ready_time: at(ready),
+        })
+    }
+
+    ////////////////////////
+    //                   //
+    //  SCHEDULER       //
+    //                 //
+    ////////////////////
+
+    #[test]
+    pub fn schedule_queues_orders_once_slots_are_taken() {
+        let orders = [
+            station_order(1, Some(Station::Grill), 60, 1, at(0)),
+            station_order(2, Some(Station::Grill), 60, 1, at(10)),
+            station_order(3, Some(Station::Grill), 60, 1, at(20)),
+            station_order(4, Some(Station::Wok), 30, 1, at(20)),
+        ];
+        let schedule = KitchenSchedule::new(&orders, |_| 2);
+
+        assert_eq!(schedule.get(1), scheduled(0, 60));
+        assert_eq!(schedule.get(2), scheduled(10, 70));
+        // both grill slots are busy until the first order is done
+        assert_eq!(schedule.get(3), scheduled(60, 120));
+        // the wok doesn't wait for the grill
+        assert_eq!(schedule.get(4), scheduled(20, 50));
+    }
+
+    #[test]
+    pub fn schedule_spreads_quantity_over_slots() {
+        let orders = [
+            station_order(1, Some(Station::Fryer), 60, 3, at(0)),
+            station_order(2, Some(Station::Fryer), 60, 1, at(0)),
+        ];
+        let schedule = KitchenSchedule::new(&orders, |_| 2);
+
+        // two dishes at once, the third one waits for a slot
+        assert_eq!(schedule.get(1), scheduled(0, 120));
+        assert_eq!(schedule.get(2), scheduled(60, 120));
+    }
+
+    #[test]
+    pub fn schedule_skips_held_and_stationless_orders() {
+        let mut held = station_order(1, Some(Station::Grill), 60, 1, at(0));
+        held.held = true;
+        held.fired_time = None;
+        let orders = [
+            held,
+            station_order(2, None, 60, 5, at(0)),
+            station_order(3, Some(Station::Grill), 60, 1, at(0)),
+        ];
+        let schedule = KitchenSchedule::new(&orders, |_| 1);
+
+        assert_eq!(schedule.get(1), None);
+        assert_eq!(schedule.get(2), scheduled(0, 60));
+        // the held order doesn't take the only slot
+        assert_eq!(schedule.get(3), scheduled(0, 60));
+    }
+
+    #[test]
+    pub fn schedule_sets_cook_status() {
+        let orders = [
+            station_order(1, Some(Station::Grill), 60, 1, at(0)),
+            station_order(2, Some(Station::Grill), 60, 1, at(0)),
+        ];
+        let schedule = KitchenSchedule::new(&orders, |_| 1);
+
+        let first = schedule.order(orders[0].clone(), at(30));
+        let second = schedule.order(orders[1].clone(), at(30));
+        assert_eq!(first.cook_status, CookStatus::InProgress);
+        assert_eq!(second.cook_status, CookStatus::Queued);
+        assert_eq!(second.started_time, Some(at(60)));
+        assert_eq!(second.estimated_ready_time, Some(at(120)));
+
+        let first = schedule.order(orders[0].clone(), at(90));
+        let second = schedule.order(orders[1].clone(), at(90));
+        assert_eq!(first.cook_status, CookStatus::Done);
+        assert_eq!(second.cook_status, CookStatus::InProgress);
+    }
+
+    ////////////////////////
+    //                   //
+    //  LIST_STATIONS   //
+    //                 //
+    ////////////////////
+
+    #[test]
+    pub fn successful_build_stations() {
+        let orders = vec![
+            station_order(1, Some(Station::Grill), 60, 1, at(0)),
+            station_order(2, Some(Station::Grill), 60, 1, at(0)),
+            station_order(3, Some(Station::Grill), 60, 1, at(0)),
+            station_order(4, None, 60, 1, at(0)),
+            station_order(5, Some(Station::Wok), 10, 1, at(0)),
+        ];
+
+        let stations = build_stations(orders, |_| 2, at(30)).stations;
+        assert_eq!(
+            stations
+                .iter()
+                .map(|station| station.station)
+                .collect::<Vec<Station>>(),
+            Station::ALL.to_vec()
+        );
+
+        let grill = &stations[0];
+        assert_eq!(grill.slots, 2);
+        assert_eq!(
+            grill
+                .cooking
+                .iter()
+                .map(|order| order.order_id)
+                .collect::<Vec<i64>>(),
+            vec![1, 2]
+        );
+        assert_eq!(
+            grill
+                .queued
+                .iter()
+                .map(|order| order.order_id)
+                .collect::<Vec<i64>>(),
+            vec![3]
+        );
+        assert_eq!(grill.busy_until, Some(at(120)));
+
+        // the wok is already done with its only order
+        let wok = &stations[2];
+        assert!(wok.cooking.is_empty());
+        assert!(wok.queued.is_empty());
+        assert_eq!(wok.busy_until, None);
+    }
+
+    #[tokio::test]
+    pub async fn successful_list_stations() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders)
+            .then(|_| Ok(vec![station_order(1, Some(Station::Wok), 60, 1, at(0))]));
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(FakeClock::new(at(0))),
+        });
+
+        match list_stations(State(app_state)).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::OK);
+                let wok = resp
+                    .1
+                    .stations
+                    .iter()
+                    .find(|station| station.station == Station::Wok)
+                    .unwrap();
+                assert_eq!(wok.cooking.len(), 1);
+                assert_eq!(wok.cooking[0].cook_status, CookStatus::InProgress);
+            }
+            Err(_) => panic!("request failed!"),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_list_stations_queue_moves_with_clock() {
+        let clock = Arc::new(FakeClock::new(at(0)));
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| {
+            Ok(vec![
+                station_order(1, Some(Station::Fryer), 60, 1, at(0)),
+                station_order(2, Some(Station::Fryer), 60, 1, at(0)),
+                station_order(3, Some(Station::Fryer), 60, 1, at(0)),
+            ])
+        });
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: clock.clone(),
+        });
+        let fryer = || async {
+            match list_stations(State(app_state.clone())).await {
+                Ok(resp) => {
+                    let fryer = resp
+                        .1
+                         .0
+                        .stations
+                        .into_iter()
+                        .find(|station| station.station == Station::Fryer)
+                        .unwrap();
+                    (fryer.cooking.len(), fryer.queued.len())
+                }
+                Err(_) => panic!("request failed!"),
+            }
+        };
+
+        // the env isn't set in the tests, the fryer has the default two slots
+        assert_eq!(fryer().await, (2, 1));
+        clock.advance(chrono::Duration::seconds(60));
+        assert_eq!(fryer().await, (1, 0));
+        clock.advance(chrono::Duration::seconds(60));
+        assert_eq!(fryer().await, (0, 0));
+    }
+
+    #[tokio::test]
+    pub async fn failed_list_stations_db_error() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| {
+            Err(ErrorResponse {
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                error: AxumErrors::DBError.into(),
+            })
+        });
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+
+        match list_stations(State(app_state)).await {
+            Ok(_) => panic!("shouldn't succeed!"),
+            Err(e) => {
+                assert_eq!(e.1.error_type, AxumErrors::DBError.to_string());
+            }
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_kitchen_schedule_only_reads_recent_orders() {
+        let clock = Arc::new(FakeClock::new(at(0)));
+        let reads = Arc::new(AtomicUsize::new(0));
+        let mut mock_db = DB::faux();
+        let counted_reads = reads.clone();
+        faux::when!(mock_db.get_fired_orders).then(move |since| {
+            counted_reads.fetch_add(1, Ordering::SeqCst);
+            assert_eq!(
+                since,
+                at(0) - chrono::Duration::from_std(kitchen_schedule_lookback()).unwrap()
+            );
+            Ok(vec![station_order(1, Some(Station::Wok), 60, 1, at(0))])
+        });
+        let app_state = AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::from_secs(60)),
+            clock: clock.clone(),
+        };
+
+        // the reads share the schedule until a write changes the orders
+        assert_eq!(kitchen_schedule(&app_state).await.get(1), scheduled(0, 60));
+        assert_eq!(kitchen_schedule(&app_state).await.get(1), scheduled(0, 60));
+        assert_eq!(reads.load(Ordering::SeqCst), 1);
+
+        app_state.schedule_cache.invalidate().await;
+        kitchen_schedule(&app_state).await;
+        assert_eq!(reads.load(Ordering::SeqCst), 2);
+    }
+}
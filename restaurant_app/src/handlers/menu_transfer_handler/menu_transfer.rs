@@ -0,0 +1,381 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+use crate::{
+    common::{
+        config::{validation_limits, ValidationLimits},
+        database::DB,
+        errors::{AxumErrorResponse, AxumErrors, ErrorResponse, FieldError, ProblemJson},
+        extractors::{Json, ValidQuery},
+        menu_records::{parse_menu_records, write_menu_records, MenuFormat},
+        models::restaurant_schema::Item,
+        validation::{field_errors, validate_menu_record},
+    },
+    handlers::{
+        daypart_handler::daypart_db::DBDaypartTrait,
+        inventory_handler::inventory_db::DBInventoryTrait, item_handler::item_db::DBTableTrait,
+    },
+    AppState,
+};
+
+use super::menu_transfer_db::DBMenuTransferTrait;
+
+/// What happens to the items that are already on the menu.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// only adds the items that aren't on the menu yet, the others are left as they are
+    #[default]
+    Merge,
+    /// adds the new items and overwrites the ones that are on the menu
+    Upsert,
+    /// the menu becomes the file, items that aren't in it are deleted
+    Replace,
+}
+
+#[derive(Deserialize, Serialize, IntoParams, Validate)]
+#[into_params(parameter_in = Query)]
+#[validate(context = ValidationLimits)]
+pub struct MenuImportRequest {
+    /// the format of the body, taken from its `Content-Type` when left out
+    pub format: Option<MenuFormat>,
+    #[serde(default)]
+    pub mode: ImportMode,
+    /// only reports what the import would change
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Deserialize, Serialize, IntoParams, Validate)]
+#[into_params(parameter_in = Query)]
+#[validate(context = ValidationLimits)]
+pub struct MenuExportRequest {
+    #[serde(default = "default_format")]
+    pub format: MenuFormat,
+}
+
+fn default_format() -> MenuFormat {
+    MenuFormat::Json
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct MenuImportResponse {
+    pub mode: ImportMode,
+    /// nothing was written, the lists are what the import would do
+    pub dry_run: bool,
+    /// items in the file
+    pub rows: usize,
+    /// the item names of each outcome, sorted
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    /// on the menu already, just like in the file
+    pub unchanged: Vec<String>,
+    /// on the menu but different from the file, left as they are by a merge
+    pub skipped: Vec<String>,
+    /// not in the file, taken off the menu by a replace
+    pub deleted: Vec<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/menu/import",
+    tag = "menu_transfer",
+    params(MenuImportRequest),
+    request_body(content = String, description = "The menu file, csv, json or yaml", content_type = "text/csv"),
+    responses(
+        (status = 200, description = "What the import changed, or would change on a dry run", body = MenuImportResponse),
+        (status = 415, description = "The format is neither given nor one of csv, json and yaml", body = AxumErrorResponse),
+        (status = 422, description = "The file can't be read, or some of its rows are invalid, every offending field is listed", body = AxumErrorResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
+pub async fn import_menu_file(
+    State(app_state): State<Arc<AppState>>,
+    ValidQuery(request): ValidQuery<MenuImportRequest>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<(StatusCode, Json<MenuImportResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)> {
+    let db = &app_state.db;
+
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok());
+    let Some(format) = request
+        .format
+        .or_else(|| content_type.and_then(MenuFormat::from_content_type))
+    else {
+        return Err(ErrorResponse::from(
+            AxumErrorResponse::from(AxumErrors::UnsupportedMediaType).with_detail(
+                "The menu file has to be sent as `text/csv`, `application/json` or `application/yaml`, or with a `format`!",
+            ),
+        )
+        .to_axum_error());
+    };
+    let Ok(text) = std::str::from_utf8(&body) else {
+        return Err(ErrorResponse::from(
+            AxumErrorResponse::from(AxumErrors::InvalidMenuImport)
+                .with_detail("The menu file has to be utf-8 text!"),
+        )
+        .to_axum_error());
+    };
+
+    match import_menu(db, text, format, request.mode, request.dry_run).await {
+        Ok(summary) => {
+            if !summary.dry_run {
+                //new orders have to see the imported items right away
+                app_state.menu_cache.invalidate().await;
+            }
+            Ok((StatusCode::OK, Json(summary)))
+        }
+        Err(e) => Err(e.to_axum_error()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/menu/export",
+    tag = "menu_transfer",
+    params(MenuExportRequest),
+    responses(
+        (status = 200, description = "Every item on the menu, in a file the import reads back", body = String, content_type = "text/csv"),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
+pub async fn export_menu_file(
+    State(app_state): State<Arc<AppState>>,
+    ValidQuery(request): ValidQuery<MenuExportRequest>,
+) -> Result<Response, (StatusCode, ProblemJson<AxumErrorResponse>)> {
+    let db = &app_state.db;
+
+    match export_menu(db, request.format).await {
+        Ok(text) => Ok((
+            StatusCode::OK,
+            [
+                (
+                    header::CONTENT_TYPE,
+                    request.format.content_type().to_string(),
+                ),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!(
+                        "attachment; filename=\"menu.{}\"",
+                        request.format.extension()
+                    ),
+                ),
+            ],
+            text,
+        )
+            .into_response()),
+        Err(e) => Err(e.to_axum_error()),
+    }
+}
+
+/// Reads the menu file, checks every row and applies it in `mode`, unless it is a `dry_run`.
+/// Nothing is written when any row is invalid, the error lists all of them.
+/// Shared by the import end point and the `import-menu` command.
+pub async fn import_menu(
+    db: &DB,
+    text: &str,
+    format: MenuFormat,
+    mode: ImportMode,
+    dry_run: bool,
+) -> Result<MenuImportResponse, ErrorResponse> {
+    let items = read_menu_file(db, text, format).await?;
+    apply_menu_items(db, items, mode, dry_run).await
+}
+
+/// The items of the menu file, once every row is valid and names ingredients and dayparts that exist.
+pub async fn read_menu_file(
+    db: &DB,
+    text: &str,
+    format: MenuFormat,
+) -> Result<Vec<Item>, ErrorResponse> {
+    let rows = parse_menu_records(format, text)?;
+
+    let mut errors = vec![];
+    let mut items = vec![];
+    for (index, row) in rows.into_iter().enumerate() {
+        match row {
+            Ok(item) => {
+                if let Err(row_errors) = validate_menu_record(&item, validation_limits()) {
+                    errors.extend(at_row(index, field_errors(&row_errors)));
+                }
+                items.push((index, item));
+            }
+            Err(row_errors) => errors.extend(row_errors),
+        }
+    }
+    errors.extend(check_references(db, &items).await?);
+    if !errors.is_empty() {
+        return Err(ErrorResponse::from(
+            AxumErrorResponse::from(AxumErrors::InvalidMenuImport).with_errors(errors),
+        ));
+    }
+    Ok(items.into_iter().map(|(_, item)| item).collect())
+}
+
+/// Compares the items with the menu and writes what `mode` changes, unless it is a `dry_run`.
+pub async fn apply_menu_items(
+    db: &DB,
+    items: Vec<Item>,
+    mode: ImportMode,
+    dry_run: bool,
+) -> Result<MenuImportResponse, ErrorResponse> {
+    let mut menu = db
+        .get_menu()
+        .await?
+        .into_iter()
+        .map(|item| (item.item_name.clone(), item))
+        .collect::<HashMap<String, Item>>();
+
+    let mut summary = MenuImportResponse {
+        mode,
+        dry_run,
+        rows: items.len(),
+        created: vec![],
+        updated: vec![],
+        unchanged: vec![],
+        skipped: vec![],
+        deleted: vec![],
+    };
+    let mut writes = vec![];
+    for item in items {
+        match menu.remove(&item.item_name) {
+            None => summary.created.push(item.item_name.clone()),
+            Some(current) if same_menu_item(&current, &item) => {
+                summary.unchanged.push(item.item_name);
+                continue;
+            }
+            Some(_) if mode == ImportMode::Merge => {
+                summary.skipped.push(item.item_name);
+                continue;
+            }
+            Some(_) => summary.updated.push(item.item_name.clone()),
+        }
+        writes.push(item);
+    }
+    if mode == ImportMode::Replace {
+        summary.deleted = menu.into_keys().collect();
+    }
+    for names in [
+        &mut summary.created,
+        &mut summary.updated,
+        &mut summary.unchanged,
+        &mut summary.skipped,
+        &mut summary.deleted,
+    ] {
+        names.sort();
+    }
+
+    if !dry_run && (!writes.is_empty() || !summary.deleted.is_empty()) {
+        db.import_menu_items(&writes, &summary.deleted).await?;
+    }
+    Ok(summary)
+}
+
+/// Every item on the menu, by name, written in the format.
+pub async fn export_menu(db: &DB, format: MenuFormat) -> Result<String, ErrorResponse> {
+    let mut items = db.get_menu().await?;
+    items.sort_by(|a, b| a.item_name.cmp(&b.item_name));
+    write_menu_records(format, &items)
+}
+
+/// The names of the file have to be unique, and its recipes and dayparts have to name what exists.
+async fn check_references(
+    db: &DB,
+    items: &[(usize, Item)],
+) -> Result<Vec<FieldError>, ErrorResponse> {
+    let ingredient_names = items
+        .iter()
+        .flat_map(|(_, item)| item.recipe.iter())
+        .map(|ingredient| ingredient.ingredient_name.clone())
+        .collect::<HashSet<String>>()
+        .into_iter()
+        .collect::<Vec<String>>();
+    let inventory = match ingredient_names.is_empty() {
+        true => HashMap::new(),
+        false => db.get_ingredients_by_name(&ingredient_names).await?,
+    };
+    let uses_dayparts = items.iter().any(|(_, item)| !item.dayparts.is_empty());
+    let dayparts = match uses_dayparts {
+        true => db.list_dayparts().await?,
+        false => vec![],
+    };
+
+    let mut errors = vec![];
+    let mut seen = HashSet::new();
+    for (index, item) in items {
+        if !seen.insert(item.item_name.as_str()) {
+            errors.push(FieldError::new(
+                format!("rows[{index}].item_name"),
+                "duplicate_item",
+                format!("{} is already in an earlier row", item.item_name),
+            ));
+        }
+        for (recipe_index, ingredient) in item.recipe.iter().enumerate() {
+            if !inventory.contains_key(&ingredient.ingredient_name) {
+                errors.push(FieldError::new(
+                    format!("rows[{index}].recipe[{recipe_index}].ingredient_name"),
+                    "unknown_ingredient",
+                    format!("{} is not in the inventory", ingredient.ingredient_name),
+                ));
+            }
+        }
+        for (daypart_index, daypart_name) in item.dayparts.iter().enumerate() {
+            if !dayparts
+                .iter()
+                .any(|daypart| &daypart.daypart_name == daypart_name)
+            {
+                errors.push(FieldError::new(
+                    format!("rows[{index}].dayparts[{daypart_index}]"),
+                    "unknown_daypart",
+                    format!("{daypart_name} is not a daypart"),
+                ));
+            }
+        }
+    }
+    Ok(errors)
+}
+
+fn at_row(index: usize, errors: Vec<FieldError>) -> Vec<FieldError> {
+    errors
+        .into_iter()
+        .map(|error| FieldError {
+            field: format!("rows[{index}].{}", error.field),
+            ..error
+        })
+        .collect()
+}
+
+// the menu version an item came from isn't part of the file,
+// and the availability and stock of an item on the menu aren't taken from it
+fn same_menu_item(current: &Item, imported: &Item) -> bool {
+    let current = Item {
+        menu_version: None,
+        ..current.clone()
+    };
+    let imported = Item {
+        available: current.available,
+        stock: current.stock,
+        ..imported.clone()
+    };
+    match (
+        serde_json::to_value(&current),
+        serde_json::to_value(&imported),
+    ) {
+        (Ok(current), Ok(imported)) => current == imported,
+        _ => false,
+    }
+}
@@ -0,0 +1,73 @@
+use async_trait::async_trait;
+use futures::FutureExt;
+use mongodb::bson::{doc, Document};
+use mongodb::options::UpdateOptions;
+use tracing::Instrument;
+
+use crate::common::{
+    database, database_helpers::menu_item_update, errors::ErrorResponse,
+    models::restaurant_schema::Item, telemetry::mongo_span, transactions::run_in_transaction,
+};
+
+#[async_trait]
+pub trait DBMenuTransferTrait {
+    /// Writes the imported items over the ones with their names and deletes `deleted_item_names`, all in one transaction.
+    /// The items that are on the menu already keep their availability, stock and menu version.
+    async fn import_menu_items(
+        &self,
+        items: &[Item],
+        deleted_item_names: &[String],
+    ) -> Result<(), ErrorResponse>;
+}
+
+#[faux::methods]
+#[async_trait]
+impl DBMenuTransferTrait for database::DB {
+    async fn import_menu_items(
+        &self,
+        items: &[Item],
+        deleted_item_names: &[String],
+    ) -> Result<(), ErrorResponse> {
+        let item_collection = self
+            .db
+            .database("item_management")
+            .collection::<Item>("items");
+        //the kitchen owns the availability and the stock, orders keep taking from them while the file is written
+        let updates = items
+            .iter()
+            .map(|item| Ok((item.item_name.clone(), menu_item_update(item, None)?)))
+            .collect::<Result<Vec<(String, Document)>, ErrorResponse>>()?;
+
+        run_in_transaction(&self.db, self.transactions, "import the menu", |session| {
+            let item_collection = item_collection.clone();
+            let updates = updates.clone();
+            let deleted_item_names = deleted_item_names.to_vec();
+            async move {
+                for (item_name, update) in updates {
+                    item_collection
+                        .update_one_with_session(
+                            doc! { "item_name": item_name },
+                            update,
+                            UpdateOptions::builder().upsert(true).build(),
+                            session,
+                        )
+                        .instrument(mongo_span("update_one", "items"))
+                        .await?;
+                }
+                if !deleted_item_names.is_empty() {
+                    item_collection
+                        .delete_many_with_session(
+                            doc! { "item_name": { "$in": deleted_item_names } },
+                            None,
+                            session,
+                        )
+                        .instrument(mongo_span("delete_many", "items"))
+                        .await?;
+                }
+                Ok(())
+            }
+            .boxed()
+        })
+        .await
+    }
+}
@@ -0,0 +1,3 @@
+pub mod menu_transfer;
+pub mod menu_transfer_db;
+pub mod unit_tests;
@@ -0,0 +1,287 @@
+#[cfg(test)]
+pub mod menu_transfer_unit_tests {
+
+    use std::{collections::HashMap, sync::Arc, time::Duration};
+
+    use axum::{
+        body::Bytes,
+        extract::State,
+        http::{header, HeaderMap, HeaderValue, StatusCode},
+    };
+
+    use crate::{
+        cli::Command,
+        common::{
+            clock::SystemClock,
+            database::DB,
+            errors::AxumErrors,
+            extractors::ValidQuery,
+            menu_cache::MenuCache,
+            menu_records::{parse_menu_records, write_menu_records, MenuFormat},
+            models::restaurant_schema::{
+                Allergen, Category, Diet, Item, Modifier, ModifierKind, RecipeIngredient, Station,
+            },
+        },
+        handlers::kitchen_handler::scheduler::ScheduleCache,
+        handlers::menu_transfer_handler::menu_transfer::{
+            import_menu, import_menu_file, ImportMode, MenuImportRequest,
+        },
+        tests::fixtures::item,
+        AppState,
+    };
+
+    const CSV_MENU: &str = "\
+item_name,cook_time,category,description,display_order,station,available,stock,modifiers,recipe,allergens,diets,dayparts
+Ramen,30,Main,Pork broth,1,Wok,true,,No Scallions:Remove;Extra Egg:Add:150,Noodles:1;Broth:300,gluten;egg,,
+Lemonade,1,Drink,,0,,false,12,,,,vegan;gluten_free,Lunch
+";
+
+    fn parsed(format: MenuFormat, text: &str) -> Vec<Item> {
+        parse_menu_records(format, text)
+            .unwrap()
+            .into_iter()
+            .map(|row| row.unwrap())
+            .collect()
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    ////////////////////////
+    //                   //
+    //  FORMATS         //
+    //                 //
+    ////////////////////
+
+    #[test]
+    pub fn successful_parse_csv_lists() {
+        let items = parsed(MenuFormat::Csv, CSV_MENU);
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].station, Some(Station::Wok));
+        assert_eq!(
+            items[0].modifiers,
+            vec![
+                Modifier {
+                    name: "No Scallions".to_string(),
+                    kind: ModifierKind::Remove,
+                    price_delta: None,
+                },
+                Modifier {
+                    name: "Extra Egg".to_string(),
+                    kind: ModifierKind::Add,
+                    price_delta: Some(150),
+                },
+            ]
+        );
+        assert_eq!(
+            items[0].recipe[1],
+            RecipeIngredient {
+                ingredient_name: "Broth".to_string(),
+                quantity: 300,
+            }
+        );
+        assert_eq!(items[0].allergens, vec![Allergen::Gluten, Allergen::Egg]);
+        assert_eq!(items[1].category, Category::Drink);
+        assert!(!items[1].available);
+        assert_eq!(items[1].stock, Some(12));
+        assert_eq!(items[1].diets, vec![Diet::Vegan, Diet::GlutenFree]);
+        assert_eq!(items[1].dayparts, vec!["Lunch".to_string()]);
+    }
+
+    #[test]
+    pub fn successful_parse_seed_file() {
+        let seed_file = include_str!("../../common/item_records.json");
+
+        let items = parsed(MenuFormat::Json, seed_file);
+        assert!(items.iter().any(|item| item.item_name == "Ramen"));
+    }
+
+    #[test]
+    pub fn successful_export_reads_back_in_every_format() {
+        let items = parsed(MenuFormat::Csv, CSV_MENU);
+
+        for format in [MenuFormat::Csv, MenuFormat::Json, MenuFormat::Yaml] {
+            let written = write_menu_records(format, &items).unwrap();
+            let read_back = parsed(format, &written);
+            assert_eq!(
+                serde_json::to_value(&read_back).unwrap(),
+                serde_json::to_value(&items).unwrap(),
+                "{format:?} didn't read back the same"
+            );
+        }
+    }
+
+    #[test]
+    pub fn failed_parse_reports_the_bad_rows() {
+        let csv = "item_name,cook_time,allergens\nRamen,soon,\nUdon,5,wheat\nGyoza,15,soy\n";
+
+        let rows = parse_menu_records(MenuFormat::Csv, csv).unwrap();
+        assert_eq!(rows[0].as_ref().unwrap_err()[0].field, "rows[0]");
+        assert_eq!(rows[1].as_ref().unwrap_err()[0].field, "rows[1].allergens");
+        assert!(rows[2].is_ok());
+    }
+
+    #[test]
+    pub fn failed_parse_unreadable_file() {
+        match parse_menu_records(MenuFormat::Yaml, "records: 5") {
+            Ok(_) => panic!("shouldn't succeed!"),
+            Err(e) => assert_eq!(
+                e.error.error_type,
+                AxumErrors::InvalidMenuImport.to_string()
+            ),
+        }
+    }
+
+    ////////////////////////
+    //                   //
+    //  IMPORT          //
+    //                 //
+    ////////////////////
+
+    #[tokio::test]
+    pub async fn successful_import_dry_run_writes_nothing() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_menu).then(|_| {
+            Ok(vec![
+                Item {
+                    menu_version: Some(2),
+                    available: false,
+                    stock: Some(4),
+                    ..item("Ramen", 30)
+                },
+                item("Udon", 5),
+                item("Gyoza", 15),
+            ])
+        });
+        faux::when!(mock_db.import_menu_items).then(|_| panic!("a dry run shouldn't write!"));
+
+        let json = r#"[
+            {"item_name": "Ramen", "cook_time": 30},
+            {"item_name": "Udon", "cook_time": 6},
+            {"item_name": "Curry", "cook_time": 10}
+        ]"#;
+        let summary = import_menu(&mock_db, json, MenuFormat::Json, ImportMode::Replace, true)
+            .await
+            .unwrap();
+        assert_eq!(summary.rows, 3);
+        assert_eq!(summary.created, vec!["Curry".to_string()]);
+        assert_eq!(summary.updated, vec!["Udon".to_string()]);
+        // the version it came from isn't part of the file, and the kitchen's availability and stock are kept
+        assert_eq!(summary.unchanged, vec!["Ramen".to_string()]);
+        assert_eq!(summary.deleted, vec!["Gyoza".to_string()]);
+    }
+
+    #[tokio::test]
+    pub async fn successful_import_merge_keeps_the_existing_items() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_menu).then(|_| Ok(vec![item("Udon", 5), item("Gyoza", 15)]));
+        faux::when!(mock_db.import_menu_items).then(|(items, deleted_item_names)| {
+            assert_eq!(items.len(), 1);
+            assert_eq!(items[0].item_name, "Curry");
+            assert!(deleted_item_names.is_empty());
+            Ok(())
+        });
+
+        let yaml = "records:\n  - item_name: Udon\n    cook_time: 6\n  - item_name: Curry\n    cook_time: 10\n";
+        let summary = import_menu(&mock_db, yaml, MenuFormat::Yaml, ImportMode::Merge, false)
+            .await
+            .unwrap();
+        assert_eq!(summary.created, vec!["Curry".to_string()]);
+        assert_eq!(summary.skipped, vec!["Udon".to_string()]);
+        assert!(summary.deleted.is_empty());
+    }
+
+    #[tokio::test]
+    pub async fn failed_import_invalid_rows() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_ingredients_by_name).then(|_| Ok(HashMap::new()));
+        faux::when!(mock_db.list_dayparts).then(|_| Ok(vec![]));
+        faux::when!(mock_db.import_menu_items).then(|_| panic!("an invalid file shouldn't write!"));
+
+        let csv = "\
+item_name,cook_time,recipe,dayparts
+Ramen,-1,,
+Udon,5,Noodles:1,
+Ramen,30,,Brunch
+";
+        match import_menu(&mock_db, csv, MenuFormat::Csv, ImportMode::Upsert, false).await {
+            Ok(_) => panic!("shouldn't succeed!"),
+            Err(e) => {
+                assert_eq!(e.status_code, StatusCode::UNPROCESSABLE_ENTITY);
+                assert_eq!(
+                    e.error
+                        .errors
+                        .iter()
+                        .map(|error| (error.field.as_str(), error.code.as_str()))
+                        .collect::<Vec<(&str, &str)>>(),
+                    vec![
+                        ("rows[0].cook_time", "negative"),
+                        ("rows[1].recipe[0].ingredient_name", "unknown_ingredient"),
+                        ("rows[2].item_name", "duplicate_item"),
+                        ("rows[2].dayparts[0]", "unknown_daypart"),
+                    ]
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_import_file_without_format() {
+        let app_state = State(Arc::new(AppState {
+            db: DB::faux(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        }));
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        let request = ValidQuery(MenuImportRequest {
+            format: None,
+            mode: ImportMode::Merge,
+            dry_run: true,
+        });
+
+        match import_menu_file(app_state, request, headers, Bytes::from(CSV_MENU)).await {
+            Ok(_) => panic!("shouldn't succeed!"),
+            Err(e) => {
+                assert_eq!(e.0, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+            }
+        }
+    }
+
+    ////////////////////////
+    //                   //
+    //  CLI             //
+    //                 //
+    ////////////////////
+
+    #[test]
+    pub fn successful_parse_cli_commands() {
+        assert_eq!(
+            Command::parse(&args(&[
+                "import-menu",
+                "menu.yml",
+                "--mode",
+                "replace",
+                "--dry-run"
+            ])),
+            Ok(Command::ImportMenu {
+                file: "menu.yml".to_string(),
+                format: MenuFormat::Yaml,
+                mode: ImportMode::Replace,
+                dry_run: true,
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["export-menu", "--output", "menu.csv"])),
+            Ok(Command::ExportMenu {
+                format: MenuFormat::Csv,
+                output: Some("menu.csv".to_string()),
+            })
+        );
+        assert!(Command::parse(&args(&["import-menu", "menu.txt"])).is_err());
+        assert!(Command::parse(&args(&["import-menu", "menu.csv", "--mode", "sideways"])).is_err());
+    }
+}
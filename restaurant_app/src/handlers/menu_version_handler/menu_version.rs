@@ -0,0 +1,525 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{extract::State, http::StatusCode};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::{
+    common::{
+        config::{menu_version_poll_interval, ValidationLimits},
+        counters_db::{Counter, DBCounterTrait},
+        errors::{AxumErrorResponse, ErrorResponse, ProblemJson},
+        extractors::{Json, ValidJson, ValidPath},
+        models::{
+            path_schema::{MenuVersionDiffPath, MenuVersionItemPath, MenuVersionPath},
+            restaurant_schema::{
+                Allergen, Category, Diet, Item, KitchenItemResponse, MenuVersion,
+                MenuVersionStatus, Modifier, RecipeIngredient, Station,
+            },
+        },
+        validation::{validate_daypart_names, validate_recipe},
+    },
+    handlers::item_handler::{
+        item::{check_dayparts, check_ingredients},
+        item_db::DBTableTrait,
+    },
+    AppState,
+};
+
+use super::menu_version_db::DBMenuVersionTrait;
+
+#[derive(Deserialize, Serialize, ToSchema, Validate)]
+#[validate(context = ValidationLimits)]
+pub struct CreateMenuVersionRequest {
+    /// what the version changes, e.g. `summer menu`
+    #[validate(length(
+        max = 500,
+        code = "too_long",
+        message = "must be at most 500 characters long"
+    ))]
+    pub note: Option<String>,
+    /// start from the items of this version, the menu as it is now when left out
+    #[validate(range(min = 1, code = "not_positive", message = "must be at least 1"))]
+    pub copy_from: Option<i64>,
+}
+
+/// An item of a draft, its availability and stock are the kitchen's and aren't part of the menu.
+#[derive(Deserialize, Serialize, ToSchema, Validate)]
+#[validate(context = ValidationLimits)]
+pub struct MenuVersionItemRequest {
+    #[validate(range(min = 0, code = "negative", message = "must not be negative"))]
+    pub cook_time: i64,
+    #[serde(default)]
+    pub modifiers: Vec<Modifier>,
+    #[serde(default)]
+    pub category: Category,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub display_order: i64,
+    pub station: Option<Station>,
+    /// every ingredient once and in the inventory
+    #[serde(default)]
+    #[validate(custom(function = "validate_recipe", use_context))]
+    pub recipe: Vec<RecipeIngredient>,
+    #[serde(default)]
+    pub allergens: Vec<Allergen>,
+    #[serde(default)]
+    pub diets: Vec<Diet>,
+    /// every daypart once and existing, none to serve it all day
+    #[serde(default)]
+    #[validate(custom(function = "validate_daypart_names", use_context))]
+    pub dayparts: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Validate)]
+#[validate(context = ValidationLimits)]
+pub struct PublishMenuVersionRequest {
+    /// when the version takes over the menu, right away when left out or in the past
+    pub effective_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MenuVersionResponse {
+    pub version: i64,
+    pub status: MenuVersionStatus,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// missing while it is a draft
+    pub effective_at: Option<DateTime<Utc>>,
+    /// sorted by name
+    pub items: Vec<KitchenItemResponse>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MenuVersionSummaryResponse {
+    pub version: i64,
+    pub status: MenuVersionStatus,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub effective_at: Option<DateTime<Utc>>,
+    pub item_count: usize,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MenuVersionsResponse {
+    /// newest first
+    pub menu_versions: Vec<MenuVersionSummaryResponse>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MenuVersionDiffResponse {
+    pub version: i64,
+    pub other_version: i64,
+    /// items only `other_version` has
+    pub added: Vec<KitchenItemResponse>,
+    /// items only `version` has
+    pub removed: Vec<KitchenItemResponse>,
+    /// items both have but that differ, availability and stock aren't compared
+    pub changed: Vec<ItemChangeResponse>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ItemChangeResponse {
+    pub item_name: String,
+    /// names of the fields that differ, e.g. `cook_time`
+    pub fields: Vec<String>,
+    pub before: KitchenItemResponse,
+    pub after: KitchenItemResponse,
+}
+
+impl From<MenuVersion> for MenuVersionResponse {
+    fn from(menu_version: MenuVersion) -> Self {
+        let MenuVersion {
+            version,
+            status,
+            note,
+            mut items,
+            created_at,
+            effective_at,
+        } = menu_version;
+        items.sort_by(|a, b| a.item_name.cmp(&b.item_name));
+        Self {
+            version,
+            status,
+            note,
+            created_at,
+            effective_at,
+            items: items.into_iter().map(|item| item.into()).collect(),
+        }
+    }
+}
+
+impl From<MenuVersion> for MenuVersionSummaryResponse {
+    fn from(menu_version: MenuVersion) -> Self {
+        Self {
+            version: menu_version.version,
+            status: menu_version.status,
+            note: menu_version.note,
+            created_at: menu_version.created_at,
+            effective_at: menu_version.effective_at,
+            item_count: menu_version.items.len(),
+        }
+    }
+}
+
+impl MenuVersionItemRequest {
+    fn into_item(self, item_name: String) -> Item {
+        Item {
+            item_name,
+            cook_time: self.cook_time,
+            modifiers: self.modifiers,
+            category: self.category,
+            description: self.description,
+            display_order: self.display_order,
+            station: self.station,
+            available: true,
+            stock: None,
+            recipe: self.recipe,
+            allergens: self.allergens,
+            diets: self.diets,
+            dayparts: self.dayparts,
+            menu_version: None,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/menu/versions",
+    tag = "menu_version",
+    responses(
+        (status = 200, description = "Every version of the menu", body = MenuVersionsResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
+pub async fn list_menu_versions(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<MenuVersionsResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)>
+{
+    let db = &app_state.db;
+
+    match db.list_menu_versions().await {
+        Ok(menu_versions) => Ok((
+            StatusCode::OK,
+            Json(MenuVersionsResponse {
+                menu_versions: menu_versions.into_iter().map(|v| v.into()).collect(),
+            }),
+        )),
+        Err(e) => Err(e.to_axum_error()),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/menu/versions",
+    tag = "menu_version",
+    request_body = CreateMenuVersionRequest,
+    responses(
+        (status = 201, description = "The draft, with the items it started from", body = MenuVersionResponse),
+        (status = 404, description = "The version to copy from was not found", body = AxumErrorResponse),
+        (status = 422, description = "The note or the version to copy from is invalid", body = AxumErrorResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
+pub async fn create_menu_version(
+    State(app_state): State<Arc<AppState>>,
+    ValidJson(request): ValidJson<CreateMenuVersionRequest>,
+) -> Result<(StatusCode, Json<MenuVersionResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)> {
+    let db = &app_state.db;
+
+    let items = match request.copy_from {
+        Some(copy_from) => db.get_menu_version(copy_from).await.map(|v| v.items),
+        None => db.get_menu().await,
+    };
+    let items = match items {
+        Ok(items) => items
+            .into_iter()
+            .map(|item| Item {
+                menu_version: None,
+                ..item
+            })
+            .collect(),
+        Err(e) => return Err(e.to_axum_error()),
+    };
+
+    let version = match db.next_ids(Counter::MenuVersion, 1).await {
+        Ok(version) => version,
+        Err(e) => return Err(e.to_axum_error()),
+    };
+    let menu_version = MenuVersion {
+        version,
+        status: MenuVersionStatus::Draft,
+        note: request.note,
+        items,
+        created_at: app_state.clock.now(),
+        effective_at: None,
+    };
+    match db.create_menu_version(&menu_version).await {
+        Ok(_) => Ok((StatusCode::CREATED, Json(menu_version.into()))),
+        Err(e) => Err(e.to_axum_error()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/menu/versions/{version}",
+    tag = "menu_version",
+    params(("version" = i64, Path, description = "Number of the menu version")),
+    responses(
+        (status = 200, description = "The menu version with its items", body = MenuVersionResponse),
+        (status = 404, description = "Menu version was not found", body = AxumErrorResponse),
+        (status = 422, description = "The version is invalid", body = AxumErrorResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
+pub async fn get_menu_version(
+    State(app_state): State<Arc<AppState>>,
+    ValidPath(MenuVersionPath { version }): ValidPath<MenuVersionPath>,
+) -> Result<(StatusCode, Json<MenuVersionResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)> {
+    let db = &app_state.db;
+
+    match db.get_menu_version(version).await {
+        Ok(menu_version) => Ok((StatusCode::OK, Json(menu_version.into()))),
+        Err(e) => Err(e.to_axum_error()),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/menu/versions/{version}/items/{item_name}",
+    tag = "menu_version",
+    params(
+        ("version" = i64, Path, description = "Number of the draft"),
+        ("item_name" = String, Path, description = "Name of the item"),
+    ),
+    request_body = MenuVersionItemRequest,
+    responses(
+        (status = 200, description = "The draft as it is now, the item added if it didn't have it", body = MenuVersionResponse),
+        (status = 404, description = "Menu version was not found", body = AxumErrorResponse),
+        (status = 409, description = "The menu version was already published", body = AxumErrorResponse),
+        (status = 422, description = "The item is invalid, or some of its ingredients or dayparts don't exist", body = AxumErrorResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
+pub async fn set_menu_version_item(
+    State(app_state): State<Arc<AppState>>,
+    ValidPath(MenuVersionItemPath { version, item_name }): ValidPath<MenuVersionItemPath>,
+    ValidJson(request): ValidJson<MenuVersionItemRequest>,
+) -> Result<(StatusCode, Json<MenuVersionResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)> {
+    let db = &app_state.db;
+
+    if let Err(e) = check_ingredients(db, &request.recipe).await {
+        return Err(e.to_axum_error());
+    }
+    if let Err(e) = check_dayparts(db, &request.dayparts).await {
+        return Err(e.to_axum_error());
+    }
+
+    match db
+        .set_draft_item(version, &request.into_item(item_name))
+        .await
+    {
+        Ok(menu_version) => Ok((StatusCode::OK, Json(menu_version.into()))),
+        Err(e) => Err(e.to_axum_error()),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/menu/versions/{version}/items/{item_name}",
+    tag = "menu_version",
+    params(
+        ("version" = i64, Path, description = "Number of the draft"),
+        ("item_name" = String, Path, description = "Name of the item"),
+    ),
+    responses(
+        (status = 200, description = "The draft as it is now", body = MenuVersionResponse),
+        (status = 404, description = "Menu version was not found, or it doesn't have the item", body = AxumErrorResponse),
+        (status = 409, description = "The menu version was already published", body = AxumErrorResponse),
+        (status = 422, description = "The version or the item name is invalid", body = AxumErrorResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
+pub async fn remove_menu_version_item(
+    State(app_state): State<Arc<AppState>>,
+    ValidPath(MenuVersionItemPath { version, item_name }): ValidPath<MenuVersionItemPath>,
+) -> Result<(StatusCode, Json<MenuVersionResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)> {
+    let db = &app_state.db;
+
+    match db.remove_draft_item(version, &item_name).await {
+        Ok(menu_version) => Ok((StatusCode::OK, Json(menu_version.into()))),
+        Err(e) => Err(e.to_axum_error()),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/menu/versions/{version}/publish",
+    tag = "menu_version",
+    params(("version" = i64, Path, description = "Number of the draft")),
+    request_body = PublishMenuVersionRequest,
+    responses(
+        (status = 200, description = "The published version, live already if its effective time has come", body = MenuVersionResponse),
+        (status = 404, description = "Menu version was not found", body = AxumErrorResponse),
+        (status = 409, description = "The menu version was already published", body = AxumErrorResponse),
+        (status = 422, description = "The version or the effective time is invalid", body = AxumErrorResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
+pub async fn publish_menu_version(
+    State(app_state): State<Arc<AppState>>,
+    ValidPath(MenuVersionPath { version }): ValidPath<MenuVersionPath>,
+    ValidJson(request): ValidJson<PublishMenuVersionRequest>,
+) -> Result<(StatusCode, Json<MenuVersionResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)> {
+    let db = &app_state.db;
+
+    let now = app_state.clock.now();
+    let effective_at = request.effective_at.unwrap_or(now);
+    let menu_version = match db.publish_menu_version(version, effective_at).await {
+        Ok(menu_version) => menu_version,
+        Err(e) => return Err(e.to_axum_error()),
+    };
+    if effective_at > now {
+        return Ok((StatusCode::OK, Json(menu_version.into())));
+    }
+
+    //no need to wait for the next poll
+    if let Err(e) = apply_due_menu_version(&app_state).await {
+        return Err(e.to_axum_error());
+    }
+    match db.get_menu_version(version).await {
+        Ok(menu_version) => Ok((StatusCode::OK, Json(menu_version.into()))),
+        Err(e) => Err(e.to_axum_error()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/menu/versions/{version}/diff/{other_version}",
+    tag = "menu_version",
+    params(
+        ("version" = i64, Path, description = "The version to compare from"),
+        ("other_version" = i64, Path, description = "The version to compare to"),
+    ),
+    responses(
+        (status = 200, description = "What changed from one version to the other", body = MenuVersionDiffResponse),
+        (status = 404, description = "One of the menu versions was not found", body = AxumErrorResponse),
+        (status = 422, description = "The versions are invalid", body = AxumErrorResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
+pub async fn diff_menu_versions(
+    State(app_state): State<Arc<AppState>>,
+    ValidPath(MenuVersionDiffPath {
+        version,
+        other_version,
+    }): ValidPath<MenuVersionDiffPath>,
+) -> Result<(StatusCode, Json<MenuVersionDiffResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)>
+{
+    let db = &app_state.db;
+
+    let before = match db.get_menu_version(version).await {
+        Ok(menu_version) => menu_version,
+        Err(e) => return Err(e.to_axum_error()),
+    };
+    let after = match db.get_menu_version(other_version).await {
+        Ok(menu_version) => menu_version,
+        Err(e) => return Err(e.to_axum_error()),
+    };
+    Ok((StatusCode::OK, Json(diff_menu_items(before, after))))
+}
+
+/// Compares the items of two versions by name.
+pub fn diff_menu_items(before: MenuVersion, after: MenuVersion) -> MenuVersionDiffResponse {
+    let mut before_items = before
+        .items
+        .into_iter()
+        .map(|item| (item.item_name.clone(), KitchenItemResponse::from(item)))
+        .collect::<HashMap<String, KitchenItemResponse>>();
+
+    let mut added = vec![];
+    let mut changed = vec![];
+    for item in after.items {
+        let after_item = KitchenItemResponse::from(item);
+        match before_items.remove(&after_item.item.item_name) {
+            Some(before_item) => {
+                let fields = changed_fields(&before_item, &after_item);
+                if !fields.is_empty() {
+                    changed.push(ItemChangeResponse {
+                        item_name: after_item.item.item_name.clone(),
+                        fields,
+                        before: before_item,
+                        after: after_item,
+                    });
+                }
+            }
+            None => added.push(after_item),
+        }
+    }
+    let mut removed = before_items
+        .into_values()
+        .collect::<Vec<KitchenItemResponse>>();
+
+    added.sort_by(|a, b| a.item.item_name.cmp(&b.item.item_name));
+    removed.sort_by(|a, b| a.item.item_name.cmp(&b.item.item_name));
+    changed.sort_by(|a, b| a.item_name.cmp(&b.item_name));
+    MenuVersionDiffResponse {
+        version: before.version,
+        other_version: after.version,
+        added,
+        removed,
+        changed,
+    }
+}
+
+// the kitchen's side of an item, and where it came from, aren't part of the menu
+const UNVERSIONED_FIELDS: [&str; 3] = ["available", "stock", "menu_version"];
+
+fn changed_fields(before: &KitchenItemResponse, after: &KitchenItemResponse) -> Vec<String> {
+    let (Ok(serde_json::Value::Object(before)), Ok(serde_json::Value::Object(after))) =
+        (serde_json::to_value(before), serde_json::to_value(after))
+    else {
+        return vec![];
+    };
+    before
+        .iter()
+        .filter(|(field, _)| !UNVERSIONED_FIELDS.contains(&field.as_str()))
+        .filter(|(field, value)| after.get(*field) != Some(*value))
+        .map(|(field, _)| field.clone())
+        .collect()
+}
+
+/// Puts the newest published version whose effective time has come on the menu, if there is one.
+/// Returns the version that went live.
+pub async fn apply_due_menu_version(app_state: &AppState) -> Result<Option<i64>, ErrorResponse> {
+    let db = &app_state.db;
+
+    let Some(menu_version) = db.due_menu_version(app_state.clock.now()).await? else {
+        return Ok(None);
+    };
+    db.apply_menu_version(&menu_version).await?;
+    //new orders have to be placed against the new version right away
+    app_state.menu_cache.invalidate().await;
+    info!(
+        menu_version = menu_version.version,
+        "the menu version went live"
+    );
+    Ok(Some(menu_version.version))
+}
+
+/// Checks for a due menu version every `MENU_VERSION_POLL_SECS`, for as long as the app runs.
+pub async fn apply_menu_versions_when_due(app_state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(menu_version_poll_interval());
+    loop {
+        interval.tick().await;
+        if let Err(e) = apply_due_menu_version(&app_state).await {
+            warn!(
+                "Was unable to put the due menu version on the menu, trying again on the next poll. Error: {}",
+                e.error.detail
+            );
+        }
+    }
+}
@@ -0,0 +1,450 @@
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use futures::{stream::TryStreamExt, FutureExt};
+use mongodb::bson::{self, doc, Bson, Document};
+use mongodb::options::{FindOneAndUpdateOptions, FindOneOptions, ReturnDocument, UpdateOptions};
+use tracing::{error, Instrument};
+
+use crate::common::{
+    database,
+    database_helpers::menu_item_update,
+    errors::{AxumErrorResponse, AxumErrors, ErrorResponse},
+    models::restaurant_schema::{Item, MenuVersion, MenuVersionStatus},
+    telemetry::mongo_span,
+    transactions::run_in_transaction,
+};
+
+#[async_trait]
+pub trait DBMenuVersionTrait {
+    /// Every version of the menu, newest first.
+    async fn list_menu_versions(&self) -> Result<Vec<MenuVersion>, ErrorResponse>;
+    async fn get_menu_version(&self, version: i64) -> Result<MenuVersion, ErrorResponse>;
+    async fn create_menu_version(&self, menu_version: &MenuVersion) -> Result<(), ErrorResponse>;
+    /// Adds the item to the draft, or replaces the one with its name.
+    async fn set_draft_item(&self, version: i64, item: &Item)
+        -> Result<MenuVersion, ErrorResponse>;
+    async fn remove_draft_item(
+        &self,
+        version: i64,
+        item_name: &str,
+    ) -> Result<MenuVersion, ErrorResponse>;
+    /// Turns the draft into a version that takes over the menu at `effective_at`.
+    async fn publish_menu_version(
+        &self,
+        version: i64,
+        effective_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<MenuVersion, ErrorResponse>;
+    /// The newest published version whose effective time has come but that isn't live yet.
+    async fn due_menu_version(
+        &self,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<MenuVersion>, ErrorResponse>;
+    /// Replaces the items with the ones of the version, all in one transaction.
+    /// Only the menu fields are written, the live availability and stock are never read or rewritten,
+    /// the version that was live and the ones it supersedes are retired.
+    async fn apply_menu_version(&self, menu_version: &MenuVersion) -> Result<(), ErrorResponse>;
+}
+
+#[faux::methods]
+#[async_trait]
+impl DBMenuVersionTrait for database::DB {
+    async fn list_menu_versions(&self) -> Result<Vec<MenuVersion>, ErrorResponse> {
+        let menu_version_collection = self
+            .db
+            .database("item_management")
+            .collection::<MenuVersion>("menu_versions");
+
+        let options = mongodb::options::FindOptions::builder()
+            .sort(doc! { "version": -1 })
+            .build();
+
+        match menu_version_collection
+            .find(doc! {}, options)
+            .instrument(mongo_span("find", "menu_versions"))
+            .await
+        {
+            Ok(cursor) => match cursor.try_collect().await {
+                Ok(menu_versions) => Ok(menu_versions),
+                Err(e) => {
+                    error!("Unexpected error occured while reading the Menu Versions from the Database. Error: {e}");
+                    Err(ErrorResponse {
+                        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                        error: AxumErrors::DBError.into(),
+                    })
+                }
+            },
+            Err(e) => {
+                error!("Unexpected error occured while searching for the Menu Versions in the Database. Error: {e}");
+                Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                })
+            }
+        }
+    }
+
+    async fn get_menu_version(&self, version: i64) -> Result<MenuVersion, ErrorResponse> {
+        let menu_version_collection = self
+            .db
+            .database("item_management")
+            .collection::<MenuVersion>("menu_versions");
+
+        match menu_version_collection
+            .find_one(doc! { "version": version }, None)
+            .instrument(mongo_span("find_one", "menu_versions"))
+            .await
+        {
+            Ok(Some(menu_version)) => Ok(menu_version),
+            Ok(None) => Err(menu_version_not_found(version)),
+            Err(e) => {
+                error!(
+                    "Unexpected error occured while finding the Menu Version {version}. Error: {e}"
+                );
+                Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                })
+            }
+        }
+    }
+
+    async fn create_menu_version(&self, menu_version: &MenuVersion) -> Result<(), ErrorResponse> {
+        let menu_version_collection = self
+            .db
+            .database("item_management")
+            .collection::<MenuVersion>("menu_versions");
+
+        match menu_version_collection
+            .insert_one(menu_version, None)
+            .instrument(mongo_span("insert_one", "menu_versions"))
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!(
+                    "Unexpected error occured while creating the Menu Version {}. Error: {e}",
+                    menu_version.version
+                );
+                Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                })
+            }
+        }
+    }
+
+    async fn set_draft_item(
+        &self,
+        version: i64,
+        item: &Item,
+    ) -> Result<MenuVersion, ErrorResponse> {
+        let menu_version_collection = self
+            .db
+            .database("item_management")
+            .collection::<MenuVersion>("menu_versions");
+
+        let item_bson = match bson::to_bson(item) {
+            Ok(item_bson) => item_bson,
+            Err(e) => {
+                error!(
+                    "Unexpected error occured while serializing the Item {}. Error: {e}",
+                    item.item_name
+                );
+                return Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::BsonSerializeError.into(),
+                });
+            }
+        };
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        //replace the item if the draft has it, add it to the end if it doesn't
+        let updates = [
+            (
+                doc! {
+                    "version": version,
+                    "status": MenuVersionStatus::Draft.to_string(),
+                    "items.item_name": &item.item_name,
+                },
+                doc! { "$set": { "items.$": item_bson.clone() } },
+            ),
+            (
+                doc! {
+                    "version": version,
+                    "status": MenuVersionStatus::Draft.to_string(),
+                    "items.item_name": { "$ne": &item.item_name },
+                },
+                doc! { "$push": { "items": item_bson } },
+            ),
+        ];
+        for (filter, update) in updates {
+            match menu_version_collection
+                .find_one_and_update(filter, update, options.clone())
+                .instrument(mongo_span("find_one_and_update", "menu_versions"))
+                .await
+            {
+                Ok(Some(menu_version)) => return Ok(menu_version),
+                Ok(None) => (),
+                Err(e) => {
+                    error!(
+                        "Unexpected error occured while setting {} in the Menu Version {version}. Error: {e}",
+                        item.item_name
+                    );
+                    return Err(ErrorResponse {
+                        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                        error: AxumErrors::DBError.into(),
+                    });
+                }
+            }
+        }
+        Err(not_a_draft(self.get_menu_version(version).await, version))
+    }
+
+    async fn remove_draft_item(
+        &self,
+        version: i64,
+        item_name: &str,
+    ) -> Result<MenuVersion, ErrorResponse> {
+        let menu_version_collection = self
+            .db
+            .database("item_management")
+            .collection::<MenuVersion>("menu_versions");
+
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        match menu_version_collection
+            .find_one_and_update(
+                doc! {
+                    "version": version,
+                    "status": MenuVersionStatus::Draft.to_string(),
+                    "items.item_name": item_name,
+                },
+                doc! { "$pull": { "items": { "item_name": item_name } } },
+                options,
+            )
+            .instrument(mongo_span("find_one_and_update", "menu_versions"))
+            .await
+        {
+            Ok(Some(menu_version)) => Ok(menu_version),
+            Ok(None) => match self.get_menu_version(version).await {
+                Ok(menu_version) if menu_version.status == MenuVersionStatus::Draft => {
+                    Err(ErrorResponse::from(
+                        AxumErrorResponse::from(AxumErrors::ItemNotFound).with_detail(format!(
+                            "{item_name} is not on the menu version {version}!"
+                        )),
+                    ))
+                }
+                Ok(_) => Err(menu_version_not_draft(version)),
+                Err(e) => Err(e),
+            },
+            Err(e) => {
+                error!(
+                    "Unexpected error occured while removing {item_name} from the Menu Version {version}. Error: {e}"
+                );
+                Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                })
+            }
+        }
+    }
+
+    async fn publish_menu_version(
+        &self,
+        version: i64,
+        effective_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<MenuVersion, ErrorResponse> {
+        let menu_version_collection = self
+            .db
+            .database("item_management")
+            .collection::<MenuVersion>("menu_versions");
+
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        match menu_version_collection
+            .find_one_and_update(
+                doc! { "version": version, "status": MenuVersionStatus::Draft.to_string() },
+                doc! { "$set": {
+                    "status": MenuVersionStatus::Scheduled.to_string(),
+                    "effective_at": bson::DateTime::from_chrono(effective_at),
+                } },
+                options,
+            )
+            .instrument(mongo_span("find_one_and_update", "menu_versions"))
+            .await
+        {
+            Ok(Some(menu_version)) => Ok(menu_version),
+            Ok(None) => Err(not_a_draft(self.get_menu_version(version).await, version)),
+            Err(e) => {
+                error!("Unexpected error occured while publishing the Menu Version {version}. Error: {e}");
+                Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                })
+            }
+        }
+    }
+
+    async fn due_menu_version(
+        &self,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<MenuVersion>, ErrorResponse> {
+        let menu_version_collection = self
+            .db
+            .database("item_management")
+            .collection::<MenuVersion>("menu_versions");
+
+        let options = FindOneOptions::builder()
+            .sort(doc! { "effective_at": -1, "version": -1 })
+            .build();
+
+        match menu_version_collection
+            .find_one(
+                doc! {
+                    "status": MenuVersionStatus::Scheduled.to_string(),
+                    "effective_at": { "$lte": bson::DateTime::from_chrono(now) },
+                },
+                options,
+            )
+            .instrument(mongo_span("find_one", "menu_versions"))
+            .await
+        {
+            Ok(menu_version) => Ok(menu_version),
+            Err(e) => {
+                error!("Unexpected error occured while looking for a due Menu Version. Error: {e}");
+                Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                })
+            }
+        }
+    }
+
+    async fn apply_menu_version(&self, menu_version: &MenuVersion) -> Result<(), ErrorResponse> {
+        let item_collection = self
+            .db
+            .database("item_management")
+            .collection::<Item>("items");
+        let menu_version_collection = self
+            .db
+            .database("item_management")
+            .collection::<MenuVersion>("menu_versions");
+
+        let version = menu_version.version;
+        let effective_at = menu_version
+            .effective_at
+            .map(bson::DateTime::from_chrono)
+            .map_or(Bson::Null, Bson::DateTime);
+        //the availability and stock are about the kitchen, not the menu, orders keep taking from them meanwhile
+        let updates = menu_version
+            .items
+            .iter()
+            .map(|item| {
+                Ok((
+                    item.item_name.clone(),
+                    menu_item_update(item, Some(version))?,
+                ))
+            })
+            .collect::<Result<Vec<(String, Document)>, ErrorResponse>>()?;
+        let item_names = menu_version
+            .items
+            .iter()
+            .map(|item| item.item_name.clone())
+            .collect::<Vec<String>>();
+
+        run_in_transaction(
+            &self.db,
+            self.transactions,
+            "apply the Menu Version",
+            |session| {
+                let item_collection = item_collection.clone();
+                let menu_version_collection = menu_version_collection.clone();
+                let updates = updates.clone();
+                let item_names = item_names.clone();
+                let effective_at = effective_at.clone();
+                async move {
+                    for (item_name, update) in updates {
+                        item_collection
+                            .update_one_with_session(
+                                doc! { "item_name": item_name },
+                                update,
+                                UpdateOptions::builder().upsert(true).build(),
+                                session,
+                            )
+                            .instrument(mongo_span("update_one", "items"))
+                            .await?;
+                    }
+                    item_collection
+                        .delete_many_with_session(
+                            doc! { "item_name": { "$nin": item_names } },
+                            None,
+                            session,
+                        )
+                        .instrument(mongo_span("delete_many", "items"))
+                        .await?;
+
+                    //the live one is over, and so are the ones this one got ahead of
+                    menu_version_collection
+                        .update_many_with_session(
+                            doc! {
+                                "version": { "$ne": version },
+                                "$or": [
+                                    { "status": MenuVersionStatus::Live.to_string() },
+                                    {
+                                        "status": MenuVersionStatus::Scheduled.to_string(),
+                                        "effective_at": { "$lte": effective_at },
+                                    },
+                                ],
+                            },
+                            doc! { "$set": { "status": MenuVersionStatus::Retired.to_string() } },
+                            None,
+                            session,
+                        )
+                        .instrument(mongo_span("update_many", "menu_versions"))
+                        .await?;
+                    menu_version_collection
+                        .update_one_with_session(
+                            doc! { "version": version },
+                            doc! { "$set": { "status": MenuVersionStatus::Live.to_string() } },
+                            None,
+                            session,
+                        )
+                        .instrument(mongo_span("update_one", "menu_versions"))
+                        .await?;
+                    Ok(())
+                }
+                .boxed()
+            },
+        )
+        .await
+    }
+}
+
+/// Why a change to a draft didn't match it, it either doesn't exist or was published already.
+fn not_a_draft(found: Result<MenuVersion, ErrorResponse>, version: i64) -> ErrorResponse {
+    match found {
+        Ok(_) => menu_version_not_draft(version),
+        Err(e) => e,
+    }
+}
+
+fn menu_version_not_found(version: i64) -> ErrorResponse {
+    ErrorResponse::from(
+        AxumErrorResponse::from(AxumErrors::MenuVersionNotFound)
+            .with_detail(format!("There is no menu version {version}!")),
+    )
+}
+
+fn menu_version_not_draft(version: i64) -> ErrorResponse {
+    ErrorResponse::from(
+        AxumErrorResponse::from(AxumErrors::MenuVersionNotDraft).with_detail(format!(
+            "The menu version {version} was already published, only drafts can be changed!"
+        )),
+    )
+}
@@ -0,0 +1,3 @@
+pub mod menu_version;
+pub mod menu_version_db;
+pub mod unit_tests;
@@ -0,0 +1,343 @@
+#[cfg(test)]
+pub mod menu_version_unit_tests {
+
+    use axum::http::StatusCode;
+    use chrono::{DateTime, TimeZone, Utc};
+
+    use crate::{
+        common::{
+            counters_db::Counter,
+            database::DB,
+            errors::{AxumErrorResponse, AxumErrors, ErrorResponse},
+            extractors::{ValidJson, ValidPath},
+            models::{
+                path_schema::{MenuVersionDiffPath, MenuVersionItemPath, MenuVersionPath},
+                restaurant_schema::{
+                    Category, Item, MenuVersion, MenuVersionStatus, RecipeIngredient,
+                },
+            },
+        },
+        handlers::menu_version_handler::menu_version::{
+            apply_due_menu_version, create_menu_version, diff_menu_versions, publish_menu_version,
+            set_menu_version_item, CreateMenuVersionRequest, MenuVersionItemRequest,
+            PublishMenuVersionRequest,
+        },
+        tests::fixtures::{app_state_at, item},
+    };
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap()
+    }
+
+    fn menu_version(version: i64, status: MenuVersionStatus, items: Vec<Item>) -> MenuVersion {
+        MenuVersion {
+            version,
+            status,
+            note: None,
+            items,
+            created_at: now(),
+            effective_at: None,
+        }
+    }
+
+    fn item_request(cook_time: i64) -> MenuVersionItemRequest {
+        MenuVersionItemRequest {
+            cook_time,
+            modifiers: vec![],
+            category: Category::Main,
+            description: None,
+            display_order: 0,
+            station: None,
+            recipe: vec![],
+            allergens: vec![],
+            diets: vec![],
+            dayparts: vec![],
+        }
+    }
+
+    ////////////////////////
+    //                   //
+    //  DRAFTS          //
+    //                 //
+    ////////////////////
+
+    #[tokio::test]
+    pub async fn successful_create_menu_version_from_the_live_menu() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_menu).then(|_| {
+            Ok(vec![Item {
+                menu_version: Some(3),
+                ..item("Burger", 5)
+            }])
+        });
+        faux::when!(mock_db.next_ids).then(|(counter, count)| {
+            assert_eq!(counter, Counter::MenuVersion);
+            assert_eq!(count, 1);
+            Ok(4)
+        });
+        faux::when!(mock_db.create_menu_version).then(|menu_version| {
+            assert_eq!(menu_version.status, MenuVersionStatus::Draft);
+            Ok(())
+        });
+
+        let request = ValidJson(CreateMenuVersionRequest {
+            note: Some("summer menu".to_string()),
+            copy_from: None,
+        });
+        match create_menu_version(app_state_at(mock_db, now()), request).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::CREATED);
+                assert_eq!(resp.1.version, 4);
+                assert_eq!(resp.1.effective_at, None);
+                // the draft's items aren't on the menu as any version yet
+                assert_eq!(resp.1.items[0].item.item_name, "Burger");
+                assert_eq!(resp.1.items[0].item.menu_version, None);
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_create_menu_version_copy_from_not_found() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_menu_version)
+            .then(|_| Err(ErrorResponse::from(AxumErrors::MenuVersionNotFound)));
+
+        let request = ValidJson(CreateMenuVersionRequest {
+            note: None,
+            copy_from: Some(9),
+        });
+        match create_menu_version(app_state_at(mock_db, now()), request).await {
+            Ok(_) => panic!("shouldn't succeed!"),
+            Err(e) => {
+                assert_eq!(e.0, StatusCode::NOT_FOUND);
+                assert_eq!(e.1.error_type, AxumErrors::MenuVersionNotFound.to_string());
+            }
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_set_menu_version_item() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_ingredients_by_name).then(|_| Ok(Default::default()));
+        faux::when!(mock_db.list_dayparts).then(|_| Ok(vec![]));
+        faux::when!(mock_db.set_draft_item).then(|(version, item)| {
+            assert_eq!(version, 2);
+            assert_eq!(item.item_name, "Burger");
+            Ok(menu_version(
+                2,
+                MenuVersionStatus::Draft,
+                vec![item.clone()],
+            ))
+        });
+
+        let path = ValidPath(MenuVersionItemPath {
+            version: 2,
+            item_name: "Burger".to_string(),
+        });
+        match set_menu_version_item(
+            app_state_at(mock_db, now()),
+            path,
+            ValidJson(item_request(7)),
+        )
+        .await
+        {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::OK);
+                assert_eq!(resp.1.items[0].item.cook_time, 7);
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_set_menu_version_item_unknown_ingredient() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_ingredients_by_name).then(|_| Ok(Default::default()));
+
+        let path = ValidPath(MenuVersionItemPath {
+            version: 2,
+            item_name: "Burger".to_string(),
+        });
+        let request = MenuVersionItemRequest {
+            recipe: vec![RecipeIngredient {
+                ingredient_name: "Bun".to_string(),
+                quantity: 1,
+            }],
+            ..item_request(7)
+        };
+        match set_menu_version_item(app_state_at(mock_db, now()), path, ValidJson(request)).await {
+            Ok(_) => panic!("shouldn't succeed!"),
+            Err(e) => {
+                assert_eq!(e.1.error_type, AxumErrors::UnknownIngredients.to_string());
+                assert_eq!(e.1.errors[0].field, "recipe[0].ingredient_name");
+            }
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_set_menu_version_item_not_a_draft() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_ingredients_by_name).then(|_| Ok(Default::default()));
+        faux::when!(mock_db.list_dayparts).then(|_| Ok(vec![]));
+        faux::when!(mock_db.set_draft_item).then(|_| {
+            Err(ErrorResponse::from(AxumErrorResponse::from(
+                AxumErrors::MenuVersionNotDraft,
+            )))
+        });
+
+        let path = ValidPath(MenuVersionItemPath {
+            version: 1,
+            item_name: "Burger".to_string(),
+        });
+        match set_menu_version_item(
+            app_state_at(mock_db, now()),
+            path,
+            ValidJson(item_request(7)),
+        )
+        .await
+        {
+            Ok(_) => panic!("shouldn't succeed!"),
+            Err(e) => {
+                assert_eq!(e.0, StatusCode::CONFLICT);
+                assert_eq!(e.1.error_type, AxumErrors::MenuVersionNotDraft.to_string());
+            }
+        }
+    }
+
+    ////////////////////////
+    //                   //
+    //  PUBLISHING      //
+    //                 //
+    ////////////////////
+
+    #[tokio::test]
+    pub async fn successful_publish_menu_version_later() {
+        let effective_at = now() + chrono::Duration::hours(2);
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.publish_menu_version).then(move |(version, at)| {
+            assert_eq!(at, effective_at);
+            Ok(MenuVersion {
+                effective_at: Some(at),
+                ..menu_version(version, MenuVersionStatus::Scheduled, vec![])
+            })
+        });
+        // nothing is applied before its effective time
+        faux::when!(mock_db.due_menu_version).then(|_| panic!("shouldn't look for a due version!"));
+
+        let request = ValidJson(PublishMenuVersionRequest {
+            effective_at: Some(effective_at),
+        });
+        match publish_menu_version(
+            app_state_at(mock_db, now()),
+            ValidPath(MenuVersionPath { version: 2 }),
+            request,
+        )
+        .await
+        {
+            Ok(resp) => {
+                assert_eq!(resp.1.status, MenuVersionStatus::Scheduled);
+                assert_eq!(resp.1.effective_at, Some(effective_at));
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_publish_menu_version_now_goes_live() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.publish_menu_version).then(|(version, at)| {
+            assert_eq!(at, now());
+            Ok(MenuVersion {
+                effective_at: Some(at),
+                ..menu_version(version, MenuVersionStatus::Scheduled, vec![])
+            })
+        });
+        faux::when!(mock_db.due_menu_version).then(|at| {
+            assert_eq!(at, now());
+            Ok(Some(menu_version(2, MenuVersionStatus::Scheduled, vec![])))
+        });
+        faux::when!(mock_db.apply_menu_version).then(|menu_version| {
+            assert_eq!(menu_version.version, 2);
+            Ok(())
+        });
+        faux::when!(mock_db.get_menu_version)
+            .then(|version| Ok(menu_version(version, MenuVersionStatus::Live, vec![])));
+
+        let request = ValidJson(PublishMenuVersionRequest { effective_at: None });
+        match publish_menu_version(
+            app_state_at(mock_db, now()),
+            ValidPath(MenuVersionPath { version: 2 }),
+            request,
+        )
+        .await
+        {
+            Ok(resp) => assert_eq!(resp.1.status, MenuVersionStatus::Live),
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_apply_due_menu_version_nothing_due() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.due_menu_version).then(|_| Ok(None));
+
+        let state = app_state_at(mock_db, now());
+        match apply_due_menu_version(&state.0).await {
+            Ok(applied) => assert_eq!(applied, None),
+            Err(e) => panic!("error! {:?}", e.error.error_type),
+        }
+    }
+
+    ////////////////////////
+    //                   //
+    //  DIFF            //
+    //                 //
+    ////////////////////
+
+    #[tokio::test]
+    pub async fn successful_diff_menu_versions() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_menu_version).then(|version| match version {
+            1 => Ok(menu_version(
+                1,
+                MenuVersionStatus::Retired,
+                vec![item("Burger", 5), item("Fries", 3), item("Soda", 0)],
+            )),
+            _ => Ok(menu_version(
+                2,
+                MenuVersionStatus::Live,
+                vec![
+                    item("Burger", 6),
+                    // the availability belongs to the kitchen, it isn't a change to the menu
+                    Item {
+                        available: false,
+                        stock: Some(0),
+                        ..item("Soda", 0)
+                    },
+                    item("Salad", 2),
+                ],
+            )),
+        });
+
+        let path = ValidPath(MenuVersionDiffPath {
+            version: 1,
+            other_version: 2,
+        });
+        match diff_menu_versions(app_state_at(mock_db, now()), path).await {
+            Ok(resp) => {
+                let diff = resp.1 .0;
+                assert_eq!(diff.version, 1);
+                assert_eq!(diff.other_version, 2);
+                assert_eq!(diff.added[0].item.item_name, "Salad");
+                assert_eq!(diff.removed[0].item.item_name, "Fries");
+                assert_eq!(diff.changed.len(), 1);
+                assert_eq!(diff.changed[0].item_name, "Burger");
+                assert_eq!(diff.changed[0].fields, vec!["cook_time".to_string()]);
+                assert_eq!(diff.changed[0].before.item.cook_time, 5);
+                assert_eq!(diff.changed[0].after.item.cook_time, 6);
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+}
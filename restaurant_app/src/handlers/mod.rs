@@ -1,3 +1,8 @@
+pub mod daypart_handler;
+pub mod inventory_handler;
 pub mod item_handler;
+pub mod kitchen_handler;
+pub mod menu_transfer_handler;
+pub mod menu_version_handler;
 pub mod order_handler;
 pub mod table_handler;
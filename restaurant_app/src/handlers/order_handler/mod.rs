@@ -1,3 +1,5 @@
 pub mod order;
+pub mod order_builder;
 pub mod order_db;
+pub mod stock;
 pub mod unit_tests;
@@ -1,56 +1,103 @@
 use std::sync::Arc;
 
-use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    Json,
-};
-use axum_extra::extract::Query as ExtraQuery;
-
-use chrono::Utc;
+use axum::{extract::State, http::StatusCode};
 
 use serde::{Deserialize, Serialize};
-use tracing::error;
+use tracing::{error, Span};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
 
+use super::order_builder::{build_orders, BuiltOrders, OrderBatch, OrderTarget};
 use super::order_db::DBOrderTrait;
+use super::stock::{give_back_stock, give_back_unstarted_stock};
+use crate::handlers::kitchen_handler::scheduler::kitchen_schedule;
 use crate::{
     common::{
-        errors::{AxumErrorResponse, AxumErrors},
+        config::ValidationLimits,
+        errors::{AxumErrorResponse, AxumErrors, ErrorResponse, FieldError, ProblemJson},
+        etag::{ETag, IfMatch},
+        extractors::{ApiVersion, Json, ValidExtraQuery, ValidJson, ValidPath, ValidQuery},
         models::{
             pagination_schema::Pagination,
-            restaurant_schema::{CookStatus, Order, OrderResponse, TableResponse},
+            path_schema::{OrderPath, TablePath},
+            restaurant_schema::{CookStatus, OrderRequest, OrderResponse, TableResponse},
         },
+        validation::{validate_course, validate_ids, validate_item_names, validate_order_count},
     },
     AppState,
 };
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema, Validate)]
+#[validate(context = ValidationLimits)]
 pub struct CreateOrdersRequest {
-    pub orders: Vec<String>,
+    /// items to order, at least one and at most `MAX_ORDERS_PER_REQUEST`
+    #[validate(
+        length(min = 1, code = "empty", message = "at least one item must be ordered"),
+        custom(function = "validate_order_count", use_context)
+    )]
+    pub orders: Vec<OrderRequest>,
+    /// place the orders for the known items even if some of the requested ones are not on the menu
+    #[serde(default)]
+    pub allow_partial: bool,
+    /// a manager lets the items be ordered outside the dayparts they are served in
+    #[serde(default)]
+    pub manager_override: bool,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct ReturnTableResponse {
     pub table: TableResponse,
 }
 
-#[derive(Serialize)]
-pub struct ListTableOrdersResponse {
-    pub table_id: i64,
-    pub orders: Vec<OrderResponse>,
+#[derive(Deserialize, Serialize, ToSchema, Validate)]
+#[validate(context = ValidationLimits)]
+pub struct MoveOrdersRequest {
+    /// table the orders are moved to
+    #[validate(range(min = 1, code = "not_positive", message = "must be at least 1"))]
+    pub to_table_id: i64,
+    /// orders to move, every one of them has to be on the table
+    #[validate(
+        length(min = 1, code = "empty", message = "at least one order must be moved"),
+        custom(function = "validate_ids")
+    )]
+    pub order_ids: Vec<i64>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct MoveOrdersResponse {
+    /// the table the orders were moved from
+    pub from: TableResponse,
+    /// the table the orders were moved to
+    pub to: TableResponse,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, IntoParams, ToSchema, Validate)]
+#[into_params(parameter_in = Query)]
+#[validate(context = ValidationLimits)]
+pub struct FireCourseQuery {
+    /// the course to start, held orders of the courses before it are started with it
+    #[validate(custom(function = "validate_course", use_context))]
+    pub course: i64,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct GetOrderResponse {
     pub order: OrderResponse,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, IntoParams, ToSchema, Validate)]
+#[into_params(parameter_in = Query)]
+#[validate(context = ValidationLimits)]
 pub struct ListOrderFiltersRequest {
+    /// only orders of these tables, repeat the param for each table
     #[serde(default = "default_vec_i64")]
+    #[validate(custom(function = "validate_ids"))]
     pub table_ids: Vec<i64>,
+    /// only orders for these items, repeat the param for each item
     #[serde(default = "default_vec_strings")]
+    #[validate(custom(function = "validate_item_names", use_context))]
     pub item_names: Vec<String>,
+    /// only orders that are in this state
     pub cook_status: Option<CookStatus>,
 }
 pub fn default_vec_i64() -> Vec<i64> {
@@ -61,104 +108,208 @@ pub fn default_vec_strings() -> Vec<String> {
     vec![]
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct ListOrdersResponse {
     pub orders: Vec<OrderResponse>,
     pub pagination: ListOrderPaginationResponse,
     pub filters: ListOrderFiltersRequest,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct ListOrderPaginationResponse {
     pub total: u64,
     pub limit: i64,
     pub offset: u64,
 }
 
+#[utoipa::path(
+    post,
+    path = "/table/{table_id}/order",
+    tag = "order",
+    params(
+        ("table_id" = i64, Path, description = "Id of the table"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Makes retries safe, a retry with the same key and body replays the first response"),
+        ("If-Match" = Option<String>, Header, description = "Only add the orders if the table is still at this ETag"),
+    ),
+    request_body = CreateOrdersRequest,
+    responses(
+        (status = 201, description = "Orders were added to the table", body = ReturnTableResponse,
+            headers(("ETag" = String, description = "New version of the table"))),
+        (status = 412, description = "The table changed since the ETag in If-Match", body = AxumErrorResponse),
+        (status = 422, description = "The request is invalid, or some items are not on the menu and allow_partial is off", body = AxumErrorResponse),
+        (status = 404, description = "Table was not found", body = AxumErrorResponse),
+        (status = 409, description = "The Idempotency-Key was used for another request, or that request is still running", body = AxumErrorResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
 pub async fn create_order(
     State(app_state): State<Arc<AppState>>,
-    Path(table_id): Path<i64>,
-    Json(create_order_request): Json<CreateOrdersRequest>,
-) -> Result<(StatusCode, Json<ReturnTableResponse>), (StatusCode, Json<AxumErrorResponse>)> {
+    api_version: ApiVersion,
+    ValidPath(TablePath { table_id }): ValidPath<TablePath>,
+    if_match: IfMatch,
+    ValidJson(create_order_request): ValidJson<CreateOrdersRequest>,
+) -> Result<
+    (StatusCode, ETag, Json<ReturnTableResponse>),
+    (StatusCode, ProblemJson<AxumErrorResponse>),
+> {
+    Span::current().record("table_id", table_id);
     let db = &app_state.db;
-    let mut order_id = app_state.orders.lock().await;
+    let expected_versions = if_match.expected_versions();
 
-    //validate that the table exists
-    match db.get_table_order(&table_id).await {
+    //validate that the table exists, no need to look up the items if it already changed
+    let table = match db.get_table_order(&table_id).await {
         Ok(table) => table,
         Err(e) => {
             return Err(e.to_axum_error());
         }
     };
+    if let Some(versions) = &expected_versions {
+        if !versions.contains(&table.version) {
+            return Err(ErrorResponse::from(AxumErrors::PreconditionFailed).to_axum_error());
+        }
+    }
+
+    //the orders go to the kitchen once the server fired their course
+    let BuiltOrders {
+        orders,
+        stock_needs,
+        ..
+    } = match build_orders(
+        &app_state,
+        OrderBatch {
+            orders: create_order_request.orders,
+            allow_partial: create_order_request.allow_partial,
+            manager_override: create_order_request.manager_override,
+        },
+        OrderTarget {
+            table_id: Some(table_id),
+            fired_course: table.fired_course,
+            allergy_note: table.allergy_note.as_ref(),
+        },
+    )
+    .await
+    {
+        Ok(built) => built,
+        Err(e) => return Err(e.to_axum_error()),
+    };
 
     let mut order_docs = vec![];
-    //prepare the orders
-    for item_name in create_order_request.orders.into_iter() {
-        let item = match db.get_item(item_name).await {
-            Ok(opt_item) => opt_item,
-            Err(e) => return Err(e.to_axum_error()),
-        };
-        match item {
-            Some(item) => {
-                *order_id += 1;
-                let order = Order {
-                    order_id: *order_id,
-                    table_id,
-                    ordered_time: Utc::now(),
-                    cook_status: CookStatus::InProgress,
-                    item,
-                };
-                match mongodb::bson::to_document(&order) {
-                    Ok(document) => order_docs.push(document),
-                    Err(e) => {
-                        error!("unexpected error occured while converting orders into documents! Error: {e}");
-                        return Err((
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            Json(AxumErrors::BsonSerializeError.into()),
-                        ));
-                    }
-                }
+    for order in &orders {
+        match mongodb::bson::to_document(order) {
+            Ok(document) => order_docs.push(document),
+            Err(e) => {
+                error!(
+                    "unexpected error occured while converting orders into documents! Error: {e}"
+                );
+                give_back_stock(&app_state, &stock_needs).await;
+                return Err(ErrorResponse::from(AxumErrors::BsonSerializeError).to_axum_error());
             }
-            None => continue,
         }
     }
 
     //insert the order into the table
-    match db.create_orders(&table_id, order_docs).await {
-        Ok(table) => Ok((
-            StatusCode::CREATED,
-            Json(ReturnTableResponse {
-                table: table.into(),
-            }),
-        )),
-        Err(e) => Err(e.to_axum_error()),
+    match db
+        .create_orders(&table_id, order_docs, expected_versions)
+        .await
+    {
+        Ok(table) => {
+            app_state.schedule_cache.invalidate().await;
+            let schedule = kitchen_schedule(&app_state).await;
+            Ok((
+                StatusCode::CREATED,
+                ETag(table.version),
+                Json(ReturnTableResponse {
+                    table: schedule
+                        .table(table, app_state.clock.now())
+                        .in_version(api_version),
+                }),
+            ))
+        }
+        Err(e) => {
+            give_back_stock(&app_state, &stock_needs).await;
+            Err(e.to_axum_error())
+        }
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/table/{table_id}/order/{order_id}",
+    tag = "order",
+    params(
+        ("table_id" = i64, Path, description = "Id of the table"),
+        ("order_id" = i64, Path, description = "Id of the order"),
+    ),
+    responses(
+        (status = 200, description = "The order", body = GetOrderResponse),
+        (status = 404, description = "Order was not found", body = AxumErrorResponse),
+        (status = 422, description = "The table or order id is not positive", body = AxumErrorResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
 pub async fn get_order(
     State(app_state): State<Arc<AppState>>,
-    Path((table_id, order_id)): Path<(i64, i64)>,
-) -> Result<(StatusCode, Json<GetOrderResponse>), (StatusCode, Json<AxumErrorResponse>)> {
+    api_version: ApiVersion,
+    ValidPath(OrderPath { table_id, order_id }): ValidPath<OrderPath>,
+) -> Result<(StatusCode, Json<GetOrderResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)> {
+    Span::current()
+        .record("table_id", table_id)
+        .record("order_id", order_id);
     let db = &app_state.db;
 
     match db.get_order(&table_id, &order_id).await {
-        Ok(order) => Ok((
-            StatusCode::OK,
-            Json(GetOrderResponse {
-                order: order.into(),
-            }),
-        )),
+        Ok(order) => {
+            let schedule = kitchen_schedule(&app_state).await;
+            Ok((
+                StatusCode::OK,
+                Json(GetOrderResponse {
+                    order: schedule
+                        .order(order, app_state.clock.now())
+                        .in_version(api_version),
+                }),
+            ))
+        }
         Err(e) => Err(e.to_axum_error()),
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/table/order",
+    tag = "order",
+    params(Pagination, ListOrderFiltersRequest),
+    responses(
+        (status = 200, description = "Orders matching the filters", body = ListOrdersResponse),
+        (status = 422, description = "The pagination or the filters are invalid", body = AxumErrorResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
 pub async fn list_all_orders(
     State(app_state): State<Arc<AppState>>,
-    pagination: Query<Pagination>,
-    filters: ExtraQuery<ListOrderFiltersRequest>,
-) -> Result<(StatusCode, Json<ListOrdersResponse>), (StatusCode, Json<AxumErrorResponse>)> {
+    api_version: ApiVersion,
+    pagination: ValidQuery<Pagination>,
+    filters: ValidExtraQuery<ListOrderFiltersRequest>,
+) -> Result<(StatusCode, Json<ListOrdersResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)> {
     let db = &app_state.db;
 
+    if api_version == ApiVersion::V1
+        && matches!(
+            filters.cook_status,
+            Some(CookStatus::Held) | Some(CookStatus::Queued)
+        )
+    {
+        return Err(ErrorResponse::from(
+            AxumErrorResponse::from(AxumErrors::ValidationFailed).with_errors(vec![
+                FieldError::new(
+                    "cook_status",
+                    "unknown_in_v1",
+                    "v1 only knows the InProgress and Done cook statuses",
+                ),
+            ]),
+        )
+        .to_axum_error());
+    }
+
     let pagination = Pagination {
         limit: pagination.limit,
         offset: pagination.offset,
@@ -172,6 +323,13 @@ pub async fn list_all_orders(
 
     match db.list_all_orders(&filters).await {
         Ok(mut list_order_result) => {
+            let schedule = kitchen_schedule(&app_state).await;
+            let now = app_state.clock.now();
+            list_order_result.orders = list_order_result
+                .orders
+                .into_iter()
+                .map(|order| schedule.order(order, now).in_version(api_version))
+                .collect();
             list_order_result.orders = match &filters.cook_status {
                 Some(cook_status) => {
                     handle_cooking_status_filter(list_order_result.orders, cook_status)
@@ -200,19 +358,188 @@ pub async fn list_all_orders(
     }
 }
 
+#[utoipa::path(
+    delete,
+    path = "/table/{table_id}/order/{order_id}",
+    tag = "order",
+    params(
+        ("table_id" = i64, Path, description = "Id of the table"),
+        ("order_id" = i64, Path, description = "Id of the order"),
+        ("If-Match" = Option<String>, Header, description = "Only delete the order if the table is still at this ETag"),
+    ),
+    responses(
+        (status = 200, description = "The table without the deleted order", body = ReturnTableResponse,
+            headers(("ETag" = String, description = "New version of the table"))),
+        (status = 412, description = "The table changed since the ETag in If-Match", body = AxumErrorResponse),
+        (status = 404, description = "Table was not found", body = AxumErrorResponse),
+        (status = 422, description = "The table or order id is not positive", body = AxumErrorResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
 pub async fn delete_order(
     State(app_state): State<Arc<AppState>>,
-    Path((table_id, order_id)): Path<(i64, i64)>,
-) -> Result<(StatusCode, Json<ReturnTableResponse>), (StatusCode, Json<AxumErrorResponse>)> {
+    api_version: ApiVersion,
+    ValidPath(OrderPath { table_id, order_id }): ValidPath<OrderPath>,
+    if_match: IfMatch,
+) -> Result<
+    (StatusCode, ETag, Json<ReturnTableResponse>),
+    (StatusCode, ProblemJson<AxumErrorResponse>),
+> {
+    Span::current()
+        .record("table_id", table_id)
+        .record("order_id", order_id);
     let db = &app_state.db;
 
-    match db.delete_order(&table_id, &order_id).await {
-        Ok(table) => Ok((
-            StatusCode::OK,
-            Json(ReturnTableResponse {
-                table: table.into(),
-            }),
-        )),
+    //the schedule from before the void tells whether the kitchen started on the order
+    let schedule = kitchen_schedule(&app_state).await;
+    match db
+        .delete_order(&table_id, &order_id, if_match.expected_versions())
+        .await
+    {
+        Ok((table, deleted_order)) => {
+            //an order the kitchen hasn't started on gives back its portions and ingredients
+            give_back_unstarted_stock(&app_state, &schedule, deleted_order.as_slice()).await;
+            app_state.schedule_cache.invalidate().await;
+            let schedule = kitchen_schedule(&app_state).await;
+            Ok((
+                StatusCode::OK,
+                ETag(table.version),
+                Json(ReturnTableResponse {
+                    table: schedule
+                        .table(table, app_state.clock.now())
+                        .in_version(api_version),
+                }),
+            ))
+        }
+        Err(e) => Err(e.to_axum_error()),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/table/{table_id}/order/move",
+    tag = "order",
+    params(
+        ("table_id" = i64, Path, description = "Id of the table the orders are on"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Makes retries safe, a retry with the same key and body replays the first response"),
+        ("If-Match" = Option<String>, Header, description = "Only move the orders if the table is still at this ETag"),
+    ),
+    request_body = MoveOrdersRequest,
+    responses(
+        (status = 200, description = "Orders were moved, both tables as they are now", body = MoveOrdersResponse,
+            headers(("ETag" = String, description = "New version of the table the orders were moved from"))),
+        (status = 412, description = "The table changed since the ETag in If-Match", body = AxumErrorResponse),
+        (status = 422, description = "The request is invalid, or the orders would move onto their own table", body = AxumErrorResponse),
+        (status = 404, description = "Either table or some of the orders were not found", body = AxumErrorResponse),
+        (status = 409, description = "The Idempotency-Key was used for another request, or that request is still running", body = AxumErrorResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
+pub async fn move_orders(
+    State(app_state): State<Arc<AppState>>,
+    api_version: ApiVersion,
+    ValidPath(TablePath { table_id }): ValidPath<TablePath>,
+    if_match: IfMatch,
+    ValidJson(move_orders_request): ValidJson<MoveOrdersRequest>,
+) -> Result<
+    (StatusCode, ETag, Json<MoveOrdersResponse>),
+    (StatusCode, ProblemJson<AxumErrorResponse>),
+> {
+    Span::current().record("table_id", table_id);
+    let db = &app_state.db;
+
+    if move_orders_request.to_table_id == table_id {
+        return Err(ErrorResponse::from(
+            AxumErrorResponse::from(AxumErrors::ValidationFailed).with_errors(vec![
+                FieldError::new(
+                    "to_table_id",
+                    "same_table",
+                    "must be another table than the one the orders are on",
+                ),
+            ]),
+        )
+        .to_axum_error());
+    }
+
+    match db
+        .move_orders(
+            &table_id,
+            &move_orders_request.to_table_id,
+            move_orders_request.order_ids,
+            if_match.expected_versions(),
+        )
+        .await
+    {
+        Ok((from_table, to_table)) => {
+            app_state.schedule_cache.invalidate().await;
+            let schedule = kitchen_schedule(&app_state).await;
+            let now = app_state.clock.now();
+            Ok((
+                StatusCode::OK,
+                ETag(from_table.version),
+                Json(MoveOrdersResponse {
+                    from: schedule.table(from_table, now).in_version(api_version),
+                    to: schedule.table(to_table, now).in_version(api_version),
+                }),
+            ))
+        }
+        Err(e) => Err(e.to_axum_error()),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/table/{table_id}/fire",
+    tag = "order",
+    params(
+        ("table_id" = i64, Path, description = "Id of the table"),
+        FireCourseQuery,
+        ("If-Match" = Option<String>, Header, description = "Only fire the course if the table is still at this ETag"),
+    ),
+    responses(
+        (status = 200, description = "The course was fired, the table as it is now", body = ReturnTableResponse,
+            headers(("ETag" = String, description = "New version of the table"))),
+        (status = 412, description = "The table changed since the ETag in If-Match", body = AxumErrorResponse),
+        (status = 404, description = "Table was not found", body = AxumErrorResponse),
+        (status = 422, description = "The table id or the course is out of range", body = AxumErrorResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
+pub async fn fire_course(
+    State(app_state): State<Arc<AppState>>,
+    api_version: ApiVersion,
+    ValidPath(TablePath { table_id }): ValidPath<TablePath>,
+    ValidQuery(FireCourseQuery { course }): ValidQuery<FireCourseQuery>,
+    if_match: IfMatch,
+) -> Result<
+    (StatusCode, ETag, Json<ReturnTableResponse>),
+    (StatusCode, ProblemJson<AxumErrorResponse>),
+> {
+    Span::current().record("table_id", table_id);
+    let db = &app_state.db;
+
+    match db
+        .fire_course(
+            &table_id,
+            course,
+            app_state.clock.now(),
+            if_match.expected_versions(),
+        )
+        .await
+    {
+        Ok(table) => {
+            app_state.schedule_cache.invalidate().await;
+            let schedule = kitchen_schedule(&app_state).await;
+            Ok((
+                StatusCode::OK,
+                ETag(table.version),
+                Json(ReturnTableResponse {
+                    table: schedule
+                        .table(table, app_state.clock.now())
+                        .in_version(api_version),
+                }),
+            ))
+        }
         Err(e) => Err(e.to_axum_error()),
     }
 }
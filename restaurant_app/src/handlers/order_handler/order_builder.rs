@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+
+use tracing::{info, Span};
+
+use crate::{
+    common::{
+        counters_db::{Counter, DBCounterTrait},
+        errors::{AxumErrorResponse, AxumErrors, ErrorResponse, FieldError},
+        models::restaurant_schema::{
+            AllergyNote, CookStatus, Item, Modifier, Order, OrderRequest, TakenStock,
+        },
+    },
+    handlers::daypart_handler::{daypart::active_dayparts, daypart_db::DBDaypartTrait},
+    AppState,
+};
+
+use super::stock::{give_back_stock, take_stock, taken_stock, StockNeeds};
+
+/// What the orders are placed on, for a table that is being seated there is no id yet.
+pub struct OrderTarget<'a> {
+    /// `None` reserves the id of a new table once the orders are known to go through
+    pub table_id: Option<i64>,
+    /// courses after this one are held
+    pub fired_course: i64,
+    pub allergy_note: Option<&'a AllergyNote>,
+}
+
+/// What a create request asked for.
+pub struct OrderBatch {
+    pub orders: Vec<OrderRequest>,
+    /// skip the items that aren't on the menu instead of rejecting the request
+    pub allow_partial: bool,
+    /// a manager lets the items be ordered outside their dayparts
+    pub manager_override: bool,
+}
+
+/// The orders ready to be stored, their stock is already taken.
+pub struct BuiltOrders {
+    pub table_id: i64,
+    pub orders: Vec<Order>,
+    /// what was taken for the orders, to give back if storing them fails
+    pub stock_needs: StockNeeds,
+}
+
+/// Turns the requested orders into orders of the target table.
+/// If reserving the ids fails the stock is given back, once the orders are built storing them is up to the caller.
+pub async fn build_orders(
+    app_state: &AppState,
+    batch: OrderBatch,
+    target: OrderTarget<'_>,
+) -> Result<BuiltOrders, ErrorResponse> {
+    let db = &app_state.db;
+
+    //all the items at once, from the menu cache
+    let item_names = batch
+        .orders
+        .iter()
+        .map(|order| order.item_name().to_string())
+        .collect::<Vec<String>>();
+    let menu = app_state.menu_cache.get_items(db, &item_names).await?;
+    let active_dayparts = served_dayparts(app_state, &menu, batch.manager_override).await?;
+    let ResolvedOrders {
+        orders,
+        unknown_items,
+        unknown_modifiers,
+        unavailable_items,
+        too_large_orders,
+    } = resolve_orders(batch.orders, &menu, active_dayparts.as_deref());
+
+    if !unknown_items.is_empty() && !batch.allow_partial {
+        return Err(ErrorResponse::from(
+            AxumErrorResponse::from(AxumErrors::UnknownItems).with_errors(unknown_items),
+        ));
+    }
+    //a modifier is never dropped quietly, the kitchen would make something else than was asked for
+    if !unknown_modifiers.is_empty() {
+        return Err(ErrorResponse::from(
+            AxumErrorResponse::from(AxumErrors::UnknownModifiers).with_errors(unknown_modifiers),
+        ));
+    }
+    //neither is an item that ran out, the guest has to pick something else
+    if !unavailable_items.is_empty() {
+        return Err(ErrorResponse::from(
+            AxumErrorResponse::from(AxumErrors::ItemUnavailable).with_errors(unavailable_items),
+        ));
+    }
+
+    //amounts that can't be counted would wrap around and fill the stock up instead
+    if !too_large_orders.is_empty() {
+        return Err(ErrorResponse::from(
+            AxumErrorResponse::from(AxumErrors::ValidationFailed).with_errors(too_large_orders),
+        ));
+    }
+    let stock_needs = StockNeeds::of_resolved(&orders)?;
+
+    //the counted portions and the ingredients are taken before anything else, so two requests can't both get the last one
+    take_stock(app_state, &stock_needs).await?;
+
+    //the ids are only reserved once the request is known to go through, so rejected requests don't use any up
+    let ids = async {
+        let table_id = match target.table_id {
+            Some(table_id) => table_id,
+            None => db.next_ids(Counter::Table, 1).await?,
+        };
+        let first_order_id = match orders.is_empty() {
+            true => 0,
+            false => db.next_ids(Counter::Order, orders.len() as i64).await?,
+        };
+        Ok::<(i64, i64), ErrorResponse>((table_id, first_order_id))
+    };
+    let (table_id, first_order_id) = match ids.await {
+        Ok(ids) => ids,
+        Err(e) => {
+            give_back_stock(app_state, &stock_needs).await;
+            return Err(e);
+        }
+    };
+    Span::current().record("table_id", table_id);
+
+    let orders = orders
+        .into_iter()
+        .zip(first_order_id..)
+        .map(|(resolved, order_id)| {
+            let ordered_time = app_state.clock.now();
+            //courses the server hasn't fired yet wait, the rest goes straight to the kitchen
+            let held = resolved.course > target.fired_course;
+            //the order is still placed, it's up to the server to check with the guest
+            let allergy_conflicts = resolved.item.allergy_conflicts(target.allergy_note);
+            Order {
+                order_id,
+                table_id,
+                ordered_time,
+                cook_status: match held {
+                    true => CookStatus::Held,
+                    false => CookStatus::InProgress,
+                },
+                //the portions left are the menu's business, an order doesn't keep a copy of them
+                item: Item {
+                    stock: None,
+                    ..resolved.item
+                },
+                quantity: resolved.quantity,
+                modifiers: resolved.modifiers,
+                course: resolved.course,
+                held,
+                fired_time: match held {
+                    true => None,
+                    false => Some(ordered_time),
+                },
+                allergy_conflicts,
+                //kept on the order, voiding it gives back what was taken and not what the menu counts by then
+                taken_stock: resolved.taken_stock,
+            }
+        })
+        .collect();
+
+    Ok(BuiltOrders {
+        table_id,
+        orders,
+        stock_needs,
+    })
+}
+
+/// The dayparts on right now, to check the orders of the items in `menu` against.
+/// `None` when there is nothing to check, the manager overrode it or none of the items is limited to dayparts,
+/// in which case the dayparts aren't read at all.
+pub async fn served_dayparts(
+    app_state: &AppState,
+    menu: &HashMap<String, Item>,
+    manager_override: bool,
+) -> Result<Option<Vec<String>>, ErrorResponse> {
+    if menu.values().all(|item| item.dayparts.is_empty()) {
+        return Ok(None);
+    }
+    if manager_override {
+        info!("A manager let the items be ordered whatever dayparts are on");
+        return Ok(None);
+    }
+    let dayparts = app_state.db.list_dayparts().await?;
+    Ok(Some(active_dayparts(&dayparts, app_state.clock.now())))
+}
+
+/// The orders of a request in the order they were asked for, duplicates included.
+pub struct ResolvedOrders {
+    pub orders: Vec<ResolvedOrder>,
+    /// one entry per requested name that isn't on the menu, e.g. `orders[2]`
+    pub unknown_items: Vec<FieldError>,
+    /// one entry per modifier the item doesn't offer, e.g. `orders[2].modifiers[0]`
+    pub unknown_modifiers: Vec<FieldError>,
+    /// one entry per order of an item that is off the menu or not served in the dayparts that are on, e.g. `orders[2]`
+    pub unavailable_items: Vec<FieldError>,
+    /// one entry per order that takes more of an ingredient than can be counted, e.g. `orders[2].quantity`
+    pub too_large_orders: Vec<FieldError>,
+}
+
+pub struct ResolvedOrder {
+    pub item: Item,
+    pub quantity: i64,
+    pub modifiers: Vec<Modifier>,
+    pub course: i64,
+    /// what placing the order takes from the stock, see `taken_stock`
+    pub taken_stock: TakenStock,
+}
+
+/// Matches the requested orders against the items found by `get_items_by_name`, and their modifiers against the ones of the item.
+/// The stock isn't checked here, the copy in `menu` may be behind, `reserve_stock` decides whether enough portions are left.
+/// The items are only checked against the dayparts when there are `active_dayparts`, see `served_dayparts`.
+pub fn resolve_orders(
+    orders: Vec<OrderRequest>,
+    menu: &HashMap<String, Item>,
+    active_dayparts: Option<&[String]>,
+) -> ResolvedOrders {
+    let mut resolved = ResolvedOrders {
+        orders: vec![],
+        unknown_items: vec![],
+        unknown_modifiers: vec![],
+        unavailable_items: vec![],
+        too_large_orders: vec![],
+    };
+    for (index, order) in orders.into_iter().enumerate() {
+        let Some(item) = menu.get(order.item_name()) else {
+            resolved.unknown_items.push(FieldError::new(
+                format!("orders[{index}]"),
+                "unknown_item",
+                format!("{} is not on the menu", order.item_name()),
+            ));
+            continue;
+        };
+
+        if !item.available {
+            resolved.unavailable_items.push(FieldError::new(
+                format!("orders[{index}]"),
+                "item_unavailable",
+                format!("{} is not available right now", item.item_name),
+            ));
+        } else if active_dayparts.is_some_and(|active| !item.is_served(active)) {
+            resolved.unavailable_items.push(FieldError::new(
+                format!("orders[{index}]"),
+                "outside_daypart",
+                format!(
+                    "{} is only served at {}",
+                    item.item_name,
+                    item.dayparts.join(", ")
+                ),
+            ));
+        }
+
+        let mut modifiers = vec![];
+        for (modifier_index, modifier_name) in order.modifiers().iter().enumerate() {
+            match item
+                .modifiers
+                .iter()
+                .find(|modifier| &modifier.name == modifier_name)
+            {
+                Some(modifier) => modifiers.push(modifier.clone()),
+                None => resolved.unknown_modifiers.push(FieldError::new(
+                    format!("orders[{index}].modifiers[{modifier_index}]"),
+                    "unknown_modifier",
+                    format!("{} doesn't offer {modifier_name}", item.item_name),
+                )),
+            }
+        }
+        let Some(taken_stock) = taken_stock(item, order.quantity()) else {
+            resolved.too_large_orders.push(FieldError::new(
+                format!("orders[{index}].quantity"),
+                "too_large",
+                format!(
+                    "{} of {} take more of its ingredients than can be counted",
+                    order.quantity(),
+                    item.item_name
+                ),
+            ));
+            continue;
+        };
+        resolved.orders.push(ResolvedOrder {
+            item: item.clone(),
+            quantity: order.quantity(),
+            modifiers,
+            course: order.course(),
+            taken_stock,
+        });
+    }
+    resolved
+}
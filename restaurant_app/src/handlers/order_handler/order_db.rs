@@ -1,29 +1,25 @@
 use async_trait::async_trait;
 use axum::http::StatusCode;
 use chrono::{DateTime, Utc};
-use mongodb::bson::{doc, Document};
+use futures::FutureExt;
+use mongodb::bson::{doc, DateTime as BsonDateTime, Document};
 use mongodb::options::ReturnDocument;
-use serde::{Deserialize, Serialize};
-use tracing::error;
+use mongodb::Collection;
+use serde::Deserialize;
+use tracing::{error, Instrument};
 
 use crate::common::database;
-use crate::common::database_helpers::collect_cursor;
+use crate::common::database_helpers::{collect_cursor, with_expected_versions};
+use crate::common::telemetry::mongo_span;
+use crate::common::transactions::run_in_transaction;
 
-use crate::common::errors::{AxumErrors, ErrorResponse};
-use crate::common::models::restaurant_schema::{Item, Order, OrderResponse, Table};
+use crate::common::errors::{AxumErrorResponse, AxumErrors, ErrorResponse};
+use crate::common::models::restaurant_schema::{CookStatus, Order, OrderResponse, Table};
 
 use super::order::ListOrderFiltersRequest;
 
-#[derive(Serialize)]
-struct CreateOrderUpdateDoc {
-    #[serde(rename = "$push")]
-    push: Vec<Order>,
-}
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Dat {
-    ordered_time: DateTime<Utc>,
-}
-
+//the handler only answers with the orders, the rest is what the database reported
+#[allow(dead_code)]
 #[derive(Deserialize, Clone)]
 pub struct ListOrderResult {
     pub orders: Vec<OrderResponse>,
@@ -35,47 +31,48 @@ pub struct ListOrderResult {
 #[async_trait]
 pub trait DBOrderTrait {
     async fn get_table_order(&self, table_id: &i64) -> Result<Table, ErrorResponse>;
+    /// `expected_versions` comes from `If-Match`, the orders are only added while the table is at one of them.
     async fn create_orders(
         &self,
         table_id: &i64,
         orders: Vec<Document>,
+        expected_versions: Option<Vec<i64>>,
     ) -> Result<Table, ErrorResponse>;
-    async fn get_item(&self, item_name: String) -> Result<Option<Item>, ErrorResponse>;
-    async fn delete_order(&self, table_id: &i64, order_id: &i64) -> Result<Table, ErrorResponse>;
+    /// Returns the table without the order, and the order if it was on the table, to give back what it took from the stock.
+    async fn delete_order(
+        &self,
+        table_id: &i64,
+        order_id: &i64,
+        expected_versions: Option<Vec<i64>>,
+    ) -> Result<(Table, Option<Order>), ErrorResponse>;
     async fn get_order(&self, table_id: &i64, order_id: &i64) -> Result<Order, ErrorResponse>;
+    /// Moves the orders to another table in one transaction, returning the source and the destination table.
+    /// `expected_versions` comes from `If-Match` and applies to the source table.
+    async fn move_orders(
+        &self,
+        table_id: &i64,
+        to_table_id: &i64,
+        order_ids: Vec<i64>,
+        expected_versions: Option<Vec<i64>>,
+    ) -> Result<(Table, Table), ErrorResponse>;
     async fn list_all_orders(
         &self,
         filters: &ListOrderFiltersRequest,
     ) -> Result<ListOrderResult, ErrorResponse>;
+    /// Starts the held orders of `course` and of the courses before it, later orders for them aren't held anymore.
+    /// `expected_versions` comes from `If-Match`.
+    async fn fire_course(
+        &self,
+        table_id: &i64,
+        course: i64,
+        fired_time: DateTime<Utc>,
+        expected_versions: Option<Vec<i64>>,
+    ) -> Result<Table, ErrorResponse>;
 }
 
 #[faux::methods]
 #[async_trait]
 impl DBOrderTrait for database::DB {
-    async fn get_item(&self, item_name: String) -> Result<Option<Item>, ErrorResponse> {
-        let item_collection = self
-            .db
-            .database("item_management")
-            .collection::<Item>("items");
-
-        let filter = doc! {
-            "item_name":item_name
-        };
-
-        match item_collection.find_one(filter, None).await {
-            Ok(opt_item) => Ok(opt_item),
-            Err(e) => {
-                error!(
-                    "Unexpected error occured while searching for Item in the Database. Error: {e}"
-                );
-                return Err(ErrorResponse {
-                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
-                    error: AxumErrors::DBError.into(),
-                });
-            }
-        }
-    }
-
     async fn get_table_order(&self, table_id: &i64) -> Result<Table, ErrorResponse> {
         let table_collection = self
             .db
@@ -87,12 +84,16 @@ impl DBOrderTrait for database::DB {
             "table_id": table_id
         };
 
-        match table_collection.find_one(filter, None).await {
+        match table_collection
+            .find_one(filter, None)
+            .instrument(mongo_span("find_one", "tables"))
+            .await
+        {
             Ok(opt_table) => match opt_table {
                 Some(table) => Ok(table),
                 None => Err(ErrorResponse {
                     status_code: StatusCode::NOT_FOUND,
-                    error: AxumErrors::NotFound.into(),
+                    error: AxumErrors::TableNotFound.into(),
                 }),
             },
             Err(e) => {
@@ -109,21 +110,28 @@ impl DBOrderTrait for database::DB {
         &self,
         table_id: &i64,
         orders: Vec<Document>,
+        expected_versions: Option<Vec<i64>>,
     ) -> Result<Table, ErrorResponse> {
         let table_collection = self
             .db
             .database("table_management")
             .collection::<Table>("tables");
 
-        //check if the table exists
-        let filter = doc! {
-            "table_id": table_id
-        };
+        //check if the table exists, and is still at the version the caller expects
+        let filter = with_expected_versions(
+            doc! {
+                "table_id": table_id
+            },
+            &expected_versions,
+        );
 
         //upsert order
         let update = doc! {
             "$push":{
                 "orders":{"$each": orders}
+            },
+            "$inc":{
+                "version": 1
             }
         };
 
@@ -133,14 +141,12 @@ impl DBOrderTrait for database::DB {
 
         match table_collection
             .find_one_and_update(filter, update, options)
+            .instrument(mongo_span("find_one_and_update", "tables"))
             .await
         {
             Ok(opt_table) => match opt_table {
                 Some(table) => Ok(table),
-                None => Err(ErrorResponse {
-                    status_code: StatusCode::NOT_FOUND,
-                    error: AxumErrors::NotFound.into(),
-                }),
+                None => Err(missing_or_changed_table(&table_collection, table_id).await),
             },
             Err(e) => {
                 error!("Unexpected error occured while inserting Orders for Table in the Database. Error: {e}");
@@ -152,39 +158,57 @@ impl DBOrderTrait for database::DB {
         }
     }
 
-    async fn delete_order(&self, table_id: &i64, order_id: &i64) -> Result<Table, ErrorResponse> {
+    async fn delete_order(
+        &self,
+        table_id: &i64,
+        order_id: &i64,
+        expected_versions: Option<Vec<i64>>,
+    ) -> Result<(Table, Option<Order>), ErrorResponse> {
         let table_collection = self
             .db
             .database("table_management")
             .collection::<Table>("tables");
 
-        //check if the table exists
-        let filter = doc! {
-            "table_id": table_id
-        };
+        //check if the table exists, and is still at the version the caller expects
+        let filter = with_expected_versions(
+            doc! {
+                "table_id": table_id
+            },
+            &expected_versions,
+        );
         //upsert order
         let update = doc! {
             "$pull":{
                 "orders":{
                     "order_id":order_id
                 }
+            },
+            "$inc":{
+                "version": 1
             }
         };
 
+        //the table from before the update is the only place the pulled order is still on
         let options = mongodb::options::FindOneAndUpdateOptions::builder()
-            .return_document(ReturnDocument::After)
+            .return_document(ReturnDocument::Before)
             .build();
 
         match table_collection
             .find_one_and_update(filter, update, options)
+            .instrument(mongo_span("find_one_and_update", "tables"))
             .await
         {
             Ok(opt_table) => match opt_table {
-                Some(table) => Ok(table),
-                None => Err(ErrorResponse {
-                    status_code: StatusCode::NOT_FOUND,
-                    error: AxumErrors::NotFound.into(),
-                }),
+                Some(mut table) => {
+                    let deleted_order = table
+                        .orders
+                        .iter()
+                        .position(|order| order.order_id == *order_id)
+                        .map(|index| table.orders.remove(index));
+                    table.version += 1;
+                    Ok((table, deleted_order))
+                }
+                None => Err(missing_or_changed_table(&table_collection, table_id).await),
             },
             Err(e) => {
                 error!("Unexpected error occured while Deleting Order for Table in the Database. Error: {e}");
@@ -212,7 +236,11 @@ impl DBOrderTrait for database::DB {
             }
         };
 
-        match table_collection.find_one(filter, None).await {
+        match table_collection
+            .find_one(filter, None)
+            .instrument(mongo_span("find_one", "tables"))
+            .await
+        {
             Ok(opt_table) => match opt_table {
                 Some(table) => {
                     let order_vec: Vec<Order> = table
@@ -224,7 +252,7 @@ impl DBOrderTrait for database::DB {
                 }
                 None => Err(ErrorResponse {
                     status_code: StatusCode::NOT_FOUND,
-                    error: AxumErrors::NotFound.into(),
+                    error: AxumErrors::OrderNotFound.into(),
                 }),
             },
             Err(e) => {
@@ -237,7 +265,186 @@ impl DBOrderTrait for database::DB {
         }
     }
 
+    async fn move_orders(
+        &self,
+        table_id: &i64,
+        to_table_id: &i64,
+        order_ids: Vec<i64>,
+        expected_versions: Option<Vec<i64>>,
+    ) -> Result<(Table, Table), ErrorResponse> {
+        let table_collection = self
+            .db
+            .database("table_management")
+            .collection::<Table>("tables");
+        let (table_id, to_table_id) = (*table_id, *to_table_id);
+
+        let transactions = self.transactions;
+
+        //pull the orders from one table and push them onto the other, in a transaction a failure in between undoes both,
+        //without one the pulled orders are pushed back onto their table
+        run_in_transaction(&self.db, transactions, "move the Orders", |session| {
+            let table_collection = table_collection.clone();
+            let order_ids = order_ids.clone();
+            let expected_versions = expected_versions.clone();
+            async move {
+                let from_table = match table_collection
+                    .find_one_with_session(doc! { "table_id": table_id }, None, session)
+                    .instrument(mongo_span("find_one", "tables"))
+                    .await?
+                {
+                    Some(table) => table,
+                    None => {
+                        return Err(ErrorResponse {
+                            status_code: StatusCode::NOT_FOUND,
+                            error: AxumErrors::TableNotFound.into(),
+                        }
+                        .into())
+                    }
+                };
+
+                if let Some(versions) = &expected_versions {
+                    if !versions.contains(&from_table.version) {
+                        return Err(ErrorResponse::from(AxumErrors::PreconditionFailed).into());
+                    }
+                }
+
+                let missing_orders = order_ids
+                    .iter()
+                    .filter(|order_id| {
+                        !from_table
+                            .orders
+                            .iter()
+                            .any(|order| &order.order_id == *order_id)
+                    })
+                    .map(|order_id| order_id.to_string())
+                    .collect::<Vec<String>>();
+                if !missing_orders.is_empty() {
+                    return Err(ErrorResponse::from(
+                        AxumErrorResponse::from(AxumErrors::OrderNotFound).with_detail(format!(
+                            "Table {table_id} has no orders {}!",
+                            missing_orders.join(", ")
+                        )),
+                    )
+                    .into());
+                }
+
+                //the table the orders go to has to be there before any of them leave this one
+                if table_collection
+                    .find_one_with_session(doc! { "table_id": to_table_id }, None, session)
+                    .instrument(mongo_span("find_one", "tables"))
+                    .await?
+                    .is_none()
+                {
+                    return Err(to_table_not_found(to_table_id).into());
+                }
+
+                let mut moved_orders = vec![];
+                let mut pulled_orders = vec![];
+                for order in from_table
+                    .orders
+                    .iter()
+                    .filter(|order| order_ids.contains(&order.order_id))
+                {
+                    let moved_order = Order {
+                        table_id: to_table_id,
+                        ..order.clone()
+                    };
+                    match mongodb::bson::to_document(&moved_order)
+                        .and_then(|moved| Ok((moved, mongodb::bson::to_document(order)?)))
+                    {
+                        Ok((moved, pulled)) => {
+                            moved_orders.push(moved);
+                            pulled_orders.push(pulled);
+                        }
+                        Err(e) => {
+                            error!("unexpected error occured while converting orders into documents! Error: {e}");
+                            return Err(
+                                ErrorResponse::from(AxumErrors::BsonSerializeError).into()
+                            );
+                        }
+                    }
+                }
+
+                let options = mongodb::options::FindOneAndUpdateOptions::builder()
+                    .return_document(ReturnDocument::After)
+                    .build();
+
+                //pinned to the version that was read, so a concurrent change isn't lost without transactions
+                let filter = doc! {
+                    "table_id": table_id,
+                    "version": from_table.version
+                };
+                let update = doc! {
+                    "$pull":{
+                        "orders":{
+                            "order_id":{"$in": order_ids.clone()}
+                        }
+                    },
+                    "$inc":{
+                        "version": 1
+                    }
+                };
+                let from_table = match table_collection
+                    .find_one_and_update_with_session(filter, update, options.clone(), session)
+                    .instrument(mongo_span("find_one_and_update", "tables"))
+                    .await?
+                {
+                    Some(table) => table,
+                    None => return Err(ErrorResponse::from(AxumErrors::PreconditionFailed).into()),
+                };
+
+                let filter = doc! {
+                    "table_id": to_table_id
+                };
+                let update = doc! {
+                    "$push":{
+                        "orders":{"$each": moved_orders}
+                    },
+                    "$inc":{
+                        "version": 1
+                    }
+                };
+                let pushed = table_collection
+                    .find_one_and_update_with_session(filter, update, options, session)
+                    .instrument(mongo_span("find_one_and_update", "tables"))
+                    .await;
+                if let Ok(Some(to_table)) = pushed {
+                    return Ok((from_table, to_table));
+                }
+
+                //the table was deleted since it was looked up, or the push failed, the transaction would undo the pull
+                if !transactions {
+                    let filter = doc! {
+                        "table_id": table_id
+                    };
+                    let update = doc! {
+                        "$push":{
+                            "orders":{"$each": pulled_orders}
+                        },
+                        "$inc":{
+                            "version": 1
+                        }
+                    };
+                    if let Err(e) = table_collection
+                        .update_one_with_session(filter, update, None, session)
+                        .instrument(mongo_span("update_one", "tables"))
+                        .await
+                    {
+                        error!("Was unable to put the orders {order_ids:?} back onto Table {table_id} after they couldn't be moved, they are lost. Error: {e}");
+                    }
+                }
+                match pushed {
+                    Err(e) => Err(e.into()),
+                    _ => Err(to_table_not_found(to_table_id).into()),
+                }
+            }
+            .boxed()
+        })
+        .await
+    }
+
     //needs filters for the orders by completed orders, and
+    #[allow(clippy::redundant_field_names)]
     async fn list_all_orders(
         &self,
         filters: &ListOrderFiltersRequest,
@@ -265,7 +472,11 @@ impl DBOrderTrait for database::DB {
         let aggregate_filter = list_all_orders_aggregate_helpers(filters.item_names.clone()).await;
         filter.extend(aggregate_filter);
 
-        match table_collection.aggregate(filter, None).await {
+        match table_collection
+            .aggregate(filter, None)
+            .instrument(mongo_span("aggregate", "tables"))
+            .await
+        {
             Ok(cursor) => {
                 let (orders, failed_orders, dropped) =
                     match collect_cursor::<Order, OrderResponse>(cursor).await {
@@ -296,6 +507,99 @@ impl DBOrderTrait for database::DB {
             }
         }
     }
+
+    async fn fire_course(
+        &self,
+        table_id: &i64,
+        course: i64,
+        fired_time: DateTime<Utc>,
+        expected_versions: Option<Vec<i64>>,
+    ) -> Result<Table, ErrorResponse> {
+        let table_collection = self
+            .db
+            .database("table_management")
+            .collection::<Table>("tables");
+
+        //check if the table exists, and is still at the version the caller expects
+        let filter = with_expected_versions(
+            doc! {
+                "table_id": table_id
+            },
+            &expected_versions,
+        );
+
+        //one update, so the kitchen never sees half a course
+        let update = doc! {
+            "$set":{
+                "orders.$[held].held": false,
+                "orders.$[held].fired_time": BsonDateTime::from_chrono(fired_time),
+                "orders.$[held].cook_status": CookStatus::InProgress.to_string()
+            },
+            "$max":{
+                "fired_course": course
+            },
+            "$inc":{
+                "version": 1
+            }
+        };
+
+        let options = mongodb::options::FindOneAndUpdateOptions::builder()
+            .array_filters(vec![doc! {
+                "held.held": true,
+                "held.course": { "$lte": course }
+            }])
+            .return_document(ReturnDocument::After)
+            .build();
+
+        match table_collection
+            .find_one_and_update(filter, update, options)
+            .instrument(mongo_span("find_one_and_update", "tables"))
+            .await
+        {
+            Ok(opt_table) => match opt_table {
+                Some(table) => Ok(table),
+                None => Err(missing_or_changed_table(&table_collection, table_id).await),
+            },
+            Err(e) => {
+                error!("Unexpected error occured while firing a course for Table in the Database. Error: {e}");
+                Err(ErrorResponse {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    error: AxumErrors::DBError.into(),
+                })
+            }
+        }
+    }
+}
+
+/// Tells apart why a versioned update of a table matched nothing: either the table is gone or it was changed in between.
+async fn missing_or_changed_table(
+    table_collection: &Collection<Table>,
+    table_id: &i64,
+) -> ErrorResponse {
+    let filter = doc! {
+        "table_id": table_id
+    };
+
+    match table_collection
+        .find_one(filter, None)
+        .instrument(mongo_span("find_one", "tables"))
+        .await
+    {
+        Ok(Some(_)) => ErrorResponse::from(AxumErrors::PreconditionFailed),
+        Ok(None) => ErrorResponse {
+            status_code: StatusCode::NOT_FOUND,
+            error: AxumErrors::TableNotFound.into(),
+        },
+        Err(e) => {
+            error!(
+                "Unexpected error occured while searching for Table in the Database. Error: {e}"
+            );
+            ErrorResponse {
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                error: AxumErrors::DBError.into(),
+            }
+        }
+    }
 }
 
 pub async fn list_all_orders_aggregate_helpers(item_names: Vec<String>) -> Vec<Document> {
@@ -341,3 +645,11 @@ pub async fn list_all_orders_aggregate_helpers(item_names: Vec<String>) -> Vec<D
     aggregate_doc.extend(projections);
     aggregate_doc
 }
+
+fn to_table_not_found(to_table_id: i64) -> ErrorResponse {
+    ErrorResponse::from(
+        AxumErrorResponse::from(AxumErrors::TableNotFound).with_detail(format!(
+            "Table {to_table_id} to move the orders to was not found!"
+        )),
+    )
+}
@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+
+use tracing::{error, warn};
+
+use crate::{
+    common::{
+        errors::{AxumErrorResponse, AxumErrors, ErrorResponse, FieldError},
+        models::restaurant_schema::{CookStatus, Item, Order, RecipeIngredient, TakenStock},
+    },
+    handlers::{
+        inventory_handler::inventory_db::DBInventoryTrait, item_handler::item_db::DBTableTrait,
+        kitchen_handler::scheduler::KitchenSchedule,
+    },
+    AppState,
+};
+
+use super::order_builder::ResolvedOrder;
+
+/// What placing some orders takes from the stock.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct StockNeeds {
+    /// portions by item, only of the items whose portions are counted
+    pub portions: HashMap<String, i64>,
+    /// amounts by ingredient, in the unit of the ingredient
+    pub ingredients: HashMap<String, i64>,
+}
+
+impl StockNeeds {
+    /// `None` when a total would grow too large to count.
+    fn add_taken(&mut self, item_name: &str, taken: &TakenStock) -> Option<()> {
+        if taken.portions > 0 {
+            let portions = self.portions.entry(item_name.to_string()).or_insert(0);
+            *portions = portions.checked_add(taken.portions)?;
+        }
+        for ingredient in &taken.ingredients {
+            let amount = self
+                .ingredients
+                .entry(ingredient.ingredient_name.clone())
+                .or_insert(0);
+            *amount = amount.checked_add(ingredient.quantity)?;
+        }
+        Some(())
+    }
+
+    /// What the orders took when they were placed, as kept on each of them, `None` if it adds up to more than can be counted.
+    pub fn of_orders<'a>(orders: impl IntoIterator<Item = &'a Order>) -> Option<Self> {
+        let mut needs = Self::default();
+        for order in orders {
+            needs.add_taken(&order.item.item_name, &order.taken_stock)?;
+        }
+        Some(needs)
+    }
+
+    /// What the resolved orders take together, portions only of the items whose stock is counted.
+    /// Orders that each fit but add up to more than can be counted are rejected like a single one that doesn't.
+    pub fn of_resolved(orders: &[ResolvedOrder]) -> Result<Self, ErrorResponse> {
+        let mut needs = Self::default();
+        for order in orders {
+            if needs
+                .add_taken(&order.item.item_name, &order.taken_stock)
+                .is_none()
+            {
+                return Err(ErrorResponse::from(
+                    AxumErrorResponse::from(AxumErrors::ValidationFailed).with_errors(vec![
+                        FieldError::new(
+                            "orders",
+                            "too_large",
+                            "the orders together take more of an ingredient than can be counted",
+                        ),
+                    ]),
+                ));
+            }
+        }
+        Ok(needs)
+    }
+}
+
+/// Gives back what the voided orders took, only for the ones the kitchen hasn't started on.
+/// `schedule` is from before they were voided, held and queued orders are given back, the rest are already cooked.
+pub async fn give_back_unstarted_stock(
+    app_state: &AppState,
+    schedule: &KitchenSchedule,
+    orders: &[Order],
+) {
+    let now = app_state.clock.now();
+    let unstarted = orders.iter().filter(|order| {
+        matches!(
+            schedule.order((*order).clone(), now).cook_status,
+            CookStatus::Held | CookStatus::Queued
+        )
+    });
+    match StockNeeds::of_orders(unstarted) {
+        Some(needs) => give_back_stock(app_state, &needs).await,
+        None => error!("What the voided orders took adds up to more than can be counted, nothing was given back"),
+    }
+}
+
+/// Takes what the orders need from the stock, the portions first and then the ingredients.
+/// If the ingredients run short the portions are given back, so either all of it is taken or nothing.
+pub async fn take_stock(app_state: &AppState, needs: &StockNeeds) -> Result<(), ErrorResponse> {
+    let db = &app_state.db;
+
+    if !needs.portions.is_empty() {
+        db.reserve_stock(&needs.portions).await?;
+        app_state.menu_cache.take_portions(&needs.portions).await;
+    }
+    if !needs.ingredients.is_empty() {
+        match db.reserve_ingredients(&needs.ingredients).await {
+            Ok(ingredients) => {
+                for ingredient in ingredients.iter().filter(|ingredient| ingredient.is_low()) {
+                    warn!(
+                        "{} is low on stock, {} {} left",
+                        ingredient.ingredient_name, ingredient.stock, ingredient.unit
+                    );
+                }
+            }
+            Err(e) => {
+                give_back_stock(
+                    app_state,
+                    &StockNeeds {
+                        portions: needs.portions.clone(),
+                        ingredients: HashMap::new(),
+                    },
+                )
+                .await;
+                return Err(e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Gives back what `take_stock` took, for orders that couldn't be placed after all or were voided.
+/// Failing to do so only leaves the stock short, so it is logged instead of failing the request a second time.
+pub async fn give_back_stock(app_state: &AppState, needs: &StockNeeds) {
+    if !needs.portions.is_empty() {
+        match app_state.db.release_stock(&needs.portions).await {
+            Ok(()) => {
+                app_state
+                    .menu_cache
+                    .give_back_portions(&needs.portions)
+                    .await
+            }
+            Err(e) => {
+                error!(
+                    "Was unable to give back the portions of orders, the stock is short by up to {:?}. Error: {}",
+                    needs.portions, e.error.detail
+                );
+                //the copy can't tell which ones made it back
+                app_state.menu_cache.invalidate().await;
+            }
+        }
+    }
+    if !needs.ingredients.is_empty() {
+        if let Err(e) = app_state.db.release_ingredients(&needs.ingredients).await {
+            error!(
+                "Was unable to give back the ingredients of orders, the inventory is short by {:?}. Error: {}",
+                needs.ingredients, e.error.detail
+            );
+        }
+    }
+}
+
+/// What placing `quantity` of the item takes from the stock, the portions only when the item's are counted.
+/// `None` when an ingredient's amount is too large to count.
+pub fn taken_stock(item: &Item, quantity: i64) -> Option<TakenStock> {
+    let ingredients = item
+        .recipe
+        .iter()
+        .map(|ingredient| {
+            Some(RecipeIngredient {
+                ingredient_name: ingredient.ingredient_name.clone(),
+                quantity: ingredient.quantity.checked_mul(quantity)?,
+            })
+        })
+        .collect::<Option<Vec<RecipeIngredient>>>()?;
+    Some(TakenStock {
+        portions: match item.stock {
+            Some(_) => quantity,
+            None => 0,
+        },
+        ingredients,
+    })
+}
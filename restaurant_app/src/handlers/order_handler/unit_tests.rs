@@ -1,34 +1,41 @@
 #[cfg(test)]
 pub mod order_unit_tests {
 
-    use std::sync::Arc;
+    use std::{collections::HashMap, sync::Arc, time::Duration};
 
-    use axum::{
-        extract::{Path, Query, State},
-        http::StatusCode,
-        Json,
-    };
-    use axum_extra::extract::Query as ExtraQuery;
-    use chrono::Utc;
+    use axum::{extract::State, http::StatusCode};
+    use chrono::{DateTime, Utc};
     use mongodb::bson::oid::ObjectId;
-    use tokio::sync::Mutex;
 
     use crate::{
         common::{
+            clock::{Clock, FakeClock, SystemClock},
             database::DB,
-            errors::{AxumErrors, ErrorResponse},
+            errors::{AxumErrorResponse, AxumErrors, ErrorResponse, FieldError},
+            etag::{EntityTag, EntityTags, IfMatch},
+            extractors::{ApiVersion, ValidExtraQuery, ValidJson, ValidPath, ValidQuery},
+            menu_cache::MenuCache,
             models::{
                 pagination_schema::Pagination,
-                restaurant_schema::{CookStatus, Item, ItemResponse, Order, OrderResponse, Table},
+                path_schema::{OrderPath, TablePath},
+                restaurant_schema::{
+                    Allergen, AllergyNote, Category, CookStatus, Daypart, DetailedOrderRequest,
+                    Ingredient, Item, ItemResponse, Modifier, ModifierKind, Order, OrderRequest,
+                    OrderResponse, RecipeIngredient, Table, TakenStock, Unit,
+                },
             },
         },
+        handlers::kitchen_handler::scheduler::ScheduleCache,
         handlers::order_handler::{
             order::{
-                create_order, delete_order, get_order, list_all_orders, CreateOrdersRequest,
-                ListOrderFiltersRequest,
+                create_order, delete_order, fire_course, get_order, list_all_orders, move_orders,
+                CreateOrdersRequest, FireCourseQuery, ListOrderFiltersRequest, MoveOrdersRequest,
             },
+            order_builder::resolve_orders,
             order_db::ListOrderResult,
+            stock::StockNeeds,
         },
+        tests::fixtures::{app_state, app_state_at, item, recipe_item},
         AppState,
     };
 
@@ -41,16 +48,18 @@ pub mod order_unit_tests {
     #[tokio::test]
     pub async fn successful_create_order() {
         let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        faux::when!(mock_db.next_ids).then(|_| Ok(1));
         let mut table = Table {
             id: ObjectId::new().to_hex(),
             table_id: 1,
             orders: [].to_vec(),
+            version: 1,
+            fired_course: 1,
+            allergy_note: None,
         };
 
-        let item = Item {
-            item_name: "Burger".to_string(),
-            cook_time: 5,
-        };
+        let item = item("Burger", 5);
 
         let order = Order {
             order_id: 1,
@@ -58,6 +67,13 @@ pub mod order_unit_tests {
             ordered_time: Utc::now(),
             cook_status: CookStatus::InProgress,
             item: item.clone(),
+            quantity: 1,
+            modifiers: vec![],
+            course: 1,
+            held: false,
+            fired_time: None,
+            allergy_conflicts: vec![],
+            taken_stock: TakenStock::default(),
         };
 
         let table_get = table.clone();
@@ -68,21 +84,260 @@ pub mod order_unit_tests {
 
         faux::when!(mock_db.get_table_order).then(move |_| Ok(table_get.to_owned()));
 
-        faux::when!(mock_db.get_item).then(move |_| Ok(Some(item.to_owned())));
+        faux::when!(mock_db.get_items_by_name).then(move |item_names| {
+            Ok(item_names
+                .iter()
+                .map(|item_name| (item_name.clone(), item.to_owned()))
+                .collect())
+        });
 
         faux::when!(mock_db.create_orders).then(move |_| Ok(table_create.to_owned()));
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(1),
-            orders: Mutex::new(1),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+        let state = State(app_state);
+        let path = ValidPath(TablePath { table_id: 1 });
+        let json_body = ValidJson(CreateOrdersRequest {
+            orders: ["Burger".into()].to_vec(),
+            allow_partial: false,
+            manager_override: false,
+        });
+        match create_order(state, ApiVersion::V2, path, IfMatch(None), json_body).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::CREATED);
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_create_order_duplicates_one_lookup() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        faux::when!(mock_db.next_ids).then(|(_, count)| {
+            assert_eq!(count, 3);
+            Ok(10)
+        });
+        let table = Table {
+            id: ObjectId::new().to_hex(),
+            table_id: 1,
+            orders: [].to_vec(),
+            version: 1,
+            fired_course: 1,
+            allergy_note: None,
+        };
+        let table_create = table.clone();
+
+        faux::when!(mock_db.get_table_order).then(move |_| Ok(table.to_owned()));
+        faux::when!(mock_db.get_items_by_name)
+            .once()
+            .then(|item_names| {
+                assert_eq!(item_names.len(), 4);
+                Ok(["Burger", "Ramen"]
+                    .into_iter()
+                    .map(|item_name| (item_name.to_string(), item(item_name, 5)))
+                    .collect())
+            });
+        faux::when!(mock_db.create_orders).then(move |(_, orders, _)| {
+            let ordered = orders
+                .iter()
+                .map(|order| {
+                    (
+                        order.get_i64("order_id").unwrap(),
+                        order
+                            .get_document("item")
+                            .unwrap()
+                            .get_str("item_name")
+                            .unwrap()
+                            .to_string(),
+                    )
+                })
+                .collect::<Vec<(i64, String)>>();
+            assert_eq!(
+                ordered,
+                vec![
+                    (10, "Burger".to_string()),
+                    (11, "Burger".to_string()),
+                    (12, "Ramen".to_string())
+                ]
+            );
+            Ok(table_create.to_owned())
+        });
+
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+        let state = State(app_state);
+        let path = ValidPath(TablePath { table_id: 1 });
+        let json_body = ValidJson(CreateOrdersRequest {
+            orders: ["Burger", "Burger", "Ramen", "Pizza"]
+                .map(OrderRequest::from)
+                .to_vec(),
+            allow_partial: true,
+            manager_override: false,
+        });
+        match create_order(state, ApiVersion::V2, path, IfMatch(None), json_body).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::CREATED);
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    fn ramen_with_modifiers() -> Item {
+        Item {
+            modifiers: vec![
+                Modifier {
+                    name: "No Scallions".to_string(),
+                    kind: ModifierKind::Remove,
+                    price_delta: None,
+                },
+                Modifier {
+                    name: "Extra Egg".to_string(),
+                    kind: ModifierKind::Add,
+                    price_delta: Some(150),
+                },
+            ],
+            ..item("Ramen", 30)
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_create_order_quantity_and_modifiers() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        faux::when!(mock_db.next_ids).then(|(_, count)| {
+            assert_eq!(count, 1);
+            Ok(1)
+        });
+        let table = Table {
+            id: ObjectId::new().to_hex(),
+            table_id: 1,
+            orders: [].to_vec(),
+            version: 1,
+            fired_course: 1,
+            allergy_note: None,
+        };
+        let table_create = table.clone();
+
+        faux::when!(mock_db.get_table_order).then(move |_| Ok(table.to_owned()));
+        faux::when!(mock_db.get_items_by_name).then(|_| {
+            Ok(HashMap::from([(
+                "Ramen".to_string(),
+                ramen_with_modifiers(),
+            )]))
+        });
+        faux::when!(mock_db.create_orders).then(move |(_, orders, _)| {
+            assert_eq!(orders.len(), 1);
+            assert_eq!(orders[0].get_i64("quantity").unwrap(), 3);
+            let modifiers = orders[0].get_array("modifiers").unwrap();
+            assert_eq!(modifiers.len(), 1);
+            assert_eq!(
+                modifiers[0].as_document().unwrap().get_str("name").unwrap(),
+                "No Scallions"
+            );
+            Ok(table_create.to_owned())
+        });
+
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+        let state = State(app_state);
+        let path = ValidPath(TablePath { table_id: 1 });
+        let json_body = ValidJson(CreateOrdersRequest {
+            orders: vec![OrderRequest::Detailed(DetailedOrderRequest {
+                item_name: "Ramen".to_string(),
+                quantity: 3,
+                modifiers: vec!["No Scallions".to_string()],
+                course: 1,
+            })],
+            allow_partial: false,
+            manager_override: false,
+        });
+        match create_order(state, ApiVersion::V2, path, IfMatch(None), json_body).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::CREATED);
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_create_order_later_course_held() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        faux::when!(mock_db.next_ids).then(|_| Ok(1));
+        let table = Table {
+            id: ObjectId::new().to_hex(),
+            table_id: 1,
+            orders: [].to_vec(),
+            version: 1,
+            fired_course: 1,
+            allergy_note: None,
+        };
+        let table_create = table.clone();
+
+        faux::when!(mock_db.get_table_order).then(move |_| Ok(table.to_owned()));
+        faux::when!(mock_db.get_items_by_name).then(|_| {
+            Ok(HashMap::from([(
+                "Ramen".to_string(),
+                ramen_with_modifiers(),
+            )]))
+        });
+        faux::when!(mock_db.create_orders).then(move |(_, orders, _)| {
+            let held = orders
+                .iter()
+                .map(|order| {
+                    (
+                        order.get_i64("course").unwrap(),
+                        order.get_bool("held").unwrap(),
+                        order.get_str("cook_status").unwrap().to_string(),
+                        order.get_datetime("fired_time").is_ok(),
+                    )
+                })
+                .collect::<Vec<(i64, bool, String, bool)>>();
+            assert_eq!(
+                held,
+                vec![
+                    (1, false, "InProgress".to_string(), true),
+                    (2, true, "Held".to_string(), false)
+                ]
+            );
+            Ok(table_create.to_owned())
+        });
+
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
         let state = State(app_state);
-        let path = Path(1);
-        let json_body = Json(CreateOrdersRequest {
-            orders: ["Burger".to_string()].to_vec(),
+        let path = ValidPath(TablePath { table_id: 1 });
+        let json_body = ValidJson(CreateOrdersRequest {
+            orders: vec![
+                "Ramen".into(),
+                OrderRequest::Detailed(DetailedOrderRequest {
+                    item_name: "Ramen".to_string(),
+                    quantity: 1,
+                    modifiers: vec![],
+                    course: 2,
+                }),
+            ],
+            allow_partial: false,
+            manager_override: false,
         });
-        match create_order(state, path, json_body).await {
+        match create_order(state, ApiVersion::V2, path, IfMatch(None), json_body).await {
             Ok(resp) => {
                 assert_eq!(resp.0, StatusCode::CREATED);
             }
@@ -90,6 +345,98 @@ pub mod order_unit_tests {
         }
     }
 
+    #[tokio::test]
+    pub async fn failed_create_order_unknown_modifier() {
+        // next_ids and create_orders aren't mocked, nothing may be ordered
+        let mut mock_db = DB::faux();
+        let table = Table {
+            id: ObjectId::new().to_hex(),
+            table_id: 1,
+            orders: [].to_vec(),
+            version: 1,
+            fired_course: 1,
+            allergy_note: None,
+        };
+
+        faux::when!(mock_db.get_table_order).then(move |_| Ok(table.to_owned()));
+        faux::when!(mock_db.get_items_by_name).then(|_| {
+            Ok(HashMap::from([(
+                "Ramen".to_string(),
+                ramen_with_modifiers(),
+            )]))
+        });
+
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+        let state = State(app_state);
+        let path = ValidPath(TablePath { table_id: 1 });
+        let json_body = ValidJson(CreateOrdersRequest {
+            orders: vec![OrderRequest::Detailed(DetailedOrderRequest {
+                item_name: "Ramen".to_string(),
+                quantity: 1,
+                modifiers: vec!["Extra Egg".to_string(), "No Pork".to_string()],
+                course: 1,
+            })],
+            // only unknown items may be skipped, never modifiers
+            allow_partial: true,
+            manager_override: false,
+        });
+        match create_order(state, ApiVersion::V2, path, IfMatch(None), json_body).await {
+            Ok(_) => panic!("shouldn't succeed!"),
+            Err(e) => {
+                assert_eq!(e.0, StatusCode::UNPROCESSABLE_ENTITY);
+                assert_eq!(e.1.error_type, AxumErrors::UnknownModifiers.to_string());
+                assert_eq!(e.1.errors.len(), 1);
+                assert_eq!(e.1.errors[0].field, "orders[0].modifiers[1]");
+            }
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_create_order_precondition_failed() {
+        // get_items_by_name and create_orders aren't mocked, the stale version has to be caught before them
+        let mut mock_db = DB::faux();
+        let table = Table {
+            id: ObjectId::new().to_hex(),
+            table_id: 1,
+            orders: [].to_vec(),
+            version: 2,
+            fired_course: 1,
+            allergy_note: None,
+        };
+
+        faux::when!(mock_db.get_table_order).then(move |_| Ok(table.to_owned()));
+
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+        let state = State(app_state);
+        let path = ValidPath(TablePath { table_id: 1 });
+        let if_match = IfMatch(Some(EntityTags::Tags(vec![EntityTag {
+            weak: false,
+            tag: "1".to_string(),
+        }])));
+        let json_body = ValidJson(CreateOrdersRequest {
+            orders: ["Burger".into()].to_vec(),
+            allow_partial: false,
+            manager_override: false,
+        });
+        match create_order(state, ApiVersion::V2, path, if_match, json_body).await {
+            Ok(_) => panic!("shouldn't succeed!"),
+            Err(e) => {
+                assert_eq!(e.0, StatusCode::PRECONDITION_FAILED);
+                assert_eq!(e.1.error_type, AxumErrors::PreconditionFailed.to_string());
+            }
+        }
+    }
+
     #[tokio::test]
     pub async fn failed_create_order_get_table_not_found_error() {
         let mut mock_db = DB::faux();
@@ -102,16 +449,19 @@ pub mod order_unit_tests {
         });
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(1),
-            orders: Mutex::new(1),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
         let state = State(app_state);
-        let path = Path(1);
-        let json_body = Json(CreateOrdersRequest {
-            orders: ["Burger".to_string()].to_vec(),
+        let path = ValidPath(TablePath { table_id: 1 });
+        let json_body = ValidJson(CreateOrdersRequest {
+            orders: ["Burger".into()].to_vec(),
+            allow_partial: false,
+            manager_override: false,
         });
-        match create_order(state, path, json_body).await {
+        match create_order(state, ApiVersion::V2, path, IfMatch(None), json_body).await {
             Ok(_) => panic!("shouldn't succeed!"),
             Err(e) => {
                 assert_eq!(e.1.error_type, AxumErrors::NotFound.to_string());
@@ -131,16 +481,19 @@ pub mod order_unit_tests {
         });
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(1),
-            orders: Mutex::new(1),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
         let state = State(app_state);
-        let path = Path(1);
-        let json_body = Json(CreateOrdersRequest {
-            orders: ["Burger".to_string()].to_vec(),
+        let path = ValidPath(TablePath { table_id: 1 });
+        let json_body = ValidJson(CreateOrdersRequest {
+            orders: ["Burger".into()].to_vec(),
+            allow_partial: false,
+            manager_override: false,
         });
-        match create_order(state, path, json_body).await {
+        match create_order(state, ApiVersion::V2, path, IfMatch(None), json_body).await {
             Ok(_) => panic!("shouldn't succeed!"),
             Err(e) => {
                 assert_eq!(e.1.error_type, AxumErrors::DBError.to_string());
@@ -155,12 +508,12 @@ pub mod order_unit_tests {
             id: ObjectId::new().to_hex(),
             table_id: 1,
             orders: [].to_vec(),
+            version: 1,
+            fired_course: 1,
+            allergy_note: None,
         };
 
-        let item = Item {
-            item_name: "Burger".to_string(),
-            cook_time: 5,
-        };
+        let item = item("Burger", 5);
 
         let order = Order {
             order_id: 1,
@@ -168,6 +521,13 @@ pub mod order_unit_tests {
             ordered_time: Utc::now(),
             cook_status: CookStatus::InProgress,
             item: item.clone(),
+            quantity: 1,
+            modifiers: vec![],
+            course: 1,
+            held: false,
+            fired_time: None,
+            allergy_conflicts: vec![],
+            taken_stock: TakenStock::default(),
         };
 
         let table_get = table.clone();
@@ -176,7 +536,7 @@ pub mod order_unit_tests {
 
         faux::when!(mock_db.get_table_order).then(move |_| Ok(table_get.to_owned()));
 
-        faux::when!(mock_db.get_item).then(move |_| {
+        faux::when!(mock_db.get_items_by_name).then(move |_| {
             Err(ErrorResponse {
                 status_code: StatusCode::INTERNAL_SERVER_ERROR,
                 error: AxumErrors::DBError.into(),
@@ -184,16 +544,19 @@ pub mod order_unit_tests {
         });
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(1),
-            orders: Mutex::new(1),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
         let state = State(app_state);
-        let path = Path(1);
-        let json_body = Json(CreateOrdersRequest {
-            orders: ["Burger".to_string()].to_vec(),
+        let path = ValidPath(TablePath { table_id: 1 });
+        let json_body = ValidJson(CreateOrdersRequest {
+            orders: ["Burger".into()].to_vec(),
+            allow_partial: false,
+            manager_override: false,
         });
-        match create_order(state, path, json_body).await {
+        match create_order(state, ApiVersion::V2, path, IfMatch(None), json_body).await {
             Ok(_) => panic!("shouldn't succeed!"),
             Err(e) => {
                 assert_eq!(e.1.error_type, AxumErrors::DBError.to_string());
@@ -202,22 +565,108 @@ pub mod order_unit_tests {
     }
 
     #[tokio::test]
-    pub async fn failed_create_order_create_orders_db_error() {
+    pub async fn failed_create_order_unknown_item() {
         let mut mock_db = DB::faux();
         let table = Table {
             id: ObjectId::new().to_hex(),
             table_id: 1,
             orders: [].to_vec(),
+            version: 1,
+            fired_course: 1,
+            allergy_note: None,
         };
 
-        let item = Item {
-            item_name: "Burger".to_string(),
-            cook_time: 5,
+        faux::when!(mock_db.get_table_order).then(move |_| Ok(table.to_owned()));
+
+        faux::when!(mock_db.get_items_by_name).then(|_| Ok(HashMap::new()));
+
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+        let state = State(app_state);
+        let path = ValidPath(TablePath { table_id: 1 });
+        let json_body = ValidJson(CreateOrdersRequest {
+            orders: ["Pizza".into()].to_vec(),
+            allow_partial: false,
+            manager_override: false,
+        });
+        match create_order(state, ApiVersion::V2, path, IfMatch(None), json_body).await {
+            Ok(_) => panic!("shouldn't succeed!"),
+            Err(e) => {
+                assert_eq!(e.0, StatusCode::UNPROCESSABLE_ENTITY);
+                assert_eq!(e.1.error_type, AxumErrors::UnknownItems.to_string());
+                assert_eq!(e.1.errors[0].field, "orders[0]");
+            }
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_create_order_partial_unknown_item() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        let table = Table {
+            id: ObjectId::new().to_hex(),
+            table_id: 1,
+            orders: [].to_vec(),
+            version: 1,
+            fired_course: 1,
+            allergy_note: None,
+        };
+        let table_create = table.clone();
+
+        faux::when!(mock_db.get_table_order).then(move |_| Ok(table.to_owned()));
+
+        faux::when!(mock_db.get_items_by_name).then(|_| Ok(HashMap::new()));
+
+        faux::when!(mock_db.create_orders).then(move |_| Ok(table_create.to_owned()));
+
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+        let state = State(app_state);
+        let path = ValidPath(TablePath { table_id: 1 });
+        let json_body = ValidJson(CreateOrdersRequest {
+            orders: ["Pizza".into()].to_vec(),
+            allow_partial: true,
+            manager_override: false,
+        });
+        match create_order(state, ApiVersion::V2, path, IfMatch(None), json_body).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::CREATED);
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_create_order_create_orders_db_error() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.next_ids).then(|_| Ok(1));
+        let table = Table {
+            id: ObjectId::new().to_hex(),
+            table_id: 1,
+            orders: [].to_vec(),
+            version: 1,
+            fired_course: 1,
+            allergy_note: None,
         };
 
+        let item = item("Burger", 5);
+
         faux::when!(mock_db.get_table_order).then(move |_| Ok(table.to_owned()));
 
-        faux::when!(mock_db.get_item).then(move |_| Ok(Some(item.to_owned())));
+        faux::when!(mock_db.get_items_by_name).then(move |item_names| {
+            Ok(item_names
+                .iter()
+                .map(|item_name| (item_name.clone(), item.to_owned()))
+                .collect())
+        });
 
         faux::when!(mock_db.create_orders).then(move |_| {
             Err(ErrorResponse {
@@ -227,16 +676,19 @@ pub mod order_unit_tests {
         });
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(1),
-            orders: Mutex::new(1),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
         let state = State(app_state);
-        let path = Path(1);
-        let json_body = Json(CreateOrdersRequest {
-            orders: ["Burger".to_string()].to_vec(),
+        let path = ValidPath(TablePath { table_id: 1 });
+        let json_body = ValidJson(CreateOrdersRequest {
+            orders: ["Burger".into()].to_vec(),
+            allow_partial: false,
+            manager_override: false,
         });
-        match create_order(state, path, json_body).await {
+        match create_order(state, ApiVersion::V2, path, IfMatch(None), json_body).await {
             Ok(_) => panic!("shouldn't succeed!"),
             Err(e) => {
                 assert_eq!(e.1.error_type, AxumErrors::DBError.to_string());
@@ -247,20 +699,26 @@ pub mod order_unit_tests {
     #[tokio::test]
     pub async fn failed_create_order_create_orders_not_found_error() {
         let mut mock_db = DB::faux();
+        faux::when!(mock_db.next_ids).then(|_| Ok(1));
         let table = Table {
             id: ObjectId::new().to_hex(),
             table_id: 1,
             orders: [].to_vec(),
+            version: 1,
+            fired_course: 1,
+            allergy_note: None,
         };
 
-        let item = Item {
-            item_name: "Burger".to_string(),
-            cook_time: 5,
-        };
+        let item = item("Burger", 5);
 
         faux::when!(mock_db.get_table_order).then(move |_| Ok(table.to_owned()));
 
-        faux::when!(mock_db.get_item).then(move |_| Ok(Some(item.to_owned())));
+        faux::when!(mock_db.get_items_by_name).then(move |item_names| {
+            Ok(item_names
+                .iter()
+                .map(|item_name| (item_name.clone(), item.to_owned()))
+                .collect())
+        });
 
         faux::when!(mock_db.create_orders).then(move |_| {
             Err(ErrorResponse {
@@ -270,16 +728,19 @@ pub mod order_unit_tests {
         });
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(1),
-            orders: Mutex::new(1),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
         let state = State(app_state);
-        let path = Path(1);
-        let json_body = Json(CreateOrdersRequest {
-            orders: ["Burger".to_string()].to_vec(),
+        let path = ValidPath(TablePath { table_id: 1 });
+        let json_body = ValidJson(CreateOrdersRequest {
+            orders: ["Burger".into()].to_vec(),
+            allow_partial: false,
+            manager_override: false,
         });
-        match create_order(state, path, json_body).await {
+        match create_order(state, ApiVersion::V2, path, IfMatch(None), json_body).await {
             Ok(_) => panic!("shouldn't succeed!"),
             Err(e) => {
                 assert_eq!(e.1.error_type, AxumErrors::NotFound.to_string());
@@ -296,6 +757,7 @@ pub mod order_unit_tests {
     #[tokio::test]
     pub async fn successful_list_all_orders() {
         let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
         let orders = [OrderResponse {
             order_id: 1,
             table_id: 1,
@@ -304,7 +766,26 @@ pub mod order_unit_tests {
             item: ItemResponse {
                 item_name: "Hamburger".to_string(),
                 cook_time: 5,
+                modifiers: vec![],
+                category: Category::Main,
+                description: None,
+                display_order: 0,
+                station: None,
+                available: true,
+                allergens: vec![],
+                diets: vec![],
+                dayparts: vec![],
+                menu_version: None,
             },
+            quantity: 1,
+            modifiers: vec![],
+            course: 1,
+            fired_time: None,
+            station: None,
+            started_time: None,
+            estimated_ready_time: None,
+            allergy_conflicts: vec![],
+            menu_version: None,
         }]
         .to_vec();
 
@@ -318,21 +799,23 @@ pub mod order_unit_tests {
         faux::when!(mock_db.list_all_orders).then(move |_| Ok(list_order_result.to_owned()));
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(0),
-            orders: Mutex::new(0),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
         let state = State(app_state);
-        let pagination = Query(Pagination {
+        let pagination = ValidQuery(Pagination {
             offset: 0,
             limit: 10,
         });
-        let filters: ExtraQuery<ListOrderFiltersRequest> = ExtraQuery(ListOrderFiltersRequest {
-            table_ids: [1].to_vec(),
-            item_names: ["Hamburger".to_string()].to_vec(),
-            cook_status: Some(CookStatus::InProgress),
-        });
-        match list_all_orders(state, pagination, filters).await {
+        let filters: ValidExtraQuery<ListOrderFiltersRequest> =
+            ValidExtraQuery(ListOrderFiltersRequest {
+                table_ids: [1].to_vec(),
+                item_names: ["Hamburger".to_string()].to_vec(),
+                cook_status: Some(CookStatus::InProgress),
+            });
+        match list_all_orders(state, ApiVersion::V2, pagination, filters).await {
             Ok(resp) => {
                 assert_eq!(resp.0, StatusCode::OK);
             }
@@ -352,21 +835,23 @@ pub mod order_unit_tests {
         });
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(0),
-            orders: Mutex::new(0),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
         let state = State(app_state);
-        let pagination = Query(Pagination {
+        let pagination = ValidQuery(Pagination {
             offset: 0,
             limit: 10,
         });
-        let filters: ExtraQuery<ListOrderFiltersRequest> = ExtraQuery(ListOrderFiltersRequest {
-            table_ids: [1].to_vec(),
-            item_names: ["Hamburger".to_string()].to_vec(),
-            cook_status: Some(CookStatus::InProgress),
-        });
-        match list_all_orders(state, pagination, filters).await {
+        let filters: ValidExtraQuery<ListOrderFiltersRequest> =
+            ValidExtraQuery(ListOrderFiltersRequest {
+                table_ids: [1].to_vec(),
+                item_names: ["Hamburger".to_string()].to_vec(),
+                cook_status: Some(CookStatus::InProgress),
+            });
+        match list_all_orders(state, ApiVersion::V2, pagination, filters).await {
             Ok(_) => panic!("shouldn't succeed!"),
             Err(e) => {
                 assert_eq!(e.1.error_type, AxumErrors::DBError.to_string());
@@ -386,21 +871,23 @@ pub mod order_unit_tests {
         });
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(0),
-            orders: Mutex::new(0),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
         let state = State(app_state);
-        let pagination = Query(Pagination {
+        let pagination = ValidQuery(Pagination {
             offset: 0,
             limit: 10,
         });
-        let filters: ExtraQuery<ListOrderFiltersRequest> = ExtraQuery(ListOrderFiltersRequest {
-            table_ids: [1].to_vec(),
-            item_names: ["Hamburger".to_string()].to_vec(),
-            cook_status: Some(CookStatus::InProgress),
-        });
-        match list_all_orders(state, pagination, filters).await {
+        let filters: ValidExtraQuery<ListOrderFiltersRequest> =
+            ValidExtraQuery(ListOrderFiltersRequest {
+                table_ids: [1].to_vec(),
+                item_names: ["Hamburger".to_string()].to_vec(),
+                cook_status: Some(CookStatus::InProgress),
+            });
+        match list_all_orders(state, ApiVersion::V2, pagination, filters).await {
             Ok(_) => panic!("shouldn't succeed!"),
             Err(e) => {
                 assert_eq!(e.1.error_type, AxumErrors::BsonDeserializeError.to_string());
@@ -408,6 +895,94 @@ pub mod order_unit_tests {
         }
     }
 
+    fn held_order_response() -> OrderResponse {
+        OrderResponse {
+            order_id: 1,
+            table_id: 1,
+            ordered_time: Utc::now(),
+            cook_status: CookStatus::Held,
+            item: ItemResponse {
+                item_name: "Hamburger".to_string(),
+                cook_time: 5,
+                modifiers: vec![],
+                category: Category::Main,
+                description: None,
+                display_order: 0,
+                station: None,
+                available: true,
+                allergens: vec![],
+                diets: vec![],
+                dayparts: vec![],
+                menu_version: None,
+            },
+            quantity: 1,
+            modifiers: vec![],
+            course: 1,
+            fired_time: None,
+            station: None,
+            started_time: None,
+            estimated_ready_time: None,
+            allergy_conflicts: vec![],
+            menu_version: None,
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_list_all_orders_v1_in_progress_includes_held() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        faux::when!(mock_db.list_all_orders).then(move |_| {
+            Ok(ListOrderResult {
+                count: 1,
+                dropped: 0,
+                orders: vec![held_order_response()],
+                failed_orders: None,
+            })
+        });
+
+        let filters: ValidExtraQuery<ListOrderFiltersRequest> =
+            ValidExtraQuery(ListOrderFiltersRequest {
+                table_ids: vec![],
+                item_names: vec![],
+                cook_status: Some(CookStatus::InProgress),
+            });
+        let pagination = ValidQuery(Pagination {
+            offset: 0,
+            limit: 10,
+        });
+        match list_all_orders(app_state(mock_db), ApiVersion::V1, pagination, filters).await {
+            Ok((status, body)) => {
+                assert_eq!(status, StatusCode::OK);
+                assert_eq!(body.0.pagination.total, 1);
+                assert_eq!(body.0.orders[0].cook_status, CookStatus::InProgress);
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_list_all_orders_v1_held_filter() {
+        let mock_db = DB::faux();
+
+        let filters: ValidExtraQuery<ListOrderFiltersRequest> =
+            ValidExtraQuery(ListOrderFiltersRequest {
+                table_ids: vec![],
+                item_names: vec![],
+                cook_status: Some(CookStatus::Held),
+            });
+        let pagination = ValidQuery(Pagination {
+            offset: 0,
+            limit: 10,
+        });
+        match list_all_orders(app_state(mock_db), ApiVersion::V1, pagination, filters).await {
+            Ok(_) => panic!("shouldn't succeed!"),
+            Err(e) => {
+                assert_eq!(e.0, StatusCode::UNPROCESSABLE_ENTITY);
+                assert_eq!(e.1.errors[0].field, "cook_status");
+            }
+        }
+    }
+
     ////////////////////////
     //                   //
     //  DELETE_TABLE    //
@@ -417,16 +992,17 @@ pub mod order_unit_tests {
     #[tokio::test]
     pub async fn successful_delete_order() {
         let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
         let mut table = Table {
             id: ObjectId::new().to_hex(),
             table_id: 1,
             orders: [].to_vec(),
+            version: 1,
+            fired_course: 1,
+            allergy_note: None,
         };
 
-        let item = Item {
-            item_name: "Burger".to_string(),
-            cook_time: 5,
-        };
+        let item = item("Burger", 5);
 
         let order = Order {
             order_id: 1,
@@ -434,20 +1010,31 @@ pub mod order_unit_tests {
             ordered_time: Utc::now(),
             cook_status: CookStatus::InProgress,
             item: item.clone(),
+            quantity: 1,
+            modifiers: vec![],
+            course: 1,
+            held: false,
+            fired_time: None,
+            allergy_conflicts: vec![],
+            taken_stock: TakenStock::default(),
         };
 
         table.orders = [order.clone()].to_vec();
 
-        faux::when!(mock_db.delete_order).then(move |_| Ok(table.to_owned()));
+        faux::when!(mock_db.delete_order).then(move |_| Ok((table.to_owned(), None)));
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(1),
-            orders: Mutex::new(1),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
         let state = State(app_state);
-        let path = Path((1, 1));
-        match delete_order(state, path).await {
+        let path = ValidPath(OrderPath {
+            table_id: 1,
+            order_id: 1,
+        });
+        match delete_order(state, ApiVersion::V2, path, IfMatch(None)).await {
             Ok(resp) => {
                 assert_eq!(resp.0, StatusCode::OK);
             }
@@ -456,36 +1043,391 @@ pub mod order_unit_tests {
     }
 
     #[tokio::test]
-    pub async fn failed_delete_order_not_found() {
+    pub async fn successful_delete_order_gives_back_stock() {
         let mut mock_db = DB::faux();
-
-        faux::when!(mock_db.delete_order).then(move |_| {
-            Err(ErrorResponse {
-                status_code: StatusCode::NOT_FOUND,
-                error: AxumErrors::NotFound.into(),
-            })
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        let table = Table {
+            id: ObjectId::new().to_hex(),
+            table_id: 1,
+            orders: [].to_vec(),
+            version: 2,
+            fired_course: 1,
+            allergy_note: None,
+        };
+        let order = Order {
+            order_id: 1,
+            table_id: 1,
+            ordered_time: Utc::now(),
+            cook_status: CookStatus::Held,
+            item: Item {
+                recipe: vec![
+                    RecipeIngredient {
+                        ingredient_name: "Bun".to_string(),
+                        quantity: 1,
+                    },
+                    RecipeIngredient {
+                        ingredient_name: "Beef".to_string(),
+                        quantity: 150,
+                    },
+                ],
+                ..item("Burger", 5)
+            },
+            quantity: 2,
+            modifiers: vec![],
+            course: 2,
+            held: true,
+            fired_time: None,
+            allergy_conflicts: vec![],
+            taken_stock: TakenStock {
+                portions: 2,
+                ingredients: vec![
+                    RecipeIngredient {
+                        ingredient_name: "Bun".to_string(),
+                        quantity: 2,
+                    },
+                    RecipeIngredient {
+                        ingredient_name: "Beef".to_string(),
+                        quantity: 300,
+                    },
+                ],
+            },
+        };
+        faux::when!(mock_db.delete_order)
+            .then(move |_| Ok((table.to_owned(), Some(order.to_owned()))));
+        faux::when!(mock_db.release_stock).once().then(|portions| {
+            assert_eq!(portions.get("Burger"), Some(&2));
+            Ok(())
         });
+        // the kitchen hasn't started on the held order, it gives back what it took when it was placed
+        faux::when!(mock_db.release_ingredients)
+            .once()
+            .then(|ingredients| {
+                assert_eq!(ingredients.get("Bun"), Some(&2));
+                assert_eq!(ingredients.get("Beef"), Some(&300));
+                Ok(())
+            });
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(1),
-            orders: Mutex::new(1),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
-        let state = State(app_state);
-        let path = Path((1, 1));
-        match delete_order(state, path).await {
-            Ok(_) => panic!("not supposed to succeed!"),
-            Err(e) => {
-                assert_eq!(e.1.error_type, AxumErrors::NotFound.to_string());
+        let path = ValidPath(OrderPath {
+            table_id: 1,
+            order_id: 1,
+        });
+        match delete_order(State(app_state), ApiVersion::V2, path, IfMatch(None)).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::OK);
+                assert!(resp.2.table.orders.is_empty());
             }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
         }
     }
 
     #[tokio::test]
-    pub async fn failed_delete_order_db_error() {
+    pub async fn successful_delete_order_gives_back_only_taken_stock() {
         let mut mock_db = DB::faux();
-
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        let table = Table {
+            id: ObjectId::new().to_hex(),
+            table_id: 1,
+            orders: [].to_vec(),
+            version: 2,
+            fired_course: 1,
+            allergy_note: None,
+        };
+        // the portions of the burger were only counted after it was ordered, it took just its ingredients
+        let order = Order {
+            order_id: 1,
+            table_id: 1,
+            ordered_time: Utc::now(),
+            cook_status: CookStatus::Held,
+            item: recipe_item("Burger", &[("Bun", 1)]),
+            quantity: 2,
+            modifiers: vec![],
+            course: 2,
+            held: true,
+            fired_time: None,
+            allergy_conflicts: vec![],
+            taken_stock: TakenStock {
+                portions: 0,
+                ingredients: vec![RecipeIngredient {
+                    ingredient_name: "Bun".to_string(),
+                    quantity: 2,
+                }],
+            },
+        };
+        faux::when!(mock_db.delete_order)
+            .then(move |_| Ok((table.to_owned(), Some(order.to_owned()))));
+        faux::when!(mock_db.release_stock).then(|_| panic!("shouldn't give back the portions!"));
+        faux::when!(mock_db.release_ingredients)
+            .once()
+            .then(|ingredients| {
+                assert_eq!(ingredients.get("Bun"), Some(&2));
+                Ok(())
+            });
+
+        let path = ValidPath(OrderPath {
+            table_id: 1,
+            order_id: 1,
+        });
+        match delete_order(app_state(mock_db), ApiVersion::V2, path, IfMatch(None)).await {
+            Ok(resp) => assert_eq!(resp.0, StatusCode::OK),
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_delete_order_started_keeps_stock() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        let table = Table {
+            id: ObjectId::new().to_hex(),
+            table_id: 1,
+            orders: [].to_vec(),
+            version: 2,
+            fired_course: 1,
+            allergy_note: None,
+        };
+        let fired_time = Utc::now() - chrono::Duration::hours(1);
+        let order = Order {
+            order_id: 1,
+            table_id: 1,
+            ordered_time: fired_time,
+            cook_status: CookStatus::InProgress,
+            item: recipe_item("Burger", &[("Bun", 1), ("Beef", 150)]),
+            quantity: 2,
+            modifiers: vec![],
+            course: 1,
+            held: false,
+            fired_time: Some(fired_time),
+            allergy_conflicts: vec![],
+            taken_stock: TakenStock::default(),
+        };
+        faux::when!(mock_db.delete_order)
+            .then(move |_| Ok((table.to_owned(), Some(order.to_owned()))));
+        // the burger is already cooked, nothing goes back
+        faux::when!(mock_db.release_stock).then(|_| panic!("shouldn't give back the portions!"));
+        faux::when!(mock_db.release_ingredients)
+            .then(|_| panic!("shouldn't give back the ingredients!"));
+
+        let path = ValidPath(OrderPath {
+            table_id: 1,
+            order_id: 1,
+        });
+        match delete_order(app_state(mock_db), ApiVersion::V2, path, IfMatch(None)).await {
+            Ok(resp) => assert_eq!(resp.0, StatusCode::OK),
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_delete_order_not_found() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+
         faux::when!(mock_db.delete_order).then(move |_| {
+            Err(ErrorResponse {
+                status_code: StatusCode::NOT_FOUND,
+                error: AxumErrors::NotFound.into(),
+            })
+        });
+
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+        let state = State(app_state);
+        let path = ValidPath(OrderPath {
+            table_id: 1,
+            order_id: 1,
+        });
+        match delete_order(state, ApiVersion::V2, path, IfMatch(None)).await {
+            Ok(_) => panic!("not supposed to succeed!"),
+            Err(e) => {
+                assert_eq!(e.1.error_type, AxumErrors::NotFound.to_string());
+            }
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_delete_order_db_error() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+
+        faux::when!(mock_db.delete_order).then(move |_| {
+            Err(ErrorResponse {
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                error: AxumErrors::DBError.into(),
+            })
+        });
+
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+        let state = State(app_state);
+        let path = ValidPath(OrderPath {
+            table_id: 1,
+            order_id: 1,
+        });
+        match delete_order(state, ApiVersion::V2, path, IfMatch(None)).await {
+            Ok(_) => panic!("not supposed to succeed!"),
+            Err(e) => {
+                assert_eq!(e.1.error_type, AxumErrors::DBError.to_string());
+            }
+        }
+    }
+
+    ////////////////////////
+    //                   //
+    //  MOVE_ORDERS     //
+    //                 //
+    ////////////////////
+
+    #[tokio::test]
+    pub async fn successful_move_orders() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        let order = Order {
+            order_id: 1,
+            table_id: 2,
+            ordered_time: Utc::now(),
+            cook_status: CookStatus::InProgress,
+            item: item("Burger", 5),
+            quantity: 1,
+            modifiers: vec![],
+            course: 1,
+            held: false,
+            fired_time: None,
+            allergy_conflicts: vec![],
+            taken_stock: TakenStock::default(),
+        };
+        let from_table = Table {
+            id: ObjectId::new().to_hex(),
+            table_id: 1,
+            orders: [].to_vec(),
+            version: 3,
+            fired_course: 1,
+            allergy_note: None,
+        };
+        let to_table = Table {
+            id: ObjectId::new().to_hex(),
+            table_id: 2,
+            orders: [order].to_vec(),
+            version: 2,
+            fired_course: 1,
+            allergy_note: None,
+        };
+
+        faux::when!(mock_db.move_orders).then(
+            move |(table_id, to_table_id, order_ids, expected_versions)| {
+                assert_eq!((*table_id, *to_table_id), (1, 2));
+                assert_eq!(order_ids, vec![1]);
+                assert_eq!(expected_versions, Some(vec![2]));
+                Ok((from_table.to_owned(), to_table.to_owned()))
+            },
+        );
+
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+        let state = State(app_state);
+        let path = ValidPath(TablePath { table_id: 1 });
+        let if_match = IfMatch(Some(EntityTags::Tags(vec![EntityTag {
+            weak: false,
+            tag: "2".to_string(),
+        }])));
+        let json_body = ValidJson(MoveOrdersRequest {
+            to_table_id: 2,
+            order_ids: [1].to_vec(),
+        });
+        match move_orders(state, ApiVersion::V2, path, if_match, json_body).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::OK);
+                assert_eq!(resp.1 .0, 3);
+                assert!(resp.2.from.orders.is_empty());
+                assert_eq!(resp.2.to.orders.len(), 1);
+                assert_eq!(resp.2.to.orders[0].table_id, 2);
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_move_orders_same_table() {
+        // move_orders isn't mocked, moving onto the same table has to be rejected before the db
+        let mock_db = DB::faux();
+
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+        let state = State(app_state);
+        let path = ValidPath(TablePath { table_id: 1 });
+        let json_body = ValidJson(MoveOrdersRequest {
+            to_table_id: 1,
+            order_ids: [1].to_vec(),
+        });
+        match move_orders(state, ApiVersion::V2, path, IfMatch(None), json_body).await {
+            Ok(_) => panic!("not supposed to succeed!"),
+            Err(e) => {
+                assert_eq!(e.0, StatusCode::UNPROCESSABLE_ENTITY);
+                assert_eq!(e.1.error_type, AxumErrors::ValidationFailed.to_string());
+                assert_eq!(e.1.errors[0].field, "to_table_id");
+                assert_eq!(e.1.errors[0].code, "same_table");
+            }
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_move_orders_order_not_found() {
+        let mut mock_db = DB::faux();
+
+        faux::when!(mock_db.move_orders).then(move |_| {
+            Err(ErrorResponse {
+                status_code: StatusCode::NOT_FOUND,
+                error: AxumErrors::OrderNotFound.into(),
+            })
+        });
+
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+        let state = State(app_state);
+        let path = ValidPath(TablePath { table_id: 1 });
+        let json_body = ValidJson(MoveOrdersRequest {
+            to_table_id: 2,
+            order_ids: [5].to_vec(),
+        });
+        match move_orders(state, ApiVersion::V2, path, IfMatch(None), json_body).await {
+            Ok(_) => panic!("not supposed to succeed!"),
+            Err(e) => {
+                assert_eq!(e.0, StatusCode::NOT_FOUND);
+                assert_eq!(e.1.error_type, AxumErrors::OrderNotFound.to_string());
+            }
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_move_orders_db_error() {
+        let mut mock_db = DB::faux();
+
+        faux::when!(mock_db.move_orders).then(move |_| {
             Err(ErrorResponse {
                 status_code: StatusCode::INTERNAL_SERVER_ERROR,
                 error: AxumErrors::DBError.into(),
@@ -493,13 +1435,18 @@ pub mod order_unit_tests {
         });
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(1),
-            orders: Mutex::new(1),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
         let state = State(app_state);
-        let path = Path((1, 1));
-        match delete_order(state, path).await {
+        let path = ValidPath(TablePath { table_id: 1 });
+        let json_body = ValidJson(MoveOrdersRequest {
+            to_table_id: 2,
+            order_ids: [1].to_vec(),
+        });
+        match move_orders(state, ApiVersion::V2, path, IfMatch(None), json_body).await {
             Ok(_) => panic!("not supposed to succeed!"),
             Err(e) => {
                 assert_eq!(e.1.error_type, AxumErrors::DBError.to_string());
@@ -516,27 +1463,36 @@ pub mod order_unit_tests {
     #[tokio::test]
     pub async fn successful_get_order() {
         let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
         let order = Order {
             order_id: 1,
             table_id: 1,
             ordered_time: Utc::now(),
             cook_status: CookStatus::InProgress,
-            item: Item {
-                item_name: "Hamburger".to_string(),
-                cook_time: 5,
-            },
+            item: item("Hamburger", 5),
+            quantity: 1,
+            modifiers: vec![],
+            course: 1,
+            held: false,
+            fired_time: None,
+            allergy_conflicts: vec![],
+            taken_stock: TakenStock::default(),
         };
 
         faux::when!(mock_db.get_order).then(move |_| Ok(order.to_owned()));
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(0),
-            orders: Mutex::new(0),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
         let state = State(app_state);
-        let path = Path((1, 1));
-        match get_order(state, path).await {
+        let path = ValidPath(OrderPath {
+            table_id: 1,
+            order_id: 1,
+        });
+        match get_order(state, ApiVersion::V2, path).await {
             Ok(resp) => {
                 assert_eq!(resp.0, StatusCode::OK);
             }
@@ -544,6 +1500,40 @@ pub mod order_unit_tests {
         }
     }
 
+    #[tokio::test]
+    pub async fn successful_get_order_done_once_cooked() {
+        let clock = Arc::new(FakeClock::new(Utc::now()));
+        let mut order = course_order(1, false, Some(clock.now()));
+        order.item.cook_time = 30;
+
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        faux::when!(mock_db.get_order).then(move |_| Ok(order.to_owned()));
+
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: clock.clone(),
+        });
+        let cook_status = || async {
+            let path = ValidPath(OrderPath {
+                table_id: 1,
+                order_id: 1,
+            });
+            match get_order(State(app_state.clone()), ApiVersion::V2, path).await {
+                Ok(resp) => resp.1.order.cook_status.clone(),
+                Err(e) => panic!("error! {:?}", e.1.error_type),
+            }
+        };
+
+        assert_eq!(cook_status().await, CookStatus::InProgress);
+        clock.advance(chrono::Duration::seconds(29));
+        assert_eq!(cook_status().await, CookStatus::InProgress);
+        clock.advance(chrono::Duration::seconds(1));
+        assert_eq!(cook_status().await, CookStatus::Done);
+    }
+
     #[tokio::test]
     pub async fn failed_get_order_not_found() {
         let mut mock_db = DB::faux();
@@ -556,13 +1546,17 @@ pub mod order_unit_tests {
         });
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(0),
-            orders: Mutex::new(0),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
         let state = State(app_state);
-        let path = Path((1, 1));
-        match get_order(state, path).await {
+        let path = ValidPath(OrderPath {
+            table_id: 1,
+            order_id: 1,
+        });
+        match get_order(state, ApiVersion::V2, path).await {
             Ok(_) => panic!("shouldn't succeed!"),
             Err(e) => {
                 assert_eq!(e.1.error_type, AxumErrors::NotFound.to_string());
@@ -582,17 +1576,804 @@ pub mod order_unit_tests {
         });
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(0),
-            orders: Mutex::new(0),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
         let state = State(app_state);
-        let path = Path((1, 1));
-        match get_order(state, path).await {
+        let path = ValidPath(OrderPath {
+            table_id: 1,
+            order_id: 1,
+        });
+        match get_order(state, ApiVersion::V2, path).await {
             Ok(_) => panic!("shouldn't succeed!"),
             Err(e) => {
                 assert_eq!(e.1.error_type, AxumErrors::DBError.to_string());
             }
         }
     }
+
+    ////////////////////////
+    //                   //
+    //  FIRE_COURSE     //
+    //                 //
+    ////////////////////
+
+    fn course_order(course: i64, held: bool, fired_time: Option<DateTime<Utc>>) -> Order {
+        Order {
+            order_id: course,
+            table_id: 1,
+            ordered_time: Utc::now() - chrono::Duration::minutes(10),
+            cook_status: CookStatus::InProgress,
+            item: item("Ramen", 30),
+            quantity: 1,
+            modifiers: vec![],
+            course,
+            held,
+            fired_time,
+            allergy_conflicts: vec![],
+            taken_stock: TakenStock::default(),
+        }
+    }
+
+    #[test]
+    pub fn cook_status_counts_from_fire_time() {
+        // ordered ten minutes ago, a 30 second item is only still cooking if it was fired just now
+        let now = Utc::now();
+        let held = OrderResponse::from(course_order(2, true, None)).at(now);
+        assert_eq!(held.cook_status, CookStatus::Held);
+        let fired = OrderResponse::from(course_order(2, false, Some(now))).at(now);
+        assert_eq!(fired.cook_status, CookStatus::InProgress);
+        let from_before_courses = OrderResponse::from(course_order(1, false, None)).at(now);
+        assert_eq!(from_before_courses.cook_status, CookStatus::Done);
+    }
+
+    #[tokio::test]
+    pub async fn successful_fire_course() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        let table = Table {
+            id: ObjectId::new().to_hex(),
+            table_id: 1,
+            orders: [course_order(2, false, Some(Utc::now()))].to_vec(),
+            version: 4,
+            fired_course: 2,
+            allergy_note: None,
+        };
+
+        faux::when!(mock_db.fire_course).then(move |(table_id, course, _, expected_versions)| {
+            assert_eq!((*table_id, course), (1, 2));
+            assert_eq!(expected_versions, Some(vec![3]));
+            Ok(table.to_owned())
+        });
+
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+        let state = State(app_state);
+        let path = ValidPath(TablePath { table_id: 1 });
+        let query = ValidQuery(FireCourseQuery { course: 2 });
+        let if_match = IfMatch(Some(EntityTags::Tags(vec![EntityTag {
+            weak: false,
+            tag: "3".to_string(),
+        }])));
+        match fire_course(state, ApiVersion::V2, path, query, if_match).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::OK);
+                assert_eq!(resp.1 .0, 4);
+                assert_eq!(resp.2.table.fired_course, 2);
+                assert_eq!(resp.2.table.orders[0].cook_status, CookStatus::InProgress);
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_fire_course_table_not_found() {
+        let mut mock_db = DB::faux();
+
+        faux::when!(mock_db.fire_course).then(move |_| {
+            Err(ErrorResponse {
+                status_code: StatusCode::NOT_FOUND,
+                error: AxumErrors::TableNotFound.into(),
+            })
+        });
+
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+        let state = State(app_state);
+        let path = ValidPath(TablePath { table_id: 1 });
+        let query = ValidQuery(FireCourseQuery { course: 2 });
+        match fire_course(state, ApiVersion::V2, path, query, IfMatch(None)).await {
+            Ok(_) => panic!("shouldn't succeed!"),
+            Err(e) => {
+                assert_eq!(e.0, StatusCode::NOT_FOUND);
+                assert_eq!(e.1.error_type, AxumErrors::TableNotFound.to_string());
+            }
+        }
+    }
+
+    ////////////////////////
+    //                   //
+    //  ITEM_STOCK      //
+    //                 //
+    ////////////////////
+
+    fn stocked_item(available: bool, stock: Option<i64>) -> Item {
+        Item {
+            available,
+            stock,
+            ..item("UnagiDon", 5)
+        }
+    }
+
+    fn stock_db(item: Item) -> DB {
+        let mut mock_db = DB::faux();
+        let table = Table {
+            id: ObjectId::new().to_hex(),
+            table_id: 1,
+            orders: [].to_vec(),
+            version: 1,
+            fired_course: 1,
+            allergy_note: None,
+        };
+        faux::when!(mock_db.get_table_order).then(move |_| Ok(table.to_owned()));
+        faux::when!(mock_db.get_items_by_name).then(move |item_names| {
+            Ok(item_names
+                .iter()
+                .map(|item_name| (item_name.clone(), item.to_owned()))
+                .collect())
+        });
+        mock_db
+    }
+
+    fn stock_request(quantities: &[i64]) -> ValidJson<CreateOrdersRequest> {
+        ValidJson(CreateOrdersRequest {
+            orders: quantities
+                .iter()
+                .map(|quantity| {
+                    OrderRequest::Detailed(DetailedOrderRequest {
+                        item_name: "UnagiDon".to_string(),
+                        quantity: *quantity,
+                        modifiers: vec![],
+                        course: 1,
+                    })
+                })
+                .collect(),
+            allow_partial: false,
+            manager_override: false,
+        })
+    }
+
+    #[tokio::test]
+    pub async fn successful_create_order_takes_stock() {
+        let mut mock_db = stock_db(stocked_item(true, Some(5)));
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        faux::when!(mock_db.next_ids).then(|_| Ok(1));
+        faux::when!(mock_db.reserve_stock).once().then(|portions| {
+            assert_eq!(portions.get("UnagiDon"), Some(&4));
+            Ok(())
+        });
+        faux::when!(mock_db.create_orders).then(|(_, orders, _)| {
+            // the orders don't keep a copy of the portions left
+            for order in &orders {
+                assert!(!order.get_document("item").unwrap().contains_key("stock"));
+            }
+            Ok(Table {
+                id: ObjectId::new().to_hex(),
+                table_id: 1,
+                orders: [].to_vec(),
+                version: 2,
+                fired_course: 1,
+                allergy_note: None,
+            })
+        });
+
+        let path = ValidPath(TablePath { table_id: 1 });
+        match create_order(
+            app_state(mock_db),
+            ApiVersion::V2,
+            path,
+            IfMatch(None),
+            stock_request(&[3, 1]),
+        )
+        .await
+        {
+            Ok(resp) => assert_eq!(resp.0, StatusCode::CREATED),
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_create_order_item_unavailable() {
+        // neither the stock nor the ids are touched
+        let mock_db = stock_db(stocked_item(false, None));
+
+        let path = ValidPath(TablePath { table_id: 1 });
+        match create_order(
+            app_state(mock_db),
+            ApiVersion::V2,
+            path,
+            IfMatch(None),
+            stock_request(&[1]),
+        )
+        .await
+        {
+            Ok(_) => panic!("shouldn't succeed!"),
+            Err(e) => {
+                assert_eq!(e.0, StatusCode::CONFLICT);
+                assert_eq!(e.1.error_type, AxumErrors::ItemUnavailable.to_string());
+                assert_eq!(e.1.errors[0].field, "orders[0]");
+                assert_eq!(e.1.errors[0].code, "item_unavailable");
+            }
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_create_order_stale_cached_stock() {
+        // the cached menu is behind, portions came back since it was read
+        let mut mock_db = stock_db(stocked_item(true, Some(0)));
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        faux::when!(mock_db.next_ids).then(|_| Ok(1));
+        faux::when!(mock_db.reserve_stock).once().then(|_| Ok(()));
+        faux::when!(mock_db.create_orders).then(|_| {
+            Ok(Table {
+                id: ObjectId::new().to_hex(),
+                table_id: 1,
+                orders: [].to_vec(),
+                version: 2,
+                fired_course: 1,
+                allergy_note: None,
+            })
+        });
+
+        let path = ValidPath(TablePath { table_id: 1 });
+        match create_order(
+            app_state(mock_db),
+            ApiVersion::V2,
+            path,
+            IfMatch(None),
+            stock_request(&[2]),
+        )
+        .await
+        {
+            Ok(resp) => assert_eq!(resp.0, StatusCode::CREATED),
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_create_order_stock_taken_concurrently() {
+        // the cached menu still has portions, but another request got them first
+        let mut mock_db = stock_db(stocked_item(true, Some(3)));
+        faux::when!(mock_db.reserve_stock)
+            .then(|_| Err(ErrorResponse::from(AxumErrors::ItemUnavailable)));
+
+        let path = ValidPath(TablePath { table_id: 1 });
+        match create_order(
+            app_state(mock_db),
+            ApiVersion::V2,
+            path,
+            IfMatch(None),
+            stock_request(&[2]),
+        )
+        .await
+        {
+            Ok(_) => panic!("shouldn't succeed!"),
+            Err(e) => {
+                assert_eq!(e.0, StatusCode::CONFLICT);
+                assert_eq!(e.1.error_type, AxumErrors::ItemUnavailable.to_string());
+            }
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_create_order_gives_back_stock() {
+        let mut mock_db = stock_db(stocked_item(true, Some(3)));
+        faux::when!(mock_db.next_ids).then(|_| Ok(1));
+        faux::when!(mock_db.reserve_stock).then(|_| Ok(()));
+        faux::when!(mock_db.create_orders).then(|_| {
+            Err(ErrorResponse {
+                status_code: StatusCode::PRECONDITION_FAILED,
+                error: AxumErrors::PreconditionFailed.into(),
+            })
+        });
+        faux::when!(mock_db.release_stock).once().then(|portions| {
+            assert_eq!(portions.get("UnagiDon"), Some(&2));
+            Ok(())
+        });
+
+        let path = ValidPath(TablePath { table_id: 1 });
+        match create_order(
+            app_state(mock_db),
+            ApiVersion::V2,
+            path,
+            IfMatch(None),
+            stock_request(&[2]),
+        )
+        .await
+        {
+            Ok(_) => panic!("shouldn't succeed!"),
+            Err(e) => {
+                assert_eq!(e.1.error_type, AxumErrors::PreconditionFailed.to_string());
+            }
+        }
+    }
+
+    fn eel_item(stock: Option<i64>) -> Item {
+        Item {
+            stock,
+            ..recipe_item("UnagiDon", &[("Eel", 120)])
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_create_order_takes_ingredients() {
+        let mut mock_db = stock_db(eel_item(None));
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        faux::when!(mock_db.next_ids).then(|_| Ok(1));
+        // the portions aren't counted, only the ingredients are taken
+        faux::when!(mock_db.reserve_ingredients)
+            .once()
+            .then(|ingredients| {
+                assert_eq!(ingredients.get("Eel"), Some(&480));
+                Ok(vec![Ingredient {
+                    ingredient_name: "Eel".to_string(),
+                    unit: Unit::Gram,
+                    stock: 100,
+                    low_stock_threshold: Some(500),
+                }])
+            });
+        faux::when!(mock_db.create_orders).then(|(_, orders, _)| {
+            // the orders keep the recipe and what they took by it, but no portions as those aren't counted
+            for (order, quantity) in orders.iter().zip([3, 1]) {
+                assert!(order.get_document("item").unwrap().contains_key("recipe"));
+                let taken_stock = order.get_document("taken_stock").unwrap();
+                assert_eq!(taken_stock.get_i64("portions").unwrap(), 0);
+                let eel = taken_stock.get_array("ingredients").unwrap()[0]
+                    .as_document()
+                    .unwrap();
+                assert_eq!(eel.get_i64("quantity").unwrap(), 120 * quantity);
+            }
+            Ok(Table {
+                id: ObjectId::new().to_hex(),
+                table_id: 1,
+                orders: [].to_vec(),
+                version: 2,
+                fired_course: 1,
+                allergy_note: None,
+            })
+        });
+
+        let path = ValidPath(TablePath { table_id: 1 });
+        match create_order(
+            app_state(mock_db),
+            ApiVersion::V2,
+            path,
+            IfMatch(None),
+            stock_request(&[3, 1]),
+        )
+        .await
+        {
+            Ok(resp) => assert_eq!(resp.0, StatusCode::CREATED),
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_create_order_out_of_ingredients() {
+        let mut mock_db = stock_db(eel_item(Some(5)));
+        faux::when!(mock_db.reserve_stock).then(|_| Ok(()));
+        faux::when!(mock_db.reserve_ingredients).then(|_| {
+            Err(ErrorResponse::from(
+                AxumErrorResponse::from(AxumErrors::ItemUnavailable).with_errors(vec![
+                    FieldError::new("orders", "out_of_ingredients", "not enough Eel"),
+                ]),
+            ))
+        });
+        // the portions taken before the ingredients ran short go back
+        faux::when!(mock_db.release_stock).once().then(|portions| {
+            assert_eq!(portions.get("UnagiDon"), Some(&2));
+            Ok(())
+        });
+
+        let path = ValidPath(TablePath { table_id: 1 });
+        match create_order(
+            app_state(mock_db),
+            ApiVersion::V2,
+            path,
+            IfMatch(None),
+            stock_request(&[2]),
+        )
+        .await
+        {
+            Ok(_) => panic!("shouldn't succeed!"),
+            Err(e) => {
+                assert_eq!(e.0, StatusCode::CONFLICT);
+                assert_eq!(e.1.errors[0].code, "out_of_ingredients");
+            }
+        }
+    }
+
+    ////////////////////////
+    //                   //
+    //  ALLERGIES       //
+    //                 //
+    ////////////////////
+
+    fn allergy_db(allergy_note: Option<AllergyNote>) -> DB {
+        let mut mock_db = DB::faux();
+        let table = Table {
+            id: ObjectId::new().to_hex(),
+            table_id: 1,
+            orders: [].to_vec(),
+            version: 1,
+            fired_course: 1,
+            allergy_note,
+        };
+        let item = Item {
+            allergens: vec![Allergen::Soy, Allergen::Peanut, Allergen::Gluten],
+            ..stocked_item(true, None)
+        };
+        faux::when!(mock_db.get_table_order).then(move |_| Ok(table.to_owned()));
+        faux::when!(mock_db.get_items_by_name).then(move |item_names| {
+            Ok(item_names
+                .iter()
+                .map(|item_name| (item_name.clone(), item.to_owned()))
+                .collect())
+        });
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        faux::when!(mock_db.next_ids).then(|_| Ok(1));
+        mock_db
+    }
+
+    #[tokio::test]
+    pub async fn successful_create_order_flags_allergy_conflicts() {
+        let mut mock_db = allergy_db(Some(AllergyNote {
+            allergens: vec![Allergen::Peanut, Allergen::Soy, Allergen::Dairy],
+            note: Some("severe peanut allergy".to_string()),
+        }));
+        faux::when!(mock_db.create_orders).then(|(_, orders, _)| {
+            // the order is placed anyway, flagged with the allergens of the item that are on the note
+            let order: Order = mongodb::bson::from_document(orders[0].clone()).unwrap();
+            assert_eq!(
+                order.allergy_conflicts,
+                vec![Allergen::Soy, Allergen::Peanut]
+            );
+            Ok(Table {
+                id: ObjectId::new().to_hex(),
+                table_id: 1,
+                orders: vec![order],
+                version: 2,
+                fired_course: 1,
+                allergy_note: None,
+            })
+        });
+
+        let path = ValidPath(TablePath { table_id: 1 });
+        match create_order(
+            app_state(mock_db),
+            ApiVersion::V2,
+            path,
+            IfMatch(None),
+            stock_request(&[1]),
+        )
+        .await
+        {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::CREATED);
+                assert_eq!(
+                    resp.2.table.orders[0].allergy_conflicts,
+                    vec![Allergen::Soy, Allergen::Peanut]
+                );
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_create_order_without_allergy_note() {
+        let mut mock_db = allergy_db(None);
+        faux::when!(mock_db.create_orders).then(|(_, orders, _)| {
+            // nothing to flag, the field is left out of the document
+            assert!(!orders[0].contains_key("allergy_conflicts"));
+            Ok(Table {
+                id: ObjectId::new().to_hex(),
+                table_id: 1,
+                orders: [].to_vec(),
+                version: 2,
+                fired_course: 1,
+                allergy_note: None,
+            })
+        });
+
+        let path = ValidPath(TablePath { table_id: 1 });
+        match create_order(
+            app_state(mock_db),
+            ApiVersion::V2,
+            path,
+            IfMatch(None),
+            stock_request(&[1]),
+        )
+        .await
+        {
+            Ok(resp) => assert_eq!(resp.0, StatusCode::CREATED),
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    ////////////////////////
+    //                   //
+    //  DAYPARTS        //
+    //                 //
+    ////////////////////
+
+    /// A breakfast only item.
+    fn breakfast_db() -> DB {
+        let mut mock_db = stock_db(Item {
+            dayparts: vec!["Breakfast".to_string()],
+            ..stocked_item(true, None)
+        });
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        faux::when!(mock_db.next_ids).then(|_| Ok(1));
+        mock_db
+    }
+
+    fn dinner_time() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-03-01T19:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[tokio::test]
+    pub async fn failed_create_order_outside_daypart() {
+        let mut mock_db = breakfast_db();
+        faux::when!(mock_db.list_dayparts).then(|_| {
+            Ok(vec![Daypart {
+                daypart_name: "Breakfast".to_string(),
+                days: vec![],
+                start: "07:00:00".parse().unwrap(),
+                end: "11:00:00".parse().unwrap(),
+            }])
+        });
+
+        let path = ValidPath(TablePath { table_id: 1 });
+        match create_order(
+            app_state_at(mock_db, dinner_time()),
+            ApiVersion::V2,
+            path,
+            IfMatch(None),
+            stock_request(&[1]),
+        )
+        .await
+        {
+            Ok(_) => panic!("shouldn't succeed!"),
+            Err(e) => {
+                assert_eq!(e.0, StatusCode::CONFLICT);
+                assert_eq!(e.1.error_type, AxumErrors::ItemUnavailable.to_string());
+                assert_eq!(e.1.errors[0].field, "orders[0]");
+                assert_eq!(e.1.errors[0].code, "outside_daypart");
+            }
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_create_order_manager_override() {
+        // the dayparts aren't even read, an unstubbed `list_dayparts` would panic
+        let mut mock_db = breakfast_db();
+        faux::when!(mock_db.create_orders).then(|_| {
+            Ok(Table {
+                id: ObjectId::new().to_hex(),
+                table_id: 1,
+                orders: [].to_vec(),
+                version: 2,
+                fired_course: 1,
+                allergy_note: None,
+            })
+        });
+
+        let ValidJson(mut request) = stock_request(&[1]);
+        request.manager_override = true;
+        let path = ValidPath(TablePath { table_id: 1 });
+        match create_order(
+            app_state_at(mock_db, dinner_time()),
+            ApiVersion::V2,
+            path,
+            IfMatch(None),
+            ValidJson(request),
+        )
+        .await
+        {
+            Ok(resp) => assert_eq!(resp.0, StatusCode::CREATED),
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    ////////////////////////
+    //                   //
+    //  ORDER_BUILDER   //
+    //                 //
+    ////////////////////
+
+    #[test]
+    pub fn resolve_orders_keeps_duplicates_and_reports_unknown() {
+        let menu = HashMap::from([("Burger".to_string(), item("Burger", 5))]);
+        let resolved = resolve_orders(
+            ["Burger", "Pizza", "Burger"]
+                .map(OrderRequest::from)
+                .to_vec(),
+            &menu,
+            None,
+        );
+        assert_eq!(resolved.orders.len(), 2);
+        assert!(resolved
+            .orders
+            .iter()
+            .all(|order| order.item.item_name == "Burger" && order.quantity == 1));
+        assert_eq!(resolved.unknown_items.len(), 1);
+        assert_eq!(resolved.unknown_items[0].field, "orders[1]");
+        assert_eq!(resolved.unknown_items[0].code, "unknown_item");
+        assert!(resolved.unknown_modifiers.is_empty());
+    }
+
+    #[test]
+    pub fn resolve_orders_picks_modifiers_and_reports_unknown() {
+        let no_scallions = Modifier {
+            name: "No Scallions".to_string(),
+            kind: ModifierKind::Remove,
+            price_delta: None,
+        };
+        let menu = HashMap::from([(
+            "Ramen".to_string(),
+            Item {
+                modifiers: vec![
+                    no_scallions.clone(),
+                    Modifier {
+                        name: "Extra Egg".to_string(),
+                        kind: ModifierKind::Add,
+                        price_delta: Some(150),
+                    },
+                ],
+                ..item("Ramen", 30)
+            },
+        )]);
+        let resolved = resolve_orders(
+            vec![
+                OrderRequest::Detailed(DetailedOrderRequest {
+                    item_name: "Ramen".to_string(),
+                    quantity: 3,
+                    modifiers: vec!["No Scallions".to_string()],
+                    course: 1,
+                }),
+                OrderRequest::Detailed(DetailedOrderRequest {
+                    item_name: "Ramen".to_string(),
+                    quantity: 1,
+                    modifiers: vec!["Extra Egg".to_string(), "No Pork".to_string()],
+                    course: 1,
+                }),
+            ],
+            &menu,
+            None,
+        );
+        assert_eq!(resolved.orders[0].quantity, 3);
+        assert_eq!(resolved.orders[0].modifiers, vec![no_scallions]);
+        assert!(resolved.unknown_items.is_empty());
+        assert_eq!(resolved.unknown_modifiers.len(), 1);
+        assert_eq!(
+            resolved.unknown_modifiers[0].field,
+            "orders[1].modifiers[1]"
+        );
+        assert_eq!(resolved.unknown_modifiers[0].code, "unknown_modifier");
+    }
+
+    #[test]
+    pub fn resolve_orders_counts_portions_per_item() {
+        let mut ramen = item("Ramen", 5);
+        ramen.stock = Some(4);
+        let mut udon = item("Udon", 5);
+        udon.available = false;
+        let menu = HashMap::from([("Ramen".to_string(), ramen), ("Udon".to_string(), udon)]);
+        let resolved = resolve_orders(
+            vec![
+                OrderRequest::ItemName("Ramen".to_string()),
+                OrderRequest::Detailed(DetailedOrderRequest {
+                    item_name: "Ramen".to_string(),
+                    quantity: 3,
+                    modifiers: vec![],
+                    course: 1,
+                }),
+                OrderRequest::ItemName("Udon".to_string()),
+                OrderRequest::ItemName("Ramen".to_string()),
+            ],
+            &menu,
+            None,
+        );
+        // the cached count may be behind, reserve_stock decides whether the fifth portion is left
+        assert_eq!(resolved.unavailable_items.len(), 1);
+        assert_eq!(resolved.unavailable_items[0].field, "orders[2]");
+        assert_eq!(resolved.unavailable_items[0].code, "item_unavailable");
+        assert_eq!(
+            StockNeeds::of_resolved(&resolved.orders)
+                .unwrap()
+                .portions
+                .get("Ramen"),
+            Some(&5)
+        );
+    }
+
+    #[test]
+    pub fn resolve_orders_rejects_uncountable_amounts() {
+        let mut broth = item("Ramen", 5);
+        broth.recipe = vec![RecipeIngredient {
+            ingredient_name: "Broth".to_string(),
+            quantity: i64::MAX / 2,
+        }];
+        let menu = HashMap::from([("Ramen".to_string(), broth)]);
+        let ramen = |quantity| {
+            OrderRequest::Detailed(DetailedOrderRequest {
+                item_name: "Ramen".to_string(),
+                quantity,
+                modifiers: vec![],
+                course: 1,
+            })
+        };
+
+        // three bowls would wrap around
+        let resolved = resolve_orders(vec![ramen(3)], &menu, None);
+        assert!(resolved.orders.is_empty());
+        assert_eq!(resolved.too_large_orders.len(), 1);
+        assert_eq!(resolved.too_large_orders[0].field, "orders[0].quantity");
+        assert_eq!(resolved.too_large_orders[0].code, "too_large");
+
+        // two bowls each fit but not together
+        let resolved = resolve_orders(vec![ramen(2), ramen(2)], &menu, None);
+        assert!(resolved.too_large_orders.is_empty());
+        match StockNeeds::of_resolved(&resolved.orders) {
+            Ok(_) => panic!("shouldn't add up!"),
+            Err(e) => {
+                assert_eq!(e.status_code, StatusCode::UNPROCESSABLE_ENTITY);
+                assert_eq!(e.error.errors[0].code, "too_large");
+            }
+        }
+    }
+
+    #[test]
+    pub fn resolve_orders_reports_items_outside_their_dayparts() {
+        let mut pancakes = item("Pancakes", 5);
+        pancakes.dayparts = vec!["Breakfast".to_string()];
+        let mut ramen = item("Ramen", 5);
+        ramen.dayparts = vec!["Lunch".to_string(), "Dinner".to_string()];
+        let menu = HashMap::from([
+            ("Pancakes".to_string(), pancakes),
+            ("Ramen".to_string(), ramen),
+            (
+                "Tea".to_string(),
+                Item {
+                    category: Category::Drink,
+                    ..item("Tea", 5)
+                },
+            ),
+        ]);
+        let orders = ["Pancakes", "Ramen", "Tea"]
+            .map(OrderRequest::from)
+            .to_vec();
+
+        // the tea has no dayparts and is served all day
+        let resolved = resolve_orders(orders.clone(), &menu, Some(&["Dinner".to_string()]));
+        assert_eq!(resolved.unavailable_items.len(), 1);
+        assert_eq!(resolved.unavailable_items[0].field, "orders[0]");
+        assert_eq!(resolved.unavailable_items[0].code, "outside_daypart");
+
+        // without active dayparts to check against, e.g. a manager override, nothing is reported
+        let resolved = resolve_orders(orders, &menu, None);
+        assert!(resolved.unavailable_items.is_empty());
+    }
 }
@@ -1,36 +1,63 @@
 use std::sync::Arc;
 
-use axum::{extract::Path, extract::Query, extract::State, http::StatusCode, Json};
-use axum_extra::extract::Query as ExtraQuery;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
 
-use chrono::Utc;
 use serde::{Deserialize, Serialize};
+use tracing::Span;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
 
 use crate::{
     common::{
-        errors::AxumErrorResponse,
+        config::ValidationLimits,
+        errors::{AxumErrorResponse, ProblemJson},
+        etag::{ETag, IfMatch, IfNoneMatch, ReadETag},
+        extractors::{ApiVersion, Json, ValidExtraQuery, ValidJson, ValidPath, ValidQuery},
         models::{
             pagination_schema::Pagination,
-            restaurant_schema::{CookStatus, Order, Table, TableResponse},
+            path_schema::TablePath,
+            restaurant_schema::{AllergyNote, OrderRequest, Table, TableResponse},
+        },
+        validation::{
+            validate_allergy_note, validate_item_name, validate_item_names, validate_order_count,
         },
     },
     AppState,
 };
 
 use super::table_db::DBTableTrait;
+use crate::handlers::kitchen_handler::scheduler::kitchen_schedule;
+use crate::handlers::order_handler::order_builder::{
+    build_orders, BuiltOrders, OrderBatch, OrderTarget,
+};
+use crate::handlers::order_handler::stock::{give_back_stock, give_back_unstarted_stock};
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct PostTableResponse {
     pub id: String,
     pub table: TableResponse,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, IntoParams, ToSchema, Validate)]
+#[into_params(parameter_in = Query)]
+#[validate(context = ValidationLimits)]
 pub struct ListTableFiltersRequest {
+    /// only the table with this id
+    #[validate(range(min = 1, code = "not_positive", message = "must be at least 1"))]
     pub table_id: Option<i64>,
+    /// only tables holding this order
+    #[validate(range(min = 1, code = "not_positive", message = "must be at least 1"))]
     pub order_id: Option<i64>,
+    /// case insensitive fuzzy match on the ordered items
+    #[validate(custom(function = "validate_item_name", use_context))]
     pub item_name: Option<String>,
+    /// only tables that ordered every one of these items, repeat the param for each item
     #[serde(default = "default_vec_strings")]
+    #[validate(custom(function = "validate_item_names", use_context))]
     pub item_names: Vec<String>,
 }
 
@@ -38,7 +65,7 @@ pub fn default_vec_strings() -> Vec<String> {
     vec![]
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct ListTableResponse {
     pub tables: Vec<TableResponse>,
     pub pagination: ListTablePaginationResponse,
@@ -46,91 +73,146 @@ pub struct ListTableResponse {
     pub errors: ListTableErrorResponse,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct ListTablePaginationResponse {
     pub total: u64,
     pub limit: i64,
     pub offset: u64,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct ListTableErrorResponse {
     pub failed_table_ids: Option<Vec<String>>,
     pub failed_table_count: u64,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct DeleteTableResponse {
     pub table: TableResponse,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema, Validate)]
+#[validate(context = ValidationLimits)]
 pub struct CreateTableOrdersRequest {
-    #[serde(default = "default_vec_strings")]
-    pub orders: Vec<String>,
+    /// items to order right away, at most `MAX_ORDERS_PER_REQUEST` of them
+    #[serde(default)]
+    #[validate(custom(function = "validate_order_count", use_context))]
+    pub orders: Vec<OrderRequest>,
+    /// seat the table with the known items even if some of the requested ones are not on the menu
+    #[serde(default)]
+    pub allow_partial: bool,
+    /// a manager lets the items be ordered outside the dayparts they are served in
+    #[serde(default)]
+    pub manager_override: bool,
+    /// what the guests can't eat, orders of items with these allergens are flagged for the kitchen
+    #[validate(custom(function = "validate_allergy_note", use_context))]
+    pub allergy_note: Option<AllergyNote>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/table",
+    tag = "table",
+    params(("Idempotency-Key" = Option<String>, Header, description = "Makes retries safe, a retry with the same key and body replays the first response")),
+    request_body = CreateTableOrdersRequest,
+    responses(
+        (status = 201, description = "Table was created", body = PostTableResponse,
+            headers(("ETag" = String, description = "Version of the table"))),
+        (status = 200, description = "A table with the reserved id already existed, it is returned as stored and no orders were placed", body = PostTableResponse,
+            headers(("ETag" = String, description = "Version of the stored table"))),
+        (status = 422, description = "The request is invalid, or some items are not on the menu and allow_partial is off", body = AxumErrorResponse),
+        (status = 409, description = "The Idempotency-Key was used for another request, or that request is still running", body = AxumErrorResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
 pub async fn create_table(
     State(app_state): State<Arc<AppState>>,
-    Json(create_order_request): Json<CreateTableOrdersRequest>,
-) -> Result<(StatusCode, Json<PostTableResponse>), (StatusCode, Json<AxumErrorResponse>)> {
-    let mut table_id = app_state.tables.lock().await;
-    let mut order_id = app_state.orders.lock().await;
-
+    api_version: ApiVersion,
+    ValidJson(create_order_request): ValidJson<CreateTableOrdersRequest>,
+) -> Result<(StatusCode, ETag, Json<PostTableResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)>
+{
     let db = &app_state.db;
 
-    let mut order_added_counter = 0;
-
-    let mut orders: Vec<Order> = [].to_vec();
-
-    //if items are inside the order, then let's get the item and custom make orders
-    for item_name in create_order_request.orders.into_iter() {
-        let item = match db.get_item_table(item_name).await {
-            Ok(opt_item) => opt_item,
-            Err(e) => return Err(e.to_axum_error()),
-        };
-        match item {
-            Some(item) => {
-                order_added_counter += 1;
-                orders.push(Order {
-                    order_id: *order_id + order_added_counter,
-                    table_id: *table_id + 1,
-                    ordered_time: Utc::now(),
-                    cook_status: CookStatus::InProgress,
-                    item,
-                })
-            }
-            None => continue,
-        }
-    }
+    //only the first course is fired when the table is seated
+    let BuiltOrders {
+        table_id,
+        orders,
+        stock_needs,
+    } = match build_orders(
+        &app_state,
+        OrderBatch {
+            orders: create_order_request.orders,
+            allow_partial: create_order_request.allow_partial,
+            manager_override: create_order_request.manager_override,
+        },
+        OrderTarget {
+            table_id: None,
+            fired_course: 1,
+            allergy_note: create_order_request.allergy_note.as_ref(),
+        },
+    )
+    .await
+    {
+        Ok(built) => built,
+        Err(e) => return Err(e.to_axum_error()),
+    };
 
     let table = Table {
         id: mongodb::bson::oid::ObjectId::new().to_hex(),
-        table_id: *table_id + 1,
+        table_id,
         orders,
+        version: 1,
+        fired_course: 1,
+        allergy_note: create_order_request.allergy_note,
     };
 
     match db.create_table(&table).await {
-        Ok(_) => {
-            *table_id += 1;
-            *order_id += order_added_counter;
+        Ok(stored) => {
+            //the table id was already taken, that table is left as it is and the new orders are not placed
+            let status_code = match stored.id == table.id {
+                true => StatusCode::CREATED,
+                false => {
+                    give_back_stock(&app_state, &stock_needs).await;
+                    StatusCode::OK
+                }
+            };
+            app_state.schedule_cache.invalidate().await;
+            let schedule = kitchen_schedule(&app_state).await;
             Ok((
-                StatusCode::CREATED,
+                status_code,
+                ETag(stored.version),
                 Json(PostTableResponse {
-                    id: table.id.clone(),
-                    table: table.into(),
+                    id: stored.id.clone(),
+                    table: schedule
+                        .table(stored, app_state.clock.now())
+                        .in_version(api_version),
                 }),
             ))
         }
-        Err(e) => Err(e.to_axum_error()),
+        Err(e) => {
+            give_back_stock(&app_state, &stock_needs).await;
+            Err(e.to_axum_error())
+        }
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/table",
+    tag = "table",
+    params(Pagination, ListTableFiltersRequest),
+    responses(
+        (status = 200, description = "Tables matching the filters", body = ListTableResponse),
+        (status = 422, description = "The pagination or the filters are invalid", body = AxumErrorResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
 pub async fn list_table(
     State(app_state): State<Arc<AppState>>,
-    pagination: Query<Pagination>,
-    filters: ExtraQuery<ListTableFiltersRequest>,
-) -> Result<(StatusCode, Json<ListTableResponse>), (StatusCode, Json<AxumErrorResponse>)> {
+    api_version: ApiVersion,
+    pagination: ValidQuery<Pagination>,
+    filters: ValidExtraQuery<ListTableFiltersRequest>,
+) -> Result<(StatusCode, Json<ListTableResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)> {
     let db = &app_state.db;
     let pagination = Pagination {
         limit: pagination.limit,
@@ -145,53 +227,121 @@ pub async fn list_table(
     };
 
     match db.list_tables(&pagination, filters.clone()).await {
-        Ok(list_result) => Ok((
-            StatusCode::OK,
-            Json(ListTableResponse {
-                tables: list_result.tables,
-                filters,
-                pagination: ListTablePaginationResponse {
-                    total: list_result.count,
-                    limit: pagination.limit,
-                    offset: pagination.offset,
-                },
-                errors: ListTableErrorResponse {
-                    failed_table_ids: list_result.failed_tables,
-                    failed_table_count: list_result.dropped,
-                },
-            }),
-        )),
+        Ok(list_result) => {
+            let schedule = kitchen_schedule(&app_state).await;
+            let now = app_state.clock.now();
+            Ok((
+                StatusCode::OK,
+                Json(ListTableResponse {
+                    tables: list_result
+                        .tables
+                        .into_iter()
+                        .map(|table| schedule.table(table, now).in_version(api_version))
+                        .collect(),
+                    filters,
+                    pagination: ListTablePaginationResponse {
+                        total: list_result.count,
+                        limit: pagination.limit,
+                        offset: pagination.offset,
+                    },
+                    errors: ListTableErrorResponse {
+                        failed_table_ids: list_result.failed_tables,
+                        failed_table_count: list_result.dropped,
+                    },
+                }),
+            ))
+        }
         Err(e) => Err(e.to_axum_error()),
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/table/{table_id}",
+    tag = "table",
+    params(
+        ("table_id" = i64, Path, description = "Id of the table"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of the copy the caller already has"),
+    ),
+    responses(
+        (status = 200, description = "The table", body = TableResponse,
+            headers(("ETag" = String, description = "Version of the table and a digest of its cook statuses and times"))),
+        (status = 304, description = "The table and its cook statuses are still as given in If-None-Match"),
+        (status = 404, description = "Table was not found", body = AxumErrorResponse),
+        (status = 422, description = "The table id is not positive", body = AxumErrorResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
 pub async fn get_table(
     State(app_state): State<Arc<AppState>>,
-    Path(table_id): Path<i64>,
-) -> Result<(StatusCode, Json<TableResponse>), (StatusCode, Json<AxumErrorResponse>)> {
+    api_version: ApiVersion,
+    ValidPath(TablePath { table_id }): ValidPath<TablePath>,
+    if_none_match: IfNoneMatch,
+) -> Result<Response, (StatusCode, ProblemJson<AxumErrorResponse>)> {
+    Span::current().record("table_id", table_id);
     let db = &app_state.db;
-    let table_id = table_id;
 
     match db.get_table(table_id).await {
-        Ok(table_result) => Ok((StatusCode::OK, Json(TableResponse::from(table_result)))),
+        Ok(table_result) => {
+            let schedule = kitchen_schedule(&app_state).await;
+            let table = schedule
+                .table(table_result, app_state.clock.now())
+                .in_version(api_version);
+            // the cook statuses move on with the clock, so the copy is only current while they are the same too
+            let etag = ReadETag::of(table.version, &table);
+            match if_none_match.matches(&etag.tag()) {
+                true => Ok((StatusCode::NOT_MODIFIED, etag, ()).into_response()),
+                false => Ok((StatusCode::OK, etag, Json(table)).into_response()),
+            }
+        }
         Err(e) => Err(e.to_axum_error()),
     }
 }
 
+#[utoipa::path(
+    delete,
+    path = "/table/{table_id}",
+    tag = "table",
+    params(
+        ("table_id" = i64, Path, description = "Id of the table"),
+        ("If-Match" = Option<String>, Header, description = "Only delete the table if it is still at this ETag"),
+    ),
+    responses(
+        (status = 200, description = "The deleted table", body = DeleteTableResponse),
+        (status = 412, description = "The table changed since the ETag in If-Match", body = AxumErrorResponse),
+        (status = 404, description = "Table was not found", body = AxumErrorResponse),
+        (status = 422, description = "The table id is not positive", body = AxumErrorResponse),
+        (status = 500, description = "Unexpected database error", body = AxumErrorResponse),
+    )
+)]
 pub async fn delete_table(
     State(app_state): State<Arc<AppState>>,
-    Path(table_id): Path<i64>,
-) -> Result<(StatusCode, Json<DeleteTableResponse>), (StatusCode, Json<AxumErrorResponse>)> {
+    api_version: ApiVersion,
+    ValidPath(TablePath { table_id }): ValidPath<TablePath>,
+    if_match: IfMatch,
+) -> Result<(StatusCode, Json<DeleteTableResponse>), (StatusCode, ProblemJson<AxumErrorResponse>)> {
+    Span::current().record("table_id", table_id);
     let db = &app_state.db;
-    let table_id = table_id;
-
-    match db.delete_table(table_id).await {
-        Ok(table_response) => Ok((
-            StatusCode::OK,
-            Json(DeleteTableResponse {
-                table: table_response,
-            }),
-        )),
+
+    //the schedule from before the delete tells which orders the kitchen started on
+    let schedule = kitchen_schedule(&app_state).await;
+    match db
+        .delete_table(table_id, if_match.expected_versions())
+        .await
+    {
+        Ok(table) => {
+            //the orders the kitchen hasn't started on give back their portions and ingredients, like a voided order
+            give_back_unstarted_stock(&app_state, &schedule, &table.orders).await;
+            app_state.schedule_cache.invalidate().await;
+            Ok((
+                StatusCode::OK,
+                Json(DeleteTableResponse {
+                    table: schedule
+                        .table(table, app_state.clock.now())
+                        .in_version(api_version),
+                }),
+            ))
+        }
         Err(e) => Err(e.to_axum_error()),
     }
 }
@@ -1,19 +1,23 @@
 use async_trait::async_trait;
+use futures::FutureExt;
 
 use mongodb::bson::doc;
 use mongodb::bson::Document;
 use mongodb::bson::Regex;
 use serde::{Deserialize, Serialize};
-use tracing::error;
+use tracing::{error, Instrument};
 
 use crate::common::database;
-use crate::common::database_helpers::collect_cursor;
+use crate::common::database_helpers::{
+    collect_cursor, is_duplicate_key_error, with_expected_versions,
+};
 use crate::common::errors::AxumErrors;
 use crate::common::errors::ErrorResponse;
 use crate::common::models::pagination_schema::Pagination;
-use crate::common::models::restaurant_schema::Item;
 use crate::common::models::restaurant_schema::{Table, TableResponse};
-use crate::table_handler::table::ListTableFiltersRequest;
+use crate::common::telemetry::mongo_span;
+use crate::common::transactions::run_in_transaction;
+use crate::handlers::table_handler::table::ListTableFiltersRequest;
 use axum::http::StatusCode;
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ListTablesResult {
@@ -51,6 +55,7 @@ pub struct ItemNameStrictCheck {
 }
 
 impl From<ListTableFiltersRequest> for ListTableFiltersBson {
+    #[allow(clippy::manual_map, clippy::redundant_field_names)]
     fn from(filters: ListTableFiltersRequest) -> Self {
         Self {
             table_id: match filters.table_id {
@@ -96,51 +101,62 @@ pub trait DBTableTrait {
         pagination: &Pagination,
         filters: ListTableFiltersRequest,
     ) -> Result<ListTablesResult, ErrorResponse>;
-    async fn delete_table(&self, table_id: i64) -> Result<TableResponse, ErrorResponse>;
-    async fn get_item_table(&self, item_name: String) -> Result<Option<Item>, ErrorResponse>;
+    /// `expected_versions` comes from `If-Match`, the table is only deleted while it is at one of them.
+    async fn delete_table(
+        &self,
+        table_id: i64,
+        expected_versions: Option<Vec<i64>>,
+    ) -> Result<Table, ErrorResponse>;
 }
 
 #[faux::methods]
 #[async_trait]
 impl DBTableTrait for database::DB {
-    //this function is going to need session manager
     async fn create_table(&self, table: &Table) -> Result<Table, ErrorResponse> {
         let table_collection = self
             .db
             .database("table_management")
             .collection::<Table>("tables");
 
-        //make sure the table doesn't exist then insert
-        let filter = doc! {
-            "table_id": &table.table_id as &i64
-        };
+        //make sure the table doesn't exist then insert, both in one transaction
+        let created =
+            run_in_transaction(&self.db, self.transactions, "create the Table", |session| {
+                let table_collection = table_collection.clone();
+                let table = table.clone();
+                async move {
+                    let filter = doc! {
+                        "table_id": &table.table_id as &i64
+                    };
 
-        //first check if the id is in there
-        match table_collection.find_one(filter.clone(), None).await {
-            Ok(table) => match table {
-                Some(table) => return Ok(table.into()),
-                None => (),
-            },
-            Err(e) => {
-                error!("Unexpected error occured while searching for the Table in the Database. Error: {e}");
-                return Err(ErrorResponse {
-                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
-                    error: AxumErrors::DBError.into(),
-                });
-            }
-        };
+                    //first check if the id is in there
+                    if let Some(existing) = table_collection
+                        .find_one_with_session(filter, None, session)
+                        .instrument(mongo_span("find_one", "tables"))
+                        .await?
+                    {
+                        return Ok(Some(existing));
+                    }
 
-        // let table_as_bson = mongodb::bson::to_document(table).unwrap();
+                    match table_collection
+                        .insert_one_with_session(&table, None, session)
+                        .instrument(mongo_span("insert_one", "tables"))
+                        .await
+                    {
+                        Ok(_) => Ok(Some(table)),
+                        //without transactions a concurrent request can make the same table in between,
+                        //the unique index on the table_id keeps it from being made twice
+                        Err(e) if is_duplicate_key_error(&e) => Ok(None),
+                        Err(e) => Err(e.into()),
+                    }
+                }
+                .boxed()
+            })
+            .await?;
 
-        match table_collection.insert_one(table, None).await {
-            Ok(_) => Ok(table.clone().into()),
-            Err(e) => {
-                error!("Unexpected error occured while inserting the Table into the Database. Error: {e}");
-                return Err(ErrorResponse {
-                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
-                    error: AxumErrors::DBError.into(),
-                });
-            }
+        match created {
+            Some(table) => Ok(table),
+            //the table that request made is the one given back, as if it had been there already
+            None => self.get_table(table.table_id).await,
         }
     }
     async fn get_table(&self, table_id: i64) -> Result<Table, ErrorResponse> {
@@ -153,13 +169,17 @@ impl DBTableTrait for database::DB {
             "table_id": table_id
         };
 
-        match table_collection.find_one(filter, None).await {
+        match table_collection
+            .find_one(filter, None)
+            .instrument(mongo_span("find_one", "tables"))
+            .await
+        {
             Ok(opt_table) => match opt_table {
                 Some(table) => Ok(table),
                 None => {
                     return Err(ErrorResponse {
                         status_code: StatusCode::NOT_FOUND,
-                        error: AxumErrors::NotFound.into(),
+                        error: AxumErrors::TableNotFound.into(),
                     });
                 }
             },
@@ -173,6 +193,7 @@ impl DBTableTrait for database::DB {
         }
     }
 
+    #[allow(clippy::redundant_field_names)]
     async fn list_tables(
         &self,
         pagination: &Pagination,
@@ -205,6 +226,7 @@ impl DBTableTrait for database::DB {
 
         let count = match table_collection
             .count_documents(filter.clone(), Some(count_options))
+            .instrument(mongo_span("count_documents", "tables"))
             .await
         {
             Ok(count) => count,
@@ -217,7 +239,11 @@ impl DBTableTrait for database::DB {
             }
         };
 
-        match table_collection.find(filter, find_options).await {
+        match table_collection
+            .find(filter, find_options)
+            .instrument(mongo_span("find", "tables"))
+            .await
+        {
             Ok(cursor) => {
                 let (tables, failed_tables, dropped) =
                     match collect_cursor::<Table, TableResponse>(cursor).await {
@@ -249,69 +275,55 @@ impl DBTableTrait for database::DB {
             }
         }
     }
-    async fn delete_table(&self, table_id: i64) -> Result<TableResponse, ErrorResponse> {
+    async fn delete_table(
+        &self,
+        table_id: i64,
+        expected_versions: Option<Vec<i64>>,
+    ) -> Result<Table, ErrorResponse> {
         let table_collection = self
             .db
             .database("table_management")
             .collection::<Table>("tables");
 
-        //get the table first then delete it
-        let filter = doc! {
-            "table_id": table_id
-        };
+        //the table is found and deleted in one step, so the orders given back are the ones that were deleted
+        run_in_transaction(&self.db, self.transactions, "delete the Table", |session| {
+            let table_collection = table_collection.clone();
+            let expected_versions = expected_versions.clone();
+            async move {
+                let filter = doc! {
+                    "table_id": table_id
+                };
 
-        let table = match table_collection.find_one(filter.clone(), None).await {
-            Ok(opt_table) => match opt_table {
-                Some(table) => table,
-                None => {
-                    return Err(ErrorResponse {
-                        status_code: StatusCode::NOT_FOUND,
-                        error: AxumErrors::NotFound.into(),
-                    })
+                if let Some(table) = table_collection
+                    .find_one_and_delete_with_session(
+                        with_expected_versions(filter.clone(), &expected_versions),
+                        None,
+                        session,
+                    )
+                    .instrument(mongo_span("find_one_and_delete", "tables"))
+                    .await?
+                {
+                    return Ok(table);
                 }
-            },
-            Err(e) => {
-                error!("Unexpected error occured while searching the Table to delete from the Database. Error: {e}");
-                return Err(ErrorResponse {
-                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
-                    error: AxumErrors::DBError.into(),
-                });
-            }
-        };
-
-        match table_collection.delete_one(filter, None).await {
-            Ok(_) => Ok(table.into()),
-            Err(e) => {
-                error!("Unexpected error occured while Deleting the Table from the Database. Error: {e}");
-                Err(ErrorResponse {
-                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
-                    error: AxumErrors::DBError.into(),
-                })
-            }
-        }
-    }
-
-    async fn get_item_table(&self, item_name: String) -> Result<Option<Item>, ErrorResponse> {
-        let item_collection = self
-            .db
-            .database("item_management")
-            .collection::<Item>("items");
 
-        let filter = doc! {
-            "item_name":item_name
-        };
-
-        match item_collection.find_one(filter, None).await {
-            Ok(opt_item) => Ok(opt_item),
-            Err(e) => {
-                error!(
-                    "Unexpected error occured while searching for Item in the Database. Error: {e}"
-                );
-                return Err(ErrorResponse {
-                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
-                    error: AxumErrors::DBError.into(),
-                });
+                //nothing was deleted, either the table is gone or it moved past the If-Match versions
+                let exists = expected_versions.is_some()
+                    && table_collection
+                        .find_one_with_session(filter, None, session)
+                        .instrument(mongo_span("find_one", "tables"))
+                        .await?
+                        .is_some();
+                match exists {
+                    true => Err(ErrorResponse::from(AxumErrors::PreconditionFailed).into()),
+                    false => Err(ErrorResponse {
+                        status_code: StatusCode::NOT_FOUND,
+                        error: AxumErrors::TableNotFound.into(),
+                    }
+                    .into()),
+                }
             }
-        }
+            .boxed()
+        })
+        .await
     }
 }
@@ -1,27 +1,31 @@
 #[cfg(test)]
 pub mod table_unit_tests {
 
-    use std::sync::Arc;
+    use std::{collections::HashMap, sync::Arc, time::Duration};
 
-    use axum::{
-        extract::{Path, Query, State},
-        http::StatusCode,
-        Json,
-    };
-    use axum_extra::extract::Query as ExtraQuery;
-    use chrono::Utc;
+    use axum::{extract::State, http::StatusCode};
+    use chrono::{DateTime, Utc};
     use mongodb::bson::oid::ObjectId;
-    use tokio::sync::Mutex;
 
     use crate::{
         common::{
+            clock::{FakeClock, SystemClock},
+            counters_db::Counter,
             database::DB,
             errors::{AxumErrors, ErrorResponse},
+            etag::{EntityTag, EntityTags, IfMatch, IfNoneMatch},
+            extractors::{ApiVersion, ValidExtraQuery, ValidJson, ValidPath, ValidQuery},
+            menu_cache::MenuCache,
             models::{
                 pagination_schema::Pagination,
-                restaurant_schema::{CookStatus, Item, Order, Table, TableResponse},
+                path_schema::TablePath,
+                restaurant_schema::{
+                    Allergen, AllergyNote, CookStatus, Item, Order, Station, Table, TableResponse,
+                    TakenStock,
+                },
             },
         },
+        handlers::kitchen_handler::scheduler::ScheduleCache,
         handlers::table_handler::{
             table::{
                 create_table, delete_table, get_table, list_table, CreateTableOrdersRequest,
@@ -29,6 +33,7 @@ pub mod table_unit_tests {
             },
             table_db::ListTablesResult,
         },
+        tests::fixtures::{app_state_at, item},
         AppState,
     };
 
@@ -39,38 +44,201 @@ pub mod table_unit_tests {
     #[tokio::test]
     pub async fn successful_create() {
         let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        faux::when!(mock_db.next_ids).then(|_| Ok(1));
+        let item = item("Unagi", 10);
+
+        faux::when!(mock_db.get_items_by_name).then(move |item_names| {
+            Ok(item_names
+                .iter()
+                .map(|item_name| (item_name.clone(), item.to_owned()))
+                .collect())
+        });
+        faux::when!(mock_db.create_table).then(|table| Ok(table.to_owned()));
+
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+        let state = State(app_state);
+        let body = ValidJson(CreateTableOrdersRequest {
+            orders: ["Unagi".into()].to_vec(),
+            allow_partial: false,
+            allergy_note: None,
+            manager_override: false,
+        });
+        match create_table(state, ApiVersion::V2, body).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::CREATED);
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_create_flags_allergy_conflicts() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        faux::when!(mock_db.next_ids).then(|_| Ok(1));
+        faux::when!(mock_db.get_items_by_name).then(|item_names| {
+            Ok(item_names
+                .iter()
+                .map(|item_name| {
+                    let allergens = match item_name.as_str() {
+                        "Gyoza" => vec![Allergen::Gluten, Allergen::Soy],
+                        _ => vec![Allergen::Fish],
+                    };
+                    let item = Item {
+                        allergens,
+                        ..item(item_name, 10)
+                    };
+                    (item_name.clone(), item)
+                })
+                .collect())
+        });
+        faux::when!(mock_db.create_table).then(|table| {
+            // the note is kept on the table, later orders are checked against it too
+            assert_eq!(
+                table.allergy_note.as_ref().unwrap().allergens,
+                vec![Allergen::Soy]
+            );
+            Ok(table.to_owned())
+        });
+
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+        let body = ValidJson(CreateTableOrdersRequest {
+            orders: ["Gyoza".into(), "Unagi".into()].to_vec(),
+            allow_partial: false,
+            allergy_note: Some(AllergyNote {
+                allergens: vec![Allergen::Soy],
+                note: None,
+            }),
+            manager_override: false,
+        });
+        match create_table(State(app_state), ApiVersion::V2, body).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::CREATED);
+                let orders = &resp.2.table.orders;
+                assert_eq!(orders[0].allergy_conflicts, vec![Allergen::Soy]);
+                assert!(orders[1].allergy_conflicts.is_empty());
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_create_ids_from_counters() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        let item = item("Unagi", 10);
+
+        faux::when!(mock_db.get_items_by_name).then(move |item_names| {
+            Ok(item_names
+                .iter()
+                .map(|item_name| (item_name.clone(), item.to_owned()))
+                .collect())
+        });
+        faux::when!(mock_db.next_ids).then(|(counter, count)| match counter {
+            Counter::Table => {
+                assert_eq!(count, 1);
+                Ok(7)
+            }
+            Counter::Order => {
+                assert_eq!(count, 2);
+                Ok(40)
+            }
+            Counter::MenuVersion => panic!("a table doesn't need a menu version!"),
+        });
+        faux::when!(mock_db.create_table).then(|table| Ok(table.to_owned()));
+
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+        let state = State(app_state);
+        let body = ValidJson(CreateTableOrdersRequest {
+            orders: ["Unagi".into(), "Unagi".into()].to_vec(),
+            allow_partial: false,
+            allergy_note: None,
+            manager_override: false,
+        });
+        match create_table(state, ApiVersion::V2, body).await {
+            Ok(resp) => {
+                let table = &resp.2.table;
+                assert_eq!(table.table_id, 7);
+                assert_eq!(
+                    table
+                        .orders
+                        .iter()
+                        .map(|order| (order.order_id, order.table_id))
+                        .collect::<Vec<(i64, i64)>>(),
+                    vec![(40, 7), (41, 7)]
+                );
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_create_existing_table_returned() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        faux::when!(mock_db.next_ids).then(|_| Ok(1));
         let item = Item {
-            item_name: "Unagi".to_string(),
-            cook_time: 10,
+            stock: Some(5),
+            ..item("Unagi", 10)
         };
-        let table = Table {
+        let stored = Table {
             id: ObjectId::new().to_hex(),
             table_id: 1,
-            orders: [Order {
-                order_id: 1,
-                table_id: 1,
-                ordered_time: Utc::now(),
-                cook_status: CookStatus::InProgress,
-                item: item.clone(),
-            }]
-            .to_vec(),
+            orders: [].to_vec(),
+            version: 3,
+            fired_course: 2,
+            allergy_note: None,
         };
+        let stored_id = stored.id.clone();
 
-        faux::when!(mock_db.get_item_table).then(move |_| Ok(Some(item.to_owned())));
-        faux::when!(mock_db.create_table).then(move |_| Ok(table.to_owned()));
+        faux::when!(mock_db.get_items_by_name).then(move |item_names| {
+            Ok(item_names
+                .iter()
+                .map(|item_name| (item_name.clone(), item.to_owned()))
+                .collect())
+        });
+        faux::when!(mock_db.reserve_stock).once().then(|_| Ok(()));
+        // the table id was already taken, the portions taken for the new orders go back
+        faux::when!(mock_db.release_stock).once().then(|portions| {
+            assert_eq!(portions.get("Unagi"), Some(&1));
+            Ok(())
+        });
+        faux::when!(mock_db.create_table).then(move |_| Ok(stored.to_owned()));
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(0),
-            orders: Mutex::new(0),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
-        let state = State(app_state);
-        let body = Json(CreateTableOrdersRequest {
-            orders: ["Unagi".to_string()].to_vec(),
+        let body = ValidJson(CreateTableOrdersRequest {
+            orders: ["Unagi".into()].to_vec(),
+            allow_partial: false,
+            allergy_note: None,
+            manager_override: false,
         });
-        match create_table(state, body).await {
+        match create_table(State(app_state), ApiVersion::V2, body).await {
             Ok(resp) => {
-                assert_eq!(resp.0, StatusCode::CREATED);
+                assert_eq!(resp.0, StatusCode::OK);
+                assert_eq!(resp.1 .0, 3);
+                assert_eq!(resp.2.id, stored_id);
+                assert!(resp.2.table.orders.is_empty());
             }
             Err(e) => panic!("error! {:?}", e.1.error_type),
         }
@@ -79,6 +247,8 @@ pub mod table_unit_tests {
     #[tokio::test]
     pub async fn failed_create_db_error() {
         let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_items_by_name).then(|_| Ok(HashMap::new()));
+        faux::when!(mock_db.next_ids).then(|_| Ok(1));
 
         faux::when!(mock_db.create_table).then(move |_| {
             Err(ErrorResponse {
@@ -88,15 +258,19 @@ pub mod table_unit_tests {
         });
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(0),
-            orders: Mutex::new(0),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
         let state = State(app_state);
-        let body = Json(CreateTableOrdersRequest {
+        let body = ValidJson(CreateTableOrdersRequest {
             orders: [].to_vec(),
+            allow_partial: false,
+            allergy_note: None,
+            manager_override: false,
         });
-        match create_table(state, body).await {
+        match create_table(state, ApiVersion::V2, body).await {
             Ok(_) => panic!("supposed to fail"),
             Err(e) => {
                 assert_eq!(e.1.error_type, AxumErrors::DBError.to_string());
@@ -104,6 +278,67 @@ pub mod table_unit_tests {
         }
     }
 
+    #[tokio::test]
+    pub async fn failed_create_unknown_item() {
+        let mut mock_db = DB::faux();
+
+        faux::when!(mock_db.get_items_by_name).then(|_| Ok(HashMap::new()));
+
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+        let state = State(app_state);
+        let body = ValidJson(CreateTableOrdersRequest {
+            orders: ["Pizza".into()].to_vec(),
+            allow_partial: false,
+            allergy_note: None,
+            manager_override: false,
+        });
+        match create_table(state, ApiVersion::V2, body).await {
+            Ok(_) => panic!("supposed to fail"),
+            Err(e) => {
+                assert_eq!(e.0, StatusCode::UNPROCESSABLE_ENTITY);
+                assert_eq!(e.1.error_type, AxumErrors::UnknownItems.to_string());
+                assert_eq!(e.1.errors.len(), 1);
+                assert_eq!(e.1.errors[0].field, "orders[0]");
+            }
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_create_partial_unknown_item() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        faux::when!(mock_db.next_ids).then(|_| Ok(1));
+
+        faux::when!(mock_db.get_items_by_name).then(|_| Ok(HashMap::new()));
+        faux::when!(mock_db.create_table).then(|table| Ok(table.to_owned()));
+
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+        let state = State(app_state);
+        let body = ValidJson(CreateTableOrdersRequest {
+            orders: ["Pizza".into()].to_vec(),
+            allow_partial: true,
+            allergy_note: None,
+            manager_override: false,
+        });
+        match create_table(state, ApiVersion::V2, body).await {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::CREATED);
+                assert!(resp.2.table.orders.is_empty());
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
     ////////////////////
     //  LIST_TABLE    //
     ////////////////////
@@ -111,9 +346,13 @@ pub mod table_unit_tests {
     #[tokio::test]
     pub async fn successful_list_table() {
         let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
         let table = [TableResponse {
             table_id: 1,
             orders: [].to_vec(),
+            version: 1,
+            fired_course: 1,
+            allergy_note: None,
         }]
         .to_vec();
 
@@ -127,22 +366,24 @@ pub mod table_unit_tests {
         faux::when!(mock_db.list_tables).then(move |_| Ok(list_table_result.to_owned()));
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(0),
-            orders: Mutex::new(0),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
         let state = State(app_state);
-        let pagination = Query(Pagination {
+        let pagination = ValidQuery(Pagination {
             offset: 0,
             limit: 10,
         });
-        let query: ExtraQuery<ListTableFiltersRequest> = ExtraQuery(ListTableFiltersRequest {
-            table_id: None,
-            order_id: None,
-            item_name: None,
-            item_names: [].to_vec(),
-        });
-        match list_table(state, pagination, query).await {
+        let query: ValidExtraQuery<ListTableFiltersRequest> =
+            ValidExtraQuery(ListTableFiltersRequest {
+                table_id: None,
+                order_id: None,
+                item_name: None,
+                item_names: [].to_vec(),
+            });
+        match list_table(state, ApiVersion::V2, pagination, query).await {
             Ok(resp) => {
                 assert_eq!(resp.0, StatusCode::OK);
             }
@@ -162,22 +403,24 @@ pub mod table_unit_tests {
         });
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(0),
-            orders: Mutex::new(0),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
         let state = State(app_state);
-        let pagination = Query(Pagination {
+        let pagination = ValidQuery(Pagination {
             offset: 0,
             limit: 10,
         });
-        let query: ExtraQuery<ListTableFiltersRequest> = ExtraQuery(ListTableFiltersRequest {
-            table_id: None,
-            order_id: None,
-            item_name: None,
-            item_names: [].to_vec(),
-        });
-        match list_table(state, pagination, query).await {
+        let query: ValidExtraQuery<ListTableFiltersRequest> =
+            ValidExtraQuery(ListTableFiltersRequest {
+                table_id: None,
+                order_id: None,
+                item_name: None,
+                item_names: [].to_vec(),
+            });
+        match list_table(state, ApiVersion::V2, pagination, query).await {
             Ok(_) => panic!("supposed to fail"),
             Err(e) => {
                 assert_eq!(e.1.error_type, AxumErrors::DBError.to_string());
@@ -197,22 +440,24 @@ pub mod table_unit_tests {
         });
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(0),
-            orders: Mutex::new(0),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
         let state = State(app_state);
-        let pagination = Query(Pagination {
+        let pagination = ValidQuery(Pagination {
             offset: 0,
             limit: 10,
         });
-        let query: ExtraQuery<ListTableFiltersRequest> = ExtraQuery(ListTableFiltersRequest {
-            table_id: None,
-            order_id: None,
-            item_name: None,
-            item_names: [].to_vec(),
-        });
-        match list_table(state, pagination, query).await {
+        let query: ValidExtraQuery<ListTableFiltersRequest> =
+            ValidExtraQuery(ListTableFiltersRequest {
+                table_id: None,
+                order_id: None,
+                item_name: None,
+                item_names: [].to_vec(),
+            });
+        match list_table(state, ApiVersion::V2, pagination, query).await {
             Ok(_) => panic!("supposed to fail"),
             Err(e) => {
                 assert_eq!(e.1.error_type, AxumErrors::BsonDeserializeError.to_string());
@@ -227,24 +472,114 @@ pub mod table_unit_tests {
     #[tokio::test]
     pub async fn successful_get_table() {
         let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
         let table = Table {
             id: ObjectId::new().to_hex(),
             table_id: 1,
             orders: [].to_vec(),
+            version: 1,
+            fired_course: 1,
+            allergy_note: None,
         };
 
         faux::when!(mock_db.get_table).then(move |_| Ok(table.to_owned()));
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(1),
-            orders: Mutex::new(0),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
         let state = State(app_state);
-        let path = Path(1);
-        match get_table(state, path).await {
+        let path = ValidPath(TablePath { table_id: 1 });
+        match get_table(state, ApiVersion::V2, path, IfNoneMatch(None)).await {
             Ok(resp) => {
-                assert_eq!(resp.0, StatusCode::OK);
+                assert_eq!(resp.status(), StatusCode::OK);
+                let etag = resp.headers()["etag"].to_str().unwrap();
+                assert!(etag.starts_with("\"1-"));
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_get_table_not_modified() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        let ordered_time = Utc::now();
+        let table = Table {
+            id: ObjectId::new().to_hex(),
+            table_id: 1,
+            orders: [Order {
+                order_id: 1,
+                table_id: 1,
+                ordered_time,
+                cook_status: CookStatus::InProgress,
+                item: item("Hamburger", 60),
+                quantity: 1,
+                modifiers: vec![],
+                course: 1,
+                held: false,
+                fired_time: None,
+                allergy_conflicts: vec![],
+                taken_stock: TakenStock::default(),
+            }]
+            .to_vec(),
+            version: 3,
+            fired_course: 1,
+            allergy_note: None,
+        };
+
+        faux::when!(mock_db.get_table).then(move |_| Ok(table.to_owned()));
+
+        let clock = Arc::new(FakeClock::new(ordered_time));
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: clock.clone(),
+        });
+        let get = |if_none_match: IfNoneMatch| {
+            get_table(
+                State(app_state.clone()),
+                ApiVersion::V2,
+                ValidPath(TablePath { table_id: 1 }),
+                if_none_match,
+            )
+        };
+        let etag = match get(IfNoneMatch(None)).await {
+            Ok(resp) => resp.headers()["etag"].to_str().unwrap().to_string(),
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        };
+        let if_none_match = || {
+            IfNoneMatch(Some(EntityTags::Tags(vec![
+                EntityTag {
+                    weak: false,
+                    tag: "2".to_string(),
+                },
+                EntityTag {
+                    weak: true,
+                    tag: etag.trim_matches('"').to_string(),
+                },
+            ])))
+        };
+
+        match get(if_none_match()).await {
+            Ok(resp) => {
+                assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+                assert_eq!(resp.headers()["etag"], etag.as_str());
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+
+        // the order is done by now, the copy with it in progress is stale although the version is the same
+        clock.advance(chrono::Duration::seconds(60));
+        match get(if_none_match()).await {
+            Ok(resp) => {
+                assert_eq!(resp.status(), StatusCode::OK);
+                let new_etag = resp.headers()["etag"].to_str().unwrap();
+                assert_ne!(new_etag, etag);
+                assert!(new_etag.starts_with("\"3-"));
             }
             Err(e) => panic!("error! {:?}", e.1.error_type),
         }
@@ -262,13 +597,14 @@ pub mod table_unit_tests {
         });
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(1),
-            orders: Mutex::new(0),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
         let state = State(app_state);
-        let path = Path(1);
-        match get_table(state, path).await {
+        let path = ValidPath(TablePath { table_id: 1 });
+        match get_table(state, ApiVersion::V2, path, IfNoneMatch(None)).await {
             Ok(_) => panic!("shouldn't succeed!"),
             Err(e) => {
                 assert_eq!(e.1.error_type, AxumErrors::NotFound.to_string());
@@ -288,13 +624,14 @@ pub mod table_unit_tests {
         });
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(1),
-            orders: Mutex::new(0),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
         let state = State(app_state);
-        let path = Path(1);
-        match get_table(state, path).await {
+        let path = ValidPath(TablePath { table_id: 1 });
+        match get_table(state, ApiVersion::V2, path, IfNoneMatch(None)).await {
             Ok(_) => panic!("shouldn't succeed!"),
             Err(e) => {
                 assert_eq!(e.1.error_type, AxumErrors::DBError.to_string());
@@ -309,22 +646,28 @@ pub mod table_unit_tests {
     #[tokio::test]
     pub async fn successful_delete_table() {
         let mut mock_db = DB::faux();
-        let table = TableResponse {
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+        let table = Table {
+            id: ObjectId::new().to_hex(),
             table_id: 1,
             orders: [].to_vec(),
+            version: 1,
+            fired_course: 1,
+            allergy_note: None,
         };
 
         faux::when!(mock_db.delete_table).then(move |_| Ok(table.to_owned()));
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(1),
-            orders: Mutex::new(0),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
 
         let state = State(app_state);
-        let path = Path(1);
-        match delete_table(state, path).await {
+        let path = ValidPath(TablePath { table_id: 1 });
+        match delete_table(state, ApiVersion::V2, path, IfMatch(None)).await {
             Ok(resp) => {
                 assert_eq!(resp.0, StatusCode::OK);
             }
@@ -332,9 +675,133 @@ pub mod table_unit_tests {
         }
     }
 
+    fn grill_order(order_id: i64, quantity: i64, fired_time: Option<DateTime<Utc>>) -> Order {
+        Order {
+            order_id,
+            table_id: 1,
+            ordered_time: fired_time.unwrap_or_else(Utc::now),
+            cook_status: match fired_time {
+                Some(_) => CookStatus::InProgress,
+                None => CookStatus::Held,
+            },
+            item: Item {
+                station: Some(Station::Grill),
+                ..item("Burger", 600)
+            },
+            quantity,
+            modifiers: vec![],
+            course: match fired_time {
+                Some(_) => 1,
+                None => 2,
+            },
+            held: fired_time.is_none(),
+            fired_time,
+            allergy_conflicts: vec![],
+            taken_stock: TakenStock {
+                portions: quantity,
+                ingredients: vec![],
+            },
+        }
+    }
+
+    #[tokio::test]
+    pub async fn successful_delete_table_gives_back_unstarted_stock() {
+        let now = Utc::now();
+        let mut mock_db = DB::faux();
+        // both grill slots are taken by orders of other tables, order 3 waits for one
+        faux::when!(mock_db.get_fired_orders).then(move |_| {
+            Ok(vec![
+                Order {
+                    table_id: 2,
+                    ..grill_order(1, 1, Some(now))
+                },
+                Order {
+                    table_id: 2,
+                    ..grill_order(2, 1, Some(now))
+                },
+                grill_order(3, 2, Some(now)),
+            ])
+        });
+        let table = Table {
+            id: ObjectId::new().to_hex(),
+            table_id: 1,
+            orders: vec![
+                grill_order(3, 2, Some(now)),
+                grill_order(4, 1, None),
+                grill_order(5, 3, Some(now - chrono::Duration::hours(1))),
+            ],
+            version: 3,
+            fired_course: 1,
+            allergy_note: None,
+        };
+        faux::when!(mock_db.delete_table).then(move |_| Ok(table.to_owned()));
+        // the queued and the held order give back their portions, the cooked one doesn't
+        faux::when!(mock_db.release_stock).once().then(|portions| {
+            assert_eq!(portions.get("Burger"), Some(&3));
+            Ok(())
+        });
+
+        let path = ValidPath(TablePath { table_id: 1 });
+        match delete_table(
+            app_state_at(mock_db, now),
+            ApiVersion::V2,
+            path,
+            IfMatch(None),
+        )
+        .await
+        {
+            Ok(resp) => {
+                assert_eq!(resp.0, StatusCode::OK);
+                assert_eq!(
+                    resp.1
+                        .table
+                        .orders
+                        .iter()
+                        .map(|order| order.cook_status.clone())
+                        .collect::<Vec<CookStatus>>(),
+                    vec![CookStatus::Queued, CookStatus::Held, CookStatus::Done]
+                );
+            }
+            Err(e) => panic!("error! {:?}", e.1.error_type),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_delete_table_precondition_failed() {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
+
+        faux::when!(mock_db.delete_table).then(move |(_, expected_versions)| {
+            assert_eq!(expected_versions, Some(vec![1]));
+            Err(ErrorResponse::from(AxumErrors::PreconditionFailed))
+        });
+
+        let app_state = Arc::new(AppState {
+            db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+
+        let state = State(app_state);
+        let path = ValidPath(TablePath { table_id: 1 });
+        let if_match = IfMatch(Some(EntityTags::Tags(vec![EntityTag {
+            weak: false,
+            tag: "1".to_string(),
+        }])));
+        match delete_table(state, ApiVersion::V2, path, if_match).await {
+            Ok(_) => panic!("not supposed to succeed!"),
+            Err(e) => {
+                assert_eq!(e.0, StatusCode::PRECONDITION_FAILED);
+                assert_eq!(e.1.error_type, AxumErrors::PreconditionFailed.to_string());
+            }
+        }
+    }
+
     #[tokio::test]
     pub async fn failed_delete_table_not_found() {
         let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
 
         faux::when!(mock_db.delete_table).then(move |_| {
             Err(ErrorResponse {
@@ -344,14 +811,15 @@ pub mod table_unit_tests {
         });
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(1),
-            orders: Mutex::new(0),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
 
         let state = State(app_state);
-        let path = Path(1);
-        match delete_table(state, path).await {
+        let path = ValidPath(TablePath { table_id: 1 });
+        match delete_table(state, ApiVersion::V2, path, IfMatch(None)).await {
             Ok(_) => panic!("not supposed to succeed"),
             Err(e) => {
                 assert_eq!(e.1.error_type, AxumErrors::NotFound.to_string());
@@ -362,6 +830,7 @@ pub mod table_unit_tests {
     #[tokio::test]
     pub async fn failed_delete_table_db_error() {
         let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_fired_orders).then(|_| Ok(vec![]));
 
         faux::when!(mock_db.delete_table).then(move |_| {
             Err(ErrorResponse {
@@ -371,14 +840,15 @@ pub mod table_unit_tests {
         });
 
         let app_state = Arc::new(AppState {
-            tables: Mutex::new(1),
-            orders: Mutex::new(0),
             db: mock_db.clone(),
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
         });
 
         let state = State(app_state);
-        let path = Path(1);
-        match delete_table(state, path).await {
+        let path = ValidPath(TablePath { table_id: 1 });
+        match delete_table(state, ApiVersion::V2, path, IfMatch(None)).await {
             Ok(_) => panic!("not supposed to succeed"),
             Err(e) => {
                 assert_eq!(e.1.error_type, AxumErrors::DBError.to_string());
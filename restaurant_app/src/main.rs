@@ -1,90 +1,176 @@
+mod cli;
 mod common;
 mod handlers;
-use std::sync::Arc;
+mod routes;
+use std::{future::Future, sync::Arc};
 
-use axum::{
-    routing::{delete, get, post},
-    Router,
+use axum::{middleware, routing::get, Router};
+use common::api_docs::docs_router;
+use common::clock::{Clock, FakeClock, SystemClock};
+use common::config::{
+    fake_clock, kitchen_schedule_cache_ttl, menu_cache_ttl, seed_max_attempts, seed_mode,
+    seed_retry_delay,
 };
+use common::counters_db::DBCounterTrait;
 use common::database::{DBTrait, DB};
-use common::errors::handler_404;
-use handlers::{item_handler, order_handler, table_handler};
-
-use tokio::sync::Mutex;
+use common::errors::{handler_404, ErrorResponse};
+use common::idempotency_db::DBIdempotencyTrait;
+use common::indexes_db::{check_indexes, DBIndexTrait};
+use common::menu_cache::{watch_menu_changes, MenuCache};
+use common::request_tracing::{init_tracing, request_tracing_middleware};
+use common::seeding::{set_up_item_records, with_retries, SeedError};
+use common::telemetry::Telemetry;
+use handlers::kitchen_handler::scheduler::ScheduleCache;
+use handlers::menu_version_handler::menu_version::apply_menu_versions_when_due;
+use routes::{legacy_routes, v1_routes, v2_routes, V1_PREFIX, V2_PREFIX};
 
 struct AppState {
-    // We require unique table ids
-    tables: Mutex<i64>,
-    orders: Mutex<i64>,
-    // db pool
+    // db pool, the table and order ids come from its counters
     db: DB,
+    // the items, so placing an order doesn't have to read them every time
+    menu_cache: MenuCache,
+    // the last kitchen schedule, shared by the reads of a busy moment
+    schedule_cache: ScheduleCache,
+    // the current time of the cook statuses and of new orders, tests and simulations swap in a fake one
+    clock: Arc<dyn Clock>,
 }
 
 #[tokio::main]
 async fn main() {
-    //initializing the tracer
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
-        .with_target(false)
-        .without_time()
-        .init();
+    //initializing the tracer and the meters, before anything creates its instruments
+    let telemetry = init_tracing();
+
+    //with arguments the binary runs an admin command instead of the server
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    if !args.is_empty() {
+        let exit_code = cli::run(&args).await;
+        telemetry.shutdown();
+        std::process::exit(exit_code);
+    }
+
     //initialize the db connection
     let db = match DB::init().await {
         Ok(db) => db,
         Err(e) => {
-            panic!("Was unable to initialize the db! {e}");
+            tracing::error!("Was unable to initialize the db! {e}");
+            telemetry.shutdown();
+            std::process::exit(1);
         }
     };
 
-    //pre-load the database with items
-    match db.set_up_item_records().await {
-        Ok(_) => (),
-        Err(e) => {
-            panic!("unexpected error occurred during initializing the db! Error: {e}")
-        }
+    //pre-load the database with items, waiting for it to come up
+    if let Err(e) = set_up_item_records(&db, seed_mode()).await {
+        tracing::error!("Was unable to pre-load the db! {e}");
+        telemetry.shutdown();
+        std::process::exit(1);
+    }
+
+    //expire the stored idempotent responses
+    start_up_step(&telemetry, "create the idempotency key indexes", || {
+        db.set_up_idempotency_indexes()
+    })
+    .await;
+
+    //index the lookups by table, order and item name, an index the data doesn't allow is reported and skipped
+    let failed_indexes =
+        start_up_step(&telemetry, "create the indexes", || db.set_up_indexes()).await;
+    match check_indexes(&db).await {
+        Ok(report) => report.with_failed(failed_indexes).log(),
+        Err(e) => tracing::warn!("Was unable to check the indexes! Error: {}", e.error.detail),
     };
 
-    // build our application with a single route
-    let base_app = Router::new().route("/", get(|| async { "Welcome to the Restaurant!" }));
+    //carry on with the ids after the ones already in use
+    start_up_step(&telemetry, "set up the id counters", || {
+        db.set_up_counters()
+    })
+    .await;
 
-    let table_app = base_app
-        .route("/table", post(table_handler::table::create_table))
-        .route("/table", get(table_handler::table::list_table))
-        .route("/table/:table_id", get(table_handler::table::get_table))
-        .route(
-            "/table/:table_id",
-            delete(table_handler::table::delete_table),
-        );
-
-    let order_app = table_app
-        .route(
-            "/table/:table_id/order",
-            post(order_handler::order::create_order),
-        )
-        .route(
-            "/table/:table_id/order/:order_id",
-            get(order_handler::order::get_order),
-        )
-        .route("/table/order", get(order_handler::order::list_all_orders))
-        .route(
-            "/table/:table_id/order/:order_id",
-            delete(order_handler::order::delete_order),
-        );
-
-    let item_app = order_app.route("/item", get(item_handler::item::list_items));
+    let menu_cache = MenuCache::new(menu_cache_ttl());
+    start_up_step(&telemetry, "load the menu", || menu_cache.load(&db)).await;
 
     let app_state = Arc::new(AppState {
         db,
-        tables: Mutex::new(0),
-        orders: Mutex::new(0),
+        menu_cache,
+        schedule_cache: ScheduleCache::new(kitchen_schedule_cache_ttl()),
+        clock: app_clock(),
     });
-    let app = item_app.fallback(handler_404).with_state(app_state);
+    //drop the cached menu as soon as an item changes
+    tokio::spawn(watch_menu_changes(app_state.clone()));
+    //put published menu versions on the menu once their effective time comes
+    tokio::spawn(apply_menu_versions_when_due(app_state.clone()));
+
+    // build our application with a single route
+    let base_app = Router::new().route("/", get(|| async { "Welcome to the Restaurant!" }));
+
+    // everything lives under /v1 and /v2, the old unversioned paths are kept around as deprecated aliases
+    let versioned_app = base_app
+        .nest(V1_PREFIX, v1_routes(&app_state))
+        .nest(V2_PREFIX, v2_routes(&app_state))
+        .merge(legacy_routes(&app_state));
+
+    let docs_app = versioned_app.merge(docs_router());
+
+    let app = docs_app
+        .fallback(handler_404)
+        .layer(middleware::from_fn(request_tracing_middleware))
+        .with_state(app_state);
 
     // run our app with hyper, listening globally on port 8080
     let listener = tokio::net::TcpListener::bind("127.0.0.1:9090")
         .await
         .unwrap();
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .unwrap();
+
+    //flush whatever spans and metrics are still buffered
+    telemetry.shutdown();
+}
+
+//retries a start up step while the db fails it, like the pre-loading, and exits once the attempts run out
+async fn start_up_step<T, F, Fut>(telemetry: &Telemetry, step: &str, mut attempt: F) -> T
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, ErrorResponse>>,
+{
+    let result = with_retries(seed_max_attempts(), seed_retry_delay(), || {
+        let attempt = attempt();
+        async move {
+            attempt.await.map_err(|e| SeedError::Database {
+                attempts: 1,
+                reason: e.error.detail,
+            })
+        }
+    })
+    .await;
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            tracing::error!("Was unable to {step}! {e}");
+            telemetry.shutdown();
+            std::process::exit(1);
+        }
+    }
+}
+
+//a fake clock lets a simulation be replayed faster than real time
+fn app_clock() -> Arc<dyn Clock> {
+    match fake_clock() {
+        Some((start, speed)) => {
+            tracing::warn!(
+                "Running on a fake clock starting at {start}, {speed} times the wall clock"
+            );
+            Arc::new(FakeClock::running(start, speed))
+        }
+        None => Arc::new(SystemClock),
+    }
+}
+
+async fn shutdown_signal() {
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        tracing::error!("Was unable to listen for the shutdown signal! Error: {e}");
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,268 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::{self, Next},
+    response::Response,
+    routing::{delete, get, post, put},
+    Extension, Router,
+};
+
+use crate::{
+    common::{extractors::ApiVersion, idempotency::idempotency_middleware},
+    handlers::{
+        daypart_handler, inventory_handler, item_handler, kitchen_handler, menu_transfer_handler,
+        menu_version_handler, order_handler, table_handler,
+    },
+    AppState,
+};
+
+pub const V1_PREFIX: &str = "/v1";
+pub const V2_PREFIX: &str = "/v2";
+
+// RFC 9745 structured date, the unversioned routes are deprecated since 2026-10-18
+const LEGACY_DEPRECATION: &str = "@1792281600";
+// RFC 8594 http-date, after which the unversioned routes may be removed
+const LEGACY_SUNSET: &str = "Sun, 18 Apr 2027 00:00:00 GMT";
+
+static DEPRECATION_HEADER: HeaderName = HeaderName::from_static("deprecation");
+static SUNSET_HEADER: HeaderName = HeaderName::from_static("sunset");
+static LINK_HEADER: HeaderName = HeaderName::from_static("link");
+
+// Each resource gets its own router so a new api version only has to swap out the resources whose shape changed
+// and can keep reusing the rest, e.g. a v2 with a new table response would be
+// `table_routes_v2(app_state).merge(order_routes(app_state)).merge(item_routes())`.
+// The state is only needed by the middlewares that talk to the db, e.g. the idempotency keys of the create and move end points.
+
+pub fn table_routes(app_state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route(
+            "/table",
+            post(table_handler::table::create_table).layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                idempotency_middleware,
+            )),
+        )
+        .route("/table", get(table_handler::table::list_table))
+        .route("/table/:table_id", get(table_handler::table::get_table))
+        .route(
+            "/table/:table_id",
+            delete(table_handler::table::delete_table),
+        )
+}
+
+pub fn order_routes(app_state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route(
+            "/table/:table_id/order",
+            post(order_handler::order::create_order).layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                idempotency_middleware,
+            )),
+        )
+        .route(
+            "/table/:table_id/order/move",
+            post(order_handler::order::move_orders).layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                idempotency_middleware,
+            )),
+        )
+        .route(
+            "/table/:table_id/order/:order_id",
+            get(order_handler::order::get_order),
+        )
+        .route(
+            "/table/:table_id/fire",
+            post(order_handler::order::fire_course),
+        )
+        .route("/table/order", get(order_handler::order::list_all_orders))
+        .route(
+            "/table/:table_id/order/:order_id",
+            delete(order_handler::order::delete_order),
+        )
+}
+
+pub fn item_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/item", get(item_handler::item::list_items))
+        .route(
+            "/item/:item_name/availability",
+            put(item_handler::item::update_availability),
+        )
+        .route(
+            "/item/:item_name/recipe",
+            put(item_handler::item::update_recipe),
+        )
+        .route(
+            "/item/:item_name/dayparts",
+            put(item_handler::item::update_dayparts),
+        )
+        .route("/menu", get(item_handler::item::get_menu))
+}
+
+pub fn kitchen_routes() -> Router<Arc<AppState>> {
+    Router::new().route(
+        "/kitchen/stations",
+        get(kitchen_handler::kitchen::list_stations),
+    )
+}
+
+pub fn inventory_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route(
+            "/inventory",
+            get(inventory_handler::inventory::list_ingredients),
+        )
+        .route(
+            "/inventory/alerts",
+            get(inventory_handler::inventory::list_low_stock),
+        )
+        .route(
+            "/inventory/unmakeable_items",
+            get(inventory_handler::inventory::list_unmakeable_items),
+        )
+        .route(
+            "/inventory/:ingredient_name",
+            put(inventory_handler::inventory::set_ingredient),
+        )
+}
+
+pub fn daypart_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/dayparts", get(daypart_handler::daypart::list_dayparts))
+        .route(
+            "/dayparts/:daypart_name",
+            put(daypart_handler::daypart::set_daypart),
+        )
+}
+
+pub fn menu_version_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route(
+            "/menu/versions",
+            get(menu_version_handler::menu_version::list_menu_versions),
+        )
+        .route(
+            "/menu/versions",
+            post(menu_version_handler::menu_version::create_menu_version),
+        )
+        .route(
+            "/menu/versions/:version",
+            get(menu_version_handler::menu_version::get_menu_version),
+        )
+        .route(
+            "/menu/versions/:version/items/:item_name",
+            put(menu_version_handler::menu_version::set_menu_version_item),
+        )
+        .route(
+            "/menu/versions/:version/items/:item_name",
+            delete(menu_version_handler::menu_version::remove_menu_version_item),
+        )
+        .route(
+            "/menu/versions/:version/publish",
+            post(menu_version_handler::menu_version::publish_menu_version),
+        )
+        .route(
+            "/menu/versions/:version/diff/:other_version",
+            get(menu_version_handler::menu_version::diff_menu_versions),
+        )
+}
+
+pub fn menu_transfer_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route(
+            "/admin/menu/import",
+            post(menu_transfer_handler::menu_transfer::import_menu_file),
+        )
+        .route(
+            "/admin/menu/export",
+            get(menu_transfer_handler::menu_transfer::export_menu_file),
+        )
+}
+
+/// The tables and orders as v1 served them, the cook statuses added since are reported as `InProgress`.
+pub fn v1_routes(app_state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    table_routes(app_state)
+        .merge(order_routes(app_state))
+        .merge(item_routes())
+        .merge(kitchen_routes())
+        .merge(inventory_routes())
+        .merge(daypart_routes())
+        .merge(menu_version_routes())
+        .merge(menu_transfer_routes())
+        .layer(Extension(ApiVersion::V1))
+}
+
+/// Same handlers as v1, but the orders keep the `Held` and `Queued` cook statuses.
+pub fn v2_routes(app_state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    table_routes(app_state)
+        .merge(order_routes(app_state))
+        .merge(item_routes())
+        .merge(kitchen_routes())
+        .merge(inventory_routes())
+        .merge(daypart_routes())
+        .merge(menu_version_routes())
+        .merge(menu_transfer_routes())
+}
+
+/// The routes that existed before versioning, mounted at the root.
+/// They behave exactly like v1 but every response warns the caller that they are going away.
+/// The list is frozen, end points added since are only served under `/v1`.
+pub fn legacy_routes(app_state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route(
+            "/table",
+            post(table_handler::table::create_table).layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                idempotency_middleware,
+            )),
+        )
+        .route("/table", get(table_handler::table::list_table))
+        .route("/table/:table_id", get(table_handler::table::get_table))
+        .route(
+            "/table/:table_id",
+            delete(table_handler::table::delete_table),
+        )
+        .route(
+            "/table/:table_id/order",
+            post(order_handler::order::create_order).layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                idempotency_middleware,
+            )),
+        )
+        .route(
+            "/table/:table_id/order/:order_id",
+            get(order_handler::order::get_order),
+        )
+        .route("/table/order", get(order_handler::order::list_all_orders))
+        .route(
+            "/table/:table_id/order/:order_id",
+            delete(order_handler::order::delete_order),
+        )
+        .route("/item", get(item_handler::item::list_items))
+        .layer(Extension(ApiVersion::V1))
+        .route_layer(middleware::from_fn(deprecated_route_middleware))
+}
+
+pub async fn deprecated_route_middleware(request: Request, next: Next) -> Response {
+    let successor = format!(
+        "<{V1_PREFIX}{}>; rel=\"successor-version\"",
+        request.uri().path()
+    );
+
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert(
+        DEPRECATION_HEADER.clone(),
+        HeaderValue::from_static(LEGACY_DEPRECATION),
+    );
+    headers.insert(
+        SUNSET_HEADER.clone(),
+        HeaderValue::from_static(LEGACY_SUNSET),
+    );
+    if let Ok(link) = HeaderValue::from_str(&successor) {
+        headers.insert(LINK_HEADER.clone(), link);
+    }
+    response
+}
@@ -0,0 +1,99 @@
+#[cfg(test)]
+pub mod throughput_tests {
+    use std::collections::HashSet;
+
+    use futures::future::join_all;
+    use tokio::time::Instant;
+
+    use crate::handlers::{
+        order_handler::order::{CreateOrdersRequest, ReturnTableResponse},
+        table_handler::table::{CreateTableOrdersRequest, PostTableResponse},
+    };
+
+    const CLIENTS: usize = 100;
+    const ORDERS_PER_CLIENT: usize = 5;
+
+    // each client seats a table and then orders for it one item at a time, as a waiter would
+    async fn client(http: reqwest::Client) -> (i64, Vec<i64>) {
+        let table = http
+            .post("http://127.0.0.1:9090/v1/table")
+            .json(&CreateTableOrdersRequest {
+                orders: ["Gyoza".into()].to_vec(),
+                allow_partial: false,
+                allergy_note: None,
+                manager_override: false,
+            })
+            .send()
+            .await
+            .unwrap()
+            .json::<PostTableResponse>()
+            .await
+            .unwrap();
+        let table_id = table.table.table_id;
+
+        let mut order_ids = table
+            .table
+            .orders
+            .iter()
+            .map(|order| order.order_id)
+            .collect::<Vec<i64>>();
+        for _ in 0..ORDERS_PER_CLIENT {
+            let table = http
+                .post(format!("http://127.0.0.1:9090/v1/table/{table_id}/order"))
+                .json(&CreateOrdersRequest {
+                    orders: ["Udon".into()].to_vec(),
+                    allow_partial: false,
+                    manager_override: false,
+                })
+                .send()
+                .await
+                .unwrap()
+                .json::<ReturnTableResponse>()
+                .await
+                .unwrap();
+            order_ids = table
+                .table
+                .orders
+                .iter()
+                .map(|order| order.order_id)
+                .collect();
+        }
+        (table_id, order_ids)
+    }
+
+    // Needs the app running on port 9090, run it with
+    // `cargo test benchmark -- --ignored --nocapture`
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn parallel_create_throughput() {
+        let http = reqwest::Client::new();
+        let start = Instant::now();
+
+        let results = join_all((0..CLIENTS).map(|_| tokio::spawn(client(http.clone())))).await;
+        let elapsed = start.elapsed();
+
+        let mut table_ids = HashSet::new();
+        let mut order_ids = HashSet::new();
+        for result in results {
+            let (table_id, orders) = result.unwrap();
+            assert!(
+                table_ids.insert(table_id),
+                "table id {table_id} handed out twice"
+            );
+            assert_eq!(orders.len(), ORDERS_PER_CLIENT + 1);
+            for order_id in orders {
+                assert!(
+                    order_ids.insert(order_id),
+                    "order id {order_id} handed out twice"
+                );
+            }
+        }
+
+        let requests = CLIENTS * (ORDERS_PER_CLIENT + 1);
+        println!(
+            "{CLIENTS} clients made {requests} create requests in {:.2?}, {:.0} requests/s",
+            elapsed,
+            requests as f64 / elapsed.as_secs_f64()
+        );
+    }
+}
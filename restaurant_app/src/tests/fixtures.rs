@@ -0,0 +1,58 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::extract::State;
+use chrono::{DateTime, Utc};
+
+use crate::{
+    common::{
+        clock::{FakeClock, SystemClock},
+        database::DB,
+        menu_cache::MenuCache,
+        models::restaurant_schema::{Item, RecipeIngredient},
+    },
+    handlers::kitchen_handler::scheduler::ScheduleCache,
+    AppState,
+};
+
+/// A main on the menu, everything else as an item document without it would read.
+pub fn item(item_name: &str, cook_time: i64) -> Item {
+    Item {
+        item_name: item_name.to_string(),
+        cook_time,
+        ..Default::default()
+    }
+}
+
+/// An item that takes the given quantity of each ingredient per portion.
+pub fn recipe_item(item_name: &str, recipe: &[(&str, i64)]) -> Item {
+    Item {
+        recipe: recipe
+            .iter()
+            .map(|(ingredient_name, quantity)| RecipeIngredient {
+                ingredient_name: ingredient_name.to_string(),
+                quantity: *quantity,
+            })
+            .collect(),
+        ..item(item_name, 5)
+    }
+}
+
+/// The mock database with both caches off, on the system clock.
+pub fn app_state(mock_db: DB) -> State<Arc<AppState>> {
+    State(Arc::new(AppState {
+        db: mock_db,
+        menu_cache: MenuCache::new(Duration::ZERO),
+        schedule_cache: ScheduleCache::new(Duration::ZERO),
+        clock: Arc::new(SystemClock),
+    }))
+}
+
+/// Like [`app_state`] with the clock stopped at `now`.
+pub fn app_state_at(mock_db: DB, now: DateTime<Utc>) -> State<Arc<AppState>> {
+    State(Arc::new(AppState {
+        db: mock_db,
+        menu_cache: MenuCache::new(Duration::ZERO),
+        schedule_cache: ScheduleCache::new(Duration::ZERO),
+        clock: Arc::new(FakeClock::new(now)),
+    }))
+}
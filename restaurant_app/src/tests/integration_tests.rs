@@ -1,10 +1,15 @@
 #[cfg(test)]
+#[allow(
+    clippy::module_inception,
+    clippy::needless_return,
+    clippy::async_yields_async
+)]
 pub mod integration_tests {
     use futures::future::join_all;
     use serde::Serialize;
 
     use crate::{
-        common::models::restaurant_schema::CookStatus,
+        common::models::restaurant_schema::{CookStatus, OrderRequest},
         handlers::{
             order_handler::order::{CreateOrdersRequest, ListOrdersResponse, ReturnTableResponse},
             table_handler::table::PostTableResponse,
@@ -22,11 +27,11 @@ pub mod integration_tests {
         cook_status: CookStatus,
     }
 
-    async fn create_table_call(orders: Vec<String>) -> PostTableResponse {
+    async fn create_table_call(prefix: &str, orders: Vec<String>) -> PostTableResponse {
         let client = reqwest::Client::new();
         let orders = CreateTableOrder { orders };
         let create_call = client
-            .post("http://127.0.0.1:9090/table")
+            .post(format!("http://127.0.0.1:9090{prefix}/table"))
             .header("Content-Type", "application/json")
             .json(&orders)
             .send()
@@ -38,11 +43,14 @@ pub mod integration_tests {
         return create_call;
     }
 
-    async fn list_all_orders(list_order_query: Option<ListOrderQuery>) -> ListOrdersResponse {
+    async fn list_all_orders(
+        prefix: &str,
+        list_order_query: Option<ListOrderQuery>,
+    ) -> ListOrdersResponse {
         let client = reqwest::Client::new();
 
         let list_call = client
-            .get("http://127.0.0.1:9090/table/order")
+            .get(format!("http://127.0.0.1:9090{prefix}/table/order"))
             .header("Content-Type", "application/json");
 
         match list_order_query {
@@ -64,12 +72,22 @@ pub mod integration_tests {
         }
     }
 
-    async fn create_order_call(table_id: i64, orders: Vec<String>) -> ReturnTableResponse {
+    async fn create_order_call(
+        prefix: &str,
+        table_id: i64,
+        orders: Vec<String>,
+    ) -> ReturnTableResponse {
         let client = reqwest::Client::new();
-        let order = CreateOrdersRequest { orders };
+        let order = CreateOrdersRequest {
+            orders: orders.into_iter().map(OrderRequest::from).collect(),
+            allow_partial: false,
+            manager_override: false,
+        };
 
         let create_order = client
-            .post(format!("http://127.0.0.1:9090/table/{table_id}/order"))
+            .post(format!(
+                "http://127.0.0.1:9090{prefix}/table/{table_id}/order"
+            ))
             .header("Content-Type", "application/json")
             .json(&order)
             .send()
@@ -81,12 +99,12 @@ pub mod integration_tests {
         return create_order;
     }
 
-    async fn delete_order_call(table_id: i64, order_id: i64) -> ReturnTableResponse {
+    async fn delete_order_call(prefix: &str, table_id: i64, order_id: i64) -> ReturnTableResponse {
         let client = reqwest::Client::new();
 
         let delete_order = client
             .delete(format!(
-                "http://127.0.0.1:9090/table/{table_id}/order/{order_id}"
+                "http://127.0.0.1:9090{prefix}/table/{table_id}/order/{order_id}"
             ))
             .header("Content-Type", "application/json")
             .send()
@@ -99,47 +117,52 @@ pub mod integration_tests {
     }
 
     //server one will delete the first order they see
-    async fn server_one() {
-        let table = create_table_call(["Gyoza".to_string()].to_vec()).await;
+    async fn server_one(prefix: &str) {
+        let table = create_table_call(prefix, ["Gyoza".to_string()].to_vec()).await;
         let table_id = table.table.table_id;
         let create_orders = [
-            create_order_call(table_id, ["Udon".to_string()].to_vec()),
+            create_order_call(prefix, table_id, ["Udon".to_string()].to_vec()),
             create_order_call(
+                prefix,
                 table_id,
                 ["Hotdog".to_string(), "Borsht".to_string()].to_vec(),
             ),
         ];
         join_all(create_orders).await;
-        let snap_shot = list_all_orders(None).await;
+        let snap_shot = list_all_orders(prefix, None).await;
         //delete the first order you see
         if snap_shot.pagination.total > 0 && !snap_shot.orders.is_empty() {
-            delete_order_call(table_id, snap_shot.orders[0].order_id).await;
+            delete_order_call(prefix, table_id, snap_shot.orders[0].order_id).await;
         }
     }
 
     //server two will wait for a second and then "clean up tables" that have food done
-    async fn server_two() {
+    async fn server_two(prefix: &str) {
         sleep(Duration::from_secs(10)).await;
         let list_order_query = Some(ListOrderQuery {
             cook_status: CookStatus::Done,
         });
-        let snap_shot = list_all_orders(list_order_query).await;
+        let snap_shot = list_all_orders(prefix, list_order_query).await;
         //delete the first order you see
         if snap_shot.pagination.total > 0 && !snap_shot.orders.is_empty() {
-            delete_order_call(snap_shot.orders[0].table_id, snap_shot.orders[0].order_id).await;
+            delete_order_call(
+                prefix,
+                snap_shot.orders[0].table_id,
+                snap_shot.orders[0].order_id,
+            )
+            .await;
         }
     }
 
-    #[tokio::test]
-    async fn run_async_test() {
+    async fn run_servers(prefix: &'static str) {
         let mut serve_one = vec![];
         let mut serve_two = vec![];
 
         for i in 1..5 {
             if i % 2 == 0 {
-                serve_one.push(tokio::spawn(async move { server_one() }))
+                serve_one.push(tokio::spawn(async move { server_one(prefix) }))
             } else {
-                serve_two.push(tokio::spawn(async move { server_two() }))
+                serve_two.push(tokio::spawn(async move { server_two(prefix) }))
             }
         }
 
@@ -156,4 +179,15 @@ pub mod integration_tests {
             outputs.push(task);
         }
     }
+
+    //the unversioned paths from before versioning
+    #[tokio::test]
+    async fn run_async_test() {
+        run_servers("").await;
+    }
+
+    #[tokio::test]
+    async fn run_async_v1_test() {
+        run_servers("/v1").await;
+    }
 }
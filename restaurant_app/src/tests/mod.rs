@@ -1 +1,4 @@
+pub mod benchmark_tests;
+pub mod fixtures;
 pub mod integration_tests;
+pub mod routing_tests;
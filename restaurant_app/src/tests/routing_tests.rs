@@ -0,0 +1,124 @@
+#[cfg(test)]
+pub mod routing_unit_tests {
+
+    use std::{sync::Arc, time::Duration};
+
+    use axum::{
+        body::{to_bytes, Body},
+        http::{Request, StatusCode},
+        Router,
+    };
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    use crate::{
+        common::{
+            clock::SystemClock,
+            database::DB,
+            menu_cache::MenuCache,
+            models::restaurant_schema::{Item, RecipeIngredient},
+        },
+        handlers::kitchen_handler::scheduler::ScheduleCache,
+        routes::{legacy_routes, v1_routes, v2_routes, V1_PREFIX, V2_PREFIX},
+        tests::fixtures::item,
+        AppState,
+    };
+
+    fn test_app() -> Router {
+        let mut mock_db = DB::faux();
+        faux::when!(mock_db.get_menu).then(|_| {
+            Ok(vec![Item {
+                stock: Some(3),
+                recipe: vec![RecipeIngredient {
+                    ingredient_name: "Eel".to_string(),
+                    quantity: 120,
+                }],
+                ..item("UnagiDon", 5)
+            }])
+        });
+
+        let app_state = Arc::new(AppState {
+            db: mock_db,
+            menu_cache: MenuCache::new(Duration::ZERO),
+            schedule_cache: ScheduleCache::new(Duration::ZERO),
+            clock: Arc::new(SystemClock),
+        });
+
+        Router::new()
+            .nest(V1_PREFIX, v1_routes(&app_state))
+            .nest(V2_PREFIX, v2_routes(&app_state))
+            .merge(legacy_routes(&app_state))
+            .with_state(app_state)
+    }
+
+    #[tokio::test]
+    pub async fn successful_v1_route_not_deprecated() {
+        let request = Request::builder()
+            .uri("/v1/item")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = test_app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get("deprecation").is_none());
+        assert!(response.headers().get("sunset").is_none());
+    }
+
+    #[tokio::test]
+    pub async fn successful_legacy_route_deprecated() {
+        let request = Request::builder().uri("/item").body(Body::empty()).unwrap();
+
+        let response = test_app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get("deprecation").is_some());
+        assert!(response.headers().get("sunset").is_some());
+        assert_eq!(
+            response.headers()["link"],
+            "</v1/item>; rel=\"successor-version\""
+        );
+    }
+
+    #[tokio::test]
+    pub async fn failed_legacy_route_added_after_versioning() {
+        let request = Request::builder().uri("/menu").body(Body::empty()).unwrap();
+
+        let response = test_app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    async fn into_json(response: axum::response::Response) -> Value {
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    pub async fn successful_item_routes_leave_out_stock_and_recipe() {
+        for uri in ["/item", "/v1/item", "/v2/item"] {
+            let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+            let response = test_app().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let item = &into_json(response).await["items"][0];
+            assert_eq!(item["item_name"], "UnagiDon");
+            assert!(item.get("stock").is_none());
+            assert!(item.get("recipe").is_none());
+        }
+    }
+
+    #[tokio::test]
+    pub async fn failed_v1_held_cook_status_filter() {
+        for uri in [
+            "/table/order?cook_status=Held",
+            "/v1/table/order?cook_status=Queued",
+        ] {
+            let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+            let response = test_app().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+            assert_eq!(
+                into_json(response).await["errors"][0]["field"],
+                "cook_status"
+            );
+        }
+    }
+}